@@ -0,0 +1,142 @@
+// Glue between stemgen and the HivelyTracker replayer (hvl_replay.c and hvl_replay.h from
+// https://github.com/pete-gordon/hivelytracker/tree/master/hvl2wav) which has to be copied to
+// this directory before building with the hvl feature
+#include "hvl_replay.h"
+#include <stdbool.h>
+#include <stdint.h>
+#include <string.h>
+
+// Has to match MAX_INSTRUMENTS on the Rust side
+#define MAX_INSTRUMENTS 64
+
+// Songs that never end are cut here when measuring their length
+#define MAX_SECONDS (20 * 60)
+
+// AHX and HVL songs are rendered at 50 frames per second times the speed multiplier
+#define FRAMES_PER_SECOND 50
+
+// Has to match the struct on the Rust side
+struct HvlSongInfo {
+    char name[128];
+    uint32_t channel_count;
+    uint32_t subsong_count;
+    uint32_t instrument_count;
+    float duration_seconds;
+    char instrument_names[MAX_INSTRUMENTS][128];
+};
+
+// Has to match the struct on the Rust side
+struct HvlRenderParams {
+    uint32_t sample_rate;
+    bool stereo_output;
+    int32_t channel_to_play; // if -1 play all channels, otherwise pick one
+    int32_t subsong; // if -1 play the main song
+    int32_t repeat_count; // -1 loops until the buffer is full
+    uint32_t skip_frames; // frames played and thrown away before the output starts
+    uint32_t stereo_separation; // 0 - 4 as used by AHX, from 0% to 100%
+};
+
+// Builds the waveform tables shared by all tunes. Called once before any other function
+extern void hvl_init_c(void) {
+    hvl_InitReplayer();
+}
+
+static bool init_subsong(struct hvl_tune* ht, int32_t subsong) {
+    return hvl_InitSubsong(ht, subsong < 0 ? 0 : (uint32_t)subsong) != 0;
+}
+
+// Frames (output samples per channel) produced by one hvl_DecodeFrame call
+static uint32_t samples_per_frame(const struct hvl_tune* ht) {
+    return ht->ht_Frequency / FRAMES_PER_SECOND / (ht->ht_SpeedMultiplier ? ht->ht_SpeedMultiplier : 1);
+}
+
+extern bool hvl_song_info_c(const uint8_t* data, uint32_t len, int32_t subsong, struct HvlSongInfo* info) {
+    struct hvl_tune* ht = hvl_ParseTune(data, len, 8000, 2);
+    if (!ht)
+        return false;
+
+    memset(info, 0, sizeof(*info));
+    strncpy(info->name, (const char*)ht->ht_Name, sizeof(info->name) - 1);
+    info->channel_count = ht->ht_Channels;
+    info->subsong_count = ht->ht_SubsongNr + 1;
+    info->instrument_count = ht->ht_InstrumentNr;
+
+    // Instrument 0 is the empty instrument
+    for (uint32_t i = 0; i < ht->ht_InstrumentNr && i < MAX_INSTRUMENTS; ++i) {
+        strncpy(info->instrument_names[i], (const char*)ht->ht_Instruments[i + 1].ins_Name, 127);
+    }
+
+    // Play the song (at a low rate to keep it cheap) until it loops
+    if (init_subsong(ht, subsong)) {
+        int16_t frame[2 * 8000 / FRAMES_PER_SECOND];
+        uint32_t frames = 0;
+        uint32_t max_frames = MAX_SECONDS * FRAMES_PER_SECOND * (ht->ht_SpeedMultiplier ? ht->ht_SpeedMultiplier : 1);
+        while (!ht->ht_SongEndReached && frames < max_frames) {
+            hvl_DecodeFrame(ht, (int8*)&frame[0], (int8*)&frame[1], 4);
+            frames++;
+        }
+        info->duration_seconds = (float)frames * samples_per_frame(ht) / ht->ht_Frequency;
+    }
+
+    hvl_FreeTune(ht);
+    return true;
+}
+
+extern uint32_t hvl_render_c(int16_t* output, uint32_t max_frames, const uint8_t* data, uint32_t len, const struct HvlRenderParams* params) {
+    struct hvl_tune* ht = hvl_ParseTune(data, len, params->sample_rate, params->stereo_separation);
+    if (!ht)
+        return 0;
+
+    if (!init_subsong(ht, params->subsong)) {
+        hvl_FreeTune(ht);
+        return 0;
+    }
+
+    // Muted tracks never start any notes
+    if (params->channel_to_play >= 0) {
+        for (int i = 0; i < ht->ht_Channels; ++i) {
+            ht->ht_Voices[i].vc_TrackOn = i == params->channel_to_play;
+        }
+    }
+
+    uint32_t frame_len = samples_per_frame(ht);
+    int16_t frame[2 * 4096];
+    if (frame_len == 0 || frame_len > 4096) {
+        hvl_FreeTune(ht);
+        return 0;
+    }
+
+    uint32_t output_channels = params->stereo_output ? 2 : 1;
+    uint32_t skip = params->skip_frames;
+    uint32_t rendered = 0;
+    int32_t loops = 0;
+
+    while (rendered < max_frames) {
+        // The end flag is set when the song restarts from the restart position
+        if (ht->ht_SongEndReached) {
+            ht->ht_SongEndReached = 0;
+            if (params->repeat_count >= 0 && loops++ >= params->repeat_count)
+                break;
+        }
+
+        hvl_DecodeFrame(ht, (int8*)&frame[0], (int8*)&frame[1], 4);
+
+        for (uint32_t i = 0; i < frame_len && rendered < max_frames; ++i) {
+            if (skip > 0) {
+                skip--;
+                continue;
+            }
+
+            if (output_channels == 2) {
+                output[rendered * 2] = frame[i * 2];
+                output[rendered * 2 + 1] = frame[i * 2 + 1];
+            } else {
+                output[rendered] = (int16_t)(((int32_t)frame[i * 2] + frame[i * 2 + 1]) / 2);
+            }
+            rendered++;
+        }
+    }
+
+    hvl_FreeTune(ht);
+    return rendered;
+}
@@ -2,21 +2,214 @@
 #include <libopenmpt/libopenmpt_ext.hpp>
 #include <stdafx.h>
 #include <soundlib/Sndfile.h>
+#include <soundlib/mod_specifications.h>
 #include <stdint.h>
+#include <iomanip>
 #include <iostream>
 #include <fstream>
+#include <algorithm>
+#include <cmath>
+#include <cstring>
+#include <filesystem>
+#include <memory>
+#include <set>
+#include <utility>
+#include <vector>
 
 enum SampleType {
     SampleType_Wav,
     SampleType_Flac,
 };
 
+// Max number of pattern channels supported by libopenmpt (MAX_BASECHANNELS is 127)
+#define MAX_CHANNELS 128
+
+// Has to match PathChar on the Rust side. Paths come in the native encoding of the OS so names
+// that aren't UTF-8 (or outside the code page on Windows) can still be opened
+#ifdef _WIN32
+typedef wchar_t PathChar;
+#else
+typedef char PathChar;
+#endif
+
+// <stem><suffix>, with the suffix formatted from an index like printf
+static std::filesystem::path path_with_suffix(const std::filesystem::path& stem, const char* format, int index) {
+    char suffix[64];
+    snprintf(suffix, sizeof(suffix), format, index);
+    std::filesystem::path path = stem;
+    path += suffix;
+    return path;
+}
+
 struct SongInfo {
     int num_channels;
     int num_instruments;
     float length_seconds;
+    // Duration from our own row counting pass, used when length_seconds looks bogus
+    float estimated_seconds;
+    // true if the channel has any note or instrument events in the order list(s)
+    bool channel_has_notes[MAX_CHANNELS];
+};
+
+// Walk all patterns referenced by the order lists and mark which channels
+// actually trigger notes or instruments. Muted/scratch channels stay false.
+static void find_channels_with_notes(const OpenMPT::CSoundFile* sf, SongInfo& info) {
+    const OpenMPT::CHANNELINDEX num_channels = sf->GetNumChannels();
+
+    for (const auto& sequence : sf->Order) {
+        for (OpenMPT::PATTERNINDEX pat : sequence) {
+            if (!sf->Patterns.IsValidPat(pat))
+                continue;
+
+            const auto& pattern = sf->Patterns[pat];
+
+            for (OpenMPT::ROWINDEX row = 0; row < pattern.GetNumRows(); ++row) {
+                for (OpenMPT::CHANNELINDEX chn = 0; chn < num_channels && chn < MAX_CHANNELS; ++chn) {
+                    const OpenMPT::ModCommand* m = pattern.GetpModCommand(row, chn);
+
+                    if (m->IsNote() || m->instr != 0)
+                        info.channel_has_notes[chn] = true;
+                }
+            }
+        }
+    }
+}
+
+// Has to match the struct on the Rust side
+struct SubsongInfo {
+    char name[256];
+    float duration_seconds;
+};
+
+// Has to match the struct on the Rust side
+struct SongMetadata {
+    char title[256];
+    char type[16];
+    char type_long[256];
+    char tracker[256];
+    char artist[256];
+    // Song message as entered in the tracker
+    char message[16384];
+    // Instrument and sample names one per line. Often used for credits and greetings
+    char sample_text[16384];
+    // Number of names written to sample_names
+    uint32_t sample_name_count;
+};
+
+// Has to match the struct on the Rust side
+struct ItemName {
+    char name[256];
+};
+
+// Has to match the struct on the Rust side
+struct OrderTime {
+    uint32_t order;
+    uint32_t pattern;
+    float seconds;
+};
+
+// Has to match MAX_ENVELOPE_POINTS on the Rust side
+#define MAX_ENVELOPE_POINTS 240
+
+// Has to match the struct on the Rust side
+struct EnvelopeInfo {
+    uint16_t ticks[MAX_ENVELOPE_POINTS];
+    uint8_t values[MAX_ENVELOPE_POINTS]; // 0 to 64, 32 is the centre of panning and pitch envelopes
+    uint32_t point_count;
+    bool enabled;
+    bool loop;
+    bool sustain;
+    bool carry;
+    bool filter; // the pitch envelope controls the filter cutoff instead
+    uint8_t loop_start;
+    uint8_t loop_end;
+    uint8_t sustain_start;
+    uint8_t sustain_end;
+};
+
+// Has to match the struct on the Rust side
+struct InstrumentInfo {
+    char name[256];
+    uint32_t fadeout;
+    uint32_t global_volume; // 0 to 64
+    int32_t panning; // 0 to 256, -1 if the instrument doesn't set a panning
+    uint8_t new_note_action;
+    uint8_t duplicate_check;
+    uint8_t duplicate_action;
+    int8_t pitch_pan_separation;
+    uint8_t pitch_pan_center; // MIDI note
+    uint8_t random_volume;
+    uint8_t random_pan;
+    uint16_t sample_map[128]; // sample played by each MIDI note, 0 for none
+    uint8_t note_map[128]; // MIDI note the sample is played at
+    EnvelopeInfo volume_envelope;
+    EnvelopeInfo panning_envelope;
+    EnvelopeInfo pitch_envelope;
+};
+
+// Has to match the struct on the Rust side
+struct SampleInfo {
+    char name[256];
+    uint32_t length;
+    uint32_t loop_start;
+    uint32_t loop_end;
+    uint32_t sustain_start;
+    uint32_t sustain_end;
+    uint32_t c5_speed;
+    uint16_t volume; // 0 to 256
+    uint16_t global_volume; // 0 to 64
+    int32_t panning; // 0 to 256, -1 if the sample doesn't set a panning
+    int8_t relative_tone;
+    int8_t finetune;
+    bool loop;
+    bool ping_pong_loop;
+    bool sustain_loop;
+    bool ping_pong_sustain_loop;
+    uint8_t vibrato_type;
+    uint8_t vibrato_sweep;
+    uint8_t vibrato_depth;
+    uint8_t vibrato_rate;
+};
+
+// Has to match the struct on the Rust side
+struct PatternSummary {
+    uint32_t channel_count;
+    uint32_t order_count;
+    uint32_t initial_speed;
+    float initial_tempo;
+    uint32_t rows_per_beat;
+    uint32_t rows_per_measure;
+};
+
+// Has to match the struct on the Rust side
+struct PatternInfo {
+    char name[256];
+    uint32_t rows; // 0 for pattern numbers that aren't used
+};
+
+// Has to match the struct on the Rust side
+struct PatternCell {
+    uint8_t note; // 0 for none, 1 to 120 (61 is middle C), 253 to 255 for note fade, cut and off
+    uint8_t instrument;
+    uint8_t volume; // parameter of the volume command
+    uint8_t param; // parameter of the effect
+    char note_name[8]; // as shown in the tracker, such as "C-5" or "==="
+    char volume_command; // letter of the volume column command in the notation of the format, 0 for none
+    char effect; // letter of the effect in the notation of the format, 0 for none
+};
+
+// Has to match the struct on the Rust side
+struct TempoChange {
+    double seconds;
+    double bpm;
+    uint32_t speed; // ticks per row
+    float tempo;
+    uint32_t rows_per_beat;
 };
 
+// RenderParams::subsong value that plays all subsongs back to back
+#define SUBSONG_ALL -2
+
 // Has to match the struct on the Rust size 
 struct RenderParams {
     uint32_t sample_rate;
@@ -26,23 +219,681 @@ struct RenderParams {
     int stereo_separation;
     bool stereo_separation_enabled;
     bool stereo_output;
+    int32_t repeat_count; // passed to set_repeat_count, -1 means loop forever
+    uint32_t max_frames; // stop after this many frames, 0 means no limit besides the buffer size
+    float start_seconds; // position to seek to before rendering
+    int32_t subsong; // subsong to render, -1 keeps the default selected by libopenmpt
+    uint32_t dither_seed;
+    bool dither_seed_enabled; // if false libopenmpt seeds dithering from a random device
+    int32_t interpolation_filter_length; // 0 keeps the libopenmpt default
+    int32_t master_gain_millibel;
+    float channel_pan[MAX_CHANNELS]; // -1.0 to 1.0, only used where channel_pan_mapped is set
+    float channel_volume[MAX_CHANNELS]; // 0.0 to 1.0, only used where channel_volume_mapped is set
+    bool channel_pan_mapped[MAX_CHANNELS];
+    bool channel_volume_mapped[MAX_CHANNELS];
+    int32_t amiga_filter; // AmigaFilter, AMIGA_FILTER_DEFAULT keeps the libopenmpt default
 };
 
+// Has to match the AmigaFilter enum on the Rust side
+enum AmigaFilter {
+    AMIGA_FILTER_DEFAULT,
+    AMIGA_FILTER_AUTO,
+    AMIGA_FILTER_A500,
+    AMIGA_FILTER_A1200,
+    AMIGA_FILTER_UNFILTERED,
+};
+
+static const char* amiga_filter_name(int32_t filter) {
+    switch (filter) {
+        case AMIGA_FILTER_A500: return "a500";
+        case AMIGA_FILTER_A1200: return "a1200";
+        case AMIGA_FILTER_UNFILTERED: return "unfiltered";
+        default: return "auto";
+    }
+}
+
 enum SampleFormat {
     SampleFormat_Flac,
     SampleFormat_Wav,
+    // Wav samples plus an sfz instrument playing them
+    SampleFormat_Sfz,
+    // A single SoundFont with all instruments
+    SampleFormat_Sf2,
+    // Amiga IFF 8SVX (16SV for 16-bit samples) with the loop in the VHDR chunk
+    SampleFormat_Iff,
+};
+
+// Rough duration estimation that walks the order list row by row using speed/tempo commands,
+// position jumps, pattern breaks, pattern loops and pattern delays. It stops as soon as a
+// row is visited a second time (the song loops) or when max_seconds is reached.
+static double estimate_duration_seconds(const OpenMPT::CSoundFile* sf, double max_seconds) {
+    using namespace OpenMPT;
+
+    const ModSequence& order = sf->Order();
+    const CHANNELINDEX num_channels = sf->GetNumChannels();
+
+    double speed = sf->m_nDefaultSpeed ? sf->m_nDefaultSpeed : 6;
+    double tempo = sf->m_nDefaultTempo.ToDouble() > 0.0 ? sf->m_nDefaultTempo.ToDouble() : 125.0;
+    double seconds = 0.0;
+
+    std::set<std::pair<ORDERINDEX, ROWINDEX>> visited;
+    std::vector<ROWINDEX> loop_start(num_channels, 0);
+    std::vector<int> loop_count(num_channels, 0);
+
+    ORDERINDEX ord = 0;
+    ROWINDEX row = 0;
+
+    while (seconds < max_seconds) {
+        if (ord >= order.GetLength())
+            break;
+
+        const PATTERNINDEX pat = order[ord];
+
+        if (pat == order.GetIgnoreIndex()) {
+            ord++;
+            row = 0;
+            continue;
+        }
+
+        if (!sf->Patterns.IsValidPat(pat))
+            break;
+
+        const CPattern& pattern = sf->Patterns[pat];
+
+        if (row >= pattern.GetNumRows()) {
+            ord++;
+            row = 0;
+            std::fill(loop_count.begin(), loop_count.end(), 0);
+            continue;
+        }
+
+        const bool in_pattern_loop = std::any_of(loop_count.begin(), loop_count.end(), [](int c) { return c > 0; });
+
+        // Rows inside a pattern loop are expected to repeat
+        if (!in_pattern_loop && !visited.insert(std::make_pair(ord, row)).second)
+            break;
+
+        bool position_jump = false;
+        bool pattern_break = false;
+        bool loop_jump = false;
+        ORDERINDEX jump_order = ord + 1;
+        ROWINDEX break_row = 0;
+        ROWINDEX loop_row = 0;
+        int delay_rows = 0;
+        bool song_stop = false;
+
+        for (CHANNELINDEX chn = 0; chn < num_channels; ++chn) {
+            const ModCommand* m = pattern.GetpModCommand(row, chn);
+            const uint8 param = m->param;
+
+            switch (m->command) {
+            case CMD_SPEED:
+                if (param == 0 && sf->GetType() == MOD_TYPE_MOD)
+                    song_stop = true;
+                else if (param != 0)
+                    speed = param;
+                break;
+            case CMD_TEMPO:
+                if (param >= 0x20)
+                    tempo = param;
+                break;
+            case CMD_POSITIONJUMP:
+                position_jump = true;
+                jump_order = param;
+                break;
+            case CMD_PATTERNBREAK:
+                pattern_break = true;
+                break_row = param;
+                break;
+            case CMD_MODCMDEX:
+            case CMD_S3MCMDEX: {
+                const uint8 sub = param >> 4;
+                const uint8 value = param & 0x0F;
+                const bool is_loop = (m->command == CMD_MODCMDEX) ? (sub == 0x6) : (sub == 0xB);
+
+                if (sub == 0xE) {
+                    delay_rows = std::max(delay_rows, (int)value);
+                } else if (is_loop) {
+                    if (value == 0) {
+                        loop_start[chn] = row;
+                    } else if (loop_count[chn] == 0) {
+                        loop_count[chn] = value;
+                        loop_jump = true;
+                        loop_row = loop_start[chn];
+                    } else if (--loop_count[chn] > 0) {
+                        loop_jump = true;
+                        loop_row = loop_start[chn];
+                    }
+                }
+                break;
+            }
+            default:
+                break;
+            }
+        }
+
+        seconds += (speed * 2.5 / tempo) * (1 + delay_rows);
+
+        if (song_stop)
+            break;
+
+        if (loop_jump) {
+            row = loop_row;
+        } else if (position_jump || pattern_break) {
+            ord = position_jump ? jump_order : ord + 1;
+            row = pattern_break ? break_row : 0;
+            std::fill(loop_count.begin(), loop_count.end(), 0);
+        } else {
+            row++;
+        }
+    }
+
+    return std::min(seconds, max_seconds);
+}
+
+// One <region> of an sfz instrument. The sample plays at its own rate at key_center, so the
+// finetune of the sample (baked into the rate of the exported wav) carries over
+static void write_sfz_region(std::ostream& f, const OpenMPT::ModSample& sample, const std::string& sample_file,
+                             const std::string& label, int lokey, int hikey, int key_center, double gain) {
+    f << "\n<region>\n";
+    if (!label.empty())
+        f << "region_label=" << label << "\n";
+    f << "sample=" << sample_file << "\n";
+    f << "lokey=" << lokey << " hikey=" << hikey << " pitch_keycenter=" << key_center << "\n";
+
+    if (gain < 1.0)
+        f << "volume=" << (gain > 0.0 ? 20.0 * std::log10(gain) : -144.0) << "\n";
+    if (sample.uFlags[OpenMPT::CHN_PANNING])
+        f << "pan=" << ((int)sample.nPan * 200 / 256 - 100) << "\n";
+
+    // sfz has a single loop per region, the sustain loop wins like in OpenMPT's own export
+    const char* loop_mode = "no_loop";
+    const char* loop_type = "forward";
+    OpenMPT::SmpLength loop_start = 0, loop_end = 0;
+
+    if (sample.uFlags[OpenMPT::CHN_SUSTAINLOOP]) {
+        loop_mode = "loop_sustain";
+        loop_start = sample.nSustainStart;
+        loop_end = sample.nSustainEnd;
+        if (sample.uFlags[OpenMPT::CHN_PINGPONGSUSTAIN])
+            loop_type = "alternate";
+    } else if (sample.uFlags[OpenMPT::CHN_LOOP]) {
+        loop_mode = "loop_continuous";
+        loop_start = sample.nLoopStart;
+        loop_end = sample.nLoopEnd;
+        if (sample.uFlags[OpenMPT::CHN_PINGPONGLOOP])
+            loop_type = "alternate";
+    }
+
+    f << "loop_mode=" << loop_mode << "\n";
+
+    // Loop ends are inclusive in sfz and exclusive in OpenMPT
+    if (loop_start < loop_end)
+        f << "loop_start=" << loop_start << " loop_end=" << (loop_end - 1) << " loop_type=" << loop_type << "\n";
+}
+
+// Write <stem>_instrument_NNNN.sfz for each instrument of the song (or each sample for songs
+// without instruments, matching the instrument numbering used when rendering) that plays the
+// <stem>_sample_NNNN.wav files written next to it
+static void write_sfz_instruments(const openmpt::module& song, const OpenMPT::CSoundFile* sf, const std::filesystem::path& output_with_stem) {
+    // sfz files are UTF-8 text
+    const std::string base = output_with_stem.filename().u8string();
+
+    const std::vector<std::string> sample_names = song.get_sample_names();
+    const std::vector<std::string> instrument_names = song.get_instrument_names();
+    const OpenMPT::SAMPLEINDEX num_samples = sf->GetNumSamples();
+    const bool has_instruments = sf->GetNumInstruments() != 0;
+    const int count = has_instruments ? sf->GetNumInstruments() : num_samples;
+
+    auto sample_file = [&](int index) {
+        char name[4096];
+        snprintf(name, sizeof(name), "%s_sample_%04d.wav", base.c_str(), index);
+        return std::string(name);
+    };
+
+    auto sample_name = [&](int index) {
+        return index >= 1 && index <= (int)sample_names.size() ? sample_names[index - 1] : std::string();
+    };
+
+    // Same samples as the wav export writes
+    auto exported = [&](int index) {
+        if (index < 1 || index > num_samples)
+            return false;
+        const auto& sample = sf->GetSample(index);
+        return sample.nLength != 0 && sample.HasSampleData() && !sample.uFlags[OpenMPT::CHN_ADLIB];
+    };
+
+    for (int i = 1; i < count + 1; ++i) {
+        const OpenMPT::ModInstrument* ins = has_instruments ? sf->Instruments[i] : nullptr;
+
+        if (has_instruments && ins == nullptr)
+            continue;
+        if (!has_instruments && !exported(i))
+            continue;
+
+        const std::filesystem::path name = path_with_suffix(output_with_stem, "_instrument_%04d.sfz", i);
+        std::ofstream f(name);
+
+        const std::string label = has_instruments ? (i <= (int)instrument_names.size() ? instrument_names[i - 1] : std::string()) : sample_name(i);
+
+        f << std::setprecision(6);
+        if (!label.empty())
+            f << "// " << label << "\n";
+        f << "// Written by stemgen\n\n<group>\n";
+        if (!label.empty())
+            f << "group_label=" << label << "\n";
+
+        if (!has_instruments) {
+            const auto& sample = sf->GetSample(i);
+            const double gain = sample.nVolume / 256.0 * sample.nGlobalVol / 64.0;
+            write_sfz_region(f, sample, sample_file(i), label, 0, 127, 60, gain);
+        } else {
+            // Keys mapped to the same sample with consecutive notes share a region, same as
+            // OpenMPT's own sfz export. Key k plays note NoteMap[k] which is the sample rate at
+            // middle C (note 61, MIDI 60) transposed by the distance to it
+            for (int key = 0; key < OpenMPT::NOTE_MAX; ++key) {
+                const int index = ins->Keyboard[key];
+                if (!exported(index) || !OpenMPT::ModCommand::IsNote(ins->NoteMap[key]))
+                    continue;
+
+                int end = key;
+                while (end + 1 < OpenMPT::NOTE_MAX && ins->Keyboard[end + 1] == index
+                       && ins->NoteMap[end + 1] == ins->NoteMap[key] + (end + 1 - key)) {
+                    end++;
+                }
+
+                const auto& sample = sf->GetSample(index);
+                const double gain = sample.nVolume / 256.0 * sample.nGlobalVol / 64.0 * ins->nGlobalVol / 64.0;
+                const int key_center = OpenMPT::NOTE_MIDDLEC + key - ins->NoteMap[key];
+
+                write_sfz_region(f, sample, sample_file(index), sample_name(index), key, end, key_center, gain);
+                key = end;
+            }
+        }
+
+        if (!f) {
+            printf("Failed to write instrument: %s\n", name.u8string().c_str());
+        }
+    }
+}
+
+// SoundFont 2 generator operators used by write_sf2
+enum Sf2Generator : uint16_t {
+    Sf2Gen_Pan = 17,
+    Sf2Gen_AttackVolEnv = 34,
+    Sf2Gen_HoldVolEnv = 35,
+    Sf2Gen_DecayVolEnv = 36,
+    Sf2Gen_SustainVolEnv = 37,
+    Sf2Gen_ReleaseVolEnv = 38,
+    Sf2Gen_Instrument = 41,
+    Sf2Gen_KeyRange = 43,
+    Sf2Gen_InitialAttenuation = 48,
+    Sf2Gen_CoarseTune = 51,
+    Sf2Gen_SampleId = 53,
+    Sf2Gen_SampleModes = 54,
+    Sf2Gen_OverridingRootKey = 58,
 };
 
+// Little endian RIFF data of an sf2 file
+struct Sf2Writer {
+    std::vector<uint8_t> data;
+
+    void u8(uint8_t v) { data.push_back(v); }
+    void u16(uint16_t v) { u8(v & 0xff); u8(v >> 8); }
+    void u32(uint32_t v) { u16(v & 0xffff); u16(v >> 16); }
+    void id(const char* fourcc) { data.insert(data.end(), fourcc, fourcc + 4); }
+
+    // Fixed size, zero padded name as used by the preset, instrument and sample headers
+    void name(const std::string& s, size_t size = 20) {
+        for (size_t i = 0; i < size; ++i)
+            u8(i + 1 < size && i < s.size() ? (uint8_t)s[i] : 0);
+    }
+
+    void chunk(const char* fourcc, const std::vector<uint8_t>& body) {
+        id(fourcc);
+        u32((uint32_t)body.size());
+        data.insert(data.end(), body.begin(), body.end());
+        if (body.size() & 1)
+            u8(0);
+    }
+
+    void list(const char* type, const std::vector<uint8_t>& body) {
+        id("LIST");
+        u32((uint32_t)body.size() + 4);
+        id(type);
+        data.insert(data.end(), body.begin(), body.end());
+    }
+};
+
+struct Sf2Gen {
+    uint16_t oper;
+    uint16_t amount;
+};
+
+// Time in timecents (1200 * log2 seconds) as used by the envelope generators
+static uint16_t sf2_timecents(double seconds) {
+    if (seconds < 0.001)
+        return (uint16_t)(int16_t)-12000;
+    return (uint16_t)(int16_t)std::clamp(1200.0 * std::log2(seconds), -12000.0, 8000.0);
+}
+
+// Attenuation in centibels of a linear gain
+static uint16_t sf2_centibels(double gain) {
+    if (gain <= 0.0)
+        return 1440;
+    return (uint16_t)std::clamp(-200.0 * std::log10(gain), 0.0, 1440.0);
+}
+
+// Approximate a tracker volume envelope and fadeout with the attack, decay, sustain and release
+// stages of sf2. The attack runs up to the loudest node, the decay from there to the sustain node
+// (or the last node) and the fadeout sets the release. Ticks are converted at the default tempo
+static void sf2_envelope(std::vector<Sf2Gen>& gens, const OpenMPT::ModInstrument* ins, double tick_seconds) {
+    const auto& env = ins->VolEnv;
+
+    if (env.dwFlags[OpenMPT::ENV_ENABLED] && env.size() >= 2) {
+        size_t peak = 0;
+        for (size_t i = 1; i < env.size(); ++i) {
+            if (env[i].value > env[peak].value)
+                peak = i;
+        }
+
+        size_t end = env.dwFlags[OpenMPT::ENV_SUSTAIN] ? std::min<size_t>(env.nSustainStart, env.size() - 1) : env.size() - 1;
+        end = std::max(end, peak);
+
+        const double peak_level = std::max<int>(env[peak].value, 1);
+        const uint16_t sustain = sf2_centibels(env[end].value / peak_level);
+        const double decay = (env[end].tick - env[peak].tick) * tick_seconds;
+
+        gens.push_back({ Sf2Gen_AttackVolEnv, sf2_timecents((env[peak].tick - env[0].tick) * tick_seconds) });
+        gens.push_back({ Sf2Gen_HoldVolEnv, sf2_timecents(0.0) });
+        // The sf2 decay time is for the full 144 dB so scale up the time to reach the sustain level
+        gens.push_back({ Sf2Gen_DecayVolEnv, sf2_timecents(sustain > 0 ? decay * 1440.0 / sustain : 0.0) });
+        gens.push_back({ Sf2Gen_SustainVolEnv, sustain });
+    }
+
+    if (ins->nFadeOut)
+        gens.push_back({ Sf2Gen_ReleaseVolEnv, sf2_timecents(32768.0 * tick_seconds / ins->nFadeOut) });
+}
+
+// Write <stem>.sf2 with a preset per instrument (or per sample for songs without instruments,
+// matching the instrument numbering used when rendering). sf2 samples are 16-bit mono so stereo
+// samples are mixed down. Each sample has a single loop, the sustain loop wins like for sfz
+static void write_sf2(const openmpt::module& song, const OpenMPT::CSoundFile* sf, const std::filesystem::path& output_with_stem) {
+    const std::vector<std::string> sample_names = song.get_sample_names();
+    const std::vector<std::string> instrument_names = song.get_instrument_names();
+    const OpenMPT::SAMPLEINDEX num_samples = sf->GetNumSamples();
+    const bool has_instruments = sf->GetNumInstruments() != 0;
+    const int count = has_instruments ? sf->GetNumInstruments() : num_samples;
+
+    const double tempo = sf->m_nDefaultTempo.ToDouble() > 0.0 ? sf->m_nDefaultTempo.ToDouble() : 125.0;
+    const double tick_seconds = 2.5 / tempo;
+
+    // Sample data and headers
+    Sf2Writer smpl;
+    Sf2Writer shdr;
+    std::vector<int> header_index(num_samples + 1, -1);
+    std::vector<uint16_t> sample_modes(num_samples + 1, 0);
+    uint32_t position = 0;
+    int headers = 0;
+
+    for (int i = 1; i < num_samples + 1; ++i) {
+        const auto& sample = sf->GetSample(i);
+        if (sample.nLength == 0 || !sample.HasSampleData() || sample.uFlags[OpenMPT::CHN_ADLIB])
+            continue;
+
+        const int channels = sample.GetNumChannels();
+        for (OpenMPT::SmpLength s = 0; s < sample.nLength; ++s) {
+            int32_t value = 0;
+            for (int c = 0; c < channels; ++c) {
+                const size_t index = (size_t)s * channels + c;
+                value += sample.uFlags[OpenMPT::CHN_16BIT] ? sample.sample16()[index] : sample.sample8()[index] * 256;
+            }
+            smpl.u16((uint16_t)(int16_t)(value / channels));
+        }
+
+        // The spec asks for 46 zero samples after each sample
+        for (int s = 0; s < 46; ++s)
+            smpl.u16(0);
+
+        OpenMPT::SmpLength loop_start = 0, loop_end = 0;
+        if (sample.uFlags[OpenMPT::CHN_SUSTAINLOOP]) {
+            loop_start = sample.nSustainStart;
+            loop_end = sample.nSustainEnd;
+            // Loop while the key is held, then play on to the end
+            sample_modes[i] = 3;
+        } else if (sample.uFlags[OpenMPT::CHN_LOOP]) {
+            loop_start = sample.nLoopStart;
+            loop_end = sample.nLoopEnd;
+            sample_modes[i] = 1;
+        }
+
+        if (loop_end <= loop_start)
+            sample_modes[i] = 0;
+
+        shdr.name(i <= (int)sample_names.size() ? sample_names[i - 1] : std::string());
+        shdr.u32(position);
+        shdr.u32(position + sample.nLength);
+        shdr.u32(position + loop_start);
+        shdr.u32(position + loop_end);
+        shdr.u32(sample.GetSampleRate(sf->GetType()));
+        // The rate is the one of middle C, with the finetune baked in
+        shdr.u8(60);
+        shdr.u8(0);
+        shdr.u16(0);
+        shdr.u16(1); // monoSample
+
+        position += sample.nLength + 46;
+        header_index[i] = headers++;
+    }
+
+    shdr.name("EOS");
+    for (int i = 0; i < 26; ++i)
+        shdr.u8(0);
+
+    // Instruments with a zone per key range
+    Sf2Writer inst, ibag, igen;
+    int bag_count = 0, gen_count = 0;
+
+    auto add_zone = [&](Sf2Writer& bag, Sf2Writer& gen, const std::vector<Sf2Gen>& gens) {
+        bag.u16((uint16_t)gen_count);
+        bag.u16(0);
+        for (const auto& g : gens) {
+            gen.u16(g.oper);
+            gen.u16(g.amount);
+        }
+        gen_count += (int)gens.size();
+        bag_count++;
+    };
+
+    auto zone = [&](int index, int lokey, int hikey, int root_key, double gain, int pan, const OpenMPT::ModInstrument* ins) {
+        const auto& sample = sf->GetSample(index);
+        std::vector<Sf2Gen> gens;
+
+        gens.push_back({ Sf2Gen_KeyRange, (uint16_t)(lokey | (hikey << 8)) });
+
+        if (sample.uFlags[OpenMPT::CHN_PANNING])
+            pan = sample.nPan;
+        if (pan >= 0)
+            gens.push_back({ Sf2Gen_Pan, (uint16_t)(int16_t)(pan * 1000 / 256 - 500) });
+
+        gens.push_back({ Sf2Gen_InitialAttenuation, sf2_centibels(gain) });
+
+        if (ins != nullptr)
+            sf2_envelope(gens, ins, tick_seconds);
+
+        gens.push_back({ Sf2Gen_SampleModes, sample_modes[index] });
+
+        // Root keys outside the MIDI range are moved into it with a coarse tune
+        const int clamped = std::clamp(root_key, 0, 127);
+        if (clamped != root_key)
+            gens.push_back({ Sf2Gen_CoarseTune, (uint16_t)(int16_t)(clamped - root_key) });
+        gens.push_back({ Sf2Gen_OverridingRootKey, (uint16_t)clamped });
+
+        gens.push_back({ Sf2Gen_SampleId, (uint16_t)header_index[index] });
+        add_zone(ibag, igen, gens);
+    };
+
+    std::vector<std::pair<std::string, int>> instruments;
+
+    for (int i = 1; i < count + 1; ++i) {
+        const int first_bag = bag_count;
+        std::string label;
+
+        if (!has_instruments) {
+            if (i > num_samples || header_index[i] < 0)
+                continue;
+
+            const auto& sample = sf->GetSample(i);
+            label = i <= (int)sample_names.size() ? sample_names[i - 1] : std::string();
+            zone(i, 0, 127, 60, sample.nVolume / 256.0 * sample.nGlobalVol / 64.0, -1, nullptr);
+        } else {
+            const OpenMPT::ModInstrument* ins = sf->Instruments[i];
+            if (ins == nullptr)
+                continue;
+
+            label = i <= (int)instrument_names.size() ? instrument_names[i - 1] : std::string();
+            const int pan = ins->dwFlags[OpenMPT::INS_SETPANNING] ? (int)ins->nPan : -1;
+
+            // Same key ranges as the sfz export
+            for (int key = 0; key < OpenMPT::NOTE_MAX; ++key) {
+                const int index = ins->Keyboard[key];
+                if (index < 1 || index > num_samples || header_index[index] < 0 || !OpenMPT::ModCommand::IsNote(ins->NoteMap[key]))
+                    continue;
+
+                int end = key;
+                while (end + 1 < OpenMPT::NOTE_MAX && ins->Keyboard[end + 1] == index
+                       && ins->NoteMap[end + 1] == ins->NoteMap[key] + (end + 1 - key)) {
+                    end++;
+                }
+
+                const auto& sample = sf->GetSample(index);
+                const double gain = sample.nVolume / 256.0 * sample.nGlobalVol / 64.0 * ins->nGlobalVol / 64.0;
+                zone(index, key, end, OpenMPT::NOTE_MIDDLEC + key - ins->NoteMap[key], gain, pan, ins);
+                key = end;
+            }
+        }
+
+        if (bag_count == first_bag)
+            continue;
+
+        if (label.empty())
+            label = "Instrument " + std::to_string(i);
+
+        inst.name(label);
+        inst.u16((uint16_t)first_bag);
+        instruments.emplace_back(label, i);
+    }
+
+    inst.name("EOI");
+    inst.u16((uint16_t)bag_count);
+    ibag.u16((uint16_t)gen_count);
+    ibag.u16(0);
+    igen.u32(0);
+
+    // A preset per instrument, numbered after the song instrument
+    Sf2Writer phdr, pbag, pgen;
+    bag_count = 0;
+    gen_count = 0;
+
+    for (size_t i = 0; i < instruments.size(); ++i) {
+        const int number = instruments[i].second - 1;
+        phdr.name(instruments[i].first);
+        phdr.u16((uint16_t)(number % 128));
+        phdr.u16((uint16_t)(number / 128));
+        phdr.u16((uint16_t)bag_count);
+        phdr.u32(0);
+        phdr.u32(0);
+        phdr.u32(0);
+        add_zone(pbag, pgen, { { Sf2Gen_Instrument, (uint16_t)i } });
+    }
+
+    phdr.name("EOP");
+    phdr.u16(0);
+    phdr.u16(0);
+    phdr.u16((uint16_t)bag_count);
+    phdr.u32(0);
+    phdr.u32(0);
+    phdr.u32(0);
+    pbag.u16((uint16_t)gen_count);
+    pbag.u16(0);
+    pgen.u32(0);
+
+    // Empty modulator lists only hold the terminal record
+    const std::vector<uint8_t> no_modulators(10, 0);
+
+    // Text fields are zero terminated and padded to an even size
+    auto text = [](const std::string& s) {
+        std::vector<uint8_t> data(s.begin(), s.end());
+        data.resize((s.size() + 2) & ~(size_t)1, 0);
+        return data;
+    };
+
+    Sf2Writer version;
+    version.u16(2);
+    version.u16(1);
+
+    std::string title = song.get_metadata("title");
+    if (title.empty())
+        title = "stemgen";
+
+    Sf2Writer info;
+    info.chunk("ifil", version.data);
+    info.chunk("isng", text("EMU8000"));
+    info.chunk("INAM", text(title.substr(0, 255)));
+    info.chunk("ISFT", text("stemgen"));
+
+    Sf2Writer sdta;
+    sdta.chunk("smpl", smpl.data);
+
+    Sf2Writer pdta;
+    pdta.chunk("phdr", phdr.data);
+    pdta.chunk("pbag", pbag.data);
+    pdta.chunk("pmod", no_modulators);
+    pdta.chunk("pgen", pgen.data);
+    pdta.chunk("inst", inst.data);
+    pdta.chunk("ibag", ibag.data);
+    pdta.chunk("imod", no_modulators);
+    pdta.chunk("igen", igen.data);
+    pdta.chunk("shdr", shdr.data);
+
+    Sf2Writer body;
+    body.id("sfbk");
+    body.list("INFO", info.data);
+    body.list("sdta", sdta.data);
+    body.list("pdta", pdta.data);
+
+    std::filesystem::path name = output_with_stem;
+    name += ".sf2";
+    std::ofstream f(name, std::ios::binary);
+
+    Sf2Writer riff;
+    riff.id("RIFF");
+    riff.u32((uint32_t)body.data.size());
+    f.write((const char*)riff.data.data(), riff.data.size());
+    f.write((const char*)body.data.data(), body.data.size());
+
+    if (!f) {
+        printf("Failed to write SoundFont: %s\n", name.u8string().c_str());
+    }
+}
+
 extern "C"
 {
 
-SongInfo get_song_info_c(const uint8_t* buffer, uint32_t len, const char* output_with_stem, int sample_format) {
-    SongInfo info = { 0, 0, 0.0f };
+// The duration estimate is capped at max_seconds (--max-duration on the Rust side) as anything
+// longer is considered broken
+SongInfo get_song_info_c(const uint8_t* buffer, uint32_t len, const PathChar* output_with_stem, int sample_format,
+                         float max_seconds) {
+    SongInfo info = {};
 
     try
     {
         openmpt::detail::initial_ctls_map ctls;
         ctls["load.skip_plugins"] = "1";
+        // Decoding sample data is the slowest part of loading and only needed when writing the samples
+        if (!output_with_stem) {
+            ctls["load.skip_samples"] = "1";
+        }
         openmpt::module song(buffer, (size_t)len, std::clog, ctls);
 
         info.num_channels = song.get_num_channels();
@@ -57,16 +908,30 @@ SongInfo get_song_info_c(const uint8_t* buffer, uint32_t len, const char* output
 
         info.length_seconds = (float)song.get_duration_seconds();
 
+        OpenMPT::CSoundFile* sf = song.get_snd_file();
+
+        find_channels_with_notes(sf, info);
+
+        // The estimate walks the song a second time and is only used when the duration from
+        // libopenmpt looks broken (see SongInfo::checked_duration on the Rust side)
+        if (!std::isfinite(info.length_seconds) || info.length_seconds <= 0.0f || info.length_seconds > max_seconds) {
+            info.estimated_seconds = (float)estimate_duration_seconds(sf, max_seconds);
+        }
+
         if (!output_with_stem) 
             return info;
 
-        OpenMPT::CSoundFile* sf = song.get_snd_file();
+        const std::filesystem::path stem(output_with_stem);
+
+        // The SoundFont holds the samples itself
+        if (sample_format == SampleFormat_Sf2) {
+            write_sf2(song, sf, stem);
+            return info;
+        }
 
         int num_samples = sf->GetNumSamples();
 
         for (int i = 1; i < num_samples + 1; ++i) {
-            char name[4096];
-
             // Skip zero sized samples
 	        const auto& sample = sf->GetSample(i);
 
@@ -74,29 +939,29 @@ SongInfo get_song_info_c(const uint8_t* buffer, uint32_t len, const char* output
                 continue;
 
             if (sample_format == SampleFormat_Flac) {
-                sprintf(name, "%s_sample_%04d.flac", output_with_stem, i);
+                const std::filesystem::path name = path_with_suffix(stem, "_sample_%04d.flac", i);
                 std::ofstream f(name, std::ios::binary);
                 if (!sf->SaveFLACSample(i, f)) {
-                    printf("Failed to write sample: %s\n", name);
+                    printf("Failed to write sample: %s\n", name.u8string().c_str());
+                }
+            } else if (sample_format == SampleFormat_Iff) {
+                const std::filesystem::path name = path_with_suffix(stem, "_sample_%04d.iff", i);
+                std::ofstream f(name, std::ios::binary);
+                if (!sf->SaveIFFSample(i, f)) {
+                    printf("Failed to write sample: %s\n", name.u8string().c_str());
                 }
             } else {
-                sprintf(name, "%s_sample_%04d.wav", output_with_stem, i);
+                const std::filesystem::path name = path_with_suffix(stem, "_sample_%04d.wav", i);
                 std::ofstream f(name, std::ios::binary);
                 if (!sf->SaveWAVSample(i, f)) {
-                    printf("Failed to write sample: %s\n", name);
+                    printf("Failed to write sample: %s\n", name.u8string().c_str());
                 }
             }
         }
 
-        /*
-        for (int i = 1; i < info.num_instruments + 1; ++i) {
-            char name[4096];
-            sprintf(name, "%s_song_inst_%04d.sfz", output_with_stem, i);
-            std::ofstream f(name);
-            OpenMPT::mpt::PathString mpt_string = OpenMPT::mpt::PathString::FromUTF8(name); 
-            sf->SaveSFZInstrument(i, f, mpt_string, false);
+        if (sample_format == SampleFormat_Sfz) {
+            write_sfz_instruments(song, sf, stem);
         }
-        */
     }
     catch (const std::exception&)
     {
@@ -105,19 +970,498 @@ SongInfo get_song_info_c(const uint8_t* buffer, uint32_t len, const char* output
     return info;
 }
 
-uint32_t song_render_c(
-    uint8_t* output, uint32_t output_len, 
-    const uint8_t* input, uint32_t len, 
-    RenderParams& params)
+uint32_t get_subsongs_c(const uint8_t* buffer, uint32_t len, SubsongInfo* output, uint32_t max_count) {
+    uint32_t count = 0;
+
+    try
+    {
+        openmpt::detail::initial_ctls_map ctls;
+        ctls["load.skip_plugins"] = "1";
+        ctls["load.skip_samples"] = "1";
+        openmpt::module song(buffer, (size_t)len, std::clog, ctls);
+
+        std::vector<std::string> names = song.get_subsong_names();
+        int32_t num_subsongs = song.get_num_subsongs();
+
+        for (int32_t i = 0; i < num_subsongs && count < max_count; ++i) {
+            SubsongInfo& info = output[count++];
+
+            song.select_subsong(i);
+
+            memset(info.name, 0, sizeof(info.name));
+            if (i < (int32_t)names.size()) {
+                strncpy(info.name, names[i].c_str(), sizeof(info.name) - 1);
+            }
+
+            info.duration_seconds = (float)song.get_duration_seconds();
+        }
+    }
+    catch (const std::exception&)
+    {
+    }
+
+    return count;
+}
+
+static void copy_string(char* dest, size_t size, const std::string& src) {
+    memset(dest, 0, size);
+    strncpy(dest, src.c_str(), size - 1);
+}
+
+// Fills in the song metadata and returns the number of instrument names written. Songs without
+// instruments (such as mod) use the sample names to match the instrument numbering used when rendering.
+// sample_names gets the plain sample names (up to max_count as well)
+uint32_t get_song_metadata_c(const uint8_t* buffer, uint32_t len, SongMetadata* output, ItemName* instrument_names, ItemName* sample_names, uint32_t max_count) {
+    uint32_t count = 0;
+
+    memset(output, 0, sizeof(SongMetadata));
+
+    try
+    {
+        // Only names and texts are read so the subsong durations (which walk the whole song)
+        // aren't needed. load.skip_patterns isn't set: with it this libopenmpt returns an empty
+        // format and title for MODs and a broken sample list
+        openmpt::detail::initial_ctls_map ctls;
+        ctls["load.skip_plugins"] = "1";
+        ctls["load.skip_samples"] = "1";
+        ctls["load.skip_subsongs_init"] = "1";
+        openmpt::module song(buffer, (size_t)len, std::clog, ctls);
+
+        copy_string(output->title, sizeof(output->title), song.get_metadata("title"));
+        copy_string(output->type, sizeof(output->type), song.get_metadata("type"));
+        copy_string(output->type_long, sizeof(output->type_long), song.get_metadata("type_long"));
+        copy_string(output->tracker, sizeof(output->tracker), song.get_metadata("tracker"));
+        copy_string(output->artist, sizeof(output->artist), song.get_metadata("artist"));
+
+        copy_string(output->message, sizeof(output->message), song.get_metadata("message_raw"));
+
+        std::string sample_text;
+        for (const auto& name : song.get_instrument_names()) {
+            sample_text += name + "\n";
+        }
+        for (const auto& name : song.get_sample_names()) {
+            sample_text += name + "\n";
+        }
+        copy_string(output->sample_text, sizeof(output->sample_text), sample_text);
+
+        std::vector<std::string> names = song.get_num_instruments() != 0 ? song.get_instrument_names() : song.get_sample_names();
+
+        for (size_t i = 0; i < names.size() && count < max_count; ++i) {
+            copy_string(instrument_names[count++].name, sizeof(ItemName::name), names[i]);
+        }
+
+        for (const auto& name : song.get_sample_names()) {
+            if (output->sample_name_count >= max_count)
+                break;
+            copy_string(sample_names[output->sample_name_count++].name, sizeof(ItemName::name), name);
+        }
+    }
+    catch (const std::exception&)
+    {
+    }
+
+    return count;
+}
+
+static void copy_envelope(EnvelopeInfo& output, const OpenMPT::InstrumentEnvelope& envelope) {
+    using namespace OpenMPT;
+
+    output.point_count = std::min<uint32_t>(envelope.size(), MAX_ENVELOPE_POINTS);
+    for (uint32_t i = 0; i < output.point_count; ++i) {
+        output.ticks[i] = envelope[i].tick;
+        output.values[i] = envelope[i].value;
+    }
+
+    output.enabled = envelope.dwFlags[ENV_ENABLED];
+    output.loop = envelope.dwFlags[ENV_LOOP];
+    output.sustain = envelope.dwFlags[ENV_SUSTAIN];
+    output.carry = envelope.dwFlags[ENV_CARRY];
+    output.filter = envelope.dwFlags[ENV_FILTER];
+    output.loop_start = envelope.nLoopStart;
+    output.loop_end = envelope.nLoopEnd;
+    output.sustain_start = envelope.nSustainStart;
+    output.sustain_end = envelope.nSustainEnd;
+}
+
+// Fills in the settings of the instruments and samples. Returns the number of instruments, which
+// is 0 for songs that only use samples. The number of samples is written to sample_count
+uint32_t get_instruments_c(const uint8_t* buffer, uint32_t len, InstrumentInfo* instruments, uint32_t max_instruments, SampleInfo* samples, uint32_t max_samples, uint32_t* sample_count) {
+    using namespace OpenMPT;
+
+    uint32_t count = 0;
+    *sample_count = 0;
+
+    try
+    {
+        openmpt::detail::initial_ctls_map ctls;
+        ctls["load.skip_plugins"] = "1";
+        ctls["load.skip_subsongs_init"] = "1";
+        openmpt::module song(buffer, (size_t)len, std::clog, ctls);
+
+        CSoundFile* sf = song.get_snd_file();
+        const std::vector<std::string> instrument_names = song.get_instrument_names();
+        const std::vector<std::string> sample_names = song.get_sample_names();
+
+        for (INSTRUMENTINDEX ins = 1; ins <= sf->GetNumInstruments() && count < max_instruments; ++ins) {
+            InstrumentInfo& output = instruments[count++];
+            memset(&output, 0, sizeof(InstrumentInfo));
+
+            const ModInstrument* instrument = sf->Instruments[ins];
+            if (ins - 1u < instrument_names.size())
+                copy_string(output.name, sizeof(output.name), instrument_names[ins - 1]);
+
+            // Slots between used instruments can be empty
+            if (!instrument) {
+                output.panning = -1;
+                continue;
+            }
+
+            output.fadeout = instrument->nFadeOut;
+            output.global_volume = instrument->nGlobalVol;
+            output.panning = instrument->dwFlags[INS_SETPANNING] ? (int32_t)instrument->nPan : -1;
+            output.new_note_action = (uint8_t)instrument->nNNA;
+            output.duplicate_check = (uint8_t)instrument->nDCT;
+            output.duplicate_action = (uint8_t)instrument->nDNA;
+            output.pitch_pan_separation = instrument->nPPS;
+            output.pitch_pan_center = instrument->nPPC;
+            output.random_volume = instrument->nVolSwing;
+            output.random_pan = instrument->nPanSwing;
+
+            for (int key = 0; key < 128; ++key) {
+                const ModCommand::NOTE note = instrument->NoteMap[key];
+                output.sample_map[key] = instrument->Keyboard[key];
+                output.note_map[key] = ModCommand::IsNote(note) ? (uint8_t)(note - NOTE_MIN) : (uint8_t)key;
+            }
+
+            copy_envelope(output.volume_envelope, instrument->VolEnv);
+            copy_envelope(output.panning_envelope, instrument->PanEnv);
+            copy_envelope(output.pitch_envelope, instrument->PitchEnv);
+        }
+
+        for (SAMPLEINDEX smp = 1; smp <= sf->GetNumSamples() && *sample_count < max_samples; ++smp) {
+            SampleInfo& output = samples[(*sample_count)++];
+            memset(&output, 0, sizeof(SampleInfo));
+
+            const ModSample& sample = sf->GetSample(smp);
+            if (smp - 1u < sample_names.size())
+                copy_string(output.name, sizeof(output.name), sample_names[smp - 1]);
+
+            output.length = sample.nLength;
+            output.loop_start = sample.nLoopStart;
+            output.loop_end = sample.nLoopEnd;
+            output.sustain_start = sample.nSustainStart;
+            output.sustain_end = sample.nSustainEnd;
+            output.c5_speed = sample.GetSampleRate(sf->GetType());
+            output.volume = sample.nVolume;
+            output.global_volume = sample.nGlobalVol;
+            output.panning = sample.uFlags[CHN_PANNING] ? (int32_t)sample.nPan : -1;
+            output.relative_tone = sample.RelativeTone;
+            output.finetune = sample.nFineTune;
+            output.loop = sample.uFlags[CHN_LOOP];
+            output.ping_pong_loop = sample.uFlags[CHN_PINGPONGLOOP];
+            output.sustain_loop = sample.uFlags[CHN_SUSTAINLOOP];
+            output.ping_pong_sustain_loop = sample.uFlags[CHN_PINGPONGSUSTAIN];
+            output.vibrato_type = (uint8_t)sample.nVibType;
+            output.vibrato_sweep = sample.nVibSweep;
+            output.vibrato_depth = sample.nVibDepth;
+            output.vibrato_rate = sample.nVibRate;
+        }
+    }
+    catch (const std::exception&)
+    {
+    }
+
+    return count;
+}
+
+// Fills in the name and number of rows of each pattern and the order list of the default sequence
+// (-1 for +++ and --- markers). Returns the number of patterns
+uint32_t get_patterns_c(const uint8_t* buffer, uint32_t len, PatternSummary* summary, PatternInfo* patterns, uint32_t max_patterns, int32_t* orders, uint32_t max_orders) {
+    using namespace OpenMPT;
+
+    uint32_t count = 0;
+
+    memset(summary, 0, sizeof(PatternSummary));
+
+    try
+    {
+        openmpt::detail::initial_ctls_map ctls;
+        ctls["load.skip_plugins"] = "1";
+        ctls["load.skip_samples"] = "1";
+        ctls["load.skip_subsongs_init"] = "1";
+        openmpt::module song(buffer, (size_t)len, std::clog, ctls);
+
+        CSoundFile* sf = song.get_snd_file();
+        const ModSequence& order = sf->Order();
+
+        summary->channel_count = sf->GetNumChannels();
+        summary->initial_speed = sf->m_nDefaultSpeed;
+        summary->initial_tempo = (float)sf->m_nDefaultTempo.ToDouble();
+        summary->rows_per_beat = sf->m_nDefaultRowsPerBeat;
+        summary->rows_per_measure = sf->m_nDefaultRowsPerMeasure;
+
+        for (PATTERNINDEX pat = 0; pat < sf->Patterns.Size() && count < max_patterns; ++pat) {
+            PatternInfo& output = patterns[count++];
+            memset(&output, 0, sizeof(PatternInfo));
+
+            if (!sf->Patterns.IsValidPat(pat))
+                continue;
+
+            copy_string(output.name, sizeof(output.name), sf->Patterns[pat].GetName());
+            output.rows = sf->Patterns[pat].GetNumRows();
+        }
+
+        for (ORDERINDEX ord = 0; ord < order.GetLengthTailTrimmed() && summary->order_count < max_orders; ++ord) {
+            orders[summary->order_count++] = sf->Patterns.IsValidPat(order[ord]) ? (int32_t)order[ord] : -1;
+        }
+    }
+    catch (const std::exception&)
+    {
+    }
+
+    return count;
+}
+
+// Fills in the cells of the patterns in the same order as get_patterns_c, a row at a time with a
+// cell per channel. Returns the number of cells
+uint32_t get_pattern_cells_c(const uint8_t* buffer, uint32_t len, PatternCell* cells, uint32_t max_cells) {
+    using namespace OpenMPT;
+
+    uint32_t count = 0;
+
+    try
+    {
+        openmpt::detail::initial_ctls_map ctls;
+        ctls["load.skip_plugins"] = "1";
+        ctls["load.skip_samples"] = "1";
+        ctls["load.skip_subsongs_init"] = "1";
+        openmpt::module song(buffer, (size_t)len, std::clog, ctls);
+
+        CSoundFile* sf = song.get_snd_file();
+        const CModSpecifications& specs = sf->GetModSpecifications();
+
+        for (PATTERNINDEX pat = 0; pat < sf->Patterns.Size(); ++pat) {
+            if (!sf->Patterns.IsValidPat(pat))
+                continue;
+
+            for (const ModCommand& cell : sf->Patterns[pat]) {
+                if (count >= max_cells)
+                    return count;
+
+                PatternCell& output = cells[count++];
+                memset(&output, 0, sizeof(PatternCell));
+
+                output.note = cell.note;
+                output.instrument = cell.instr;
+                output.volume = cell.vol;
+                output.param = cell.param;
+
+                if (cell.IsNote() || cell.IsSpecialNote()) {
+                    const std::string name = OpenMPT::mpt::ToCharset(OpenMPT::mpt::Charset::UTF8, sf->GetNoteName(cell.note, cell.instr));
+                    copy_string(output.note_name, sizeof(output.note_name), name);
+                }
+
+                if (cell.volcmd != VOLCMD_NONE)
+                    output.volume_command = specs.GetVolEffectLetter(cell.volcmd);
+                if (cell.command != CMD_NONE)
+                    output.effect = specs.GetEffectLetter(cell.command);
+            }
+        }
+    }
+    catch (const std::exception&)
+    {
+    }
+
+    return count;
+}
+
+// Plays the subsong (-1 for the default one, SUBSONG_ALL for all of them) once and fills in
+// the time of every speed, tempo or rows per beat change, starting with the values at the start.
+// Returns the number of changes
+uint32_t get_tempo_map_c(const uint8_t* buffer, uint32_t len, int32_t subsong, TempoChange* output, uint32_t max_count) {
+    using namespace OpenMPT;
+
+    // Only the play position matters so the rate is kept low, but high enough for the BPM
+    // calculated from the samples per tick to be accurate
+    const int32_t sample_rate = 48000;
+    const size_t step_frames = 480;
+
+    uint32_t count = 0;
+
+    try
+    {
+        openmpt::detail::initial_ctls_map ctls;
+        ctls["load.skip_plugins"] = "1";
+        ctls["play.at_end"] = "stop";
+        openmpt::module_ext song(buffer, (size_t)len, std::clog, ctls);
+
+        if (subsong >= 0) {
+            song.select_subsong(subsong);
+        } else if (subsong == SUBSONG_ALL) {
+            song.select_subsong(-1);
+        }
+
+        song.set_repeat_count(0);
+        song.set_render_param(openmpt::module::render_param::RENDER_INTERPOLATIONFILTER_LENGTH, 1);
+
+        CSoundFile* sf = song.get_snd_file();
+        std::vector<float> scratch(step_frames);
+        uint64_t frames = 0;
+
+        while (count < max_count) {
+            const uint32_t speed = (uint32_t)song.get_current_speed();
+            const float tempo = (float)song.get_current_tempo2();
+            const uint32_t rows_per_beat = sf->m_PlayState.m_nCurrentRowsPerBeat;
+
+            const bool changed = count == 0
+                || output[count - 1].speed != speed
+                || output[count - 1].tempo != tempo
+                || output[count - 1].rows_per_beat != rows_per_beat;
+
+            if (changed && speed != 0) {
+                TempoChange& change = output[count++];
+                change.seconds = (double)frames / sample_rate;
+                change.bpm = song.get_current_estimated_bpm();
+                change.speed = speed;
+                change.tempo = tempo;
+                change.rows_per_beat = rows_per_beat;
+            }
+
+            const size_t rendered = song.read(sample_rate, step_frames, scratch.data());
+            if (rendered == 0)
+                break;
+
+            frames += rendered;
+        }
+    }
+    catch (const std::exception&)
+    {
+    }
+
+    return count;
+}
+
+// Fills in the time at which each order position of the subsong is first reached (in order list
+// order) and returns the number of entries. Positions that are never played are left out.
+// subsong -1 uses the default subsong
+uint32_t get_order_times_c(const uint8_t* buffer, uint32_t len, int32_t subsong, OrderTime* output, uint32_t max_count) {
+    using namespace OpenMPT;
+
+    uint32_t count = 0;
+
+    try
+    {
+        openmpt::detail::initial_ctls_map ctls;
+        ctls["load.skip_plugins"] = "1";
+        ctls["load.skip_samples"] = "1";
+        openmpt::module song(buffer, (size_t)len, std::clog, ctls);
+
+        CSoundFile* sf = song.get_snd_file();
+
+        // Same subsong numbering as libopenmpt uses for select_subsong
+        std::vector<std::pair<SEQUENCEINDEX, GetLengthType>> subsongs;
+        for (SEQUENCEINDEX seq = 0; seq < sf->Order.GetNumSequences(); ++seq) {
+            for (const auto& length : sf->GetLength(eNoAdjust, GetLengthTarget(true).StartPos(seq, 0, 0))) {
+                subsongs.push_back(std::make_pair(seq, length));
+            }
+        }
+
+        const size_t index = subsong < 0 ? 0 : (size_t)subsong;
+        if (index >= subsongs.size())
+            return 0;
+
+        const SEQUENCEINDEX seq = subsongs[index].first;
+        const GetLengthType& start = subsongs[index].second;
+        const ModSequence& order = sf->Order(seq);
+
+        for (ORDERINDEX ord = 0; ord < order.GetLengthTailTrimmed() && count < max_count; ++ord) {
+            if (!sf->Patterns.IsValidPat(order[ord]))
+                continue;
+
+            const GetLengthType length = sf->GetLength(eNoAdjust, GetLengthTarget(ord, 0).StartPos(seq, start.startOrder, start.startRow)).back();
+
+            if (!length.targetReached)
+                continue;
+
+            OrderTime& time = output[count++];
+            time.order = ord;
+            time.pattern = order[ord];
+            time.seconds = (float)length.duration;
+        }
+    }
+    catch (const std::exception&)
+    {
+    }
+
+    return count;
+}
+
+// Song that is being rendered. The render is read in chunks so the whole song never has to be
+// kept in memory
+struct SongStream {
+    SongStream(const uint8_t* input, uint32_t len, const openmpt::detail::initial_ctls_map& ctls, const RenderParams& params)
+        : song(input, (size_t)len, std::clog, ctls), params(params) {}
+
+    openmpt::module_ext song;
+    RenderParams params;
+    // Length of the render worked out when the stream is opened, 0 if the song plays forever
+    uint64_t frame_count = 0;
+    uint64_t frames_generated = 0;
+    bool finished = false;
+};
+
+// Number of frames a render of a song lasting duration_seconds produces with params, rounded up
+// and cut to max_frames. 0 if the song plays forever
+uint64_t render_frame_count_c(double duration_seconds, const RenderParams& params)
+{
+    uint64_t frames = 0;
+
+    if (params.repeat_count >= 0) {
+        double first_pass = std::max(0.0, duration_seconds - params.start_seconds);
+        double seconds = first_pass + duration_seconds * params.repeat_count;
+        frames = (uint64_t)std::ceil(seconds * params.sample_rate);
+    }
+
+    if (params.max_frames != 0 && (frames == 0 || params.max_frames < frames))
+        frames = params.max_frames;
+
+    return frames;
+}
+
+// Number of frames a render of the selected subsong produces, from the song length libopenmpt
+// calculates
+static uint64_t stream_frame_count(SongStream* stream)
+{
+    // Without a length only max_frames limits the render
+    double duration = 0.0;
+
+    try
+    {
+        if (stream->params.repeat_count >= 0)
+            duration = stream->song.get_duration_seconds();
+    }
+    catch (const std::exception& e)
+    {
+    }
+
+    return render_frame_count_c(duration, stream->params);
+}
+
+SongStream* song_open_c(const uint8_t* input, uint32_t len, RenderParams& params)
 {
     try
     {
         openmpt::detail::initial_ctls_map ctls;
         ctls["play.at_end"] = "stop";
-        openmpt::module_ext song(input, (size_t)len, std::clog, ctls);
-        int16_t* output_16bit = (int16_t*)output;
-        float* output_float = (float*)output;
-        uint32_t samples_generated = 0;
+
+        // Paula emulation for Amiga formats (such as mod) with the selected filter model
+        if (params.amiga_filter != AMIGA_FILTER_DEFAULT) {
+            ctls["render.resampler.emulate_amiga"] = "1";
+            ctls["render.resampler.emulate_amiga_type"] = amiga_filter_name(params.amiga_filter);
+        }
+
+        std::unique_ptr<SongStream> stream(new SongStream(input, len, ctls, params));
+        openmpt::module_ext& song = stream->song;
         uint32_t sample_rate = params.sample_rate;
 
         int num_channels = song.get_num_channels();
@@ -128,13 +1472,71 @@ uint32_t song_render_c(
             instrument_count = song.get_num_samples();
         }
 
+        if (params.subsong >= 0) {
+            song.select_subsong(params.subsong);
+        } else if (params.subsong == SUBSONG_ALL) {
+            song.select_subsong(-1);
+        }
+
+        song.set_repeat_count(params.repeat_count);
+
+        // Make dithering and random effects (such as random waveforms) reproducible
+        if (params.dither_seed_enabled) {
+            song.set_prng_seed(params.dither_seed);
+        }
+
+        // Seeking only lands on row starts and may end up after the requested time, so seek
+        // further back until the position is before it and skip the remaining frames below
+        uint32_t skip_frames = 0;
+        if (params.start_seconds > 0.0f) {
+            double target = params.start_seconds;
+            double position = song.set_position_seconds(target);
+
+            while (position > params.start_seconds && target > 0.0) {
+                target = std::max(0.0, target - 1.0);
+                position = song.set_position_seconds(target);
+            }
+
+            if (position < params.start_seconds)
+                skip_frames = (uint32_t)((params.start_seconds - position) * sample_rate);
+        }
+
         if (params.stereo_separation_enabled) {
             song.set_render_param(openmpt::module::RENDER_STEREOSEPARATION_PERCENT, params.stereo_separation);
         }
 
+        if (params.master_gain_millibel != 0) {
+            song.set_render_param(openmpt::module::RENDER_MASTERGAIN_MILLIBEL, params.master_gain_millibel);
+        }
+
+        if (params.interpolation_filter_length > 0) {
+            song.set_render_param(openmpt::module::RENDER_INTERPOLATIONFILTER_LENGTH, params.interpolation_filter_length);
+        }
+
         openmpt::ext::interactive* interactive = static_cast<openmpt::ext::interactive*>(song.get_interface(openmpt::ext::interactive_id));
         openmpt::ext::interactive2* interactive2 = static_cast<openmpt::ext::interactive2*>(song.get_interface(openmpt::ext::interactive2_id));
 
+        // Pan map. The initial channel settings are used when the song restarts and the play state
+        // is updated as the song has already been reset (or seeked) at this point
+        OpenMPT::CSoundFile* sf = song.get_snd_file();
+        for (int i = 0; i < num_channels && i < MAX_CHANNELS; ++i) {
+            OpenMPT::ModChannel& chn = sf->m_PlayState.Chn[i];
+
+            if (params.channel_pan_mapped[i]) {
+                uint16_t pan = (uint16_t)std::lround((std::clamp(params.channel_pan[i], -1.0f, 1.0f) + 1.0f) * 128.0f);
+                sf->ChnSettings[i].nPan = pan;
+                sf->ChnSettings[i].dwFlags.reset(OpenMPT::CHN_SURROUND);
+                chn.nPan = pan;
+                chn.dwFlags.reset(OpenMPT::CHN_SURROUND);
+            }
+
+            if (params.channel_volume_mapped[i]) {
+                uint16_t volume = (uint16_t)std::lround(std::clamp(params.channel_volume[i], 0.0f, 1.0f) * 64.0f);
+                sf->ChnSettings[i].nVolume = volume;
+                chn.nGlobalVol = volume;
+            }
+        }
+
         if (params.channel_to_play >= 0 && interactive != nullptr) {
             // Deactivate all channels execpt the one we care about
             for (int i = 0; i < num_channels; ++i) {
@@ -157,59 +1559,144 @@ uint32_t song_render_c(
             }
         }
 
-        if (params.bytes_per_sample == 2) {
-            for (uint32_t i = 0; i < output_len; i += sample_rate) {
-                uint32_t gen_count = 0;
-
-                if (params.stereo_output) {
-                    gen_count = (uint32_t)song.read_interleaved_stereo(sample_rate, sample_rate, output_16bit);
-                    output_16bit += sample_rate * 2;
-                }
-                else {
-                    gen_count = (uint32_t)song.read(sample_rate, sample_rate, output_16bit);
-                    output_16bit += params.sample_rate;
-                }
-
-                samples_generated += gen_count;
+        if (skip_frames > 0) {
+            std::vector<float> scratch(std::min(skip_frames, sample_rate));
 
-                // if we don't get the number of samples we requested we are at the end
-                if (gen_count != sample_rate)
+            while (skip_frames > 0) {
+                uint32_t request = std::min(skip_frames, (uint32_t)scratch.size());
+                if (song.read(sample_rate, request, scratch.data()) != request)
                     break;
+                skip_frames -= request;
             }
-        } else {
-            for (uint32_t i = 0; i < output_len; i += sample_rate) {
-                uint32_t gen_count = 0;
+        }
+
+        // Worked out once the subsong and the soloed channel or instrument are set, so every
+        // read is cut to the length of this selection
+        stream->frame_count = stream_frame_count(stream.get());
 
+        return stream.release();
+    }
+    catch (const std::exception& e)
+    {
+    }
+
+    return nullptr;
+}
+
+// Render up to max_frames frames into output and return the number of frames rendered. Fewer
+// frames than requested means the song has ended
+uint32_t song_read_c(SongStream* stream, uint8_t* output, uint32_t max_frames)
+{
+    if (stream == nullptr || stream->finished)
+        return 0;
+
+    RenderParams& params = stream->params;
+    openmpt::module_ext& song = stream->song;
+    int16_t* output_16bit = (int16_t*)output;
+    float* output_float = (float*)output;
+    uint32_t samples_generated = 0;
+    uint32_t sample_rate = params.sample_rate;
+    uint32_t output_channels = params.stereo_output ? 2 : 1;
+
+    if (stream->frame_count != 0)
+        max_frames = (uint32_t)std::min((uint64_t)max_frames, stream->frame_count - stream->frames_generated);
+
+    try
+    {
+        while (samples_generated < max_frames) {
+            uint32_t request = std::min(sample_rate, max_frames - samples_generated);
+            uint32_t gen_count = 0;
+
+            if (params.bytes_per_sample == 2) {
                 if (params.stereo_output) {
-                    gen_count = (uint32_t)song.read_interleaved_stereo(sample_rate, sample_rate, output_float);
-                    output_float += sample_rate * 2;
+                    gen_count = (uint32_t)song.read_interleaved_stereo(sample_rate, request, output_16bit);
+                } else {
+                    gen_count = (uint32_t)song.read(sample_rate, request, output_16bit);
                 }
-                else {
-                    gen_count = (uint32_t)song.read(sample_rate, sample_rate, output_float);
-                    output_float += sample_rate;
+                output_16bit += gen_count * output_channels;
+            } else {
+                if (params.stereo_output) {
+                    gen_count = (uint32_t)song.read_interleaved_stereo(sample_rate, request, output_float);
+                } else {
+                    gen_count = (uint32_t)song.read(sample_rate, request, output_float);
                 }
+                output_float += gen_count * output_channels;
+            }
 
-                samples_generated += gen_count;
+            samples_generated += gen_count;
 
-                // if we don't get the number of samples we requested we are at the end
-                if (gen_count != sample_rate)
-                    break;
+            // if we don't get the number of samples we requested we are at the end
+            if (gen_count != request) {
+                stream->finished = true;
+                break;
             }
         }
+    }
+    catch (const std::exception& e)
+    {
+        stream->finished = true;
+    }
+
+    stream->frames_generated += samples_generated;
+    return samples_generated;
+}
+
+// Number of frames song_read_c produces in total. The render ends there even if libopenmpt would
+// play a few more frames. 0 if the song plays forever
+uint64_t song_frame_count_c(SongStream* stream)
+{
+    if (stream == nullptr)
+        return 0;
+
+    return stream->frame_count;
+}
+
+void song_close_c(SongStream* stream)
+{
+    delete stream;
+}
+
+uint32_t song_render_c(
+    uint8_t* output, uint32_t output_len, 
+    const uint8_t* input, uint32_t len, 
+    RenderParams& params)
+{
+    SongStream* stream = song_open_c(input, len, params);
+    uint32_t output_channels = params.stereo_output ? 2 : 1;
+    uint32_t frame_bytes = params.bytes_per_sample * output_channels;
 
-	    //bool SaveSFZInstrument(INSTRUMENTINDEX nInstr, std::ostream &f, const mpt::PathString &filename, bool useFLACsamples) const;
+    uint32_t samples_generated = song_read_c(stream, output, output_len / frame_bytes);
+    song_close_c(stream);
 
+    return samples_generated * frame_bytes;
+}
 
-        if (params.stereo_output)
-            return samples_generated * 2 * params.bytes_per_sample;
-        else
-            return samples_generated * params.bytes_per_sample;
+// Returns false when libopenmpt can tell from the header that it can't load the data
+bool probe_song_c(const uint8_t* buffer, uint32_t len) {
+    try
+    {
+        int result = openmpt::probe_file_header(openmpt::probe_file_header_flags_default2, buffer, (size_t)len, (uint64_t)len);
+        return result != openmpt::probe_file_header_result_failure;
     }
-    catch (const std::exception& e)
+    catch (const std::exception&)
     {
     }
 
-    return 0;
+    return false;
+}
+
+// Writes the file extensions supported by libopenmpt separated by ';' and returns the length
+uint32_t get_supported_extensions_c(char* output, uint32_t size) {
+    std::string extensions;
+
+    for (const std::string& extension : openmpt::get_supported_extensions()) {
+        if (!extensions.empty())
+            extensions += ';';
+        extensions += extension;
+    }
+
+    copy_string(output, size, extensions);
+    return (uint32_t)std::min(extensions.size(), (size_t)size - 1);
 }
 
 }
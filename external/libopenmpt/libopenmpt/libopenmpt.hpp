@@ -1187,6 +1187,8 @@ public:
 
 	LIBOPENMPT_CXX_API_MEMBER OpenMPT::CSoundFile* get_snd_file();
 
+	LIBOPENMPT_CXX_API_MEMBER void set_prng_seed( std::uint32_t seed );
+
 	// remember to add new functions to both C and C++ interfaces and to increase OPENMPT_API_VERSION_MINOR
 
 }; // class module
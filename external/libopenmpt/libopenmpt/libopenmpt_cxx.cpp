@@ -435,6 +435,10 @@ OpenMPT::CSoundFile* module::get_snd_file() {
     return impl->get_snd_file();
 }
 
+void module::set_prng_seed( std::uint32_t seed ) {
+    impl->set_prng_seed( seed );
+}
+
 void module::ctl_set( const std::string & ctl, const std::string & value ) {
 	impl->ctl_set( ctl, value );
 }
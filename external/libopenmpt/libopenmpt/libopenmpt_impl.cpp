@@ -20,6 +20,7 @@
 #include <iterator>
 #include <limits>
 #include <ostream>
+#include <random>
 
 #include <cmath>
 #include <cstdlib>
@@ -447,6 +448,11 @@ void module_impl::init_subsongs( subsongs_type & subsongs ) const {
 bool module_impl::has_subsongs_inited() const {
 	return !m_subsongs.empty();
 }
+void module_impl::set_prng_seed( std::uint32_t seed ) {
+	std::mt19937 rd( seed );
+	m_sndFile->SetPRNG( OpenMPT::mpt::make_prng<OpenMPT::mpt::fast_prng>( rd ) );
+	m_Dithers = std::make_unique<OpenMPT::DithersWrapperOpenMPT>( rd, m_Dithers->GetMode(), m_Dithers->GetChannels() );
+}
 void module_impl::ctor( const std::map< std::string, std::string > & ctls ) {
 	m_sndFile = std::make_unique<OpenMPT::CSoundFile>();
 	m_loaded = false;
@@ -103,6 +103,9 @@ public:
 
 	inline OpenMPT::CSoundFile* get_snd_file() { return m_sndFile.get(); }
 
+	// Replaces the randomly seeded dither and playback PRNGs with deterministic ones
+	void set_prng_seed( std::uint32_t seed );
+
 protected:
 	struct subsong_data {
 		double duration;
@@ -534,6 +534,9 @@ protected:
 	inline mpt::fast_prng & AccessPRNG() const { return const_cast<CSoundFile*>(this)->m_PRNG; }
 	inline mpt::fast_prng & AccessPRNG() { return m_PRNG; }
 
+public:
+	inline void SetPRNG(const mpt::fast_prng &prng) { m_PRNG = prng; }
+
 protected:
 	// Mix level stuff
 	CSoundFilePlayConfig m_PlayConfig;
@@ -0,0 +1,93 @@
+#include <sidplayfp/sidplayfp.h>
+#include <sidplayfp/SidConfig.h>
+#include <sidplayfp/SidInfo.h>
+#include <sidplayfp/SidTune.h>
+#include <sidplayfp/builders/residfp.h>
+#include <stdint.h>
+#include <algorithm>
+#include <iostream>
+#include <vector>
+
+// 3 voices and the sample (digi) channel per SID chip
+#define CHANNELS_PER_SID 4
+
+// Frames rendered per call to sidplayfp::play
+#define CHUNK_FRAMES 4096
+
+// Has to match the struct on the Rust side
+struct SidRenderParams {
+    uint32_t sample_rate;
+    bool stereo_output;
+    int32_t channel_to_play; // if -1 play all voices, otherwise sid * 4 + voice where voice 3 is samples
+    uint32_t tune; // counted from 1, 0 plays the start tune
+    uint32_t skip_frames; // frames played and thrown away before the output starts
+};
+
+extern "C" uint32_t sid_render_c(
+    int16_t* output,
+    uint32_t max_frames,
+    const uint8_t* input,
+    uint32_t input_len,
+    const SidRenderParams& params)
+{
+    sidplayfp engine;
+    ReSIDfpBuilder builder("stemgen");
+
+    builder.create(engine.info().maxsids());
+    if (!builder.getStatus()) {
+        std::cerr << "Unable to create the SID emulation: " << builder.error() << std::endl;
+        return 0;
+    }
+
+    SidTune tune(input, input_len);
+    if (!tune.getStatus()) {
+        std::cerr << "Unable to load SID tune: " << tune.statusString() << std::endl;
+        return 0;
+    }
+
+    tune.selectSong(params.tune);
+
+    SidConfig config = engine.config();
+    config.frequency = params.sample_rate;
+    config.playback = params.stereo_output ? SidConfig::STEREO : SidConfig::MONO;
+    config.samplingMethod = SidConfig::RESAMPLE_INTERPOLATE;
+    config.fastSampling = false;
+    config.sidEmulation = &builder;
+
+    if (!engine.config(config) || !engine.load(&tune)) {
+        std::cerr << "Unable to play SID tune: " << engine.error() << std::endl;
+        return 0;
+    }
+
+    if (params.channel_to_play >= 0) {
+        unsigned int sid_count = tune.getInfo()->sidChips();
+        for (unsigned int sid = 0; sid < sid_count; ++sid) {
+            for (unsigned int voice = 0; voice < CHANNELS_PER_SID; ++voice) {
+                bool solo = sid * CHANNELS_PER_SID + voice == (unsigned int)params.channel_to_play;
+                engine.mute(sid, voice, !solo);
+            }
+        }
+    }
+
+    uint32_t channels = params.stereo_output ? 2 : 1;
+    std::vector<int16_t> scratch(CHUNK_FRAMES * channels);
+
+    uint32_t skip = params.skip_frames;
+    while (skip > 0) {
+        uint32_t frames = std::min<uint32_t>(skip, CHUNK_FRAMES);
+        if (engine.play(scratch.data(), frames * channels) < frames * channels)
+            return 0;
+        skip -= frames;
+    }
+
+    uint32_t rendered = 0;
+    while (rendered < max_frames) {
+        uint32_t frames = std::min<uint32_t>(max_frames - rendered, CHUNK_FRAMES);
+        uint32_t samples = engine.play(output + rendered * channels, frames * channels);
+        rendered += samples / channels;
+        if (samples < frames * channels)
+            break;
+    }
+
+    return rendered;
+}
@@ -8,7 +8,10 @@
 #![allow(non_upper_case_globals)]
 #![allow(clippy::useless_transmute)]
 
-use std::path::Path;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
 use std::ffi::{CString, CStr};
 
 #[allow(clippy::upper_case_acronyms)]
@@ -16,48 +19,556 @@ pub type FILE = libc::FILE;
 
 include!("bindings.rs");
 
-pub fn encode_flac(filename: &Path, buffer: &[u8], channels: u32, bytes_per_sample: u32, sample_rate: u32) -> bool {
-    let os_path = filename.to_string_lossy().into_owned();
-    let c_filename = CString::new(os_path).unwrap();
-
-    let bits_per_sample = if bytes_per_sample == 4 { 24 } else { 16 };
+fn bits_per_sample(bytes_per_sample: u32) -> u32 {
+    if bytes_per_sample == 4 { 24 } else { 16 }
+}
 
-    let samples = if bytes_per_sample == 4 {
-        let data: &[f32] = bytemuck::cast_slice(&buffer);
-        data.iter().map(|x| (*x * (1 << (bits_per_sample - 1)) as f32) as i32).collect::<Vec<i32>>()
+fn to_flac_samples(buffer: &[u8], bytes_per_sample: u32) -> Vec<i32> {
+    if bytes_per_sample == 4 {
+        let max = (1 << (bits_per_sample(bytes_per_sample) - 1)) as f32;
+        let data: &[f32] = bytemuck::cast_slice(buffer);
+        // Gain and normalizing can push samples past full scale, which doesn't fit in 24 bits
+        data.iter().map(|x| (*x * max).clamp(-max, max - 1.0) as i32).collect::<Vec<i32>>()
     } else {
-        let data: &[i16] = bytemuck::cast_slice(&buffer);
-        data.iter().map(|x| (*x as i32)).collect::<Vec<i32>>()
-    };
+        let data: &[i16] = bytemuck::cast_slice(buffer);
+        data.iter().map(|x| *x as i32).collect::<Vec<i32>>()
+    }
+}
+
+/// Why a FLAC stream couldn't be encoded
+#[derive(Debug)]
+pub enum FlacError {
+    /// libFLAC couldn't allocate the encoder or a metadata block
+    OutOfMemory,
+    /// The output file couldn't be created
+    Create(PathBuf, std::io::Error),
+    /// The apodization spec contains a NUL byte
+    InvalidApodization(String),
+    /// The encoder couldn't be initialized. `status` is the init status and `state` the state of
+    /// the encoder
+    Init { name: String, status: String, state: String },
+    /// The buffer given to `process` doesn't end on a whole frame
+    PartialFrame { bytes: usize, frame_bytes: usize },
+    /// Encoding samples failed
+    Process { name: String, state: String },
+    /// The writer returned an error
+    Write(std::io::Error),
+    /// Flushing the last samples or updating the header failed
+    Finish { name: String, state: String },
+    /// A cover or application block is larger than a metadata block can hold
+    MetadataTooLarge { bytes: usize },
+}
+
+impl fmt::Display for FlacError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FlacError::OutOfMemory => write!(f, "libFLAC is out of memory"),
+            FlacError::Create(path, e) => write!(f, "Unable to create {:?}: {}", path, e),
+            FlacError::InvalidApodization(spec) => write!(f, "Invalid apodization {:?}, it contains a NUL byte", spec),
+            FlacError::Init { name, status, state } => write!(f, "Unable to init FLAC encoder for {}: {} ({})", name, status, state),
+            FlacError::PartialFrame { bytes, frame_bytes } => write!(f, "{} bytes of samples isn't a whole number of {} byte frames", bytes, frame_bytes),
+            FlacError::Process { name, state } => write!(f, "Unable to encode samples for {}: {}", name, state),
+            FlacError::Write(e) => write!(f, "Unable to write FLAC stream: {}", e),
+            FlacError::Finish { name, state } => write!(f, "Unable to finish FLAC stream for {}: {}", name, state),
+            FlacError::MetadataTooLarge { bytes } => write!(f, "{} bytes of metadata don't fit in a FLAC metadata block", bytes),
+        }
+    }
+}
+
+impl std::error::Error for FlacError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FlacError::Create(_, e) | FlacError::Write(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Text of an entry in one of libFLAC's status/state string tables
+unsafe fn table_string(table: &[*const libc::c_char; 0], index: u32) -> String {
+    let cstr = CStr::from_ptr(*table.as_ptr().add(index as usize));
+    String::from_utf8_lossy(cstr.to_bytes()).into_owned()
+}
+
+unsafe fn encoder_state(encoder: *const FLAC__StreamEncoder) -> String {
+    let cstr = CStr::from_ptr(FLAC__stream_encoder_get_resolved_state_string(encoder));
+    String::from_utf8_lossy(cstr.to_bytes()).into_owned()
+}
+
+/// APPLICATION metadata block written in front of the audio
+pub struct ApplicationBlock<'a> {
+    /// Application id, should be registered with the FLAC project
+    pub id: [u8; 4],
+    pub data: &'a [u8],
+}
+
+/// Front cover stored as a PICTURE metadata block
+pub struct Picture<'a> {
+    pub mime_type: &'a str,
+    pub width: u32,
+    pub height: u32,
+    pub data: &'a [u8],
+}
+
+/// How hard the encoder works. Higher compression levels give smaller files but take longer to
+/// encode, decoding speed is about the same for all of them
+#[derive(Clone, Copy)]
+pub struct EncoderSettings<'a> {
+    /// Compression level 0 (fastest) to 8 (smallest)
+    pub compression_level: u32,
+    /// Decode each frame again while encoding and compare it to the input. Roughly doubles the
+    /// encode time
+    pub verify: bool,
+    /// Samples per block, 0 uses the default of the compression level
+    pub blocksize: u32,
+    /// Apodization functions (such as "tukey(0.5);partial_tukey(2)"), overriding the ones of the
+    /// compression level
+    pub apodization: Option<&'a str>,
+}
+
+impl Default for EncoderSettings<'_> {
+    fn default() -> Self {
+        EncoderSettings { compression_level: 8, verify: false, blocksize: 0, apodization: None }
+    }
+}
+
+/// Metadata written in front of the audio
+#[derive(Default)]
+pub struct StreamMetadata<'a> {
+    pub application: Option<ApplicationBlock<'a>>,
+    pub picture: Option<Picture<'a>>,
+    /// VORBIS_COMMENT fields as (name, value) pairs
+    pub comments: &'a [(String, String)],
+}
+
+/// Metadata blocks handed to the encoder. These have to stay alive until the encoder is finished
+struct Metadata(Vec<*mut FLAC__StreamMetadata>);
+
+impl Drop for Metadata {
+    fn drop(&mut self) {
+        for block in &self.0 {
+            unsafe { FLAC__metadata_object_delete(*block) };
+        }
+    }
+}
+
+/// Allocate a metadata block, pushing it to `metadata` right away so it's freed on every path
+unsafe fn new_block(metadata: &mut Metadata, type_: FLAC__MetadataType) -> Result<*mut FLAC__StreamMetadata, FlacError> {
+    let block = FLAC__metadata_object_new(type_);
+
+    if block.is_null() {
+        return Err(FlacError::OutOfMemory);
+    }
+
+    metadata.0.push(block);
+    Ok(block)
+}
+
+/// Metadata blocks store their length in 24 bits
+const MAX_METADATA_BYTES: usize = (1 << 24) - 1;
+
+/// Length of the data of a metadata block, which libFLAC takes as u32
+fn metadata_length(data: &[u8]) -> Result<u32, FlacError> {
+    if data.len() > MAX_METADATA_BYTES {
+        return Err(FlacError::MetadataTooLarge { bytes: data.len() });
+    }
+
+    Ok(data.len() as u32)
+}
+
+/// Seek points are placed this many seconds apart, same as the flac command line tool does
+const SEEK_POINT_SECONDS: u32 = 10;
+
+/// `seek_table` is the sample rate and total sample count to lay out a SEEKTABLE for. libFLAC fills
+/// in the points while encoding and writes the table when finishing, so it needs a seekable output
+unsafe fn set_metadata(encoder: *mut FLAC__StreamEncoder, stream_metadata: &StreamMetadata, seek_table: Option<(u32, u64)>) -> Result<Metadata, FlacError> {
+    let mut metadata = Metadata(Vec::new());
+
+    // First after STREAMINFO so players find it without reading past the tags and cover
+    if let Some((sample_rate, total_samples)) = seek_table.filter(|(_, total_samples)| *total_samples > 0) {
+        let block = new_block(&mut metadata, FLAC__METADATA_TYPE_SEEKTABLE)?;
+
+        if FLAC__metadata_object_seektable_template_append_spaced_points_by_samples(block, sample_rate * SEEK_POINT_SECONDS, total_samples) == 0
+            || FLAC__metadata_object_seektable_template_sort(block, 1) == 0
+        {
+            return Err(FlacError::OutOfMemory);
+        }
+    }
+
+    if !stream_metadata.comments.is_empty() {
+        let block = new_block(&mut metadata, FLAC__METADATA_TYPE_VORBIS_COMMENT)?;
+
+        for (name, value) in stream_metadata.comments {
+            let (Ok(name), Ok(value)) = (CString::new(name.as_str()), CString::new(value.as_str())) else {
+                continue;
+            };
+
+            let mut entry = FLAC__StreamMetadata_VorbisComment_Entry { length: 0, entry: std::ptr::null_mut() };
+
+            // The entry is handed over to the block (no copy) which frees it
+            if FLAC__metadata_object_vorbiscomment_entry_from_name_value_pair(&mut entry, name.as_ptr(), value.as_ptr()) != 0 {
+                FLAC__metadata_object_vorbiscomment_append_comment(block, entry, 0);
+            }
+        }
+    }
+
+    if let Some(picture) = &stream_metadata.picture {
+        let block = new_block(&mut metadata, FLAC__METADATA_TYPE_PICTURE)?;
+        (*block).data.picture.type_ = FLAC__STREAM_METADATA_PICTURE_TYPE_FRONT_COVER;
+        (*block).data.picture.width = picture.width;
+        (*block).data.picture.height = picture.height;
+
+        // Mime type and data are copied so libFLAC never writes to them
+        let mime_type = CString::new(picture.mime_type).unwrap_or_default();
+        let length = metadata_length(picture.data)?;
+        if FLAC__metadata_object_picture_set_mime_type(block, mime_type.as_ptr() as *mut _, 1) == 0
+            || FLAC__metadata_object_picture_set_data(block, picture.data.as_ptr() as *mut _, length, 1) == 0
+        {
+            return Err(FlacError::OutOfMemory);
+        }
+    }
+
+    if let Some(application) = &stream_metadata.application {
+        let block = new_block(&mut metadata, FLAC__METADATA_TYPE_APPLICATION)?;
+        (*block).data.application.id = application.id;
+        // Data is copied so libFLAC never writes to it
+        let length = metadata_length(application.data)?;
+        if FLAC__metadata_object_application_set_data(block, application.data.as_ptr() as *mut _, length, 1) == 0 {
+            return Err(FlacError::OutOfMemory);
+        }
+    }
+
+    if !metadata.0.is_empty() {
+        FLAC__stream_encoder_set_metadata(encoder, metadata.0.as_mut_ptr(), metadata.0.len() as u32);
+    }
+
+    Ok(metadata)
+}
+
+/// Samples are converted and handed to libFLAC in blocks of this many frames so memory use doesn't
+/// grow with the length of the song
+const BLOCK_FRAMES: usize = 4096;
+
+/// Where a stream encoder writes
+enum ClientWriter<'a> {
+    /// Can't seek, so the stream is written strictly in order
+    Stream(&'a mut dyn Write),
+    /// Created by the encoder, which seeks back when done to fill in the header. The position is
+    /// kept here as libFLAC asks for it after every frame
+    File { file: BufWriter<File>, position: u64 },
+}
+
+/// Writer of a stream encoder along with the last error it returned, which libFLAC can't pass on
+struct StreamClient<'a> {
+    writer: ClientWriter<'a>,
+    error: Option<std::io::Error>,
+}
+
+impl StreamClient<'_> {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        match &mut self.writer {
+            ClientWriter::Stream(writer) => writer.write_all(data),
+            ClientWriter::File { file, position } => {
+                file.write_all(data)?;
+                *position += data.len() as u64;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Safe wrapper around a libFLAC stream encoder. Interleaved samples can be fed in any number of
+/// calls to `process` (such as while a song is still rendering) and `finish` completes the stream.
+/// The encoder is always finished and deleted, also when it's dropped after an error
+pub struct FlacEncoder<'a> {
+    encoder: *mut FLAC__StreamEncoder,
+    _metadata: Metadata,
+    // Boxed so the address handed to libFLAC as client data stays put when the encoder is moved
+    client: Option<Box<StreamClient<'a>>>,
+    channels: u32,
+    bytes_per_sample: u32,
+    name: String,
+}
+
+impl<'a> FlacEncoder<'a> {
+    /// Allocate and configure an encoder. It's owned by the returned value from here on so it's
+    /// deleted if setting it up fails later on
+    fn new(name: String, channels: u32, bytes_per_sample: u32, sample_rate: u32, total_samples: u64, settings: &EncoderSettings) -> Result<FlacEncoder<'a>, FlacError> {
+        let apodization = settings.apodization
+            .map(|spec| CString::new(spec).map_err(|_| FlacError::InvalidApodization(spec.to_owned())))
+            .transpose()?;
+
+        let encoder = unsafe { FLAC__stream_encoder_new() };
+
+        if encoder.is_null() {
+            return Err(FlacError::OutOfMemory);
+        }
+
+        unsafe {
+            FLAC__stream_encoder_set_verify(encoder, settings.verify as FLAC__bool);
+
+            // The compression level sets the blocksize and apodization among other things so it
+            // has to go first for the overrides to stick
+            FLAC__stream_encoder_set_compression_level(encoder, settings.compression_level);
+
+            if settings.blocksize != 0 {
+                FLAC__stream_encoder_set_blocksize(encoder, settings.blocksize);
+            }
+
+            // libFLAC copies the spec into its own storage
+            if let Some(apodization) = &apodization {
+                FLAC__stream_encoder_set_apodization(encoder, apodization.as_ptr());
+            }
+
+            FLAC__stream_encoder_set_channels(encoder, channels);
+            FLAC__stream_encoder_set_bits_per_sample(encoder, bits_per_sample(bytes_per_sample));
+            FLAC__stream_encoder_set_sample_rate(encoder, sample_rate);
+
+            FLAC__stream_encoder_set_total_samples_estimate(encoder, total_samples);
+
+            FLAC__stream_encoder_set_ogg_serial_number(encoder, 0); // Not using Ogg encapsulation
+        }
+
+        Ok(FlacEncoder {
+            encoder,
+            _metadata: Metadata(Vec::new()),
+            client: None,
+            channels,
+            bytes_per_sample,
+            name,
+        })
+    }
+
+    /// libFLAC writes the stream header while initializing so a failed write shows up here
+    fn check_init(&mut self, status: FLAC__StreamEncoderInitStatus) -> Result<(), FlacError> {
+        if status == FLAC__STREAM_ENCODER_INIT_STATUS_OK {
+            return Ok(());
+        }
+
+        let status = unsafe { table_string(&FLAC__StreamEncoderInitStatusString, status) };
+        Err(self.take_error(|name, state| FlacError::Init { name, status, state }))
+    }
+
+    /// Encode to a file. libFLAC seeks back when done to fill in the real sample count, the MD5
+    /// signature and the seek table. The seek table is laid out from `total_samples` so it's only
+    /// written when the length is known up front (0 if not). Seek points past the real end of a
+    /// shorter stream are left empty, which decoders skip
+    pub fn to_file(filename: &Path, channels: u32, bytes_per_sample: u32, sample_rate: u32, total_samples: u64, metadata: &StreamMetadata, settings: &EncoderSettings) -> Result<FlacEncoder<'static>, FlacError> {
+        // The file is created here rather than by libFLAC, which only takes a C string and so
+        // can't open every path the OS can (such as non-UTF-8 names or Windows paths outside the
+        // current code page)
+        let file = File::create(filename).map_err(|e| FlacError::Create(filename.to_owned(), e))?;
+
+        let mut encoder = FlacEncoder::new(filename.display().to_string(), channels, bytes_per_sample, sample_rate, total_samples, settings)?;
+        let mut client = Box::new(StreamClient {
+            writer: ClientWriter::File { file: BufWriter::new(file), position: 0 },
+            error: None,
+        });
+
+        unsafe {
+            encoder._metadata = set_metadata(encoder.encoder, metadata, Some((sample_rate, total_samples)))?;
+
+            let status = FLAC__stream_encoder_init_stream(
+                encoder.encoder,
+                Some(write_callback),
+                Some(seek_callback),
+                Some(tell_callback),
+                None,
+                &mut *client as *mut StreamClient as *mut libc::c_void,
+            );
+
+            encoder.client = Some(client);
+            encoder.check_init(status)?;
+        }
+
+        Ok(encoder)
+    }
+
+    /// Encode to a writer that can't seek (such as a pipe). The stream is written strictly in order
+    /// so the total sample count has to be given up front, the MD5 signature in the header is left
+    /// empty and no seek table is written.
+    pub fn to_writer(writer: &'a mut dyn Write, channels: u32, bytes_per_sample: u32, sample_rate: u32, total_samples: u64, metadata: &StreamMetadata, settings: &EncoderSettings) -> Result<FlacEncoder<'a>, FlacError> {
+        let mut encoder = FlacEncoder::new("<stream>".to_owned(), channels, bytes_per_sample, sample_rate, total_samples, settings)?;
+        let mut client = Box::new(StreamClient { writer: ClientWriter::Stream(writer), error: None });
+
+        unsafe {
+            encoder._metadata = set_metadata(encoder.encoder, metadata, None)?;
+
+            let status = FLAC__stream_encoder_init_stream(
+                encoder.encoder,
+                Some(write_callback),
+                None,
+                None,
+                None,
+                &mut *client as *mut StreamClient as *mut libc::c_void,
+            );
+
+            encoder.client = Some(client);
+            encoder.check_init(status)?;
+        }
+
+        Ok(encoder)
+    }
+
+    /// Error of a failed call. Errors from the writer are reported as such rather than as the
+    /// generic encoder state libFLAC ends up in
+    fn take_error(&mut self, error: impl FnOnce(String, String) -> FlacError) -> FlacError {
+        if let Some(e) = self.client.as_mut().and_then(|client| client.error.take()) {
+            return FlacError::Write(e);
+        }
+
+        error(self.name.clone(), unsafe { encoder_state(self.encoder) })
+    }
+
+    /// Encode interleaved samples. The buffer has to contain whole frames
+    pub fn process(&mut self, buffer: &[u8]) -> Result<(), FlacError> {
+        let frame_bytes = self.channels as usize * self.bytes_per_sample as usize;
 
-    unsafe {
-        let  encoder = FLAC__stream_encoder_new();
+        if !buffer.len().is_multiple_of(frame_bytes) {
+            return Err(FlacError::PartialFrame { bytes: buffer.len(), frame_bytes });
+        }
+
+        for block in buffer.chunks(BLOCK_FRAMES * frame_bytes) {
+            let samples = to_flac_samples(block, self.bytes_per_sample);
+            // At most BLOCK_FRAMES so it fits in the u32 libFLAC takes
+            let frames = (block.len() / frame_bytes) as u32;
+
+            let success = unsafe { FLAC__stream_encoder_process_interleaved(self.encoder, samples.as_ptr(), frames) };
+
+            if success == 0 {
+                return Err(self.take_error(|name, state| FlacError::Process { name, state }));
+            }
+        }
 
-        FLAC__stream_encoder_set_verify(encoder, 1);
-        FLAC__stream_encoder_set_compression_level(encoder, 8); // Max compression 
+        Ok(())
+    }
+
+    /// Flush the remaining samples and finish the stream
+    pub fn finish(mut self) -> Result<(), FlacError> {
+        if unsafe { FLAC__stream_encoder_finish(self.encoder) } == 0 {
+            return Err(self.take_error(|name, state| FlacError::Finish { name, state }));
+        }
 
-        FLAC__stream_encoder_set_channels(encoder, channels);
-        FLAC__stream_encoder_set_bits_per_sample(encoder, bits_per_sample);
-        FLAC__stream_encoder_set_sample_rate(encoder, sample_rate);
+        // Buffered writes that fail on flush (such as a pipe that was closed) would otherwise be lost
+        match self.client.as_mut().map(|client| &mut client.writer) {
+            Some(ClientWriter::File { file, .. }) => file.flush().map_err(FlacError::Write),
+            Some(ClientWriter::Stream(writer)) => writer.flush().map_err(FlacError::Write),
+            None => Ok(()),
+        }
+    }
+}
 
-        FLAC__stream_encoder_set_total_samples_estimate(encoder, 0); // Unknown number of samples
+impl Drop for FlacEncoder<'_> {
+    fn drop(&mut self) {
+        // Finishing flushes the samples already fed and closes the file. It does nothing if the
+        // encoder was already finished or never initialized
+        unsafe {
+            FLAC__stream_encoder_finish(self.encoder);
+            FLAC__stream_encoder_delete(self.encoder);
+        }
+    }
+}
 
-        FLAC__stream_encoder_set_ogg_serial_number(encoder, 0); // Not using Ogg encapsulation
+pub fn encode_flac(filename: &Path, buffer: &[u8], channels: u32, bytes_per_sample: u32, sample_rate: u32, metadata: &StreamMetadata, settings: &EncoderSettings) -> Result<(), FlacError> {
+    let total_samples = (buffer.len() / (channels as usize * bytes_per_sample as usize)) as u64;
 
-        FLAC__stream_encoder_init_file(encoder, c_filename.as_ptr(), None, std::ptr::null_mut());
+    let mut encoder = FlacEncoder::to_file(filename, channels, bytes_per_sample, sample_rate, total_samples, metadata, settings)?;
+    encoder.process(buffer)?;
+    encoder.finish()
+}
 
-        let success = FLAC__stream_encoder_process_interleaved(encoder, samples.as_ptr(), samples.len() as u32 / channels);
+unsafe extern "C" fn write_callback(
+    _encoder: *const FLAC__StreamEncoder,
+    buffer: *const FLAC__byte,
+    bytes: usize,
+    _samples: u32,
+    _current_frame: u32,
+    client_data: *mut libc::c_void,
+) -> FLAC__StreamEncoderWriteStatus {
+    let client = &mut *(client_data as *mut StreamClient);
+    let data = std::slice::from_raw_parts(buffer, bytes);
 
-        if success == 0 {
-            let cstr = CStr::from_ptr(FLAC__stream_encoder_get_resolved_state_string(encoder));
-            let error = String::from_utf8_lossy(cstr.to_bytes()).to_string();
-            println!("FLAC__stream_encoder_process_interleaved failed for file {:?} {}", filename, error);
-            
-            false
-        } else {
-            FLAC__stream_encoder_finish(encoder);
-            FLAC__stream_encoder_delete(encoder);
-            true
+    match client.write(data) {
+        Ok(()) => FLAC__STREAM_ENCODER_WRITE_STATUS_OK,
+        Err(e) => {
+            client.error = Some(e);
+            FLAC__STREAM_ENCODER_WRITE_STATUS_FATAL_ERROR
         }
     }
 }
+
+unsafe extern "C" fn seek_callback(
+    _encoder: *const FLAC__StreamEncoder,
+    absolute_byte_offset: FLAC__uint64,
+    client_data: *mut libc::c_void,
+) -> FLAC__StreamEncoderSeekStatus {
+    let client = &mut *(client_data as *mut StreamClient);
+
+    let ClientWriter::File { file, position } = &mut client.writer else {
+        return FLAC__STREAM_ENCODER_SEEK_STATUS_UNSUPPORTED;
+    };
+
+    match file.seek(SeekFrom::Start(absolute_byte_offset)) {
+        Ok(offset) => {
+            *position = offset;
+            FLAC__STREAM_ENCODER_SEEK_STATUS_OK
+        }
+        Err(e) => {
+            client.error = Some(e);
+            FLAC__STREAM_ENCODER_SEEK_STATUS_ERROR
+        }
+    }
+}
+
+unsafe extern "C" fn tell_callback(
+    _encoder: *const FLAC__StreamEncoder,
+    absolute_byte_offset: *mut FLAC__uint64,
+    client_data: *mut libc::c_void,
+) -> FLAC__StreamEncoderTellStatus {
+    let client = &*(client_data as *const StreamClient);
+
+    match client.writer {
+        ClientWriter::File { position, .. } => {
+            *absolute_byte_offset = position;
+            FLAC__STREAM_ENCODER_TELL_STATUS_OK
+        }
+        ClientWriter::Stream(_) => FLAC__STREAM_ENCODER_TELL_STATUS_UNSUPPORTED,
+    }
+}
+
+/// Encode to a writer that can't seek (such as a pipe). See `FlacEncoder::to_writer`
+pub fn encode_flac_to_writer<W: Write>(writer: &mut W, buffer: &[u8], channels: u32, bytes_per_sample: u32, sample_rate: u32, metadata: &StreamMetadata, settings: &EncoderSettings) -> Result<(), FlacError> {
+    let total_samples = (buffer.len() / (channels as usize * bytes_per_sample as usize)) as u64;
+
+    let mut encoder = FlacEncoder::to_writer(writer, channels, bytes_per_sample, sample_rate, total_samples, metadata, settings)?;
+    encoder.process(buffer)?;
+    encoder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn float_samples_are_clamped_to_24_bits() {
+        let samples = [0.0f32, 0.5, -0.5, 1.0, -1.0, 1.5, -2.0];
+        let converted = to_flac_samples(bytemuck::cast_slice(&samples), 4);
+        assert_eq!(converted, [0, 1 << 22, -(1 << 22), (1 << 23) - 1, -(1 << 23), (1 << 23) - 1, -(1 << 23)]);
+    }
+
+    #[test]
+    fn int16_samples_are_kept() {
+        let samples = [0i16, 1, -1, i16::MAX, i16::MIN];
+        let converted = to_flac_samples(bytemuck::cast_slice(&samples), 2);
+        assert_eq!(converted, [0, 1, -1, i16::MAX as i32, i16::MIN as i32]);
+    }
+
+    #[test]
+    fn metadata_has_to_fit_in_24_bits() {
+        assert_eq!(metadata_length(&[0; 16]).unwrap(), 16);
+        assert!(matches!(
+            metadata_length(&vec![0; MAX_METADATA_BYTES + 1]),
+            Err(FlacError::MetadataTooLarge { bytes }) if bytes == MAX_METADATA_BYTES + 1
+        ));
+    }
+}
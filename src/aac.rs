@@ -0,0 +1,329 @@
+use anyhow::{anyhow, Result};
+use fdk_aac::enc::{AudioObjectType, BitRate, ChannelMode, Encoder, EncoderParams, Transport};
+use mp4::{
+    AacConfig, Bytes, ChannelConfig, MediaConfig, Mp4Config, Mp4Sample, Mp4Writer, SampleFreqIndex,
+    TrackConfig, TrackType,
+};
+use std::io::{Cursor, Write};
+
+/// Movie timescale of the written files, in milliseconds
+const MOVIE_TIMESCALE: u32 = 1000;
+
+/// Encoded AAC-LC frames of one audio track
+pub struct AacTrack {
+    frames: Vec<Bytes>,
+    frame_len: u32,
+    config: AacConfig,
+    /// Number of priming samples the encoder put in front of the input
+    delay: u32,
+    /// Number of input sample frames, without the priming and the padding of the last frame
+    sample_count: u64,
+}
+
+/// Encode interleaved i16 samples as AAC-LC
+pub fn encode(
+    samples: &[i16],
+    channel_count: usize,
+    sample_rate: u32,
+    bitrate_kbps: u32,
+) -> Result<AacTrack> {
+    let freq_index = (0..=0xc)
+        .filter_map(|i| SampleFreqIndex::try_from(i).ok())
+        .find(|f| f.freq() == sample_rate)
+        .ok_or_else(|| anyhow!("Sample rate {} isn't supported by AAC", sample_rate))?;
+
+    let (channels, chan_conf) = match channel_count {
+        1 => (ChannelMode::Mono, ChannelConfig::Mono),
+        2 => (ChannelMode::Stereo, ChannelConfig::Stereo),
+        _ => return Err(anyhow!("AAC output only supports mono or stereo")),
+    };
+
+    let encoder = Encoder::new(EncoderParams {
+        bit_rate: BitRate::Cbr(bitrate_kbps * 1000),
+        sample_rate,
+        transport: Transport::Raw,
+        channels,
+        audio_object_type: AudioObjectType::Mpeg4LowComplexity,
+    })
+    .map_err(|e| anyhow!("Unable to create AAC encoder: {}", e))?;
+
+    let info = encoder
+        .info()
+        .map_err(|e| anyhow!("Unable to get AAC encoder info: {}", e))?;
+
+    let frame_len = info.frameLength as usize;
+    let block_len = frame_len * channel_count;
+    let silence = vec![0i16; block_len];
+    let mut output = vec![0u8; info.maxOutBufBytes as usize];
+    let mut frames = Vec::new();
+
+    // The encoder lags behind the input so keep feeding silence until the delay has been flushed out
+    let total_frames = samples.len() / channel_count + info.nDelay as usize;
+    let total_blocks = total_frames.div_ceil(frame_len);
+
+    for block in 0..total_blocks * 2 {
+        if frames.len() >= total_blocks {
+            break;
+        }
+
+        let start = block * block_len;
+        let input = if start < samples.len() {
+            &samples[start..(start + block_len).min(samples.len())]
+        } else {
+            &silence
+        };
+
+        let res = encoder
+            .encode(input, &mut output)
+            .map_err(|e| anyhow!("AAC encoding failed: {}", e))?;
+
+        if res.output_size > 0 {
+            frames.push(Bytes::copy_from_slice(&output[..res.output_size]));
+        }
+    }
+
+    Ok(AacTrack {
+        frames,
+        frame_len: frame_len as u32,
+        delay: info.nDelay,
+        sample_count: (samples.len() / channel_count) as u64,
+        config: AacConfig {
+            bitrate: bitrate_kbps * 1000,
+            profile: mp4::AudioObjectType::AacLowComplexity,
+            freq_index,
+            chan_conf,
+        },
+    })
+}
+
+/// Offset, size and type of the boxes in `data[start..end]`
+pub fn boxes(data: &[u8], start: usize, end: usize) -> Vec<(usize, usize, [u8; 4])> {
+    let mut output = Vec::new();
+    let mut offset = start;
+
+    while offset + 8 <= end {
+        let size = u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
+        if size < 8 || offset + size > end {
+            break;
+        }
+        output.push((
+            offset,
+            size,
+            data[offset + 4..offset + 8].try_into().unwrap(),
+        ));
+        offset += size;
+    }
+
+    output
+}
+
+/// Find the moov box and check that it's the last box in the file, so boxes can be added to it
+/// without moving the sample data
+pub fn find_moov(data: &[u8]) -> Result<(usize, usize)> {
+    let (offset, size, _) = boxes(data, 0, data.len())
+        .into_iter()
+        .find(|(_, _, kind)| kind == b"moov")
+        .ok_or_else(|| anyhow!("moov box missing"))?;
+
+    if offset + size != data.len() {
+        return Err(anyhow!("moov box isn't at the end of the file"));
+    }
+
+    Ok((offset, size))
+}
+
+/// Change the size of the box at `offset`
+fn set_box_size(data: &mut [u8], offset: usize, size: usize) -> Result<()> {
+    let size = u32::try_from(size)?;
+    data[offset..offset + 4].copy_from_slice(&size.to_be_bytes());
+    Ok(())
+}
+
+/// Box with the given type and payload
+fn make_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(8 + payload.len());
+    output.extend_from_slice(&(8 + payload.len() as u32).to_be_bytes());
+    output.extend_from_slice(kind);
+    output.extend_from_slice(payload);
+    output
+}
+
+/// Set the `moov/udta/<kind>` box. A box of the same type is replaced and an existing `udta` is
+/// reused so tagging a file again doesn't add a second one
+pub fn set_udta_box(data: &mut Vec<u8>, kind: &[u8; 4], payload: &[u8]) -> Result<()> {
+    let (moov_offset, moov_size) = find_moov(data)?;
+    let new_box = make_box(kind, payload);
+
+    let udta = boxes(data, moov_offset + 8, moov_offset + moov_size)
+        .into_iter()
+        .find(|(_, _, k)| k == b"udta");
+
+    let (removed, added) = match udta {
+        Some((udta_offset, udta_size, _)) => {
+            let (at, removed) = boxes(data, udta_offset + 8, udta_offset + udta_size)
+                .into_iter()
+                .find(|(_, _, k)| k == kind)
+                .map_or((udta_offset + udta_size, 0), |(offset, size, _)| {
+                    (offset, size)
+                });
+
+            data.splice(at..at + removed, new_box.iter().copied());
+            set_box_size(data, udta_offset, udta_size - removed + new_box.len())?;
+            (removed, new_box.len())
+        }
+        None => {
+            let udta = make_box(b"udta", &new_box);
+            data.extend_from_slice(&udta);
+            (0, udta.len())
+        }
+    };
+
+    set_box_size(data, moov_offset, moov_size - removed + added)
+}
+
+/// `edts/elst` box that skips the encoder delay at the start and the padding of the last frame
+fn edit_list(track: &AacTrack, sample_rate: u32) -> Result<Vec<u8>> {
+    let duration = track.sample_count * MOVIE_TIMESCALE as u64 / sample_rate as u64;
+    let duration = u32::try_from(duration)?;
+
+    let mut elst = Vec::with_capacity(20);
+    // Version 0, no flags, one entry
+    elst.extend_from_slice(&0u32.to_be_bytes());
+    elst.extend_from_slice(&1u32.to_be_bytes());
+    elst.extend_from_slice(&duration.to_be_bytes());
+    elst.extend_from_slice(&track.delay.to_be_bytes());
+    // Media rate 1.0
+    elst.extend_from_slice(&1u16.to_be_bytes());
+    elst.extend_from_slice(&0u16.to_be_bytes());
+
+    Ok(make_box(b"edts", &make_box(b"elst", &elst)))
+}
+
+/// Put an edit list after the `tkhd` of each track so players skip the encoder delay and the
+/// stems line up sample accurately with each other and the lossless outputs
+fn add_edit_lists(data: &mut Vec<u8>, tracks: &[AacTrack], sample_rate: u32) -> Result<()> {
+    let (moov_offset, moov_size) = find_moov(data)?;
+
+    let traks: Vec<_> = boxes(data, moov_offset + 8, moov_offset + moov_size)
+        .into_iter()
+        .filter(|(_, _, kind)| kind == b"trak")
+        .collect();
+
+    if traks.len() != tracks.len() {
+        return Err(anyhow!("expected {} tracks in moov", tracks.len()));
+    }
+
+    // Going backwards keeps the offsets of the tracks not yet done valid
+    let mut added = 0;
+    for ((offset, size, _), track) in traks.into_iter().zip(tracks).rev() {
+        let (tkhd, tkhd_size, _) = boxes(data, offset + 8, offset + size)
+            .into_iter()
+            .find(|(_, _, kind)| kind == b"tkhd")
+            .ok_or_else(|| anyhow!("tkhd box missing"))?;
+
+        let edts = edit_list(track, sample_rate)?;
+        let at = tkhd + tkhd_size;
+        data.splice(at..at, edts.iter().copied());
+        set_box_size(data, offset, size + edts.len())?;
+        added += edts.len();
+    }
+
+    set_box_size(data, moov_offset, moov_size + added)
+}
+
+/// Write encoded tracks into an mp4 container. Track ids start at 1 in the order given
+pub fn write_mp4<W: Write>(mut writer: W, tracks: &[AacTrack], sample_rate: u32) -> Result<()> {
+    // The mp4 writer seeks back to fill in box sizes and the edit lists are added afterwards so
+    // the file is put together in memory
+    let mut data = Cursor::new(Vec::new());
+    let mut mp4 = Mp4Writer::write_start(
+        &mut data,
+        &Mp4Config {
+            major_brand: str::parse("M4A ")?,
+            minor_version: 512,
+            compatible_brands: vec![
+                str::parse("M4A ")?,
+                str::parse("isom")?,
+                str::parse("mp42")?,
+            ],
+            timescale: MOVIE_TIMESCALE,
+        },
+    )?;
+
+    for track in tracks {
+        mp4.add_track(&TrackConfig {
+            track_type: TrackType::Audio,
+            timescale: sample_rate,
+            language: "und".to_owned(),
+            media_conf: MediaConfig::AacConfig(track.config.clone()),
+        })?;
+    }
+
+    for (index, track) in tracks.iter().enumerate() {
+        for (i, frame) in track.frames.iter().enumerate() {
+            mp4.write_sample(
+                index as u32 + 1,
+                &Mp4Sample {
+                    start_time: i as u64 * track.frame_len as u64,
+                    duration: track.frame_len,
+                    rendering_offset: 0,
+                    is_sync: true,
+                    bytes: frame.clone(),
+                },
+            )?;
+        }
+    }
+
+    mp4.write_end()?;
+
+    let mut data = data.into_inner();
+    add_edit_lists(&mut data, tracks, sample_rate)?;
+
+    writer.write_all(&data)?;
+    Ok(())
+}
+
+/// Encode interleaved i16 samples as AAC-LC and write them as an .m4a file
+pub fn write_m4a<W: Write>(
+    writer: W,
+    samples: &[i16],
+    channel_count: usize,
+    sample_rate: u32,
+    bitrate_kbps: u32,
+) -> Result<()> {
+    let track = encode(samples, channel_count, sample_rate, bitrate_kbps)?;
+    write_mp4(writer, &[track], sample_rate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count_boxes(data: &[u8], offset: usize, size: usize, kind: &[u8; 4]) -> usize {
+        boxes(data, offset + 8, offset + size)
+            .iter()
+            .filter(|(_, _, k)| k == kind)
+            .count()
+    }
+
+    #[test]
+    fn set_udta_box_replaces_existing_box() {
+        let mut data = make_box(b"mdat", &[0; 16]);
+        data.extend(make_box(b"moov", &make_box(b"mvhd", &[0; 4])));
+
+        set_udta_box(&mut data, b"stem", b"first").unwrap();
+        set_udta_box(&mut data, b"stem", b"second").unwrap();
+        set_udta_box(&mut data, b"name", b"song").unwrap();
+
+        let (moov, moov_size) = find_moov(&data).unwrap();
+        assert_eq!(count_boxes(&data, moov, moov_size, b"udta"), 1);
+
+        let (udta, udta_size, _) = boxes(&data, moov + 8, moov + moov_size)[1];
+        assert_eq!(udta + udta_size, data.len());
+        assert_eq!(count_boxes(&data, udta, udta_size, b"stem"), 1);
+        assert_eq!(count_boxes(&data, udta, udta_size, b"name"), 1);
+
+        let (stem, stem_size, _) = boxes(&data, udta + 8, udta + udta_size)[0];
+        assert_eq!(&data[stem + 8..stem + stem_size], b"second");
+    }
+}
@@ -0,0 +1,276 @@
+//! Ableton Live set (`<song>.als`) with a track per stem. The set is gzipped XML, the stems are
+//! referenced where they are and placed unwarped at the start of the arrangement with the tempo
+//! of the song automated on the master track
+
+use crate::session::{xml_escape, SessionTrack};
+use crate::tempo::{beat_at, TempoChange};
+use anyhow::Result;
+use flate2::{write::GzEncoder, Compression};
+use std::{fs::File, io::Write, path::Path};
+
+/// Live 11 set format. Newer versions convert it when the set is opened
+const MAJOR_VERSION: &str = "5";
+const MINOR_VERSION: &str = "11.0_433";
+
+/// Tempo range Live accepts
+const MIN_TEMPO: f64 = 20.0;
+const MAX_TEMPO: f64 = 999.0;
+
+/// Automation events before the start of the arrangement hold the initial value
+const BEFORE_START: f64 = -63072000.0;
+
+/// Hands out the ids of automation targets and events
+struct Ids(u64);
+
+impl Ids {
+    fn next(&mut self) -> u64 {
+        self.0 += 1;
+        self.0
+    }
+}
+
+/// Track name as shown in Live, which has both a user and an effective name
+fn track_name(out: &mut impl Write, name: &str) -> Result<()> {
+    writeln!(out, "        <Name>")?;
+    writeln!(out, r#"          <EffectiveName Value="{}"/>"#, name)?;
+    writeln!(out, r#"          <UserName Value="{}"/>"#, name)?;
+    writeln!(out, r#"          <Annotation Value=""/>"#)?;
+    writeln!(out, r#"          <MemorizedFirstClipName Value=""/>"#)?;
+    writeln!(out, "        </Name>")?;
+    Ok(())
+}
+
+/// Unwarped clip playing the whole stem from the start of the arrangement. Positions in the
+/// arrangement are in beats, positions in the stem in seconds
+fn audio_clip(
+    out: &mut impl Write,
+    set_dir: &Path,
+    sample_rate: u32,
+    track: &SessionTrack,
+    tempo_map: &[TempoChange],
+) -> Result<()> {
+    let seconds = track.frames as f64 / sample_rate as f64;
+    let name = xml_escape(&track.path.file_stem().unwrap_or_default().to_string_lossy());
+    let path = xml_escape(&track.path.to_string_lossy());
+    let relative_path = track
+        .path
+        .strip_prefix(set_dir)
+        .map(|p| xml_escape(&p.to_string_lossy().replace('\\', "/")))
+        .unwrap_or_default();
+    let file_size = std::fs::metadata(&track.path).map(|m| m.len()).unwrap_or(0);
+
+    writeln!(out, r#"                  <AudioClip Id="0" Time="0">"#)?;
+    writeln!(out, r#"                    <LomId Value="0"/>"#)?;
+    writeln!(out, r#"                    <CurrentStart Value="0"/>"#)?;
+    writeln!(
+        out,
+        r#"                    <CurrentEnd Value="{}"/>"#,
+        beat_at(tempo_map, seconds)
+    )?;
+    writeln!(out, "                    <Loop>")?;
+    writeln!(out, r#"                      <LoopStart Value="0"/>"#)?;
+    writeln!(
+        out,
+        r#"                      <LoopEnd Value="{}"/>"#,
+        seconds
+    )?;
+    writeln!(out, r#"                      <StartRelative Value="0"/>"#)?;
+    writeln!(out, r#"                      <LoopOn Value="false"/>"#)?;
+    writeln!(
+        out,
+        r#"                      <OutMarker Value="{}"/>"#,
+        seconds
+    )?;
+    writeln!(out, r#"                      <HiddenLoopStart Value="0"/>"#)?;
+    writeln!(
+        out,
+        r#"                      <HiddenLoopEnd Value="{}"/>"#,
+        seconds
+    )?;
+    writeln!(out, "                    </Loop>")?;
+    writeln!(out, r#"                    <Name Value="{}"/>"#, name)?;
+    writeln!(out, r#"                    <Annotation Value=""/>"#)?;
+    writeln!(out, r#"                    <Color Value="-1"/>"#)?;
+    writeln!(out, r#"                    <Disabled Value="false"/>"#)?;
+    writeln!(out, "                    <SampleRef>")?;
+    writeln!(out, "                      <FileRef>")?;
+    // Relative to the set, used when the absolute path no longer exists
+    writeln!(
+        out,
+        r#"                        <RelativePathType Value="{}"/>"#,
+        if relative_path.is_empty() { 0 } else { 1 }
+    )?;
+    writeln!(
+        out,
+        r#"                        <RelativePath Value="{}"/>"#,
+        relative_path
+    )?;
+    writeln!(out, r#"                        <Path Value="{}"/>"#, path)?;
+    writeln!(out, r#"                        <Type Value="1"/>"#)?;
+    writeln!(out, r#"                        <LivePackName Value=""/>"#)?;
+    writeln!(out, r#"                        <LivePackId Value=""/>"#)?;
+    writeln!(
+        out,
+        r#"                        <OriginalFileSize Value="{}"/>"#,
+        file_size
+    )?;
+    writeln!(out, r#"                        <OriginalCrc Value="0"/>"#)?;
+    writeln!(out, "                      </FileRef>")?;
+    writeln!(out, r#"                      <LastModDate Value="0"/>"#)?;
+    writeln!(out, "                      <SourceContext/>")?;
+    writeln!(out, r#"                      <SampleUsageHint Value="0"/>"#)?;
+    writeln!(
+        out,
+        r#"                      <DefaultDuration Value="{}"/>"#,
+        track.frames
+    )?;
+    writeln!(
+        out,
+        r#"                      <DefaultSampleRate Value="{}"/>"#,
+        sample_rate
+    )?;
+    writeln!(out, "                    </SampleRef>")?;
+    writeln!(out, r#"                    <IsWarped Value="false"/>"#)?;
+    writeln!(out, r#"                    <WarpMode Value="0"/>"#)?;
+    writeln!(out, "                    <WarpMarkers/>")?;
+    writeln!(out, "                  </AudioClip>")?;
+    Ok(())
+}
+
+/// Tempo automation on the master track. Changes are steps, so each one has an event with the
+/// old tempo and one with the new tempo at the same beat
+fn tempo_automation(
+    out: &mut impl Write,
+    ids: &mut Ids,
+    target: u64,
+    tempo_map: &[TempoChange],
+) -> Result<()> {
+    let bpm = |change: &TempoChange| change.bpm.clamp(MIN_TEMPO, MAX_TEMPO);
+
+    writeln!(out, "        <AutomationEnvelopes>")?;
+    writeln!(out, "          <Envelopes>")?;
+    writeln!(out, r#"            <AutomationEnvelope Id="0">"#)?;
+    writeln!(out, "              <EnvelopeTarget>")?;
+    writeln!(out, r#"                <PointeeId Value="{}"/>"#, target)?;
+    writeln!(out, "              </EnvelopeTarget>")?;
+    writeln!(out, "              <Automation>")?;
+    writeln!(out, "                <Events>")?;
+
+    for (index, change) in tempo_map.iter().enumerate() {
+        let (time, previous) = match index {
+            0 => (BEFORE_START, None),
+            _ => (change.beat, Some(bpm(&tempo_map[index - 1]))),
+        };
+
+        for value in previous.into_iter().chain(Some(bpm(change))) {
+            writeln!(
+                out,
+                r#"                  <FloatEvent Id="{}" Time="{}" Value="{}"/>"#,
+                ids.next(),
+                time,
+                value
+            )?;
+        }
+    }
+
+    writeln!(out, "                </Events>")?;
+    writeln!(out, "              </Automation>")?;
+    writeln!(out, "            </AutomationEnvelope>")?;
+    writeln!(out, "          </Envelopes>")?;
+    writeln!(out, "        </AutomationEnvelopes>")?;
+    Ok(())
+}
+
+/// Write the set for `tracks` to `path`. `tempo_map` sets the tempo of the set, 120 BPM is used
+/// if it's empty
+pub fn write_set(
+    path: &Path,
+    sample_rate: u32,
+    tracks: &[SessionTrack],
+    tempo_map: &[TempoChange],
+) -> Result<()> {
+    // NextPointeeId has to be above every id in the set, so the set is written first
+    let mut out = Vec::new();
+    let mut ids = Ids(100);
+    let set_dir = path
+        .parent()
+        .and_then(|p| p.canonicalize().ok())
+        .unwrap_or_default();
+
+    writeln!(out, "    <Tracks>")?;
+    for (index, track) in tracks.iter().enumerate() {
+        let name = xml_escape(&track.name);
+
+        writeln!(out, r#"      <AudioTrack Id="{}">"#, index + 1)?;
+        writeln!(out, r#"        <LomId Value="0"/>"#)?;
+        track_name(&mut out, &name)?;
+        writeln!(out, r#"        <Color Value="{}"/>"#, index % 70)?;
+        writeln!(out, r#"        <TrackGroupId Value="-1"/>"#)?;
+        writeln!(out, "        <DeviceChain>")?;
+        writeln!(out, "          <MainSequencer>")?;
+        writeln!(out, "            <Sample>")?;
+        writeln!(out, "              <ArrangerAutomation>")?;
+        writeln!(out, "                <Events>")?;
+        audio_clip(&mut out, &set_dir, sample_rate, track, tempo_map)?;
+        writeln!(out, "                </Events>")?;
+        writeln!(out, "              </ArrangerAutomation>")?;
+        writeln!(out, "            </Sample>")?;
+        writeln!(out, "          </MainSequencer>")?;
+        writeln!(out, "        </DeviceChain>")?;
+        writeln!(out, "      </AudioTrack>")?;
+    }
+    writeln!(out, "    </Tracks>")?;
+
+    let tempo_target = ids.next();
+    let initial_tempo = tempo_map
+        .first()
+        .map_or(120.0, |c| c.bpm.clamp(MIN_TEMPO, MAX_TEMPO));
+
+    writeln!(out, "    <MasterTrack>")?;
+    writeln!(out, r#"      <LomId Value="0"/>"#)?;
+    track_name(&mut out, "Master")?;
+    writeln!(out, "      <DeviceChain>")?;
+    writeln!(out, "        <Mixer>")?;
+    writeln!(out, "          <Tempo>")?;
+    writeln!(out, r#"            <LomId Value="0"/>"#)?;
+    writeln!(out, r#"            <Manual Value="{}"/>"#, initial_tempo)?;
+    writeln!(out, "            <MidiControllerRange>")?;
+    writeln!(out, r#"              <Min Value="60"/>"#)?;
+    writeln!(out, r#"              <Max Value="200"/>"#)?;
+    writeln!(out, "            </MidiControllerRange>")?;
+    writeln!(
+        out,
+        r#"            <AutomationTarget Id="{}">"#,
+        tempo_target
+    )?;
+    writeln!(out, r#"              <LockEnvelope Value="0"/>"#)?;
+    writeln!(out, "            </AutomationTarget>")?;
+    writeln!(out, "          </Tempo>")?;
+    writeln!(out, "        </Mixer>")?;
+    writeln!(out, "      </DeviceChain>")?;
+    if tempo_map.len() > 1 {
+        tempo_automation(&mut out, &mut ids, tempo_target, tempo_map)?;
+    }
+    writeln!(out, "    </MasterTrack>")?;
+    writeln!(out, "  </LiveSet>")?;
+    writeln!(out, "</Ableton>")?;
+
+    let mut file = GzEncoder::new(File::create(path)?, Compression::default());
+    writeln!(file, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        file,
+        r#"<Ableton MajorVersion="{}" MinorVersion="{}" SchemaChangeCount="3" Creator="stemgen {}" Revision="">"#,
+        MAJOR_VERSION,
+        MINOR_VERSION,
+        env!("CARGO_PKG_VERSION")
+    )?;
+    writeln!(file, "  <LiveSet>")?;
+    writeln!(file, r#"    <NextPointeeId Value="{}"/>"#, ids.0 + 1)?;
+    writeln!(file, r#"    <OverwriteProtectionNumber Value="2816"/>"#)?;
+    writeln!(file, r#"    <LomId Value="0"/>"#)?;
+    writeln!(file, r#"    <LomIdView Value="0"/>"#)?;
+    file.write_all(&out)?;
+    file.finish()?.flush()?;
+
+    Ok(())
+}
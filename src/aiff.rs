@@ -0,0 +1,194 @@
+use std::io::{Error, ErrorKind, Result, Write};
+
+/// Sample rate as an 80-bit IEEE 754 extended precision float, as used in the COMM chunk
+fn extended(value: u32) -> [u8; 10] {
+    let mut output = [0u8; 10];
+
+    if value == 0 {
+        return output;
+    }
+
+    let shift = value.leading_zeros();
+    let exponent = 16383 + 31 - shift as u16;
+    let mantissa = (value as u64) << (32 + shift);
+
+    output[..2].copy_from_slice(&exponent.to_be_bytes());
+    output[2..].copy_from_slice(&mantissa.to_be_bytes());
+    output
+}
+
+/// Frame count and the SSND and FORM chunk sizes for `data_bytes` of samples after
+/// `header_bytes` of other chunks. An error if they don't fit the 32-bit fields of AIFF
+fn sizes(data_bytes: usize, frame_bytes: usize, header_bytes: u32) -> Result<(u32, u32, u32)> {
+    let too_large = || {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} bytes of samples is too large for AIFF", data_bytes),
+        )
+    };
+
+    // Offset and block size come before the samples
+    let ssnd_size = u32::try_from(data_bytes as u64 + 8).map_err(|_| too_large())?;
+    let form_size = (header_bytes + 8)
+        .checked_add(ssnd_size)
+        .ok_or_else(too_large)?;
+
+    Ok(((data_bytes / frame_bytes) as u32, ssnd_size, form_size))
+}
+
+fn write_chunk_header<W: Write>(writer: &mut W, id: &[u8; 4], size: u32) -> Result<()> {
+    writer.write_all(id)?;
+    writer.write_all(&size.to_be_bytes())
+}
+
+/// Write interleaved native endian samples as AIFF. 16-bit samples are written as plain AIFF
+/// and 32-bit float as AIFF-C with the `fl32` compression type. Sizes are known up front so
+/// nothing has to be patched afterwards and the writer doesn't need to seek. Renders over 4 GB
+/// don't fit the chunk sizes and are an error
+pub fn write_aiff<W: Write>(
+    mut writer: W,
+    buffer: &[u8],
+    channel_count: usize,
+    sample_rate: u32,
+    bytes_per_sample: usize,
+) -> Result<()> {
+    let float = bytes_per_sample == 4;
+
+    // COMM for AIFF-C also has the compression type and a pascal string with its name
+    let compression_name = b"\x1532-bit floating point";
+    let comm_size = if float {
+        18 + 4 + compression_name.len() as u32
+    } else {
+        18
+    };
+    let fver_size = if float { 8 + 4 } else { 0 };
+    let (frames, ssnd_size, form_size) = sizes(
+        buffer.len(),
+        bytes_per_sample * channel_count,
+        4 + fver_size + 8 + comm_size,
+    )?;
+
+    write_chunk_header(&mut writer, b"FORM", form_size)?;
+    writer.write_all(if float { b"AIFC" } else { b"AIFF" })?;
+
+    if float {
+        // AIFF-C version 1
+        write_chunk_header(&mut writer, b"FVER", 4)?;
+        writer.write_all(&0xa280_5140u32.to_be_bytes())?;
+    }
+
+    write_chunk_header(&mut writer, b"COMM", comm_size)?;
+    writer.write_all(&(channel_count as u16).to_be_bytes())?;
+    writer.write_all(&frames.to_be_bytes())?;
+    writer.write_all(&(bytes_per_sample as u16 * 8).to_be_bytes())?;
+    writer.write_all(&extended(sample_rate))?;
+
+    if float {
+        writer.write_all(b"fl32")?;
+        // Name is 22 bytes with the length so no pad byte is needed
+        writer.write_all(compression_name)?;
+    }
+
+    write_chunk_header(&mut writer, b"SSND", ssnd_size)?;
+    // Offset and block size
+    writer.write_all(&[0u8; 8])?;
+
+    // AIFF is big endian
+    if cfg!(target_endian = "little") {
+        let mut data = buffer.to_vec();
+        data.chunks_exact_mut(bytes_per_sample)
+            .for_each(|sample| sample.reverse());
+        writer.write_all(&data)?;
+    } else {
+        writer.write_all(buffer)?;
+    }
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u16_at(data: &[u8], offset: usize) -> u16 {
+        u16::from_be_bytes(data[offset..offset + 2].try_into().unwrap())
+    }
+
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn sample_rates_are_80_bit_floats() {
+        assert_eq!(extended(44100), [0x40, 0x0e, 0xac, 0x44, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(extended(48000), [0x40, 0x0e, 0xbb, 0x80, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(extended(1), [0x3f, 0xff, 0x80, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(extended(0), [0; 10]);
+    }
+
+    #[test]
+    fn pcm16_is_plain_aiff() {
+        let samples: [i16; 4] = [1, -2, 0x1234, i16::MIN];
+        let mut data = Vec::new();
+        write_aiff(&mut data, bytemuck::cast_slice(&samples), 2, 44100, 2).unwrap();
+
+        assert_eq!(&data[0..4], b"FORM");
+        assert_eq!(u32_at(&data, 4), data.len() as u32 - 8);
+        assert_eq!(&data[8..12], b"AIFF");
+
+        assert_eq!(&data[12..16], b"COMM");
+        assert_eq!(u32_at(&data, 16), 18);
+        assert_eq!(u16_at(&data, 20), 2);
+        assert_eq!(u32_at(&data, 22), 2);
+        assert_eq!(u16_at(&data, 26), 16);
+        assert_eq!(data[28..38], extended(44100));
+
+        assert_eq!(&data[38..42], b"SSND");
+        assert_eq!(u32_at(&data, 42), 8 + 8);
+        assert_eq!(data[46..54], [0; 8]);
+        assert_eq!(data[54..], [0, 1, 0xff, 0xfe, 0x12, 0x34, 0x80, 0]);
+    }
+
+    #[test]
+    fn float_is_aifc_fl32() {
+        let samples = [0.5f32, -1.0, 0.25];
+        let mut data = Vec::new();
+        write_aiff(&mut data, bytemuck::cast_slice(&samples), 1, 48000, 4).unwrap();
+
+        assert_eq!(u32_at(&data, 4), data.len() as u32 - 8);
+        assert_eq!(&data[8..12], b"AIFC");
+
+        assert_eq!(&data[12..16], b"FVER");
+        assert_eq!(u32_at(&data, 16), 4);
+        assert_eq!(u32_at(&data, 20), 0xa280_5140);
+
+        assert_eq!(&data[24..28], b"COMM");
+        assert_eq!(u32_at(&data, 28), 44);
+        assert_eq!(u16_at(&data, 32), 1);
+        assert_eq!(u32_at(&data, 34), 3);
+        assert_eq!(u16_at(&data, 38), 32);
+        assert_eq!(data[40..50], extended(48000));
+        assert_eq!(&data[50..54], b"fl32");
+        assert_eq!(data[54], 21);
+        assert_eq!(&data[55..76], b"32-bit floating point");
+
+        assert_eq!(&data[76..80], b"SSND");
+        assert_eq!(u32_at(&data, 80), 8 + 12);
+        let floats: Vec<f32> = data[92..]
+            .chunks(4)
+            .map(|b| f32::from_be_bytes(b.try_into().unwrap()))
+            .collect();
+        assert_eq!(floats, samples);
+    }
+
+    #[test]
+    fn sizes_over_4_gb_are_an_error() {
+        assert_eq!(sizes(16, 4, 30).unwrap(), (4, 24, 62));
+
+        let largest = u32::MAX as usize - 8 - 38;
+        assert_eq!(sizes(largest, 2, 30).unwrap().2, u32::MAX);
+        assert!(sizes(largest + 1, 2, 30).is_err());
+        assert!(sizes(u32::MAX as usize, 2, 30).is_err());
+        assert!(sizes(5 << 30, 4, 30).is_err());
+    }
+}
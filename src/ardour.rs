@@ -0,0 +1,283 @@
+//! Ardour session (`<song>_ardour/<song>.ardour`) with a track per stem. The stems are referenced
+//! where they are (as embedded files in Ardour terms) instead of being copied into the session
+
+use crate::session::{xml_escape, SessionTrack};
+use anyhow::Result;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+/// Ardour 6 session format. Newer versions convert it when the session is opened
+const SESSION_VERSION: u32 = 6000;
+
+/// Hands out the object ids used to link sources, regions, playlists and routes together
+struct Ids(u64);
+
+impl Ids {
+    fn next(&mut self) -> u64 {
+        self.0 += 1;
+        self.0
+    }
+}
+
+/// Gain, meter and output processors every route has
+fn route_processors(out: &mut impl Write, ids: &mut Ids, name: &str) -> Result<()> {
+    writeln!(
+        out,
+        r#"      <Processor id="{}" name="Amp" active="1" user-latency="0" type="amp">"#,
+        ids.next()
+    )?;
+    writeln!(
+        out,
+        r#"        <Controllable name="gaincontrol" id="{}" flags="GainLike" value="1"/>"#,
+        ids.next()
+    )?;
+    writeln!(out, r#"      </Processor>"#)?;
+    writeln!(
+        out,
+        r#"      <Processor id="{}" name="meter-{}" active="1" user-latency="0" type="meter"/>"#,
+        ids.next(),
+        name
+    )?;
+    writeln!(
+        out,
+        r#"      <Processor id="{}" name="{}" active="1" user-latency="0" own-input="1" own-output="1" output="{}" type="main-outs" role="Main">"#,
+        ids.next(),
+        name,
+        name
+    )?;
+    writeln!(
+        out,
+        r#"        <PannerShell bypassed="0" user-panner="" linked-to-route="1"/>"#
+    )?;
+    writeln!(out, r#"      </Processor>"#)?;
+    Ok(())
+}
+
+/// Input or output ports of a route. `connections` are the ports each one is connected to
+fn route_io(
+    out: &mut impl Write,
+    ids: &mut Ids,
+    name: &str,
+    direction: &str,
+    connections: &[Option<String>],
+) -> Result<()> {
+    let port = if direction == "Input" { "in" } else { "out" };
+
+    writeln!(
+        out,
+        r#"      <IO name="{}" id="{}" direction="{}" default-type="audio" user-latency="0">"#,
+        name,
+        ids.next(),
+        direction
+    )?;
+
+    for (index, connection) in connections.iter().enumerate() {
+        match connection {
+            Some(other) => {
+                writeln!(
+                    out,
+                    r#"        <Port type="audio" name="{}/audio_{} {}">"#,
+                    name,
+                    port,
+                    index + 1
+                )?;
+                writeln!(out, r#"          <Connection other="{}"/>"#, other)?;
+                writeln!(out, r#"        </Port>"#)?;
+            }
+            None => writeln!(
+                out,
+                r#"        <Port type="audio" name="{}/audio_{} {}"/>"#,
+                name,
+                port,
+                index + 1
+            )?,
+        }
+    }
+
+    writeln!(out, r#"      </IO>"#)?;
+    Ok(())
+}
+
+/// Region covering the whole stem, both as the whole file region and the one on the playlist
+fn region(
+    out: &mut impl Write,
+    id: u64,
+    name: &str,
+    track: &SessionTrack,
+    sources: &[u64],
+    whole_file: bool,
+) -> Result<()> {
+    let sources: String = sources
+        .iter()
+        .enumerate()
+        .map(|(channel, source)| {
+            format!(
+                r#" source-{0}="{1}" master-source-{0}="{1}""#,
+                channel, source
+            )
+        })
+        .collect();
+
+    writeln!(
+        out,
+        r#"      <Region name="{}" muted="0" opaque="1" locked="0" automatic="0" whole-file="{}" import="1" external="1" hidden="0" start="0" length="{}" position="0" sync-position="0" ancestral-start="0" ancestral-length="0" stretch="1" shift="1" scale-amplitude="1" envelope-active="0" default-fade-in="1" default-fade-out="1" fade-in-active="1" fade-out-active="1" id="{}" type="audio" channels="{}"{}/>"#,
+        name, whole_file as u32, track.frames, id, track.channel_count, sources
+    )?;
+
+    Ok(())
+}
+
+/// Write the session for `tracks` to `path`. The tracks go to a stereo master bus connected to
+/// the first two outputs of the sound card
+pub fn write_session(
+    path: &Path,
+    session_name: &str,
+    sample_rate: u32,
+    tracks: &[SessionTrack],
+) -> Result<()> {
+    // The session element holds the highest id, so everything in it is written first
+    let mut out = Vec::new();
+    let mut ids = Ids(1000);
+    let session_name = xml_escape(session_name);
+    let end = tracks.iter().map(|t| t.frames).max().unwrap_or(0);
+
+    // Ids of the sources of each track (one per channel), its playlist and its route
+    let layout: Vec<(Vec<u64>, u64, u64, u64, u64)> = tracks
+        .iter()
+        .map(|track| {
+            let sources = (0..track.channel_count).map(|_| ids.next()).collect();
+            (sources, ids.next(), ids.next(), ids.next(), ids.next())
+        })
+        .collect();
+
+    let version = format!("stemgen {}", env!("CARGO_PKG_VERSION"));
+
+    writeln!(
+        out,
+        r#"  <ProgramVersion created-with="{}" modified-with="{}"/>"#,
+        version, version
+    )?;
+    writeln!(out, "  <Config>")?;
+    writeln!(
+        out,
+        r#"    <Option name="native-file-header-format" value="WAVE"/>"#
+    )?;
+    writeln!(out, "  </Config>")?;
+
+    writeln!(out, "  <Sources>")?;
+    for (track, (sources, ..)) in tracks.iter().zip(&layout) {
+        let path = xml_escape(&track.path.to_string_lossy());
+        for (channel, id) in sources.iter().enumerate() {
+            writeln!(
+                out,
+                r#"    <Source name="{}" type="audio" flags="" id="{}" captured-for="" channel="{}" origin=""/>"#,
+                path, id, channel
+            )?;
+        }
+    }
+    writeln!(out, "  </Sources>")?;
+
+    writeln!(out, "  <Regions>")?;
+    for (track, (sources, whole_region, ..)) in tracks.iter().zip(&layout) {
+        let name = xml_escape(&track.path.file_stem().unwrap_or_default().to_string_lossy());
+        region(&mut out, *whole_region, &name, track, sources, true)?;
+    }
+    writeln!(out, "  </Regions>")?;
+
+    writeln!(out, "  <Locations>")?;
+    writeln!(
+        out,
+        r#"    <Location id="{}" name="session" start="0" end="{}" flags="IsSessionRange" locked="0" timestamp="0"/>"#,
+        ids.next(),
+        end
+    )?;
+    writeln!(out, "  </Locations>")?;
+    writeln!(out, "  <Bundles/>")?;
+
+    writeln!(out, "  <Routes>")?;
+
+    let master_inputs: Vec<Option<String>> = vec![None, None];
+    let master_outputs: Vec<Option<String>> = (1..=2)
+        .map(|i| Some(format!("system:playback_{}", i)))
+        .collect();
+
+    writeln!(
+        out,
+        r#"    <Route version="{}" id="{}" name="Master" default-type="audio" strict-io="1" active="1" denormal-protection="0" meter-point="MeterPostFader" disk-io-point="DiskIOPreFader" meter-type="MeterPeak">"#,
+        SESSION_VERSION,
+        ids.next()
+    )?;
+    writeln!(
+        out,
+        r#"      <PresentationInfo order="0" flags="MasterOut,OrderSet"/>"#
+    )?;
+    route_io(&mut out, &mut ids, "Master", "Input", &master_inputs)?;
+    route_io(&mut out, &mut ids, "Master", "Output", &master_outputs)?;
+    route_processors(&mut out, &mut ids, "Master")?;
+    writeln!(out, "    </Route>")?;
+
+    for (order, (track, (_, _, playlist, route, _))) in tracks.iter().zip(&layout).enumerate() {
+        let name = xml_escape(&track.name);
+        let inputs: Vec<Option<String>> = vec![None; track.channel_count as usize];
+        let outputs: Vec<Option<String>> = (1..=2)
+            .map(|i| Some(format!("Master/audio_in {}", i)))
+            .collect();
+
+        writeln!(
+            out,
+            r#"    <Route version="{}" id="{}" name="{}" default-type="audio" strict-io="1" active="1" denormal-protection="0" meter-point="MeterPostFader" disk-io-point="DiskIOPreFader" meter-type="MeterPeak" saved-meter-point="MeterPostFader" alignment-choice="Automatic" playlist="{}" audio-playlist="{}" mode="Normal">"#,
+            SESSION_VERSION, route, name, name, playlist
+        )?;
+        writeln!(
+            out,
+            r#"      <PresentationInfo order="{}" flags="AudioTrack,OrderSet"/>"#,
+            order
+        )?;
+        route_io(&mut out, &mut ids, &name, "Input", &inputs)?;
+        route_io(&mut out, &mut ids, &name, "Output", &outputs)?;
+        writeln!(
+            out,
+            r#"      <Processor id="{}" name="player:{}" active="1" user-latency="0" type="diskreader"/>"#,
+            ids.next(),
+            name
+        )?;
+        route_processors(&mut out, &mut ids, &name)?;
+        writeln!(out, "    </Route>")?;
+    }
+
+    writeln!(out, "  </Routes>")?;
+
+    writeln!(out, "  <Playlists>")?;
+    for (track, (sources, _, playlist, route, region_id)) in tracks.iter().zip(&layout) {
+        let name = xml_escape(&track.name);
+        writeln!(
+            out,
+            r#"    <Playlist id="{}" name="{}" type="audio" orig-track-id="{}" shared-with-ids="" frozen="0" combine-ops="0">"#,
+            playlist, name, route
+        )?;
+        region(&mut out, *region_id, &name, track, sources, false)?;
+        writeln!(out, "    </Playlist>")?;
+    }
+    writeln!(out, "  </Playlists>")?;
+    writeln!(out, "  <UnusedPlaylists/>")?;
+    writeln!(out, "</Session>")?;
+
+    let mut file = BufWriter::new(File::create(path)?);
+    writeln!(file, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    // New objects created by Ardour must not reuse any of the ids above
+    writeln!(
+        file,
+        r#"<Session version="{}" name="{}" sample-rate="{}" end-is-free="0" id-counter="{}" name-counter="1" event-counter="0" vca-counter="1">"#,
+        SESSION_VERSION,
+        session_name,
+        sample_rate,
+        ids.0 + 1
+    )?;
+    file.write_all(&out)?;
+    file.flush()?;
+
+    Ok(())
+}
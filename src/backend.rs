@@ -0,0 +1,83 @@
+use crate::{RenderOptions, RenderParams, RenderProgress, SongInfo, StemSelection};
+use anyhow::Result;
+use stemgen_core::{AudioBuffer, Song};
+
+/// Playback library that renders songs. Song info and metadata always come from libopenmpt, the
+/// backend only replaces the playback
+pub trait Backend: Sync {
+    /// Render `input` into `output` as float samples and return the number of bytes written
+    fn render(&self, output: &mut [u8], input: &[u8], render_params: &RenderParams) -> u32;
+
+    /// Start a render that is read in chunks so the whole song never has to be in memory.
+    /// Backends that can only render a song in one go return None
+    fn open_stream(
+        &self,
+        _input: &[u8],
+        _render_params: &RenderParams,
+    ) -> Option<Box<dyn RenderStream>> {
+        None
+    }
+
+    /// Render `stem` of `input` through `Song::render_stem`. Backends that don't play the song
+    /// with libopenmpt return None and are rendered with `render`
+    fn render_stem(
+        &self,
+        _input: &[u8],
+        _song_info: &SongInfo,
+        _stem: StemSelection,
+        _options: &RenderOptions,
+        _on_progress: &dyn Fn(RenderProgress),
+    ) -> Option<Result<AudioBuffer>> {
+        None
+    }
+
+    /// If instruments can be soloed. Backends without it can only render channels and full mixes
+    fn can_solo_instruments(&self) -> bool;
+}
+
+/// Render in progress
+pub trait RenderStream {
+    /// Render float samples into `output` and return the number of bytes written. Fewer bytes
+    /// than the size of `output` means the song has ended
+    fn read(&mut self, output: &mut [u8]) -> usize;
+
+    /// Number of frames the whole render produces as calculated by the playback library. None if
+    /// it can't tell or the song plays forever
+    fn frame_count(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// The libopenmpt build in external/libopenmpt
+pub struct OpenMpt;
+
+impl Backend for OpenMpt {
+    fn render(&self, output: &mut [u8], input: &[u8], render_params: &RenderParams) -> u32 {
+        stemgen_core::render(output, input, render_params)
+    }
+
+    fn open_stream(
+        &self,
+        input: &[u8],
+        render_params: &RenderParams,
+    ) -> Option<Box<dyn RenderStream>> {
+        let stream = stemgen_core::SongStream::open(input, render_params)?;
+        Some(Box::new(stream))
+    }
+
+    fn render_stem(
+        &self,
+        input: &[u8],
+        song_info: &SongInfo,
+        stem: StemSelection,
+        options: &RenderOptions,
+        on_progress: &dyn Fn(RenderProgress),
+    ) -> Option<Result<AudioBuffer>> {
+        let song = Song::from_parts(input.to_vec(), *song_info);
+        Some(song.render_stem_with_progress(stem, options, on_progress))
+    }
+
+    fn can_solo_instruments(&self) -> bool {
+        true
+    }
+}
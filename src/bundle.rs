@@ -0,0 +1,52 @@
+use crate::manifest::{Manifest, OutputStatus};
+use anyhow::Result;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+/// Pack all written stems of a song together with the manifest, an info text and an optional
+/// cover image into a single zip file
+pub fn write_bundle(
+    bundle_path: &Path,
+    output_dir: &Path,
+    stemname: &str,
+    manifest: &Manifest,
+    info_text: &str,
+    cover: Option<&Path>,
+) -> Result<()> {
+    let mut zip = ZipWriter::new(BufWriter::new(File::create(bundle_path)?));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    for output in manifest
+        .outputs
+        .iter()
+        .filter(|o| matches!(o.status, OutputStatus::Written | OutputStatus::Existing))
+    {
+        zip.start_file(output.filename.as_str(), options)?;
+        let mut file = File::open(output_dir.join(&output.filename))?;
+        std::io::copy(&mut file, &mut zip)?;
+    }
+
+    zip.start_file(format!("{}_manifest.json", stemname), options)?;
+    zip.write_all(manifest.to_json()?.as_bytes())?;
+
+    zip.start_file(format!("{}_info.txt", stemname), options)?;
+    zip.write_all(info_text.as_bytes())?;
+
+    if let Some(cover) = cover {
+        let name = match cover.extension() {
+            Some(ext) => format!("cover.{}", ext.to_string_lossy()),
+            None => "cover".to_owned(),
+        };
+
+        zip.start_file(name, options)?;
+        std::io::copy(&mut File::open(cover)?, &mut zip)?;
+    }
+
+    zip.finish()?.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,35 @@
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::{fs::File, io::Read, path::Path};
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// SHA-256 of the whole file
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// MD5 of the unencoded audio data that libFLAC stores in the STREAMINFO block. Unlike a hash of
+/// the file it stays the same if only the metadata or compression settings change. Returns None
+/// if the file doesn't have one (such as when written to a pipe)
+pub fn flac_audio_md5(path: &Path) -> Result<Option<String>> {
+    // "fLaC" marker, 4 byte metadata block header and then STREAMINFO with the MD5 at offset 18
+    let mut header = [0u8; 42];
+    File::open(path)?.read_exact(&mut header)?;
+
+    if &header[0..4] != b"fLaC" || header[4] & 0x7f != 0 {
+        anyhow::bail!("{:?} doesn't start with a FLAC STREAMINFO block", path);
+    }
+
+    let md5 = &header[26..42];
+
+    if md5.iter().all(|&b| b == 0) {
+        Ok(None)
+    } else {
+        Ok(Some(to_hex(md5)))
+    }
+}
@@ -0,0 +1,116 @@
+use anyhow::{bail, Result};
+use base64::Engine;
+use id3::TagLike;
+use std::path::{Path, PathBuf};
+
+/// Extensions tried when looking up the cover of a song in a directory
+const EXTENSIONS: &[&str] = &["png", "jpg", "jpeg"];
+
+/// Cover image embedded in outputs and bundles
+#[derive(Debug, Clone)]
+pub struct Cover {
+    pub path: PathBuf,
+    pub mime_type: &'static str,
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+/// `--cover` is either a single image used for all songs or a directory with one image per song
+/// named after the song (such as `covers/song.png` for `song.xm`)
+pub fn find(cover: &Path, song_stem: &str) -> Option<PathBuf> {
+    if !cover.is_dir() {
+        return Some(cover.to_owned());
+    }
+
+    EXTENSIONS
+        .iter()
+        .map(|ext| cover.join(format!("{}.{}", song_stem, ext)))
+        .find(|path| path.is_file())
+}
+
+/// Width and height from the PNG header or the first JPEG start of frame marker
+fn dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    if data.starts_with(b"\x89PNG") && data.len() >= 24 {
+        let width = u32::from_be_bytes(data[16..20].try_into().ok()?);
+        let height = u32::from_be_bytes(data[20..24].try_into().ok()?);
+        return Some((width, height));
+    }
+
+    let mut offset = 2;
+    while offset + 9 < data.len() {
+        if data[offset] != 0xff {
+            return None;
+        }
+
+        let marker = data[offset + 1];
+        let len = u16::from_be_bytes([data[offset + 2], data[offset + 3]]) as usize;
+
+        // SOF0 - SOF15 except DHT, JPG and DAC which share the range
+        if (0xc0..=0xcf).contains(&marker) && ![0xc4, 0xc8, 0xcc].contains(&marker) {
+            let height = u16::from_be_bytes([data[offset + 5], data[offset + 6]]) as u32;
+            let width = u16::from_be_bytes([data[offset + 7], data[offset + 8]]) as u32;
+            return Some((width, height));
+        }
+
+        offset += 2 + len;
+    }
+
+    None
+}
+
+impl Cover {
+    pub fn load(path: &Path) -> Result<Cover> {
+        let data = std::fs::read(path)?;
+
+        let mime_type = if data.starts_with(b"\x89PNG") {
+            "image/png"
+        } else if data.starts_with(&[0xff, 0xd8]) {
+            "image/jpeg"
+        } else {
+            bail!("{:?} isn't a png or jpeg image", path);
+        };
+
+        let (width, height) = dimensions(&data).unwrap_or((0, 0));
+
+        Ok(Cover {
+            path: path.to_owned(),
+            mime_type,
+            width,
+            height,
+            data,
+        })
+    }
+
+    /// Picture in the layout of a FLAC PICTURE block, base64 encoded as used by the
+    /// METADATA_BLOCK_PICTURE comment in Ogg files
+    pub fn metadata_block_picture(&self) -> String {
+        let mut block = Vec::with_capacity(self.data.len() + 64);
+
+        // Front cover
+        block.extend_from_slice(&3u32.to_be_bytes());
+        block.extend_from_slice(&(self.mime_type.len() as u32).to_be_bytes());
+        block.extend_from_slice(self.mime_type.as_bytes());
+        // No description
+        block.extend_from_slice(&0u32.to_be_bytes());
+        block.extend_from_slice(&self.width.to_be_bytes());
+        block.extend_from_slice(&self.height.to_be_bytes());
+        // Color depth and palette size are left as unknown
+        block.extend_from_slice(&0u32.to_be_bytes());
+        block.extend_from_slice(&0u32.to_be_bytes());
+        block.extend_from_slice(&(self.data.len() as u32).to_be_bytes());
+        block.extend_from_slice(&self.data);
+
+        base64::engine::general_purpose::STANDARD.encode(block)
+    }
+
+    /// Add the cover as an APIC frame for mp3 files
+    pub fn add_to_id3(&self, tag: &mut id3::Tag) {
+        tag.add_frame(id3::frame::Picture {
+            mime_type: self.mime_type.to_owned(),
+            picture_type: id3::frame::PictureType::CoverFront,
+            description: String::new(),
+            data: self.data.clone(),
+        });
+    }
+}
@@ -0,0 +1,139 @@
+use std::io::{Result, Write};
+
+/// Max number of tracks on a CD
+const MAX_TRACKS: usize = 99;
+
+/// Start of a track in the cue sheet
+#[derive(Debug, Clone)]
+pub struct CuePoint {
+    pub title: String,
+    pub seconds: f32,
+}
+
+/// Number of CD frames (1/75 of a second)
+fn frames(seconds: f32) -> u64 {
+    (seconds.max(0.0) as f64 * 75.0).round() as u64
+}
+
+/// Frames as minutes, seconds and frames as used by INDEX lines
+fn index_time(frames: u64) -> String {
+    format!(
+        "{:02}:{:02}:{:02}",
+        frames / (75 * 60),
+        (frames / 75) % 60,
+        frames % 75
+    )
+}
+
+/// Cue sheets are line based with quoted strings so quotes and line breaks can't be kept
+fn quote(text: &str) -> String {
+    let text: String = text
+        .chars()
+        .map(|c| match c {
+            '"' => '\'',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .collect();
+    format!("\"{}\"", text.trim())
+}
+
+/// Write a cue sheet for `audio_filename` (relative to the cue sheet) with one track per point.
+/// Points that start in the same CD frame as the previous one are merged and only the first 99
+/// tracks are kept. The first track always starts at 00:00:00
+pub fn write_cue<W: Write>(
+    mut writer: W,
+    audio_filename: &str,
+    file_type: &str,
+    title: &str,
+    points: &[CuePoint],
+) -> Result<()> {
+    let mut tracks: Vec<(u64, &str)> = Vec::new();
+
+    for point in points {
+        match tracks.last() {
+            Some((last, _)) if *last >= frames(point.seconds) => continue,
+            Some(_) => tracks.push((frames(point.seconds), &point.title)),
+            None => tracks.push((0, &point.title)),
+        }
+    }
+
+    if tracks.len() > MAX_TRACKS {
+        log::warn!(
+            "{} has {} cue points, only the first {} are written",
+            audio_filename,
+            tracks.len(),
+            MAX_TRACKS
+        );
+        tracks.truncate(MAX_TRACKS);
+    }
+
+    if !title.is_empty() {
+        writeln!(writer, "TITLE {}", quote(title))?;
+    }
+    writeln!(writer, "FILE {} {}", quote(audio_filename), file_type)?;
+
+    for (i, (frames, title)) in tracks.iter().enumerate() {
+        writeln!(writer, "  TRACK {:02} AUDIO", i + 1)?;
+        writeln!(writer, "    TITLE {}", quote(title))?;
+        writeln!(writer, "    INDEX 01 {}", index_time(*frames))?;
+    }
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(title: &str, seconds: f32) -> CuePoint {
+        CuePoint {
+            title: title.to_owned(),
+            seconds,
+        }
+    }
+
+    fn cue(title: &str, points: &[CuePoint]) -> String {
+        let mut output = Vec::new();
+        write_cue(&mut output, "song.wav", "WAVE", title, points).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn tracks_start_at_their_cd_frame() {
+        let points = [
+            point("Intro", 0.5),
+            point("Order 1", 65.5),
+            point("Same frame", 65.501),
+        ];
+
+        assert_eq!(
+            cue("My \"song\"", &points),
+            "TITLE \"My 'song'\"\n\
+             FILE \"song.wav\" WAVE\n\
+             \x20 TRACK 01 AUDIO\n\
+             \x20   TITLE \"Intro\"\n\
+             \x20   INDEX 01 00:00:00\n\
+             \x20 TRACK 02 AUDIO\n\
+             \x20   TITLE \"Order 1\"\n\
+             \x20   INDEX 01 01:05:38\n"
+        );
+    }
+
+    #[test]
+    fn at_most_99_tracks() {
+        let points: Vec<CuePoint> = (0..120).map(|i| point("Track", i as f32)).collect();
+        let cue = cue("", &points);
+
+        assert!(!cue.contains("TITLE \"\"\nFILE"));
+        assert!(cue.starts_with("FILE"));
+        assert_eq!(cue.matches("TRACK").count(), 99);
+        assert!(cue.contains("INDEX 01 01:38:00"));
+    }
+
+    #[test]
+    fn titles_are_on_one_line() {
+        assert_eq!(quote(" a\nb\t"), "\"a b\"");
+        assert_eq!(index_time(frames(3600.0)), "60:00:00");
+    }
+}
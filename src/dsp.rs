@@ -0,0 +1,548 @@
+use rubato::{FftFixedIn, Resampler};
+
+/// Linearly fade the interleaved buffer from full volume at `start_frame` down to silence at the
+/// end of the buffer. `bytes_per_sample` selects between i16 (2) and f32 (4) samples.
+pub fn fade_out(
+    buffer: &mut [u8],
+    bytes_per_sample: usize,
+    channel_count: usize,
+    start_frame: usize,
+) {
+    let frames = buffer.len() / (bytes_per_sample * channel_count);
+
+    if start_frame >= frames {
+        return;
+    }
+
+    let fade_len = (frames - start_frame) as f32;
+    let gain = |frame: usize| 1.0 - (frame - start_frame) as f32 / fade_len;
+
+    if bytes_per_sample == 4 {
+        let data: &mut [f32] = bytemuck::cast_slice_mut(buffer);
+        for (i, frame) in data
+            .chunks_exact_mut(channel_count)
+            .enumerate()
+            .skip(start_frame)
+        {
+            let g = gain(i);
+            frame.iter_mut().for_each(|s| *s *= g);
+        }
+    } else {
+        let data: &mut [i16] = bytemuck::cast_slice_mut(buffer);
+        for (i, frame) in data
+            .chunks_exact_mut(channel_count)
+            .enumerate()
+            .skip(start_frame)
+        {
+            let g = gain(i);
+            frame.iter_mut().for_each(|s| *s = (*s as f32 * g) as i16);
+        }
+    }
+}
+
+/// Fade out for a render that is processed in chunks. `first_frame` is where the chunk starts in
+/// the render and the fade runs from `start_frame` down to silence at `end_frame`.
+pub fn fade_out_chunk(
+    buffer: &mut [u8],
+    bytes_per_sample: usize,
+    channel_count: usize,
+    first_frame: usize,
+    start_frame: usize,
+    end_frame: usize,
+) {
+    if end_frame <= start_frame {
+        return;
+    }
+
+    let fade_len = (end_frame - start_frame) as f32;
+
+    with_f32_samples(buffer, bytes_per_sample, |samples| {
+        for (i, frame) in samples.chunks_exact_mut(channel_count).enumerate() {
+            let frame_index = first_frame + i;
+
+            if frame_index < start_frame {
+                continue;
+            }
+
+            let g = (1.0 - (frame_index - start_frame) as f32 / fade_len).max(0.0);
+            frame.iter_mut().for_each(|s| *s *= g);
+        }
+    });
+}
+
+/// Resample an interleaved buffer from `from_rate` to `to_rate`. The output uses the same sample
+/// format (i16 or f32 selected by `bytes_per_sample`) as the input.
+pub fn resample(
+    buffer: &[u8],
+    bytes_per_sample: usize,
+    channel_count: usize,
+    from_rate: u32,
+    to_rate: u32,
+) -> anyhow::Result<Vec<u8>> {
+    const CHUNK_SIZE: usize = 1024;
+
+    // Deinterleave into one f32 vector per channel
+    let samples: Vec<f32> = if bytes_per_sample == 4 {
+        bytemuck::cast_slice::<u8, f32>(buffer).to_vec()
+    } else {
+        bytemuck::cast_slice::<u8, i16>(buffer)
+            .iter()
+            .map(|s| *s as f32 / 32768.0)
+            .collect()
+    };
+
+    let frames = samples.len() / channel_count;
+    let input: Vec<Vec<f32>> = (0..channel_count)
+        .map(|c| {
+            samples
+                .iter()
+                .skip(c)
+                .step_by(channel_count)
+                .copied()
+                .collect()
+        })
+        .collect();
+
+    let mut resampler = FftFixedIn::<f32>::new(
+        from_rate as usize,
+        to_rate as usize,
+        CHUNK_SIZE,
+        2,
+        channel_count,
+    )?;
+
+    let delay = resampler.output_delay();
+    let expected = (frames as u64 * to_rate as u64 / from_rate as u64) as usize;
+    let mut output: Vec<Vec<f32>> = vec![Vec::with_capacity(expected + delay); channel_count];
+    let mut pos = 0;
+
+    loop {
+        let needed = resampler.input_frames_next();
+
+        let chunk = if pos + needed <= frames {
+            let chunk: Vec<&[f32]> = input.iter().map(|c| &c[pos..pos + needed]).collect();
+            pos += needed;
+            resampler.process(&chunk, None)?
+        } else if pos < frames {
+            let chunk: Vec<&[f32]> = input.iter().map(|c| &c[pos..]).collect();
+            pos = frames;
+            resampler.process_partial(Some(&chunk), None)?
+        } else if output[0].len() < expected + delay {
+            // Flush out the samples still held back by the resampler delay
+            resampler.process_partial::<&[f32]>(None, None)?
+        } else {
+            break;
+        };
+
+        for (out, data) in output.iter_mut().zip(chunk) {
+            out.extend_from_slice(&data);
+        }
+    }
+
+    // Interleave again and skip the delay introduced by the resampler
+    let mut interleaved = Vec::with_capacity(expected * channel_count);
+    for frame in delay..delay + expected {
+        for channel in &output {
+            interleaved.push(channel[frame]);
+        }
+    }
+
+    if bytes_per_sample == 4 {
+        Ok(bytemuck::cast_slice(&interleaved).to_vec())
+    } else {
+        let data: Vec<i16> = interleaved
+            .iter()
+            .map(|s| (s * 32768.0).clamp(-32768.0, 32767.0) as i16)
+            .collect();
+        Ok(bytemuck::cast_slice(&data).to_vec())
+    }
+}
+
+/// Run `f` on the buffer as f32 samples. i16 buffers are converted to f32 and back
+fn with_f32_samples(buffer: &mut [u8], bytes_per_sample: usize, f: impl FnOnce(&mut [f32])) {
+    if bytes_per_sample == 4 {
+        f(bytemuck::cast_slice_mut(buffer));
+    } else {
+        let data: &mut [i16] = bytemuck::cast_slice_mut(buffer);
+        let mut samples: Vec<f32> = data.iter().map(|s| *s as f32 / 32768.0).collect();
+
+        f(&mut samples);
+
+        for (d, s) in data.iter_mut().zip(samples) {
+            *d = (s * 32768.0).clamp(-32768.0, 32767.0) as i16;
+        }
+    }
+}
+
+/// Second order (12 dB/octave) Butterworth low-pass filter applied in place
+pub fn lowpass(
+    buffer: &mut [u8],
+    bytes_per_sample: usize,
+    channel_count: usize,
+    sample_rate: u32,
+    cutoff: f32,
+) {
+    // Standard RBJ cookbook coefficients with Q = 1/sqrt(2)
+    let w0 = 2.0 * std::f32::consts::PI * cutoff / sample_rate as f32;
+    let alpha = w0.sin() / (2.0 * std::f32::consts::FRAC_1_SQRT_2);
+    let cos_w0 = w0.cos();
+    let a0 = 1.0 + alpha;
+
+    let b0 = (1.0 - cos_w0) / 2.0 / a0;
+    let b1 = (1.0 - cos_w0) / a0;
+    let b2 = b0;
+    let a1 = -2.0 * cos_w0 / a0;
+    let a2 = (1.0 - alpha) / a0;
+
+    with_f32_samples(buffer, bytes_per_sample, |samples| {
+        for channel in 0..channel_count {
+            let (mut x1, mut x2, mut y1, mut y2) = (0.0, 0.0, 0.0, 0.0);
+
+            for s in samples.iter_mut().skip(channel).step_by(channel_count) {
+                let x0 = *s;
+                let y0 = b0 * x0 + b1 * x1 + b2 * x2 - a1 * y1 - a2 * y2;
+
+                x2 = x1;
+                x1 = x0;
+                y2 = y1;
+                y1 = y0;
+
+                *s = y0;
+            }
+        }
+    });
+}
+
+/// Highest absolute sample value with full scale being 1.0
+pub fn peak(buffer: &[u8], bytes_per_sample: usize) -> f32 {
+    if bytes_per_sample == 4 {
+        let data: &[f32] = bytemuck::cast_slice(buffer);
+        data.iter().fold(0.0, |peak, s| peak.max(s.abs()))
+    } else {
+        let data: &[i16] = bytemuck::cast_slice(buffer);
+        data.iter()
+            .fold(0.0, |peak, s| peak.max((*s as f32 / 32768.0).abs()))
+    }
+}
+
+/// Sum of the squares of all samples with full scale being 1.0. RMS levels are calculated from
+/// it so renders read in chunks can add up the chunks
+pub fn energy(buffer: &[u8], bytes_per_sample: usize) -> f64 {
+    if bytes_per_sample == 4 {
+        let data: &[f32] = bytemuck::cast_slice(buffer);
+        data.iter().map(|s| *s as f64 * *s as f64).sum()
+    } else {
+        let data: &[i16] = bytemuck::cast_slice(buffer);
+        data.iter()
+            .map(|s| {
+                let s = *s as f64 / 32768.0;
+                s * s
+            })
+            .sum()
+    }
+}
+
+/// Level in dBFS of a level where full scale is 1.0. None for digital silence
+pub fn to_dbfs(level: f64) -> Option<f32> {
+    (level > 0.0).then(|| (20.0 * level.log10()) as f32)
+}
+
+/// Peak, RMS and length of a render as stored in the manifest
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Levels {
+    pub peak: f32,
+    pub energy: f64,
+    pub samples: u64,
+    pub frames: u64,
+}
+
+impl Levels {
+    pub fn measure(buffer: &[u8], bytes_per_sample: usize, channel_count: usize) -> Levels {
+        let samples = (buffer.len() / bytes_per_sample) as u64;
+
+        Levels {
+            peak: peak(buffer, bytes_per_sample),
+            energy: energy(buffer, bytes_per_sample),
+            samples,
+            frames: samples / channel_count as u64,
+        }
+    }
+
+    /// Add a chunk of a render that is read in parts
+    pub fn add(&mut self, other: Levels) {
+        self.peak = self.peak.max(other.peak);
+        self.energy += other.energy;
+        self.samples += other.samples;
+        self.frames += other.frames;
+    }
+
+    pub fn peak_dbfs(&self) -> Option<f32> {
+        to_dbfs(self.peak as f64)
+    }
+
+    pub fn rms_dbfs(&self) -> Option<f32> {
+        to_dbfs((self.energy / self.samples.max(1) as f64).sqrt())
+    }
+
+    pub fn seconds(&self, sample_rate: u32) -> f32 {
+        (self.frames as f64 / sample_rate as f64) as f32
+    }
+}
+
+/// Samples at or beyond full scale in a render
+#[derive(Debug, Copy, Clone)]
+pub struct Clipping {
+    pub samples: u64,
+    pub first_frame: usize,
+}
+
+/// Count the samples at or beyond full scale. None if nothing clips
+pub fn clipping(buffer: &[u8], bytes_per_sample: usize, channel_count: usize) -> Option<Clipping> {
+    let mut samples = 0u64;
+    let mut first = None;
+
+    let mut count = |index: usize| {
+        samples += 1;
+        first.get_or_insert(index);
+    };
+
+    if bytes_per_sample == 4 {
+        let data: &[f32] = bytemuck::cast_slice(buffer);
+        for (i, s) in data.iter().enumerate() {
+            if s.abs() >= 1.0 {
+                count(i);
+            }
+        }
+    } else {
+        let data: &[i16] = bytemuck::cast_slice(buffer);
+        for (i, s) in data.iter().enumerate() {
+            if *s == i16::MIN || *s == i16::MAX {
+                count(i);
+            }
+        }
+    }
+
+    first.map(|first| Clipping {
+        samples,
+        first_frame: first / channel_count.max(1),
+    })
+}
+
+/// Scale all samples by `gain`. 16-bit samples are clamped to full scale
+pub fn apply_gain(buffer: &mut [u8], bytes_per_sample: usize, gain: f32) {
+    with_f32_samples(buffer, bytes_per_sample, |samples| {
+        samples.iter_mut().for_each(|s| *s *= gain);
+    });
+}
+
+/// Interleave several mono buffers into one buffer with a channel per input. Shorter inputs are
+/// padded with silence to the length of the longest one.
+pub fn interleave(inputs: &[Vec<u8>], bytes_per_sample: usize) -> Vec<u8> {
+    let frames = inputs
+        .iter()
+        .map(|input| input.len() / bytes_per_sample)
+        .max()
+        .unwrap_or(0);
+
+    let mut output = vec![0u8; frames * inputs.len() * bytes_per_sample];
+
+    for (channel, input) in inputs.iter().enumerate() {
+        for (frame, sample) in input.chunks_exact(bytes_per_sample).enumerate() {
+            let offset = (frame * inputs.len() + channel) * bytes_per_sample;
+            output[offset..offset + bytes_per_sample].copy_from_slice(sample);
+        }
+    }
+
+    output
+}
+
+/// Mix mono float buffers into a buffer with `channel_count` channels, each input added to the
+/// channel paired with it. Shorter inputs are padded with silence to the length of the longest one.
+pub fn mix_to_channels(inputs: &[(Vec<u8>, usize)], channel_count: usize) -> Vec<u8> {
+    let frames = inputs
+        .iter()
+        .map(|(input, _)| input.len() / 4)
+        .max()
+        .unwrap_or(0);
+
+    let mut output = vec![0.0f32; frames * channel_count];
+
+    for (input, channel) in inputs {
+        let data: &[f32] = bytemuck::cast_slice(input);
+        for (frame, sample) in data.iter().enumerate() {
+            output[frame * channel_count + channel] += sample;
+        }
+    }
+
+    bytemuck::cast_slice(&output).to_vec()
+}
+
+/// Frames quantized at a time by `Dither::quantize_in_place`
+const DITHER_BLOCK_FRAMES: usize = 4096;
+
+/// TPDF (triangular) dither used when float samples are reduced to 16-bit, optionally with first
+/// order noise shaping that moves the dither noise up in frequency where it's less audible
+pub struct Dither {
+    state: u64,
+    channel_count: usize,
+    noise_shaping: bool,
+    /// Quantization error of the previous sample in each channel
+    error: Vec<f32>,
+}
+
+impl Dither {
+    pub fn new(seed: u64, channel_count: usize, noise_shaping: bool) -> Dither {
+        Dither {
+            // xorshift gets stuck at zero
+            state: seed | 1,
+            channel_count: channel_count.max(1),
+            noise_shaping,
+            error: vec![0.0; channel_count.max(1)],
+        }
+    }
+
+    /// Uniform random value in [0, 1)
+    fn random(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Convert interleaved f32 samples to i16
+    pub fn quantize(&mut self, samples: &[f32]) -> Vec<i16> {
+        samples
+            .iter()
+            .enumerate()
+            .map(|(i, s)| {
+                let channel = i % self.channel_count;
+                let mut value = s * 32768.0;
+
+                if self.noise_shaping {
+                    value -= self.error[channel];
+                }
+
+                // Sum of two uniform values gives triangular noise of +-1 LSB
+                let noise = self.random() - self.random();
+                let output = (value + noise).round().clamp(-32768.0, 32767.0);
+
+                self.error[channel] = output - value;
+                output as i16
+            })
+            .collect()
+    }
+
+    /// Convert an f32 buffer to an i16 buffer
+    pub fn quantize_bytes(&mut self, buffer: &[u8]) -> Vec<u8> {
+        bytemuck::cast_slice(&self.quantize(bytemuck::cast_slice(buffer))).to_vec()
+    }
+
+    /// Convert an f32 buffer to i16 in the same allocation, a block at a time, so a whole render
+    /// is never held at both depths
+    pub fn quantize_in_place(&mut self, mut buffer: Vec<u8>) -> Vec<u8> {
+        // Whole frames so the channel of each sample stays the same
+        let block = DITHER_BLOCK_FRAMES * self.channel_count;
+        let sample_count = buffer.len() / 4;
+
+        for start in (0..sample_count).step_by(block) {
+            let end = (start + block).min(sample_count);
+            let samples = self.quantize(bytemuck::cast_slice(&buffer[start * 4..end * 4]));
+            // The i16 output is behind the f32 samples that are still to be read
+            buffer[start * 2..end * 2].copy_from_slice(bytemuck::cast_slice(&samples));
+        }
+
+        buffer.truncate(sample_count * 2);
+        buffer.shrink_to_fit();
+        buffer
+    }
+}
+
+/// Seed of the dither of a single stem. Stems quantized with the same noise would have it add
+/// up coherently when they are summed, so each channel and instrument gets its own sequence
+pub fn stem_seed(seed: u64, channel: i32, instrument: i32) -> u64 {
+    let stem = ((channel as u32 as u64) << 32) | instrument as u32 as u64;
+
+    // splitmix64
+    let mut x = seed ^ stem.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// Sum buffers with the same layout into one i16 buffer. Shorter inputs are treated as silence
+/// past their end. Float inputs are dithered
+pub fn mix_to_i16(inputs: &[&[u8]], bytes_per_sample: usize, dither: &mut Dither) -> Vec<i16> {
+    let len = inputs
+        .iter()
+        .map(|input| input.len() / bytes_per_sample)
+        .max()
+        .unwrap_or(0);
+
+    let mut sum = vec![0.0f32; len];
+
+    for input in inputs {
+        if bytes_per_sample == 4 {
+            let data: &[f32] = bytemuck::cast_slice(input);
+            sum.iter_mut().zip(data).for_each(|(s, x)| *s += *x);
+        } else {
+            let data: &[i16] = bytemuck::cast_slice(input);
+            sum.iter_mut()
+                .zip(data)
+                .for_each(|(s, x)| *s += *x as f32 / 32768.0);
+        }
+    }
+
+    if bytes_per_sample == 4 {
+        dither.quantize(&sum)
+    } else {
+        sum.iter()
+            .map(|s| (s * 32768.0).round().clamp(-32768.0, 32767.0) as i16)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clipping_counts_samples_at_full_scale() {
+        let samples = [0.0f32, 0.5, 0.2, 1.0, -0.3, -1.5, 0.9, 1.0];
+        let clipping = clipping(bytemuck::cast_slice(&samples), 4, 2).unwrap();
+        assert_eq!(clipping.samples, 3);
+        assert_eq!(clipping.first_frame, 1);
+    }
+
+    #[test]
+    fn clipping_of_16_bit_samples() {
+        let samples = [0i16, i16::MIN, 100, i16::MAX];
+        let clipping = clipping(bytemuck::cast_slice(&samples), 2, 1).unwrap();
+        assert_eq!(clipping.samples, 2);
+        assert_eq!(clipping.first_frame, 1);
+    }
+
+    #[test]
+    fn no_clipping_below_full_scale() {
+        let samples = [0.0f32, 0.999, -0.999];
+        assert!(clipping(bytemuck::cast_slice(&samples), 4, 1).is_none());
+    }
+
+    #[test]
+    fn quantize_in_place_matches_quantize() {
+        let samples: Vec<f32> = (0..DITHER_BLOCK_FRAMES * 6 + 7)
+            .map(|i| (i as f32 * 0.01).sin() * 0.5)
+            .collect();
+        let buffer: Vec<u8> = bytemuck::cast_slice(&samples).to_vec();
+
+        let expected = Dither::new(1, 2, true).quantize_bytes(&buffer);
+        assert_eq!(Dither::new(1, 2, true).quantize_in_place(buffer), expected);
+    }
+
+    #[test]
+    fn stems_get_their_own_dither_seed() {
+        assert_eq!(stem_seed(7, 0, -1), stem_seed(7, 0, -1));
+        assert_ne!(stem_seed(7, 0, -1), stem_seed(7, 1, -1));
+        assert_ne!(stem_seed(7, -1, 0), stem_seed(7, 0, -1));
+        assert_ne!(stem_seed(7, -1, -1), stem_seed(8, -1, -1));
+    }
+}
@@ -0,0 +1,79 @@
+use crate::manifest::OutputStatus;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Something that happened during the run, written as a line of JSON to stderr with
+/// --log-format json
+#[derive(Serialize, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// Rendering of a song has started
+    FileStarted { file: &'a str },
+    /// An output of a song is done (or was skipped, see `status`)
+    StemRendered {
+        file: &'a str,
+        output: &'a str,
+        channel: i32,
+        instrument: i32,
+        status: OutputStatus,
+    },
+    /// Size of a written output
+    BytesWritten { output: &'a str, bytes: u64 },
+    /// A song or output couldn't be rendered or written
+    Failed { message: &'a str },
+    /// Rendering of a song is done
+    FileFinished {
+        file: &'a str,
+        outputs: usize,
+        seconds: f64,
+    },
+    /// Message that is printed as text otherwise
+    Message { text: &'a str },
+    /// Record from the log crate
+    Log { level: &'a str, message: &'a str },
+    /// End of the run
+    RunFinished { failures: usize },
+}
+
+/// Write events instead of text messages and log records
+pub fn enable_json() {
+    JSON.store(true, Ordering::Relaxed);
+}
+
+pub fn json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Write the event if --log-format json is used
+pub fn emit(event: &Event) {
+    if !json() {
+        return;
+    }
+
+    match serde_json::to_string(event) {
+        Ok(line) => eprintln!("{}", line),
+        Err(e) => eprintln!("Unable to serialize {:?} error: {:?}", event, e),
+    }
+}
+
+/// Logger that writes records as `Log` events
+pub struct JsonLogger;
+
+impl log::Log for JsonLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            emit(&Event::Log {
+                level: record.level().as_str(),
+                message: &record.args().to_string(),
+            });
+        }
+    }
+
+    fn flush(&self) {}
+}
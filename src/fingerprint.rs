@@ -0,0 +1,36 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rusty_chromaprint::{Configuration, FingerprintCompressor, Fingerprinter};
+
+/// Calculate a Chromaprint fingerprint of an interleaved buffer, encoded the same way as
+/// `fpcalc` and AcoustID expects it (compressed and url safe base64)
+pub fn calculate(
+    buffer: &[u8],
+    bytes_per_sample: usize,
+    channel_count: usize,
+    sample_rate: u32,
+) -> Option<String> {
+    let config = Configuration::preset_test2();
+    let mut printer = Fingerprinter::new(&config);
+
+    if let Err(e) = printer.start(sample_rate, channel_count as _) {
+        log::error!("Unable to start fingerprinting: {:?}", e);
+        return None;
+    }
+
+    if bytes_per_sample == 4 {
+        let data: &[f32] = bytemuck::cast_slice(buffer);
+        let samples: Vec<i16> = data
+            .iter()
+            .map(|x| (x.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+            .collect();
+        printer.consume(&samples);
+    } else {
+        printer.consume(bytemuck::cast_slice(buffer));
+    }
+
+    printer.finish();
+
+    let compressed = FingerprintCompressor::from(&config).compress(printer.fingerprint());
+
+    Some(URL_SAFE_NO_PAD.encode(compressed))
+}
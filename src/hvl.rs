@@ -0,0 +1,180 @@
+use crate::backend::Backend;
+use crate::tags::SongMetadata;
+use crate::{RenderParams, SongInfo, Subsong, MAX_CHANNELS};
+use std::sync::Once;
+use stemgen_core::c_string;
+
+// Has to match MAX_INSTRUMENTS in the C code
+const MAX_INSTRUMENTS: usize = 64;
+
+// Has to match the struct in the C code
+#[repr(C)]
+struct HvlSongInfo {
+    name: [u8; 128],
+    channel_count: u32,
+    subsong_count: u32,
+    instrument_count: u32,
+    duration_seconds: f32,
+    instrument_names: [[u8; 128]; MAX_INSTRUMENTS],
+}
+
+// Has to match the struct in the C code
+#[repr(C)]
+struct HvlRenderParams {
+    sample_rate: u32,
+    stereo_output: bool,
+    channel_to_play: i32,   // if -1 play all channels, otherwise pick one
+    subsong: i32,           // if -1 play the main song
+    repeat_count: i32,      // -1 loops until the buffer is full
+    skip_frames: u32,       // frames played and thrown away before the output starts
+    stereo_separation: u32, // 0 - 4 as used by AHX, from 0% to 100%
+}
+
+extern "C" {
+    fn hvl_init_c();
+    fn hvl_song_info_c(data: *const u8, len: u32, subsong: i32, info: *mut HvlSongInfo) -> bool;
+    fn hvl_render_c(
+        output: *mut i16,
+        max_frames: u32,
+        data: *const u8,
+        len: u32,
+        params: *const HvlRenderParams,
+    ) -> u32;
+}
+
+static INIT: Once = Once::new();
+
+/// AHX (THX) or HivelyTracker (HVL) song
+pub fn is_hvl(data: &[u8]) -> bool {
+    data.len() > 4 && (data.starts_with(b"THX") || data.starts_with(b"HVL")) && data[3] <= 1
+}
+
+fn song_info_c(data: &[u8], subsong: i32) -> Option<Box<HvlSongInfo>> {
+    INIT.call_once(|| unsafe { hvl_init_c() });
+
+    let mut info = Box::new(HvlSongInfo {
+        name: [0; 128],
+        channel_count: 0,
+        subsong_count: 0,
+        instrument_count: 0,
+        duration_seconds: 0.0,
+        instrument_names: [[0; 128]; MAX_INSTRUMENTS],
+    });
+
+    let ok = unsafe { hvl_song_info_c(data.as_ptr(), data.len() as u32, subsong, &mut *info) };
+    ok.then_some(info)
+}
+
+pub fn song_info(data: &[u8]) -> SongInfo {
+    let info = song_info_c(data, -1);
+
+    SongInfo {
+        channel_count: info.as_ref().map_or(0, |i| i.channel_count),
+        instrument_count: info.as_ref().map_or(0, |i| i.instrument_count),
+        duration_seconds: info.as_ref().map_or(0.0, |i| i.duration_seconds),
+        estimated_seconds: info.as_ref().map_or(0.0, |i| i.duration_seconds),
+        channel_has_notes: [true; MAX_CHANNELS],
+    }
+}
+
+/// The main song is subsong 0, followed by the subsongs of the file
+pub fn subsongs(data: &[u8]) -> Vec<Subsong> {
+    let count = song_info_c(data, -1).map_or(0, |i| i.subsong_count);
+
+    (0..count as i32)
+        .map(|index| Subsong {
+            index,
+            name: if index == 0 {
+                "Main song".to_owned()
+            } else {
+                format!("Subsong {}", index)
+            },
+            duration_seconds: song_info_c(data, index).map_or(0.0, |i| i.duration_seconds),
+        })
+        .collect()
+}
+
+pub fn metadata(data: &[u8], source: &str) -> SongMetadata {
+    let info = song_info_c(data, -1);
+    let ahx = data.starts_with(b"THX");
+
+    let instrument_names: Vec<String> = info
+        .as_ref()
+        .map(|i| {
+            i.instrument_names[..(i.instrument_count as usize).min(MAX_INSTRUMENTS)]
+                .iter()
+                .map(|name| c_string(name))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    SongMetadata {
+        title: info.as_ref().map_or(String::new(), |i| c_string(&i.name)),
+        format: if ahx { "ahx" } else { "hvl" }.to_owned(),
+        format_long: if ahx { "AHX" } else { "HivelyTracker" }.to_owned(),
+        tracker: if ahx { "AHX" } else { "HivelyTracker" }.to_owned(),
+        sample_text: instrument_names.join("\n"),
+        sample_names: instrument_names.clone(),
+        instrument_names,
+        source: source.to_owned(),
+        ..Default::default()
+    }
+}
+
+/// The HivelyTracker replayer, which plays both AHX and HVL songs. Channels can be soloed but
+/// there is no way to solo instruments
+pub struct Hvl;
+
+impl Backend for Hvl {
+    fn render(&self, output: &mut [u8], input: &[u8], render_params: &RenderParams) -> u32 {
+        INIT.call_once(|| unsafe { hvl_init_c() });
+
+        let channel_count = if render_params.stereo_output { 2 } else { 1 };
+        let rate = render_params.sample_rate as f32;
+
+        let mut max_frames = output.len() / (4 * channel_count);
+        if render_params.max_frames != 0 {
+            max_frames = max_frames.min(render_params.max_frames as usize);
+        }
+
+        // AHX separation goes from 0 to 4 (100%) which is 100% in libopenmpt
+        let stereo_separation = if render_params.stereo_separation_enabled {
+            (render_params.stereo_separation / 25).min(4)
+        } else {
+            2
+        };
+
+        let params = HvlRenderParams {
+            sample_rate: render_params.sample_rate,
+            stereo_output: render_params.stereo_output,
+            channel_to_play: render_params.channel_to_play,
+            subsong: render_params.subsong,
+            repeat_count: render_params.repeat_count,
+            skip_frames: (render_params.start_seconds * rate) as u32,
+            stereo_separation,
+        };
+
+        let mut samples = vec![0i16; max_frames * channel_count];
+        let frames = unsafe {
+            hvl_render_c(
+                samples.as_mut_ptr(),
+                max_frames as u32,
+                input.as_ptr(),
+                input.len() as u32,
+                &params,
+            )
+        } as usize;
+
+        let gain = 10f32.powf(render_params.master_gain_millibel as f32 / 2000.0);
+        for (i, sample) in samples[..frames * channel_count].iter().enumerate() {
+            let value = *sample as f32 / 32768.0 * gain;
+            output[i * 4..i * 4 + 4].copy_from_slice(&value.to_ne_bytes());
+        }
+
+        (frames * channel_count * 4) as u32
+    }
+
+    fn can_solo_instruments(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,95 @@
+use crate::format_duration;
+use serde::Serialize;
+
+/// Subsong as listed by `stemgen info`
+#[derive(Serialize, Debug)]
+pub struct SubsongReport {
+    pub index: i32,
+    pub name: String,
+    pub duration_seconds: f32,
+}
+
+/// Everything `stemgen info` knows about a song without rendering it
+#[derive(Serialize, Debug)]
+pub struct SongReport {
+    pub source: String,
+    pub title: String,
+    /// Short format name such as "xm"
+    pub format: String,
+    /// Long format name such as "FastTracker II"
+    pub format_long: String,
+    pub tracker: String,
+    pub duration_seconds: f32,
+    pub channel_count: u32,
+    /// Channels without any note data
+    pub empty_channels: Vec<u32>,
+    /// Instruments (or samples if the song has no instruments) as numbered when rendering
+    pub instrument_count: u32,
+    /// Names of the instruments, or the samples for formats without instruments (such as mod)
+    pub instrument_names: Vec<String>,
+    pub sample_names: Vec<String>,
+    pub subsongs: Vec<SubsongReport>,
+}
+
+/// Numbered list of the names that aren't empty
+fn add_names(text: &mut String, heading: &str, names: &[String]) {
+    if names.iter().all(|name| name.trim().is_empty()) {
+        return;
+    }
+
+    *text += &format!("{}:\n", heading);
+    for (i, name) in names.iter().enumerate() {
+        if !name.trim().is_empty() {
+            *text += &format!("  {:3}: {}\n", i + 1, name);
+        }
+    }
+}
+
+impl SongReport {
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+
+        text += &format!("{}\n", self.source);
+        if !self.title.is_empty() {
+            text += &format!("Title: {}\n", self.title);
+        }
+        if self.format_long.is_empty() {
+            text += &format!("Format: {}\n", self.format);
+        } else {
+            text += &format!("Format: {} ({})\n", self.format_long, self.format);
+        }
+        if !self.tracker.is_empty() {
+            text += &format!("Tracker: {}\n", self.tracker);
+        }
+        text += &format!("Duration: {}\n", format_duration(self.duration_seconds));
+        text += &format!("Channels: {}\n", self.channel_count);
+        if !self.empty_channels.is_empty() {
+            text += &format!("Empty channels: {:?}\n", self.empty_channels);
+        }
+        text += &format!("Instruments: {}\n", self.instrument_count);
+
+        // Songs without instruments use the sample names for both
+        if self.instrument_names != self.sample_names {
+            add_names(&mut text, "Instrument names", &self.instrument_names);
+        }
+        add_names(&mut text, "Sample names", &self.sample_names);
+
+        if self.subsongs.len() > 1 {
+            text += "Subsongs:\n";
+            for subsong in &self.subsongs {
+                text += &format!(
+                    "  {:3}: {} ({})\n",
+                    subsong.index,
+                    subsong.name,
+                    format_duration(subsong.duration_seconds)
+                );
+            }
+        }
+
+        text
+    }
+}
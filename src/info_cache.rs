@@ -0,0 +1,213 @@
+use crate::checksum::to_hex;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+/// Song information that is expensive to get as the whole module has to be loaded and scanned
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CachedInfo {
+    pub channel_count: u32,
+    pub instrument_count: u32,
+    pub duration_seconds: f32,
+    pub estimated_seconds: f32,
+    /// Cap of the estimated duration (--max-duration)
+    #[serde(default)]
+    pub max_seconds: f32,
+    pub empty_channels: Vec<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CachedSubsong {
+    pub name: String,
+    pub duration_seconds: f32,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct CacheFile {
+    /// Version of stemgen that wrote the cache. Entries from other versions are thrown away as
+    /// the way the info is calculated may have changed
+    version: String,
+    entries: HashMap<String, CachedInfo>,
+    /// Kept apart from the entries so listing subsongs doesn't require the full song info
+    #[serde(default)]
+    subsongs: HashMap<String, Vec<CachedSubsong>>,
+}
+
+/// Song info keyed by the SHA-256 of the module data. Without a path the cache only lives for
+/// the current run
+#[derive(Default)]
+pub struct InfoCache {
+    path: Option<PathBuf>,
+    entries: HashMap<String, CachedInfo>,
+    subsongs: HashMap<String, Vec<CachedSubsong>>,
+    dirty: bool,
+}
+
+impl InfoCache {
+    /// Load the cache from `path`. A missing or unreadable cache file gives an empty cache
+    pub fn load(path: Option<&Path>) -> Self {
+        let mut cache = InfoCache {
+            path: path.map(Path::to_path_buf),
+            ..Default::default()
+        };
+
+        let Some(path) = path else {
+            return cache;
+        };
+
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(_) => return cache,
+        };
+
+        match serde_json::from_reader::<_, CacheFile>(BufReader::new(file)) {
+            Ok(data) if data.version == env!("CARGO_PKG_VERSION") => {
+                cache.entries = data.entries;
+                cache.subsongs = data.subsongs;
+            }
+            Ok(_) => log::info!("Info cache {:?} is from another version. Ignoring", path),
+            Err(e) => log::warn!("Unable to read info cache {:?} error: {:?}", path, e),
+        }
+
+        cache
+    }
+
+    pub fn key(data: &[u8]) -> String {
+        to_hex(&Sha256::digest(data))
+    }
+
+    pub fn get(&self, key: &str) -> Option<&CachedInfo> {
+        self.entries.get(key)
+    }
+
+    pub fn insert(&mut self, key: &str, info: CachedInfo) {
+        self.entries.insert(key.to_owned(), info);
+        self.dirty = true;
+    }
+
+    pub fn get_subsongs(&self, key: &str) -> Option<&Vec<CachedSubsong>> {
+        self.subsongs.get(key)
+    }
+
+    pub fn insert_subsongs(&mut self, key: &str, subsongs: Vec<CachedSubsong>) {
+        self.subsongs.insert(key.to_owned(), subsongs);
+        self.dirty = true;
+    }
+
+    /// Write the cache back to disk if anything was added
+    pub fn save(&mut self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        if !self.dirty {
+            return Ok(());
+        }
+
+        let data = CacheFile {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            entries: self.entries.clone(),
+            subsongs: self.subsongs.clone(),
+        };
+
+        let file = File::create(path)?;
+        serde_json::to_writer(BufWriter::new(file), &data)?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("stemgen-{}-{}.json", std::process::id(), name))
+    }
+
+    fn info(channel_count: u32) -> CachedInfo {
+        CachedInfo {
+            channel_count,
+            instrument_count: 31,
+            duration_seconds: 123.5,
+            estimated_seconds: 0.0,
+            max_seconds: 3600.0,
+            empty_channels: vec![2, 5],
+        }
+    }
+
+    #[test]
+    fn entries_survive_a_save_and_load() {
+        let path = cache_path("round-trip");
+        let key = InfoCache::key(b"song");
+
+        let mut cache = InfoCache::load(Some(&path));
+        cache.insert(&key, info(8));
+        cache.insert_subsongs(
+            &key,
+            vec![CachedSubsong {
+                name: "Intro".to_owned(),
+                duration_seconds: 12.0,
+            }],
+        );
+        cache.save().unwrap();
+
+        let loaded = InfoCache::load(Some(&path));
+        std::fs::remove_file(&path).unwrap();
+
+        let entry = loaded.get(&key).unwrap();
+        assert_eq!(entry.channel_count, 8);
+        assert_eq!(entry.instrument_count, 31);
+        assert_eq!(entry.duration_seconds, 123.5);
+        assert_eq!(entry.max_seconds, 3600.0);
+        assert_eq!(entry.empty_channels, [2, 5]);
+
+        let subsongs = loaded.get_subsongs(&key).unwrap();
+        assert_eq!(subsongs.len(), 1);
+        assert_eq!(subsongs[0].name, "Intro");
+        assert_eq!(subsongs[0].duration_seconds, 12.0);
+    }
+
+    #[test]
+    fn entries_are_only_found_for_the_same_data() {
+        let mut cache = InfoCache::load(None);
+        let key = InfoCache::key(b"song");
+        cache.insert(&key, info(4));
+
+        assert_eq!(key.len(), 64);
+        assert_eq!(key, InfoCache::key(b"song"));
+        assert!(cache.get(&InfoCache::key(b"song ")).is_none());
+        assert!(cache.get_subsongs(&key).is_none());
+        assert_eq!(cache.get(&key).unwrap().channel_count, 4);
+
+        // Without a path nothing is written
+        cache.save().unwrap();
+    }
+
+    #[test]
+    fn caches_from_other_versions_are_ignored() {
+        let path = cache_path("version");
+        let key = InfoCache::key(b"song");
+        let data = CacheFile {
+            version: "0.0.1".to_owned(),
+            entries: HashMap::from([(key.clone(), info(4))]),
+            subsongs: HashMap::new(),
+        };
+        std::fs::write(&path, serde_json::to_vec(&data).unwrap()).unwrap();
+        let old = InfoCache::load(Some(&path));
+
+        std::fs::write(&path, b"{ not json").unwrap();
+        let broken = InfoCache::load(Some(&path));
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(old.get(&key).is_none());
+        assert!(broken.get(&key).is_none());
+        assert!(InfoCache::load(Some(&path)).get(&key).is_none());
+    }
+}
@@ -0,0 +1,448 @@
+use anyhow::{anyhow, Result};
+use std::io::{Cursor, Read};
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Extensions of the archives and compressed files that are read
+pub const EXTENSIONS: &[&str] = &["zip", "lha", "lzh", "gz", "xz"];
+
+/// Files in archives that are never songs, such as the texts and icons that come with them
+const SKIPPED_EXTENSIONS: &[&str] = &[
+    "txt", "diz", "nfo", "info", "doc", "readme", "me", "png", "jpg", "jpeg", "gif", "iff", "ilbm",
+    "exe", "html", "htm", "pdf",
+];
+
+/// Song read from a file or from an archive
+pub struct InputSong {
+    /// Path of the file, or of the archive followed by the path inside it (such as
+    /// `pack.lha/songs/mod.intro`)
+    pub filename: String,
+    pub data: Vec<u8>,
+    /// Modification time of the file or archive, None for downloads and stdin
+    pub modified: Option<SystemTime>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Archive {
+    Zip,
+    Lha,
+}
+
+/// Archives are detected by their headers so misnamed files work too
+fn archive_kind(data: &[u8]) -> Option<Archive> {
+    if data.starts_with(b"PK\x03\x04") {
+        Some(Archive::Zip)
+    } else if data.len() > 21 && data[2] == b'-' && data[3] == b'l' && data[6] == b'-' {
+        // -lh?- or -lz?- compression method
+        Some(Archive::Lha)
+    } else {
+        None
+    }
+}
+
+/// Modules compressed on their own (such as `song.mod.gz`) are decompressed and the compression
+/// extension is removed from the name
+fn decompress(filename: String, data: Vec<u8>) -> Result<(String, Vec<u8>)> {
+    let strip = |filename: String, extension: &str| {
+        if filename.to_ascii_lowercase().ends_with(extension) {
+            filename[..filename.len() - extension.len()].to_owned()
+        } else {
+            filename
+        }
+    };
+
+    if data.starts_with(&[0x1f, 0x8b]) {
+        let mut output = Vec::new();
+        flate2::read::MultiGzDecoder::new(data.as_slice()).read_to_end(&mut output)?;
+        Ok((strip(filename, ".gz"), output))
+    } else if data.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0]) {
+        let mut output = Vec::new();
+        lzma_rs::xz_decompress(&mut data.as_slice(), &mut output)
+            .map_err(|e| anyhow!("Invalid xz data: {}", e))?;
+        Ok((strip(filename, ".xz"), output))
+    } else {
+        Ok((filename, data))
+    }
+}
+
+fn is_skipped(name: &str) -> bool {
+    let extension = Path::new(name)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    SKIPPED_EXTENSIONS.contains(&extension.as_str())
+}
+
+fn read_zip(filename: &str, data: Vec<u8>) -> Result<Vec<InputSong>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data))?;
+    let mut songs = Vec::new();
+
+    for i in 0..archive.len() {
+        let name = archive.name_for_index(i).unwrap_or_default().to_owned();
+
+        // Entries using compression methods that aren't supported (such as implode or shrink)
+        // or that are corrupt are skipped so the rest of the archive is still read
+        let mut entry = match archive.by_index(i) {
+            Ok(entry) => entry,
+            Err(e) => {
+                fail!("{} in {}: {}", name, filename, e);
+                continue;
+            }
+        };
+
+        if !entry.is_file() || is_skipped(&name) {
+            continue;
+        }
+
+        // The size in the header isn't trusted for the allocation
+        let mut data = Vec::new();
+        if let Err(e) = entry.read_to_end(&mut data) {
+            fail!("{} in {}: {}", name, filename, e);
+            continue;
+        }
+
+        match decompress(format!("{}/{}", filename, name), data) {
+            Ok((filename, data)) => songs.push(InputSong {
+                filename,
+                data,
+                modified: None,
+            }),
+            Err(e) => fail!("{} in {}: {}", name, filename, e),
+        }
+    }
+
+    Ok(songs)
+}
+
+/// Decompress the current file of an LHA archive and check its CRC
+fn read_lha_entry<R: Read>(
+    reader: &mut delharc::LhaDecodeReader<R>,
+    filename: String,
+) -> Result<InputSong> {
+    // The size in the header isn't trusted for the allocation
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    reader.crc_check().map_err(|e| anyhow!("{}", e))?;
+
+    let (filename, data) = decompress(filename, data)?;
+    Ok(InputSong {
+        filename,
+        data,
+        modified: None,
+    })
+}
+
+fn read_lha(filename: &str, data: Vec<u8>) -> Result<Vec<InputSong>> {
+    let mut reader = delharc::LhaDecodeReader::new(Cursor::new(data))
+        .map_err(|e| anyhow!("Invalid LHA archive: {}", e))?;
+    let mut songs = Vec::new();
+
+    loop {
+        let header = reader.header();
+        let name = header.parse_pathname().to_string_lossy().replace('\\', "/");
+
+        if !header.is_directory() && !is_skipped(&name) {
+            if reader.is_decoder_supported() {
+                // Corrupt entries are skipped so the rest of the archive is still read
+                match read_lha_entry(&mut reader, format!("{}/{}", filename, name)) {
+                    Ok(song) => songs.push(song),
+                    Err(e) => fail!("{} in {}: {}", name, filename, e),
+                }
+            } else {
+                fail!(
+                    "{} in {} uses an unsupported compression method",
+                    name,
+                    filename
+                );
+            }
+        }
+
+        if !reader
+            .next_file()
+            .map_err(|e| anyhow!("Invalid LHA archive: {}", e))?
+        {
+            break;
+        }
+    }
+
+    Ok(songs)
+}
+
+/// Input name that reads the song from stdin
+pub const STDIN: &str = "-";
+
+pub fn is_url(input: &str) -> bool {
+    input.starts_with("http://") || input.starts_with("https://")
+}
+
+/// Name of a downloaded file. It's taken from the Content-Disposition header if there is one,
+/// otherwise from the fragment (as used by modarchive links such as `downloads.php?moduleid=1#song.mod`)
+/// or the last part of the path
+fn url_filename(url: &str, content_disposition: Option<&str>) -> String {
+    let from_header = content_disposition.and_then(|header| {
+        header.split(';').find_map(|part| {
+            let value = part.trim().strip_prefix("filename=")?;
+            Some(value.trim_matches('"').to_owned())
+        })
+    });
+
+    let (path, fragment) = url.split_once('#').unwrap_or((url, ""));
+    let path = path.split('?').next().unwrap_or_default();
+    let last = path
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default();
+
+    let name = from_header
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| if fragment.is_empty() { last } else { fragment }.to_owned());
+
+    // Only keep the name, never a path given by the server
+    let name = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    if name.is_empty() {
+        "download".to_owned()
+    } else {
+        name.to_owned()
+    }
+}
+
+fn download(url: &str) -> Result<(String, Vec<u8>)> {
+    message!("Downloading {}", url);
+
+    let response = ureq::get(url).call()?;
+    let filename = url_filename(url, response.header("Content-Disposition"));
+
+    let mut data = Vec::new();
+    response.into_reader().read_to_end(&mut data)?;
+
+    Ok((filename, data))
+}
+
+/// Read a song, or all files of a zip or LHA archive (extracted in memory). gzip and xz
+/// compressed songs are decompressed, http(s) URLs downloaded and `-` reads stdin
+pub fn read(filename: &str) -> Result<Vec<InputSong>> {
+    let (filename, data, modified) = if is_url(filename) {
+        let (filename, data) = download(filename)?;
+        (filename, data, None)
+    } else if filename == STDIN {
+        let mut data = Vec::new();
+        std::io::stdin().lock().read_to_end(&mut data)?;
+        ("stdin".to_owned(), data, None)
+    } else {
+        let modified = std::fs::metadata(filename).and_then(|m| m.modified()).ok();
+        (filename.to_owned(), std::fs::read(filename)?, modified)
+    };
+    let filename = filename.as_str();
+
+    let mut songs = match archive_kind(&data) {
+        Some(Archive::Zip) => read_zip(filename, data)?,
+        Some(Archive::Lha) => read_lha(filename, data)?,
+        None => {
+            let (filename, data) = decompress(filename.to_owned(), data)?;
+            vec![InputSong {
+                filename,
+                data,
+                modified: None,
+            }]
+        }
+    };
+
+    for song in &mut songs {
+        song.modified = modified;
+    }
+
+    Ok(songs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// CRC-16 that LHA stores for every file
+    fn lha_crc(data: &[u8]) -> u16 {
+        data.iter().fold(0, |crc, byte| {
+            (0..8).fold(crc ^ *byte as u16, |crc, _| {
+                if crc & 1 != 0 {
+                    (crc >> 1) ^ 0xa001
+                } else {
+                    crc >> 1
+                }
+            })
+        })
+    }
+
+    /// Level 0 LHA archive storing `files` uncompressed, each with the given CRC
+    fn lha(files: &[(&str, &[u8], u16)]) -> Vec<u8> {
+        let mut archive = Vec::new();
+
+        for (name, data, crc) in files {
+            let mut header = b"-lh0-".to_vec();
+            header.extend((data.len() as u32).to_le_bytes());
+            header.extend((data.len() as u32).to_le_bytes());
+            header.extend([0, 0, 0x21, 0x58, 0x20, 0]);
+            header.push(name.len() as u8);
+            header.extend(name.as_bytes());
+            header.extend(crc.to_le_bytes());
+
+            let checksum = header.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+            archive.extend([header.len() as u8, checksum]);
+            archive.extend(header);
+            archive.extend(*data);
+        }
+
+        archive.push(0);
+        archive
+    }
+
+    fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+
+        for (name, data) in files {
+            writer
+                .start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(data).unwrap();
+        }
+
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn xz(data: &[u8]) -> Vec<u8> {
+        let mut output = Vec::new();
+        lzma_rs::xz_compress(&mut &data[..], &mut output).unwrap();
+        output
+    }
+
+    #[test]
+    fn archives_are_detected_by_their_header() {
+        assert_eq!(
+            archive_kind(&zip(&[("song.mod", b"M.K.")])),
+            Some(Archive::Zip)
+        );
+        assert_eq!(
+            archive_kind(&lha(&[("song.mod", b"M.K.", lha_crc(b"M.K."))])),
+            Some(Archive::Lha)
+        );
+        assert_eq!(archive_kind(b"Extended Module: song"), None);
+        assert_eq!(archive_kind(b"-lh0-"), None);
+    }
+
+    #[test]
+    fn archive_entries_are_read_and_texts_skipped() {
+        let data = zip(&[
+            ("songs/mod.intro", b"intro"),
+            ("README.TXT", b"text"),
+            ("file_id.diz", b"text"),
+        ]);
+        let songs = read_zip("pack.zip", data).unwrap();
+        let names: Vec<&str> = songs.iter().map(|s| s.filename.as_str()).collect();
+
+        assert_eq!(names, ["pack.zip/songs/mod.intro"]);
+        assert_eq!(songs[0].data, b"intro");
+    }
+
+    #[test]
+    fn corrupt_lha_entries_are_skipped() {
+        let data = lha(&[
+            ("broken.mod", b"broken", lha_crc(b"broken") ^ 1),
+            ("song.mod", b"song", lha_crc(b"song")),
+            ("info.txt", b"text", lha_crc(b"text")),
+        ]);
+        let songs = read_lha("pack.lha", data).unwrap();
+        let names: Vec<&str> = songs.iter().map(|s| s.filename.as_str()).collect();
+
+        assert_eq!(names, ["pack.lha/song.mod"]);
+        assert_eq!(songs[0].data, b"song");
+    }
+
+    #[test]
+    fn compressed_songs_are_detected_by_their_magic() {
+        let song = b"Extended Module: song".to_vec();
+
+        let (name, data) = decompress("song.mod.gz".to_owned(), gzip(&song)).unwrap();
+        assert_eq!((name.as_str(), &data), ("song.mod", &song));
+
+        let (name, data) = decompress("song.xm.xz".to_owned(), xz(&song)).unwrap();
+        assert_eq!((name.as_str(), &data), ("song.xm", &song));
+
+        // Misnamed files are still decompressed and keep their name
+        let (name, data) = decompress("song.xm".to_owned(), xz(&song)).unwrap();
+        assert_eq!((name.as_str(), &data), ("song.xm", &song));
+
+        let (name, data) = decompress("song.gz".to_owned(), song.clone()).unwrap();
+        assert_eq!((name.as_str(), &data), ("song.gz", &song));
+
+        assert!(decompress("song.xz".to_owned(), xz(&song)[..20].to_vec()).is_err());
+    }
+
+    #[test]
+    fn compression_extensions_are_stripped_ignoring_case() {
+        let song = b"M.K.".to_vec();
+
+        let (name, _) = decompress("SONG.MOD.GZ".to_owned(), gzip(&song)).unwrap();
+        assert_eq!(name, "SONG.MOD");
+
+        let (name, _) = decompress("Song.It.Xz".to_owned(), xz(&song)).unwrap();
+        assert_eq!(name, "Song.It");
+
+        let (name, _) = decompress("pack.zip/mod.intro.gz".to_owned(), gzip(&song)).unwrap();
+        assert_eq!(name, "pack.zip/mod.intro");
+    }
+
+    #[test]
+    fn texts_and_images_are_skipped() {
+        for name in ["readme.txt", "FILE_ID.DIZ", "dir/cover.PNG", "song.info"] {
+            assert!(is_skipped(name), "{}", name);
+        }
+
+        for name in ["song.mod", "mod.intro", "README", "song.xm.gz"] {
+            assert!(!is_skipped(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn download_names() {
+        let name = |url, header| url_filename(url, header);
+
+        assert_eq!(name("https://example.com/mods/song.xm", None), "song.xm");
+        assert_eq!(
+            name(
+                "https://api.modarchive.org/downloads.php?moduleid=1#space_debris.mod",
+                None
+            ),
+            "space_debris.mod"
+        );
+        assert_eq!(name("https://example.com/get?id=1", None), "get");
+        assert_eq!(
+            name(
+                "https://example.com/get?id=1",
+                Some("attachment; filename=\"song.it\"")
+            ),
+            "song.it"
+        );
+        assert_eq!(
+            name(
+                "https://example.com/song.s3m",
+                Some("attachment; filename=\"../../etc/passwd\"")
+            ),
+            "passwd"
+        );
+        assert_eq!(name("https://example.com/", Some("inline")), "example.com");
+        assert_eq!(
+            name(
+                "https://example.com/",
+                Some("attachment; filename=\"songs/\"")
+            ),
+            "download"
+        );
+    }
+}
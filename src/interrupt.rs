@@ -0,0 +1,72 @@
+use anyhow::Result;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    sync::Mutex,
+};
+
+/// Name of the file in the output directory that lists the songs finished before an interrupt
+pub const CHECKPOINT_NAME: &str = "stemgen_resume.txt";
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Files that are being written and would be left truncated if the process stopped now
+static PARTIAL_FILES: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Handle Ctrl-C. The first one lets the stems that are being encoded finish while no new ones
+/// are started. A second one deletes the files that are still being written and exits
+pub fn install() -> Result<()> {
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            for path in PARTIAL_FILES.lock().unwrap().iter() {
+                let _ = std::fs::remove_file(path);
+            }
+            std::process::exit(crate::EXIT_INTERRUPTED);
+        }
+
+        eprintln!("Interrupted. Finishing the stems being encoded, press Ctrl-C again to stop now");
+    })?;
+
+    Ok(())
+}
+
+/// Ctrl-C has been pressed so no new work should be started
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// File being written. It's deleted if the run is stopped before the guard is dropped
+pub struct PartialFile(PathBuf);
+
+impl PartialFile {
+    pub fn new(path: &Path) -> PartialFile {
+        PARTIAL_FILES.lock().unwrap().push(path.to_owned());
+        PartialFile(path.to_owned())
+    }
+}
+
+impl Drop for PartialFile {
+    fn drop(&mut self) {
+        let mut files = PARTIAL_FILES.lock().unwrap();
+        if let Some(index) = files.iter().position(|p| *p == self.0) {
+            files.swap_remove(index);
+        }
+    }
+}
+
+/// Songs finished by an interrupted run, one per line. Empty if there is no checkpoint
+pub fn read_checkpoint(path: &Path) -> HashSet<String> {
+    std::fs::read_to_string(path)
+        .map(|text| text.lines().map(str::to_owned).collect())
+        .unwrap_or_default()
+}
+
+pub fn write_checkpoint(path: &Path, finished: &HashSet<String>) -> Result<()> {
+    let mut songs: Vec<&String> = finished.iter().collect();
+    songs.sort();
+
+    let text: String = songs.iter().map(|s| format!("{}\n", s)).collect();
+    std::fs::write(path, text)?;
+    Ok(())
+}
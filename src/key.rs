@@ -0,0 +1,122 @@
+//! Musical key estimation with the Krumhansl-Schmuckler algorithm: the energy of each pitch class
+//! over the whole render is correlated with the key profiles of all major and minor keys
+
+use realfft::RealFftPlanner;
+
+/// About 6 Hz per bin at 48 kHz, fine enough to tell apart semitones from the bass up
+const FFT_SIZE: usize = 8192;
+const HOP_SIZE: usize = FFT_SIZE / 2;
+
+/// Range of the notes that count. Lower bins are too coarse and higher ones are mostly harmonics
+const MIN_FREQUENCY: f32 = 55.0;
+const MAX_FREQUENCY: f32 = 2000.0;
+
+/// Probe tone ratings of each scale degree (Krumhansl and Kessler 1982)
+const MAJOR_PROFILE: [f32; 12] = [
+    6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+];
+const MINOR_PROFILE: [f32; 12] = [
+    6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+];
+
+/// Spelled the way DJ software and the ID3 TKEY frame write keys
+const PITCH_NAMES: [&str; 12] = [
+    "C", "Db", "D", "Eb", "E", "F", "F#", "G", "Ab", "A", "Bb", "B",
+];
+
+/// Pearson correlation of the pitch class energies and a profile starting at `tonic`
+fn correlation(chroma: &[f32; 12], profile: &[f32; 12], tonic: usize) -> f32 {
+    let chroma_mean = chroma.iter().sum::<f32>() / 12.0;
+    let profile_mean = profile.iter().sum::<f32>() / 12.0;
+
+    let mut covariance = 0.0;
+    let mut chroma_variance = 0.0;
+    let mut profile_variance = 0.0;
+
+    for (pitch, energy) in chroma.iter().enumerate() {
+        let c = energy - chroma_mean;
+        let p = profile[(pitch + 12 - tonic) % 12] - profile_mean;
+        covariance += c * p;
+        chroma_variance += c * c;
+        profile_variance += p * p;
+    }
+
+    covariance
+        / (chroma_variance * profile_variance)
+            .sqrt()
+            .max(f32::MIN_POSITIVE)
+}
+
+/// Estimate the key of an interleaved render, such as "Am" or "Eb". None if the render is too
+/// short or has no tonal content in the analyzed range
+pub fn estimate(
+    buffer: &[u8],
+    bytes_per_sample: usize,
+    channel_count: usize,
+    sample_rate: u32,
+) -> Option<String> {
+    let mono: Vec<f32> = if bytes_per_sample == 4 {
+        let data: &[f32] = bytemuck::cast_slice(buffer);
+        data.chunks_exact(channel_count)
+            .map(|frame| frame.iter().sum::<f32>())
+            .collect()
+    } else {
+        let data: &[i16] = bytemuck::cast_slice(buffer);
+        data.chunks_exact(channel_count)
+            .map(|frame| frame.iter().map(|&s| s as f32 / 32768.0).sum::<f32>())
+            .collect()
+    };
+
+    let fft = RealFftPlanner::<f32>::new().plan_fft_forward(FFT_SIZE);
+    let mut input = fft.make_input_vec();
+    let mut spectrum = fft.make_output_vec();
+
+    let window: Vec<f32> = (0..FFT_SIZE)
+        .map(|i| 0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / FFT_SIZE as f32).cos())
+        .collect();
+
+    // Pitch class of every bin in range, with C as 0
+    let bin_width = sample_rate as f32 / FFT_SIZE as f32;
+    let pitch_classes: Vec<(usize, usize)> = (1..spectrum.len())
+        .filter(|&bin| (MIN_FREQUENCY..MAX_FREQUENCY).contains(&(bin as f32 * bin_width)))
+        .map(|bin| {
+            let midi_note = 69.0 + 12.0 * (bin as f32 * bin_width / 440.0).log2();
+            (bin, midi_note.round() as usize % 12)
+        })
+        .collect();
+
+    let mut chroma = [0.0f32; 12];
+
+    for frames in mono.windows(FFT_SIZE).step_by(HOP_SIZE) {
+        for ((sample, w), frame) in input.iter_mut().zip(&window).zip(frames) {
+            *sample = frame * w;
+        }
+
+        if fft.process(&mut input, &mut spectrum).is_err() {
+            continue;
+        }
+
+        for &(bin, pitch) in &pitch_classes {
+            chroma[pitch] += spectrum[bin].norm();
+        }
+    }
+
+    if chroma.iter().all(|&energy| energy <= 0.0) {
+        return None;
+    }
+
+    let (major, tonic, _) = (0..12)
+        .flat_map(|tonic| {
+            [
+                (true, tonic, correlation(&chroma, &MAJOR_PROFILE, tonic)),
+                (false, tonic, correlation(&chroma, &MINOR_PROFILE, tonic)),
+            ]
+        })
+        .max_by(|a, b| a.2.total_cmp(&b.2))?;
+
+    Some(if major {
+        PITCH_NAMES[tonic].to_owned()
+    } else {
+        format!("{}m", PITCH_NAMES[tonic])
+    })
+}
@@ -0,0 +1,67 @@
+//! CSV report with the levels of every stem of a run, written with --level-report for scripts
+//! that would otherwise have to analyze the files again. The loudness columns are only filled in
+//! with --ebu-r128
+
+use crate::manifest::{OutputEntry, OutputStatus};
+use anyhow::Result;
+use std::{fs::File, io::Write, path::Path};
+
+const HEADER: &str =
+    "source,file,channel,instrument,status,duration_seconds,peak_dbfs,rms_dbfs,clipped_samples,\
+loudness_lufs,loudness_range_lu,true_peak_dbtp";
+
+/// Quote fields with separators, quotes or line breaks in them
+fn field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_owned()
+    }
+}
+
+/// Empty for values that aren't known, such as the levels of outputs kept from an earlier run
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+/// Line for an output of the song `source`. Silent stems are listed with their levels even
+/// though no file was written
+pub fn row(source: &str, entry: &OutputEntry) -> String {
+    let status = match entry.status {
+        OutputStatus::Written => "written",
+        OutputStatus::Silent => "silent",
+        OutputStatus::Existing => "existing",
+        OutputStatus::Planned => "planned",
+        OutputStatus::Failed => "failed",
+    };
+
+    [
+        field(source),
+        field(&entry.filename),
+        entry.channel.to_string(),
+        entry.instrument.to_string(),
+        status.to_owned(),
+        optional(entry.duration_seconds.map(|s| format!("{:.3}", s))),
+        optional(entry.peak_dbfs.map(|db| format!("{:.2}", db))),
+        optional(entry.rms_dbfs.map(|db| format!("{:.2}", db))),
+        optional(entry.clipped_samples),
+        optional(entry.loudness_lufs.map(|lufs| format!("{:.2}", lufs))),
+        optional(entry.loudness_range_lu.map(|lu| format!("{:.2}", lu))),
+        optional(entry.true_peak_dbtp.map(|db| format!("{:.2}", db))),
+    ]
+    .join(",")
+}
+
+/// Write the rows of all songs, sorted so the report is the same however the songs were
+/// scheduled
+pub fn write(path: &Path, mut rows: Vec<String>) -> Result<()> {
+    rows.sort();
+
+    let mut file = File::create(path)?;
+    writeln!(file, "{}", HEADER)?;
+    for row in rows {
+        writeln!(file, "{}", row)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,490 @@
+//! Loudness measurement as specified by ITU-R BS.1770-4 (the meter used by EBU R128 and
+//! ReplayGain 2.0), with the loudness range and true peak of EBU R128 reports
+
+use std::borrow::Cow;
+
+/// Blocks of 400 ms are measured every 100 ms (75% overlap)
+const STEPS_PER_BLOCK: usize = 4;
+const STEPS_PER_SECOND: u32 = 10;
+/// Short-term loudness for the loudness range uses 3 s blocks
+const STEPS_PER_SHORT_TERM_BLOCK: usize = 30;
+
+/// Blocks quieter than this never count towards the integrated loudness
+const ABSOLUTE_GATE: f64 = -70.0;
+/// Blocks more than this many LU below the loudness of the blocks above the absolute gate are
+/// left out as well
+const RELATIVE_GATE: f64 = -10.0;
+
+/// Short-term blocks more than this many LU below the mean are left out of the loudness range
+const RANGE_RELATIVE_GATE: f64 = -20.0;
+/// The loudness range is the spread between these percentiles of the short-term loudness
+const RANGE_LOW_PERCENTILE: f64 = 0.10;
+const RANGE_HIGH_PERCENTILE: f64 = 0.95;
+
+/// The true peak is measured at 4 times the sample rate with a 48 tap interpolation filter
+const OVERSAMPLING: usize = 4;
+const TAPS_PER_PHASE: usize = 12;
+
+/// ReplayGain 2.0 scales tracks to this loudness (in LUFS)
+const REPLAYGAIN_REFERENCE: f64 = -18.0;
+
+/// Biquad filter in direct form 1
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x0: f64) -> f64 {
+        let y0 = self.b[0] * x0 + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+
+        self.x = [x0, self.x[0]];
+        self.y = [y0, self.y[0]];
+        y0
+    }
+}
+
+/// The K-weighting filter: a high shelf modelling the head followed by a high-pass. The
+/// coefficients in the standard are for 48 kHz, so they are derived from the analog prototypes
+/// (same as libebur128) to work at any sample rate
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let rate = sample_rate as f64;
+
+    let f0 = 1681.974450955533;
+    let gain = 3.999843853973347;
+    let q = 0.7071752369554196;
+
+    let k = (std::f64::consts::PI * f0 / rate).tan();
+    let vh = 10f64.powf(gain / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+
+    let shelf = Biquad {
+        b: [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        x: [0.0; 2],
+        y: [0.0; 2],
+    };
+
+    let f0 = 38.13547087602444;
+    let q = 0.5003270373238773;
+
+    let k = (std::f64::consts::PI * f0 / rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+
+    let highpass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        x: [0.0; 2],
+        y: [0.0; 2],
+    };
+
+    [shelf, highpass]
+}
+
+/// Weight of a channel in the sum. The rear channels of a 4.0 layout (left, right, left
+/// surround, right surround) and the channels past the front three of a 5.1 layout (left,
+/// right, centre, LFE, left surround, right surround) are the surrounds, and the LFE is left
+/// out. Mono and stereo renders weigh every channel the same
+fn channel_weight(channel: usize, channel_count: usize) -> f64 {
+    match (channel_count, channel) {
+        (4, 2..) => 1.41,
+        (6.., 3) => 0.0,
+        (6.., 4..) => 1.41,
+        _ => 1.0,
+    }
+}
+
+fn to_lufs(energy: f64) -> f64 {
+    -0.691 + 10.0 * energy.log10()
+}
+
+/// Samples of a render with full scale being 1.0
+fn samples_f32(buffer: &[u8], bytes_per_sample: usize) -> Cow<'_, [f32]> {
+    if bytes_per_sample == 4 {
+        Cow::Borrowed(bytemuck::cast_slice(buffer))
+    } else {
+        let data: &[i16] = bytemuck::cast_slice(buffer);
+        Cow::Owned(data.iter().map(|s| *s as f32 / 32768.0).collect())
+    }
+}
+
+/// Mean square of the K-weighted signal (summed over the channels) of every 100 ms step
+fn step_energies(samples: &[f32], channel_count: usize, sample_rate: u32) -> Vec<f64> {
+    let step_frames = (sample_rate / STEPS_PER_SECOND) as usize;
+    if step_frames == 0 || channel_count == 0 {
+        return Vec::new();
+    }
+
+    let mut filters = vec![k_weighting(sample_rate); channel_count];
+
+    // Partial steps at the end are dropped
+    samples
+        .chunks_exact(step_frames * channel_count)
+        .map(|step| {
+            let mut energy = 0.0;
+
+            for frame in step.chunks_exact(channel_count) {
+                for (channel, (sample, filter)) in frame.iter().zip(&mut filters).enumerate() {
+                    let y = filter
+                        .iter_mut()
+                        .fold(*sample as f64, |x, stage| stage.process(x));
+                    energy += channel_weight(channel, channel_count) * y * y;
+                }
+            }
+
+            energy / step_frames as f64
+        })
+        .collect()
+}
+
+/// Mean square of blocks of `steps_per_block` steps, one starting at every step
+fn block_energies(steps: &[f64], steps_per_block: usize) -> Vec<f64> {
+    steps
+        .windows(steps_per_block)
+        .map(|block| block.iter().sum::<f64>() / steps_per_block as f64)
+        .collect()
+}
+
+/// Blocks louder than the absolute gate
+fn above_absolute_gate(blocks: Vec<f64>) -> Vec<f64> {
+    blocks
+        .into_iter()
+        .filter(|energy| *energy > 0.0 && to_lufs(*energy) > ABSOLUTE_GATE)
+        .collect()
+}
+
+/// Gated loudness of 400 ms blocks
+fn gated_loudness(blocks: Vec<f64>) -> Option<f64> {
+    let blocks = above_absolute_gate(blocks);
+
+    if blocks.is_empty() {
+        return None;
+    }
+
+    let mean = blocks.iter().sum::<f64>() / blocks.len() as f64;
+    let gate = to_lufs(mean) + RELATIVE_GATE;
+
+    let gated: Vec<f64> = blocks.into_iter().filter(|e| to_lufs(*e) > gate).collect();
+    let mean = gated.iter().sum::<f64>() / gated.len() as f64;
+
+    Some(to_lufs(mean))
+}
+
+/// Loudness range (EBU Tech 3342) of 3 s blocks: the spread between the 10th and 95th
+/// percentile of their loudness, after gating
+fn loudness_range(blocks: Vec<f64>) -> Option<f64> {
+    let blocks = above_absolute_gate(blocks);
+
+    if blocks.is_empty() {
+        return None;
+    }
+
+    let mean = blocks.iter().sum::<f64>() / blocks.len() as f64;
+    let gate = to_lufs(mean) + RANGE_RELATIVE_GATE;
+
+    let mut levels: Vec<f64> = blocks
+        .into_iter()
+        .map(to_lufs)
+        .filter(|lufs| *lufs > gate)
+        .collect();
+    levels.sort_by(f64::total_cmp);
+
+    let percentile = |p: f64| levels[((levels.len() - 1) as f64 * p).round() as usize];
+
+    Some(percentile(RANGE_HIGH_PERCENTILE) - percentile(RANGE_LOW_PERCENTILE))
+}
+
+/// Integrated (gated) loudness in LUFS of an interleaved render. None if the render is shorter
+/// than a block or all of it is below the absolute gate
+pub fn integrated_loudness(
+    buffer: &[u8],
+    bytes_per_sample: usize,
+    channel_count: usize,
+    sample_rate: u32,
+) -> Option<f64> {
+    let samples = samples_f32(buffer, bytes_per_sample);
+    let steps = step_energies(&samples, channel_count, sample_rate);
+
+    gated_loudness(block_energies(&steps, STEPS_PER_BLOCK))
+}
+
+/// Interpolation filter for the true peak, split into one set of taps for each of the
+/// oversampled positions between two samples. Each set is scaled to unity gain
+fn oversampling_phases() -> Vec<Vec<f32>> {
+    let taps = OVERSAMPLING * TAPS_PER_PHASE;
+    let center = (taps - 1) as f64 / 2.0;
+
+    (0..OVERSAMPLING)
+        .map(|phase| {
+            let coefficients: Vec<f64> = (0..TAPS_PER_PHASE)
+                .map(|k| {
+                    let n = phase + k * OVERSAMPLING;
+                    let x = std::f64::consts::PI * (n as f64 - center) / OVERSAMPLING as f64;
+                    let sinc = if x == 0.0 { 1.0 } else { x.sin() / x };
+                    let window = 0.5
+                        - 0.5 * (std::f64::consts::TAU * (n + 1) as f64 / (taps + 1) as f64).cos();
+                    sinc * window
+                })
+                .collect();
+
+            let sum: f64 = coefficients.iter().sum();
+            coefficients.iter().map(|c| (c / sum) as f32).collect()
+        })
+        .collect()
+}
+
+/// Highest level of the signal between the samples as well (BS.1770 annex 2), found by
+/// oversampling. Full scale is 1.0
+fn true_peak(samples: &[f32], channel_count: usize) -> f32 {
+    let phases = oversampling_phases();
+    let mut peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+
+    for channel in 0..channel_count {
+        let channel_samples: Vec<f32> = samples
+            .iter()
+            .skip(channel)
+            .step_by(channel_count)
+            .copied()
+            .collect();
+
+        for window in channel_samples.windows(TAPS_PER_PHASE) {
+            for phase in &phases {
+                let value: f32 = window
+                    .iter()
+                    .rev()
+                    .zip(phase)
+                    .map(|(sample, tap)| sample * tap)
+                    .sum();
+                peak = peak.max(value.abs());
+            }
+        }
+    }
+
+    peak
+}
+
+/// EBU R128 measurements of a render
+#[derive(Debug, Copy, Clone)]
+pub struct R128 {
+    /// Integrated loudness in LUFS. None if the render is too short or silent
+    pub integrated: Option<f64>,
+    /// Loudness range in LU. None if the render is shorter than 3 seconds or silent
+    pub range: Option<f64>,
+    /// True peak with full scale being 1.0
+    pub true_peak: f32,
+}
+
+impl R128 {
+    pub fn measure(
+        buffer: &[u8],
+        bytes_per_sample: usize,
+        channel_count: usize,
+        sample_rate: u32,
+    ) -> R128 {
+        let samples = samples_f32(buffer, bytes_per_sample);
+        let steps = step_energies(&samples, channel_count, sample_rate);
+
+        R128 {
+            integrated: gated_loudness(block_energies(&steps, STEPS_PER_BLOCK)),
+            range: loudness_range(block_energies(&steps, STEPS_PER_SHORT_TERM_BLOCK)),
+            true_peak: true_peak(&samples, channel_count.max(1)),
+        }
+    }
+}
+
+/// Gain and peak stored as ReplayGain tags
+#[derive(Debug, Copy, Clone)]
+pub struct ReplayGain {
+    /// Gain in dB that brings the track to the ReplayGain 2.0 reference loudness
+    pub gain: f64,
+    /// Highest absolute sample value with full scale being 1.0
+    pub peak: f32,
+}
+
+impl ReplayGain {
+    /// Measure a render. None if it's too short or silent to have a loudness
+    pub fn measure(
+        buffer: &[u8],
+        bytes_per_sample: usize,
+        channel_count: usize,
+        sample_rate: u32,
+    ) -> Option<ReplayGain> {
+        let loudness = integrated_loudness(buffer, bytes_per_sample, channel_count, sample_rate)?;
+
+        Some(ReplayGain {
+            gain: REPLAYGAIN_REFERENCE - loudness,
+            peak: crate::dsp::peak(buffer, bytes_per_sample),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 48000;
+
+    /// Sine of `frequency` Hz at `level` dBFS, interleaved with `gains` giving the channels
+    fn sine_at(rate: u32, frequency: f64, level: f64, seconds: f64, gains: &[f64]) -> Vec<f32> {
+        let amplitude = 10f64.powf(level / 20.0);
+        let frames = (seconds * rate as f64) as usize;
+
+        (0..frames)
+            .flat_map(|i| {
+                let phase = std::f64::consts::TAU * frequency * i as f64 / rate as f64;
+                gains
+                    .iter()
+                    .map(move |gain| (gain * amplitude * phase.sin()) as f32)
+            })
+            .collect()
+    }
+
+    fn sine(frequency: f64, level: f64, seconds: f64, gains: &[f64]) -> Vec<f32> {
+        sine_at(RATE, frequency, level, seconds, gains)
+    }
+
+    fn loudness(samples: &[f32], channel_count: usize) -> Option<f64> {
+        integrated_loudness(bytemuck::cast_slice(samples), 4, channel_count, RATE)
+    }
+
+    fn assert_near(value: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (value - expected).abs() <= tolerance,
+            "{} isn't within {} of {}",
+            value,
+            tolerance,
+            expected
+        );
+    }
+
+    #[test]
+    fn stereo_sine_at_minus_20_dbfs_is_minus_20_lufs() {
+        let samples = sine(1000.0, -20.0, 2.0, &[1.0, 1.0]);
+        assert_near(loudness(&samples, 2).unwrap(), -20.0, 0.05);
+
+        // The same sine in 16-bit
+        let pcm16: Vec<i16> = samples.iter().map(|s| (s * 32768.0) as i16).collect();
+        let lufs = integrated_loudness(bytemuck::cast_slice(&pcm16), 2, 2, RATE);
+        assert_near(lufs.unwrap(), -20.0, 0.05);
+    }
+
+    #[test]
+    fn k_weighting_follows_the_standard_at_any_rate() {
+        // A 1 kHz sine in one channel is 3 dB below the same sine in both
+        let left = sine(1000.0, -20.0, 2.0, &[1.0, 0.0]);
+        assert_near(loudness(&left, 2).unwrap(), -23.01, 0.05);
+
+        // The high shelf lifts 10 kHz 3.35 dB more than 1 kHz and the high-pass cuts the lows
+        let high = sine(10000.0, -20.0, 2.0, &[1.0, 1.0]);
+        assert_near(loudness(&high, 2).unwrap(), -16.65, 0.05);
+        let low = sine(20.0, -20.0, 2.0, &[1.0, 1.0]);
+        assert!(loudness(&low, 2).unwrap() < -30.0);
+
+        let samples = sine_at(44100, 1000.0, -20.0, 2.0, &[1.0]);
+        let lufs = integrated_loudness(bytemuck::cast_slice(&samples), 4, 1, 44100);
+        assert_near(lufs.unwrap(), -23.01, 0.1);
+    }
+
+    #[test]
+    fn short_or_quiet_renders_have_no_loudness() {
+        assert_eq!(loudness(&sine(1000.0, -20.0, 0.39, &[1.0, 1.0]), 2), None);
+        assert!(loudness(&sine(1000.0, -20.0, 0.4, &[1.0, 1.0]), 2).is_some());
+        assert_eq!(loudness(&sine(1000.0, -75.0, 2.0, &[1.0, 1.0]), 2), None);
+        assert_eq!(loudness(&[0.0; 2 * RATE as usize], 2), None);
+    }
+
+    #[test]
+    fn quiet_parts_are_gated_out() {
+        let mut samples = sine(1000.0, -20.0, 10.0, &[1.0, 1.0]);
+        samples.extend(sine(1000.0, -40.0, 10.0, &[1.0, 1.0]));
+        samples.extend(sine(1000.0, -80.0, 2.0, &[1.0, 1.0]));
+
+        assert_near(loudness(&samples, 2).unwrap(), -20.0, 0.1);
+    }
+
+    #[test]
+    fn surround_channels_are_weighted() {
+        let front = loudness(&sine(1000.0, -20.0, 2.0, &[1.0, 0.0, 0.0, 0.0]), 4).unwrap();
+        let rear = loudness(&sine(1000.0, -20.0, 2.0, &[0.0, 0.0, 1.0, 0.0]), 4).unwrap();
+        assert_near(rear - front, 1.49, 0.01);
+
+        let centre = loudness(&sine(1000.0, -20.0, 2.0, &[0., 0., 1., 0., 0., 0.]), 6).unwrap();
+        let rear = loudness(&sine(1000.0, -20.0, 2.0, &[0., 0., 0., 0., 0., 1.]), 6).unwrap();
+        assert_near(centre, front, 0.01);
+        assert_near(rear - centre, 1.49, 0.01);
+
+        let lfe = sine(1000.0, -20.0, 2.0, &[0., 0., 0., 1., 0., 0.]);
+        assert_eq!(loudness(&lfe, 6), None);
+    }
+
+    #[test]
+    fn loudness_range_is_the_spread_of_the_short_term_loudness() {
+        // A low rate keeps the true peak, which is measured as well, quick
+        let measure = |samples: &[f32]| R128::measure(bytemuck::cast_slice(samples), 4, 1, 8000);
+
+        let steady = sine_at(8000, 1000.0, -20.0, 5.0, &[1.0]);
+        assert_near(measure(&steady).range.unwrap(), 0.0, 0.05);
+
+        let mut samples = sine_at(8000, 1000.0, -20.0, 10.0, &[1.0]);
+        samples.extend(sine_at(8000, 1000.0, -30.0, 10.0, &[1.0]));
+        assert_near(measure(&samples).range.unwrap(), 10.0, 0.1);
+
+        let short = measure(&sine_at(8000, 1000.0, -20.0, 2.0, &[1.0]));
+        assert!(short.integrated.is_some());
+        assert_eq!(short.range, None);
+    }
+
+    #[test]
+    fn replaygain_brings_tracks_to_minus_18_lufs() {
+        let quiet = sine(1000.0, -20.0, 2.0, &[1.0, 1.0]);
+        let buffer: &[u8] = bytemuck::cast_slice(&quiet);
+        let replay_gain = ReplayGain::measure(buffer, 4, 2, RATE).unwrap();
+
+        assert_eq!(
+            replay_gain.gain,
+            -18.0 - integrated_loudness(buffer, 4, 2, RATE).unwrap()
+        );
+        assert_near(replay_gain.gain, 2.0, 0.05);
+        assert_near(replay_gain.peak as f64, 0.1, 1e-6);
+
+        // Louder tracks are turned down, and the peak of 16-bit renders is read as 16-bit
+        let loud: Vec<i16> = sine(1000.0, -6.0, 2.0, &[1.0, 0.25])
+            .iter()
+            .map(|s| (s * 32768.0) as i16)
+            .collect();
+        let replay_gain = ReplayGain::measure(bytemuck::cast_slice(&loud), 2, 2, RATE).unwrap();
+        let peak = loud.iter().map(|s| s.unsigned_abs()).max().unwrap() as f32 / 32768.0;
+
+        assert!(replay_gain.gain < -8.0);
+        assert_eq!(replay_gain.peak, peak);
+        assert_near(replay_gain.peak as f64, 0.501, 0.001);
+
+        assert!(ReplayGain::measure(&[0; 4 * 2 * 48000], 4, 2, RATE).is_none());
+    }
+
+    #[test]
+    fn true_peak_is_found_between_samples() {
+        // A quarter of the sample rate shifted by 45 degrees never has a sample at its peak
+        let samples: Vec<f32> = (0..RATE as usize)
+            .map(|i| {
+                let phase = std::f64::consts::FRAC_PI_2 * i as f64 + std::f64::consts::FRAC_PI_4;
+                (0.5 * phase.sin()) as f32
+            })
+            .collect();
+        let sample_peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        assert!((sample_peak - 0.354).abs() < 0.001);
+
+        let r128 = R128::measure(bytemuck::cast_slice(&samples), 4, 1, RATE);
+        assert!((r128.true_peak - 0.5).abs() < 0.02, "{}", r128.true_peak);
+    }
+}
@@ -1,24 +1,161 @@
+/// Print a message for the user. Messages go to stderr when the audio is written to stdout
+macro_rules! message {
+    ($($arg:tt)*) => {
+        if crate::events::json() {
+            crate::events::emit(&crate::events::Event::Message { text: &format!($($arg)*) })
+        } else if crate::STDOUT_AUDIO.load(std::sync::atomic::Ordering::Relaxed) {
+            eprintln!($($arg)*)
+        } else if let Some(bars) = crate::PROGRESS_BARS.get() {
+            bars.suspend(|| println!($($arg)*))
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+
+/// Log an error for a song or output that couldn't be rendered or written. These are listed
+/// again at the end of the run, which then exits with an error
+macro_rules! fail {
+    ($($arg:tt)*) => {{
+        let message = format!($($arg)*);
+        if crate::events::json() {
+            crate::events::emit(&crate::events::Event::Failed { message: &message });
+        } else {
+            log::error!("{}", message);
+        }
+        crate::FAILURES.lock().unwrap().push(message);
+    }};
+}
+
+/// `fail!` while rendering a song. The error is also added to its --song-log
+macro_rules! song_fail {
+    ($args:expr, $($arg:tt)*) => {{
+        let message = format!($($arg)*);
+        if let Some(song_log) = &$args.render_log {
+            song_log.note(&format!("Error: {}", message));
+        }
+        fail!("{}", message);
+    }};
+}
+
+/// Log a warning while rendering a song. The warning is also added to its --song-log
+macro_rules! song_warn {
+    ($args:expr, $($arg:tt)*) => {{
+        let message = format!($($arg)*);
+        if let Some(song_log) = &$args.render_log {
+            song_log.note(&format!("Warning: {}", message));
+        }
+        log::warn!("{}", message);
+    }};
+}
+
+mod aac;
+mod ableton;
+mod aiff;
+mod ardour;
+mod backend;
+mod bundle;
+mod checksum;
+mod cover;
+mod cue;
+mod dsp;
+mod events;
+mod fingerprint;
+#[cfg(feature = "hvl")]
+mod hvl;
+mod info;
+mod info_cache;
+mod input;
+mod interrupt;
+mod key;
+mod level_report;
+mod loudness;
+mod manifest;
+mod ni_stems;
+mod pan_map;
+mod priority;
+mod sanitize;
+mod session;
+#[cfg(feature = "sid")]
+mod sid;
+mod song_log;
+mod spectrogram;
+mod tags;
+mod waveform;
+#[cfg(feature = "xmp")]
+mod xmp;
+
 use anyhow::Result;
-use clap::{Parser, ValueEnum};
-use indicatif::{ProgressBar, ProgressStyle};
+use backend::{Backend, OpenMpt, RenderStream};
+use clap::{Parser, Subcommand, ValueEnum};
+use cover::Cover;
+use cue::CuePoint;
+use events::Event;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use info::{SongReport, SubsongReport};
+use info_cache::{CachedInfo, CachedSubsong, InfoCache};
+use input::InputSong;
+use instruments::SongInstruments;
+use manifest::{Combined, Manifest, OutputEntry, OutputStatus};
 use mp3lame_encoder::{Builder, FlushNoGap, InterleavedPcm, MonoPcm};
+use ni_stems::{StemGroup, StemGroups};
+use pan_map::PanMap;
+use patterns::SongPatterns;
 use rayon::prelude::*;
+use sanitize::{FilenameCase, SanitizeRules};
+use session::session_tracks;
+use sha2::{Digest, Sha256};
 use simple_logger::SimpleLogger;
+use song_log::SongLog;
+use spectrogram::Spectrogram;
 use std::{
+    collections::HashSet,
     fs::File,
-    io::{Read, Write},
+    hash::{BuildHasher, Hasher},
+    io::{BufWriter, IsTerminal, Write},
     path::Path,
     path::PathBuf,
+    sync::atomic::{AtomicBool, Ordering},
+    sync::{Arc, Mutex, OnceLock},
+    time::{Instant, SystemTime},
 };
+use stemgen_core::{
+    instruments, patterns, tempo, wav_writer, AtEnd, AudioBuffer, RenderOptions, RenderParams,
+    RenderProgress, SampleFormat, SongInfo, SongStream, StemSelection, Subsong, MAX_CHANNELS,
+    SUBSONG_ALL,
+};
+use tags::{SongMetadata, Tags};
 use vorbis_rs::{VorbisBitrateManagementStrategy, VorbisEncoderBuilder};
 use walkdir::WalkDir;
-use wav;
+use wav_writer::{Bext, WavFormat, WavWriter};
+use waveform::Waveform;
 
-#[repr(C)]
 #[derive(ValueEnum, Debug, Copy, Clone)]
 enum SampleOutputFormat {
+    /// FLAC with loop points and root note in a "riff" APPLICATION block holding a smpl chunk
     Flac,
+    /// Wav with loop points (forward or ping-pong), sustain loop and root note in a smpl chunk
     Wav,
+    /// Wav samples plus an sfz instrument (<song>_instrument_NNNN.sfz) per instrument mapping them across the keys with their loops, volume and panning
+    Sfz,
+    /// A single SoundFont (<song>.sf2) with a preset per instrument holding its samples, key mapping, loops and an approximation of the volume envelope
+    Sf2,
+    /// Amiga IFF 8SVX (<song>_sample_NNNN.iff) with the loop in the header, loadable by ProTracker. 16-bit samples are written as 16SV
+    #[value(name = "8svx")]
+    Iff8svx,
+}
+
+impl From<SampleOutputFormat> for SampleFormat {
+    fn from(format: SampleOutputFormat) -> SampleFormat {
+        match format {
+            SampleOutputFormat::Flac => SampleFormat::Flac,
+            SampleOutputFormat::Wav => SampleFormat::Wav,
+            SampleOutputFormat::Sfz => SampleFormat::Sfz,
+            SampleOutputFormat::Sf2 => SampleFormat::Sf2,
+            SampleOutputFormat::Iff8svx => SampleFormat::Iff8svx,
+        }
+    }
 }
 
 #[derive(ValueEnum, Debug, Copy, Clone, PartialEq)]
@@ -27,14 +164,46 @@ enum WriteFormat {
     Wav,
     Vorbis,
     Mp3,
+    Aac,
+    Aiff,
+    Wavpack,
+}
+
+impl WriteFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            WriteFormat::Flac => "flac",
+            WriteFormat::Wav => "wav",
+            WriteFormat::Vorbis => "ogg",
+            WriteFormat::Mp3 => "mp3",
+            WriteFormat::Aac => "m4a",
+            WriteFormat::Aiff => "aiff",
+            WriteFormat::Wavpack => "wv",
+        }
+    }
 }
 
 #[derive(ValueEnum, Debug, Copy, Clone, PartialEq)]
 enum SampleDepth {
     Int16,
+    /// 24-bit integer wav or flac
+    Int24,
+    /// 32-bit integer wav. Flac writes it like int24
+    Int32,
     Float,
 }
 
+impl SampleDepth {
+    /// Bytes of a sample in a file written at this depth
+    fn bytes(self) -> u32 {
+        match self {
+            SampleDepth::Int16 => 2,
+            SampleDepth::Int24 => 3,
+            SampleDepth::Int32 | SampleDepth::Float => 4,
+        }
+    }
+}
+
 #[derive(ValueEnum, Debug, Copy, Clone, PartialEq)]
 enum OggMode {
     Vbr,
@@ -43,74 +212,579 @@ enum OggMode {
     ConstrainedAbr,
 }
 
+/// One part of the input files when splitting a batch over several machines
+#[derive(Debug, Copy, Clone)]
+struct Shard {
+    /// 1-based index of this shard
+    index: u64,
+    count: u64,
+}
+
+impl Shard {
+    /// Files are assigned by a hash of their path relative to the input so every machine picks
+    /// the same files regardless of where the archive is mounted or the order files are listed in
+    fn contains(&self, relative_path: &str) -> bool {
+        let hash = Sha256::digest(relative_path.replace('\\', "/").as_bytes());
+        let value = u64::from_le_bytes(hash[..8].try_into().unwrap());
+        value % self.count == self.index - 1
+    }
+}
+
+impl std::str::FromStr for Shard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parsed = s
+            .split_once('/')
+            .and_then(|(index, count)| Some((index.parse().ok()?, count.parse().ok()?)));
+
+        match parsed {
+            Some((index, count)) if index >= 1 && index <= count => Ok(Shard { index, count }),
+            _ => Err(format!(
+                "Invalid shard \"{}\". Use <index>/<count> with index in [1, count]",
+                s
+            )),
+        }
+    }
+}
+
+/// Range of order positions to render, with both ends included
+#[derive(Debug, Copy, Clone)]
+struct OrderRange {
+    first: u32,
+    /// None renders until the end of the song
+    last: Option<u32>,
+}
+
+impl std::str::FromStr for OrderRange {
+    type Err = String;
+
+    /// Accepts `first..end` (end excluded), `first..=last`, `first..` and a single order
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || {
+            format!(
+                "Invalid order range \"{}\". Use <first>..<end>, <first>..=<last>, <first>.. or <order>",
+                s
+            )
+        };
+        let parse = |v: &str| v.trim().parse::<u32>().map_err(|_| error());
+
+        let range = match s.split_once("..") {
+            None => {
+                let order = parse(s)?;
+                OrderRange {
+                    first: order,
+                    last: Some(order),
+                }
+            }
+            Some((first, "")) => OrderRange {
+                first: parse(first)?,
+                last: None,
+            },
+            Some((first, last)) => {
+                let first = parse(first)?;
+                let last = match last.strip_prefix('=') {
+                    Some(last) => parse(last)?,
+                    None => parse(last)?.checked_sub(1).ok_or_else(error)?,
+                };
+                OrderRange {
+                    first,
+                    last: Some(last),
+                }
+            }
+        };
+
+        match range.last {
+            Some(last) if last < range.first => Err(error()),
+            _ => Ok(range),
+        }
+    }
+}
+
+/// Interpolation filter used by libopenmpt when resampling the instruments
+#[derive(ValueEnum, Debug, Copy, Clone, PartialEq)]
+enum Interpolation {
+    /// Nearest neighbour, keeps the crunchy sound of the original hardware
+    None,
+    Linear,
+    Cubic,
+    /// Windowed sinc with 8 taps
+    Sinc,
+}
+
+impl Interpolation {
+    /// Value for libopenmpt's RENDER_INTERPOLATIONFILTER_LENGTH
+    fn filter_length(self) -> i32 {
+        match self {
+            Interpolation::None => 1,
+            Interpolation::Linear => 2,
+            Interpolation::Cubic => 4,
+            Interpolation::Sinc => 8,
+        }
+    }
+}
+
+/// Playback library selected with --backend
+#[derive(ValueEnum, Debug, Copy, Clone, PartialEq)]
+enum BackendKind {
+    Openmpt,
+    /// libxmp, needs stemgen to be built with the xmp feature
+    Xmp,
+}
+
+/// Filter model of the Paula emulation for Amiga formats such as mod
+#[derive(ValueEnum, Debug, Copy, Clone, PartialEq)]
+enum AmigaFilter {
+    /// A500 or A1200 depending on the format of the song
+    Auto,
+    A500,
+    A1200,
+    /// Paula output without the filters of the Amiga models
+    Unfiltered,
+}
+
+impl From<AmigaFilter> for stemgen_core::AmigaFilter {
+    fn from(filter: AmigaFilter) -> stemgen_core::AmigaFilter {
+        match filter {
+            AmigaFilter::Auto => stemgen_core::AmigaFilter::Auto,
+            AmigaFilter::A500 => stemgen_core::AmigaFilter::A500,
+            AmigaFilter::A1200 => stemgen_core::AmigaFilter::A1200,
+            AmigaFilter::Unfiltered => stemgen_core::AmigaFilter::Unfiltered,
+        }
+    }
+}
+
+/// How each render is normalized before it's written
+#[derive(ValueEnum, Debug, Copy, Clone, PartialEq)]
+enum Normalize {
+    /// Scale so the highest sample reaches --normalize-target
+    Peak,
+}
+
+/// Where the tracks of a cue sheet start
+#[derive(ValueEnum, Debug, Copy, Clone, PartialEq)]
+enum CueMode {
+    /// A track for every order position
+    Orders,
+    /// A track for every subsong. The subsongs are rendered back to back
+    Subsongs,
+}
+
+/// Speaker layout of the full mix with --surround
+#[derive(ValueEnum, Debug, Copy, Clone, PartialEq)]
+enum Surround {
+    /// Front left, front right, rear left and rear right
+    #[value(name = "4.0")]
+    Quad,
+    /// Front left, front right, center, LFE, rear left and rear right
+    #[value(name = "5.1")]
+    FiveOne,
+}
+
+impl Surround {
+    fn channel_count(self) -> usize {
+        match self {
+            Surround::Quad => 4,
+            Surround::FiveOne => 6,
+        }
+    }
+
+    /// Layout used if --surround-layout isn't given. Channels go around the room in the same
+    /// left, right, right, left order as the Amiga panning
+    fn default_layout(self) -> Vec<Speaker> {
+        match self {
+            Surround::Quad => vec![Speaker::Fl, Speaker::Fr, Speaker::Rr, Speaker::Rl],
+            Surround::FiveOne => vec![
+                Speaker::Fl,
+                Speaker::Fr,
+                Speaker::Rr,
+                Speaker::Rl,
+                Speaker::C,
+            ],
+        }
+    }
+}
+
+/// Speaker a module channel is sent to with --surround
+#[derive(ValueEnum, Debug, Copy, Clone, PartialEq)]
+enum Speaker {
+    /// Front left
+    Fl,
+    /// Front right
+    Fr,
+    /// Center
+    C,
+    /// Low frequency effects
+    Lfe,
+    /// Rear left
+    Rl,
+    /// Rear right
+    Rr,
+}
+
+impl Speaker {
+    /// Channel of the speaker in WAV and FLAC files, if the layout has it
+    fn index(self, surround: Surround) -> Option<usize> {
+        match (surround, self) {
+            (_, Speaker::Fl) => Some(0),
+            (_, Speaker::Fr) => Some(1),
+            (Surround::Quad, Speaker::Rl) => Some(2),
+            (Surround::Quad, Speaker::Rr) => Some(3),
+            (Surround::Quad, _) => None,
+            (Surround::FiveOne, Speaker::C) => Some(2),
+            (Surround::FiveOne, Speaker::Lfe) => Some(3),
+            (Surround::FiveOne, Speaker::Rl) => Some(4),
+            (Surround::FiveOne, Speaker::Rr) => Some(5),
+        }
+    }
+}
+
+/// What to do with output files that already exist
 #[derive(ValueEnum, Debug, Copy, Clone, PartialEq)]
-pub enum Mp3VbrMode {
-    ///Off.
+enum Overwrite {
+    /// Replace existing files
+    Always,
+    /// Keep existing files
+    Never,
+    /// Only replace files older than the input song
+    IfNewer,
+}
+
+/// How errors and progress are reported
+#[derive(ValueEnum, Debug, Copy, Clone, PartialEq)]
+enum LogFormat {
+    /// Messages and errors as text
+    Text,
+    /// A line of JSON on stderr for each event (file started, stem rendered, bytes written, failures and messages)
+    Json,
+}
+
+/// What to do when the estimated output doesn't fit on the output volume
+#[derive(ValueEnum, Debug, Copy, Clone, PartialEq)]
+enum DiskCheck {
     Off,
-    ///MT.
-    Mt,
-    ///RH.
-    Rh,
-    ///ABR.
+    Warn,
+    Abort,
+}
+
+/// Parse a time given as seconds or as [hours:]minutes:seconds where the seconds may have decimals
+fn parse_time(s: &str) -> Result<f32, String> {
+    let mut seconds = 0.0;
+
+    for (i, part) in s.rsplit(':').enumerate() {
+        let value = match part.trim().parse::<f32>() {
+            Ok(value) if value >= 0.0 && value.is_finite() => value,
+            _ => return Err(format!("Invalid time \"{}\"", s)),
+        };
+
+        // Only the seconds may have decimals
+        if (i > 0 && value.fract() != 0.0) || i > 2 {
+            return Err(format!("Invalid time \"{}\"", s));
+        }
+
+        seconds += value * 60f32.powi(i as i32);
+    }
+
+    Ok(seconds)
+}
+
+/// LAME bitrate for a rate in kbps. None if LAME doesn't support it
+fn mp3_bitrate(kbps: u32) -> Option<mp3lame_encoder::Bitrate> {
+    use mp3lame_encoder::Bitrate;
+
+    Some(match kbps {
+        8 => Bitrate::Kbps8,
+        16 => Bitrate::Kbps16,
+        24 => Bitrate::Kbps24,
+        32 => Bitrate::Kbps32,
+        40 => Bitrate::Kbps40,
+        48 => Bitrate::Kbps48,
+        64 => Bitrate::Kbps64,
+        80 => Bitrate::Kbps80,
+        96 => Bitrate::Kbps96,
+        112 => Bitrate::Kbps112,
+        128 => Bitrate::Kbps128,
+        160 => Bitrate::Kbps160,
+        192 => Bitrate::Kbps192,
+        224 => Bitrate::Kbps224,
+        256 => Bitrate::Kbps256,
+        320 => Bitrate::Kbps320,
+        _ => return None,
+    })
+}
+
+fn parse_mp3_bitrate(s: &str) -> Result<u32, String> {
+    let kbps = s
+        .trim()
+        .trim_end_matches("kbps")
+        .parse::<u32>()
+        .map_err(|_| format!("Invalid bitrate \"{}\"", s))?;
+
+    match mp3_bitrate(kbps) {
+        Some(_) => Ok(kbps),
+        None => Err(format!(
+            "{} kbps isn't a standard mp3 bitrate (8, 16, 24, 32, 40, 48, 64, 80, 96, 112, 128, 160, 192, 224, 256 or 320)",
+            kbps
+        )),
+    }
+}
+
+fn parse_flac_level(s: &str) -> Result<u32, String> {
+    match s.trim().parse::<u32>() {
+        Ok(level) if level <= 8 => Ok(level),
+        _ => Err(format!(
+            "Invalid compression level \"{}\", has to be 0 to 8",
+            s
+        )),
+    }
+}
+
+/// Block sizes allowed in the FLAC subset so the outputs play everywhere
+fn parse_flac_blocksize(s: &str) -> Result<u32, String> {
+    match s.trim().parse::<u32>() {
+        Ok(size) if (16..=4608).contains(&size) => Ok(size),
+        _ => Err(format!(
+            "Invalid block size \"{}\", has to be 16 to 4608",
+            s
+        )),
+    }
+}
+
+fn parse_aac_bitrate(s: &str) -> Result<u32, String> {
+    match s.trim().parse::<u32>() {
+        Ok(bitrate) if (8..=512).contains(&bitrate) => Ok(bitrate),
+        _ => Err(format!(
+            "Invalid AAC bitrate \"{}\", has to be 8 to 512 kbps",
+            s
+        )),
+    }
+}
+
+/// Parse a level in dB with an optional "dB" suffix
+fn parse_db(s: &str) -> Result<f32, String> {
+    let value = s.trim();
+    let value = value
+        .strip_suffix("dB")
+        .or_else(|| value.strip_suffix("db"))
+        .unwrap_or(value);
+
+    value
+        .trim()
+        .parse::<f32>()
+        .map_err(|_| format!("Invalid level \"{}\"", s))
+}
+
+#[derive(ValueEnum, Debug, Copy, Clone, PartialEq)]
+pub enum Mp3Mode {
+    /// Constant bitrate at --mp3-bitrate
+    #[value(alias = "off")]
+    Cbr,
+    /// Average bitrate around --mp3-bitrate
     Abr,
-    ///MTRH.
-    Mtrh,
+    /// Variable bitrate at --mp3-vbr-level. Best for sparse stems as silent parts take almost no space
+    #[value(alias = "mtrh", alias = "mt", alias = "rh")]
+    Vbr,
 }
 
 #[derive(ValueEnum, Debug, Copy, Clone, PartialEq)]
 pub enum Mp3Quality {
     ///Best possible quality
+    #[value(alias = "0")]
     Best = 0,
     ///Second best
+    #[value(alias = "1")]
     SecondBest = 1,
     ///Close to best
+    #[value(alias = "2")]
     NearBest = 2,
     ///Very nice
+    #[value(alias = "3")]
     VeryNice = 3,
     ///Nice
+    #[value(alias = "4")]
     Nice = 4,
     ///Good
+    #[value(alias = "5")]
     Good = 5,
     ///Decent
+    #[value(alias = "6")]
     Decent = 6,
     ///Okayish
+    #[value(alias = "7")]
     Ok = 7,
     ///Almost worst
+    #[value(alias = "8")]
     SecondWorst = 8,
     ///Worst
+    #[value(alias = "9")]
     Worst = 9,
 }
 
-#[derive(Parser, Debug)]
-#[clap(author, version, about, long_about = None)]
+impl From<Mp3Quality> for mp3lame_encoder::Quality {
+    fn from(quality: Mp3Quality) -> Self {
+        match quality {
+            Mp3Quality::Best => mp3lame_encoder::Quality::Best,
+            Mp3Quality::SecondBest => mp3lame_encoder::Quality::SecondBest,
+            Mp3Quality::NearBest => mp3lame_encoder::Quality::NearBest,
+            Mp3Quality::VeryNice => mp3lame_encoder::Quality::VeryNice,
+            Mp3Quality::Nice => mp3lame_encoder::Quality::Nice,
+            Mp3Quality::Good => mp3lame_encoder::Quality::Good,
+            Mp3Quality::Decent => mp3lame_encoder::Quality::Decent,
+            Mp3Quality::Ok => mp3lame_encoder::Quality::Ok,
+            Mp3Quality::SecondWorst => mp3lame_encoder::Quality::SecondWorst,
+            Mp3Quality::Worst => mp3lame_encoder::Quality::Worst,
+        }
+    }
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Print channel count, instrument and sample names, duration, subsongs and title of each song without rendering anything
+    Info {
+        /// Input song, zip or LHA archive, or directory of files supported by libopenmpt
+        input: String,
+
+        /// If input is a directory recursive can be used to get the all files within that directory
+        #[clap(short, long)]
+        recursive: bool,
+
+        /// Print one JSON object per song (one per line) instead of text
+        #[clap(long, default_value = "false")]
+        json: bool,
+    },
+}
+
+#[derive(Parser, Debug, Clone)]
+#[clap(author, version, about, long_about = None, subcommand_negates_reqs = true)]
 struct Args {
-    /// Input song or directory of files supported by libopenmpt
-    #[clap(short, long)]
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    /// Input song, zip or LHA archive, or directory of files supported by libopenmpt. Archives
+    /// are extracted in memory and each song in them is rendered. gzip and xz compressed songs
+    /// (such as song.mod.gz) are decompressed. http(s) URLs are downloaded to memory and - reads
+    /// the song from stdin
+    #[clap(
+        short,
+        long,
+        required = true,
+        default_value = "",
+        hide_default_value = true
+    )]
     input: String,
 
     /// Output directory to place the generated files
-    #[clap(short, long)]
+    #[clap(
+        short,
+        long,
+        required_unless_present_any = ["list", "stdout"],
+        default_value = ""
+    )]
     output: String,
 
+    /// Write the full mix of a single song to stdout instead of a file, for use in pipelines. Messages and errors go to stderr
+    #[clap(
+        long,
+        default_value = "false",
+        conflicts_with_all = [
+            "output", "channels", "instruments", "poly", "ni_stems", "patterns", "all_subsongs",
+            "song_samples", "pattern_data", "manifest", "bundle", "ardour", "ableton", "cue", "list",
+            "repair", "spectrogram", "waveform_png", "tempo_map"
+        ]
+    )]
+    stdout: bool,
+
     /// If input is a directory recursive can be used to get the all files within that directory
     #[clap(short, long)]
     recursive: bool,
 
+    /// Comma separated file name patterns (such as '*.mod,*.xm') for the files picked up when the input is a directory. Defaults to the extensions of all supported formats, archives and compressed files, both as suffix and as the prefix Amiga modules use (such as 'mod.intro')
+    #[clap(long, value_delimiter = ',')]
+    include: Option<Vec<String>>,
+
+    /// Skip files and directories matching this pattern when the input is a directory, such as 'backup' or 'converted/**'. Patterns match the name or the path relative to the input. Can be given multiple times
+    #[clap(long)]
+    exclude: Vec<String>,
+
     /// Represents the stereo separation generated by the mixer in percent. Range is [0, 200] and default value is 100.
     #[clap(long, default_value = None)]
     stereo_separation: Option<u32>,
 
+    /// TOML file with a pan position (-1 left to 1 right) and gain in dB (0 or less) per module channel, such as `[channel.0]` with `pan = -0.5` and `gain = -3`. It sets the initial pan and channel volume so pan commands in the song still apply
+    #[clap(long)]
+    pan_map: Option<PathBuf>,
+
+    /// Pan map loaded from `pan_map`
+    #[clap(skip)]
+    channel_mix: PanMap,
+
     /// Render the whole song as is
     #[clap(long, default_value = "false")]
     full: bool,
 
-    /// Show progressbar when generating
+    /// Render the full mix as multichannel audio with each module channel sent to a speaker given by --surround-layout. Only written as wav or flac
+    #[clap(long, requires = "full", conflicts_with = "ni_stems")]
+    surround: Option<Surround>,
+
+    /// Comma separated speakers (fl, fr, c, lfe, rl, rr) for module channel 0, 1, 2 and so on, repeated for songs with more channels. Defaults to fl,fr,rr,rl for 4.0 and fl,fr,rr,rl,c for 5.1
+    #[clap(long, requires = "surround", value_delimiter = ',')]
+    surround_layout: Option<Vec<Speaker>>,
+
+    /// Write a <song>.cue sheet next to the full mix with tracks starting at each order position or subsong. With subsongs all subsongs are played back to back in every render
+    #[clap(long, requires = "full", conflicts_with_all = ["subsong", "all_subsongs"])]
+    cue: Option<CueMode>,
+
+    /// Show progressbar when generating. Each song being rendered gets its own bar below a bar for all songs
     #[clap(long, default_value = "false")]
     progress: bool,
 
-    /// Output sample rate. Should be in [8000, 192000]
-    #[clap(short, long, default_value = "48000")]
-    sample_rate: u32,
+    /// Plain output without progress bars or colors. This is the default when stdout isn't a terminal
+    #[clap(long, default_value = "false")]
+    plain: bool,
+
+    /// Disable colored output (also disabled if the NO_COLOR environment variable is set)
+    #[clap(long, default_value = "false")]
+    no_color: bool,
+
+    /// Format of messages, errors and progress. JSON turns off the progress bars
+    #[clap(long, default_value = "text")]
+    log_format: LogFormat,
+
+    /// Output sample rate. Should be in [8000, 192000]. Several comma separated rates can be given in which case the song is rendered once at the highest rate and resampled to the others, with each rate placed in its own subfolder
+    #[clap(short, long, default_value = "48000", value_delimiter = ',')]
+    sample_rate: Vec<u32>,
+
+    /// Gain in dB applied to all renders by the libopenmpt mixer (before the conversion to 16-bit, so quiet stems keep their resolution)
+    #[clap(long, allow_hyphen_values(true), default_value = "0")]
+    gain: f32,
+
+    /// Normalize every render (full mix and each stem on its own) before it's written
+    #[clap(long)]
+    normalize: Option<Normalize>,
+
+    /// Target level in dBFS for --normalize
+    #[clap(long, allow_hyphen_values(true), default_value = "-1")]
+    normalize_target: f32,
+
+    /// Measure the loudness of every render and write ReplayGain 2.0 track gain and peak tags (FLAC, Ogg Vorbis, mp3 and WavPack)
+    #[clap(long, default_value = "false")]
+    replaygain: bool,
+
+    /// Interpolation filter used when playing the instruments. Defaults to libopenmpt's choice
+    #[clap(long)]
+    interpolation: Option<Interpolation>,
+
+    /// Playback library used for rendering modules. Their song info always comes from libopenmpt. xmp can't solo instruments, so --channels writes one stem per channel and --instruments isn't available, and ignores pan positions from --pan-map. SID tunes always use libsidplayfp and AHX and HVL songs the HivelyTracker replayer
+    #[clap(long, default_value = "openmpt")]
+    backend: BackendKind,
+
+    /// Emulate the Amiga sound hardware with the given filter model when rendering Amiga formats such as mod. The models differ a lot in how bright the songs sound
+    #[clap(long)]
+    amiga_filter: Option<AmigaFilter>,
+
+    /// Render Amiga formats such as mod with libopenmpt's emulation of the Paula chip (band-limited step resampling) instead of the regular mixer so each channel sounds like the real hardware. Uses the auto filter model unless --amiga-filter is given. Replaces --interpolation for those songs
+    #[clap(long, default_value = "false")]
+    amiga_resampler: bool,
 
     /// Render the instruments to stereo wav files. mono is default
     #[clap(long, default_value = "false")]
@@ -124,10 +798,30 @@ struct Args {
     #[clap(long, default_value = "false")]
     instruments: bool,
 
-    /// Write samples in the song to disk
+    /// Write a single multichannel wav per song where file channel N is the render of tracker channel N (imports as split tracks in most DAWs)
+    #[clap(long, default_value = "false")]
+    poly: bool,
+
+    /// Write a Native Instruments Stems file (<song>.stem.mp4) with the full mix and four stems, for Traktor and other stem aware DJ software
+    #[clap(long, default_value = "false")]
+    ni_stems: bool,
+
+    /// Channels mixed into each NI stem, as [name=]channels separated by ; such as "Drums=0-3;Bass=4;5,7;6". Defaults to splitting the non-empty channels evenly into four stems
+    #[clap(long)]
+    ni_stem_groups: Option<StemGroups>,
+
+    /// Write samples in the song to disk. Loop points, loop type and root note are stored with each sample so samplers import the loops. The instrument envelopes, NNA settings and the default volume and finetune of the samples are written to <song>_instruments.json
     #[clap(long)]
     song_samples: Option<SampleOutputFormat>,
 
+    /// Write the patterns and order list (notes, instruments, volume and effect commands of every row) to <song>_patterns.json
+    #[clap(long, default_value = "false")]
+    pattern_data: bool,
+
+    /// Write the tempo of the song over time (initial tempo and speed and every change, converted to BPM) to <song>_tempo.json and as a MIDI tempo track to <song>_tempo.mid
+    #[clap(long, default_value = "false")]
+    tempo_map: bool,
+
     /// Sample depth for the rendering.
     #[clap(short, long, default_value = "int16")]
     format: SampleDepth,
@@ -148,639 +842,4495 @@ struct Args {
     #[clap(long, allow_hyphen_values(true), default_value = "0.5")]
     vorbis_quality: f32,
 
-    /// Bitrate for mp3 encoding in kbps supported values: [8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320]
-    #[clap(long, default_value = "320")]
+    /// Bitrate for mp3 encoding in kbps: 8, 16, 24, 32, 40, 48, 64, 80, 96, 112, 128, 160, 192, 224, 256 or 320. Low rates suit previews, 192 and up distribution. Used as the constant bitrate with --mp3-mode cbr and as the target with abr
+    #[clap(long, default_value = "320", value_parser = parse_mp3_bitrate)]
     mp3_bitrate: u32,
 
-    /// Vbr mode for mp3 encoding
-    #[clap(long, default_value = "abr")]
-    mp3_vbr: Mp3VbrMode,
+    /// FLAC compression level from 0 (fastest) to 8 (smallest files). Levels above 5 only shave off a little more at a much higher encode time
+    #[clap(long, default_value = "8", value_parser = parse_flac_level)]
+    flac_level: u32,
 
-    /// Quality for VBR encoding
-    #[clap(long, default_value = "good")]
-    mp3_vbr_quality: Mp3Quality,
+    /// Verify FLAC outputs by decoding each frame while encoding. Roughly doubles the encode time
+    #[clap(long, default_value = "false")]
+    flac_verify: bool,
+
+    /// FLAC block size in samples (16 to 4608) instead of the one of the compression level
+    #[clap(long, value_parser = parse_flac_blocksize)]
+    flac_blocksize: Option<u32>,
+
+    /// FLAC apodization functions (such as "tukey(0.5);partial_tukey(2)") instead of the ones of the compression level
+    #[clap(long)]
+    flac_apodization: Option<String>,
+
+    /// Bitrate for AAC encoding in kbps (8 to 512)
+    #[clap(long, default_value = "256", value_parser = parse_aac_bitrate)]
+    aac_bitrate: u32,
+
+    /// Encode WavPack in lossy hybrid mode at this bitrate in kbps. A .wvc correction file is written next to each output so the lossless original can be restored
+    #[clap(long)]
+    wavpack_hybrid: Option<u32>,
 
-    /// Quality for regular encoding
+    /// Don't write the .wvc correction files in WavPack hybrid mode
+    #[clap(long, default_value = "false")]
+    wavpack_no_correction: bool,
+
+    /// Bitrate mode for mp3 encoding
+    #[clap(long, alias = "mp3-vbr", default_value = "abr")]
+    mp3_mode: Mp3Mode,
+
+    /// Quality of the mp3 encoding algorithm (LAME -q), by name or 0 (best) to 9 (worst). Better is slower
     #[clap(long, default_value = "good")]
     mp3_quality: Mp3Quality,
-}
 
-#[repr(C)]
-#[derive(Debug)]
-struct SongInfo {
-    channel_count: u32,
-    instrument_count: u32,
-    duration_seconds: f32,
-}
+    /// Target quality with --mp3-mode vbr (LAME -V), by name or 0 (best) to 9 (worst)
+    #[clap(long, alias = "mp3-vbr-quality", default_value = "good")]
+    mp3_vbr_level: Mp3Quality,
 
-// Has to match the struct in the C code
-#[repr(C)]
-struct RenderParams {
-    sample_rate: u32,
-    bytes_per_sample: u32,
-    channel_to_play: i32, // if -1 use all channels, otherwise pick one channel
-    instrument_to_play: i32, // if -1 use all instruments, otherwise pick one
-    stereo_separation: u32,
-    stereo_separation_enabled: bool,
-    stereo_output: bool,
-}
+    /// What to do when the song ends: stop, fadeout-<seconds> (keep playing and fade out, default 10) or loop-<count> (play the song again count times)
+    #[clap(long, default_value = "stop")]
+    at_end: AtEnd,
 
-extern "C" {
-    fn get_song_info_c(
-        data: *const u8,
-        len: u32,
-        sample_output_path: *const u8,
-        sample_format: u32,
-    ) -> SongInfo;
-    fn song_render_c(
-        output: *mut u8,
-        output_len: u32,
-        input_data: *const u8,
-        input_len: u32,
-        params: *const RenderParams,
-    ) -> u32;
-}
+    /// Play the song again this many times after the first pass before stopping. Same as --at-end loop-<count>
+    #[clap(long, conflicts_with = "at_end")]
+    repeat_count: Option<u32>,
 
-fn get_song_info(
-    file_data: &[u8],
-    samples_output_path: Option<&Path>,
-    sample_format: u32,
-) -> SongInfo {
-    if let Some(path) = samples_output_path {
-        let os_path = path.to_string_lossy().into_owned();
-        let c_filename = std::ffi::CString::new(os_path).unwrap();
-        unsafe {
-            get_song_info_c(
-                file_data.as_ptr(),
-                file_data.len() as u32,
-                c_filename.as_ptr() as *const _,
-                sample_format,
-            )
-        }
-    } else {
-        unsafe {
-            get_song_info_c(
-                file_data.as_ptr(),
-                file_data.len() as u32,
-                std::ptr::null(),
-                0,
-            )
-        }
-    }
-}
-fn song_render(output: &mut [u8], input: &[u8], render_params: &RenderParams) -> u32 {
-    unsafe {
-        song_render_c(
-            output.as_mut_ptr(),
-            output.len() as u32,
-            input.as_ptr(),
-            input.len() as u32,
-            render_params,
-        )
-    }
-}
+    /// Apply a low-pass filter with the given cutoff frequency (in Hz) to all renders
+    #[clap(long)]
+    lowpass: Option<f32>,
 
-// Get files for a given directory or single filename
-fn get_files(path: &str, recurse: bool) -> Vec<String> {
-    if !Path::new(path).exists() {
-        log::info!(
-            "Path/File \"{}\" doesn't exist. No file(s) will be processed.",
-            path
-        );
-        return Vec::new();
-    }
+    /// Render each pattern in the order list (from the first time it's played) to its own set of files with a _pattern_<number> suffix. Applies to --full, --channels and --instruments and renders the full mix if none of them is given
+    #[clap(long, default_value = "false", conflicts_with_all = ["all_subsongs", "order_range", "start", "end", "cue"])]
+    patterns: bool,
 
-    // Check if "path" is a single file
-    let md = std::fs::metadata(path).unwrap();
+    /// Only render the order positions in this range, given as <first>..<end> (end excluded), <first>..=<last>, <first>.. or a single order
+    #[clap(long, conflicts_with_all = ["start", "end"])]
+    order_range: Option<OrderRange>,
 
-    if md.is_file() {
-        return vec![path.to_owned()];
-    }
+    /// Start rendering at this position in the song, given as seconds or [hours:]minutes:seconds (such as 1:30)
+    #[clap(long, value_parser = parse_time)]
+    start: Option<f32>,
 
-    let max_depth = if !recurse { 1 } else { usize::MAX };
+    /// Stop rendering at this position in the song, given as seconds or [hours:]minutes:seconds (such as 2:45)
+    #[clap(long, value_parser = parse_time)]
+    end: Option<f32>,
 
-    let files: Vec<String> = WalkDir::new(path)
-        .max_depth(max_depth)
-        .into_iter()
-        .filter_map(|e| {
-            let file = e.unwrap();
-            let metadata = file.metadata().unwrap();
+    /// Pad or cut every stem of a song to the length of the full mix, so they line up sample for sample when dropped into a DAW
+    #[clap(long, default_value = "false")]
+    equal_length: bool,
 
-            if let Some(filename) = file.path().to_str() {
-                if metadata.is_file() {
-                    return Some(filename.to_owned());
-                }
-            }
-            None
-        })
-        .collect();
-    files
-}
+    /// Seed for the dither noise (and other random playback effects) so renders are bit-identical across runs and machines. Each stem gets its own noise derived from the seed so it doesn't add up when stems are summed
+    #[clap(long)]
+    dither_seed: Option<u32>,
 
-fn write_flac_file(
-    filename: &Path,
-    buffer: Vec<u8>,
-    sample_rate: u32,
-    channel_count: usize,
-    bytes_per_sample: usize,
-) {
-    let filename = PathBuf::from(filename).with_extension("flac");
+    /// Renders with a peak level below this (in dBFS) are considered silent and not written
+    #[clap(long, allow_hyphen_values(true), default_value = "-90", value_parser = parse_db)]
+    skip_silent_below: f32,
 
-    libflac_sys::encode_flac(
-        &filename,
-        &buffer,
-        channel_count as _,
+    /// Use noise shaping for the dither when reducing renders to 16-bit
+    #[clap(long, default_value = "false")]
+    noise_shaping: bool,
+
+    /// Longest song duration in seconds that will be rendered. Songs reporting a longer (or invalid) duration fall back to an estimated duration capped at this value
+    #[clap(long, default_value_t = stemgen_core::DEFAULT_MAX_SECONDS)]
+    max_duration: f32,
+
+    /// List the subsongs (with names and durations) of each input file and exit without rendering
+    #[clap(long, default_value = "false")]
+    list: bool,
+
+    /// Subsong to render, either the index (as shown by --list) or the name of the subsong
+    #[clap(long)]
+    subsong: Option<String>,
+
+    /// Render every subsong. Outputs get a _subsong_<index> suffix
+    #[clap(long, default_value = "false", conflicts_with = "subsong")]
+    all_subsongs: bool,
+
+    /// Write a <song>_manifest.json listing all generated (and skipped) outputs
+    #[clap(long, default_value = "false")]
+    manifest: bool,
+
+    /// After the run, write a CSV with the duration, peak and RMS level and status (such as skipped as silent) of every stem of all songs to this file
+    #[clap(long)]
+    level_report: Option<PathBuf>,
+
+    /// Write a <song>.log with the render parameters, timings and any warnings or errors for the song
+    #[clap(long, default_value = "false")]
+    song_log: bool,
+
+    /// Log of the song being rendered if --song-log is used
+    #[clap(skip)]
+    render_log: Option<Arc<SongLog>>,
+
+    /// Pack the stems of each song together with the manifest, an info text and the cover (if any) into <song>_stems.zip
+    #[clap(long, default_value = "false")]
+    bundle: bool,
+
+    /// Write an Ardour session (<song>_ardour/<song>.ardour) with the stems on named tracks. The stems are used where they are, so they have to be written as wav or FLAC
+    #[clap(long, default_value = "false")]
+    ardour: bool,
+
+    /// Write an Ableton Live set (<song>.als) with the stems on named tracks and the tempo of the song. The stems are used where they are, so they have to be written as wav or FLAC
+    #[clap(long, default_value = "false")]
+    ableton: bool,
+
+    /// Calculate a Chromaprint (AcoustID) fingerprint of the full mix and store it in the manifest. Requires --full
+    #[clap(long, default_value = "false")]
+    fingerprint: bool,
+
+    /// Measure the EBU R128 integrated loudness, loudness range and true peak of every stem and store them in the manifest and --level-report
+    #[clap(long, default_value = "false")]
+    ebu_r128: bool,
+
+    /// Estimate the musical key of the full mix and store it in its tags and the manifest. Requires --full
+    #[clap(long, default_value = "false")]
+    detect_key: bool,
+
+    /// Write a spectrogram of every rendered stem as a PNG next to it, to quickly see which stem contains what
+    #[clap(long, default_value = "false")]
+    spectrogram: bool,
+
+    /// Write an overview of the waveform of every rendered stem as <stem>_waveform.png next to it, with clipping shown in red
+    #[clap(long, default_value = "false")]
+    waveform_png: bool,
+
+    /// Store the SHA-256 of every written file (and the audio MD5 for FLAC files) in the manifest
+    #[clap(long, default_value = "false")]
+    checksums: bool,
+
+    /// Read the manifest of an earlier run and only write the outputs again that failed, are missing, empty or don't match their checksum. Implies --manifest
+    #[clap(long, default_value = "false")]
+    repair: bool,
+
+    /// Don't render stems whose output files (for all sample rates) already exist, so an interrupted batch can be resumed. Silent stems have no file so they are rendered again
+    #[clap(long, default_value = "false")]
+    skip_existing: bool,
+
+    /// Continue a batch that was stopped with Ctrl-C. Songs the interrupted run finished (listed in stemgen_resume.txt in the output directory) are skipped and --skip-existing is implied for the others
+    #[clap(long, default_value = "false", conflicts_with_all = ["dry_run", "stdout"])]
+    resume: bool,
+
+    /// Whether output files that already exist are replaced. Stems are only rendered if at least one of their files gets written
+    #[clap(long, default_value = "always")]
+    overwrite: Overwrite,
+
+    /// Number of songs rendered at the same time. All of them share the same worker threads. Defaults to the number of worker threads
+    #[clap(long)]
+    jobs: Option<usize>,
+
+    /// Print the files that would be generated for each song, with estimated sizes, without rendering or writing anything. Silent stems are listed as well
+    #[clap(
+        long,
+        default_value = "false",
+        conflicts_with_all = ["song_samples", "pattern_data", "tempo_map", "repair", "stdout"]
+    )]
+    dry_run: bool,
+
+    /// Modification time of the song being rendered, for --overwrite if-newer
+    #[clap(skip)]
+    source_modified: Option<SystemTime>,
+
+    /// Characters besides letters and digits that are kept when song names are turned into filenames. Others are replaced with _. If not given only characters that are invalid on common filesystems are replaced
+    #[clap(long)]
+    filename_chars: Option<String>,
+
+    /// Replace non-ASCII characters in filenames with ASCII approximations
+    #[clap(long, default_value = "false")]
+    transliterate: bool,
+
+    /// Max length in bytes of the song name part of filenames, 0 for no limit
+    #[clap(long, default_value = "128")]
+    filename_max_length: usize,
+
+    /// Case folding of filenames
+    #[clap(long, default_value = "keep")]
+    filename_case: FilenameCase,
+
+    /// Turn song and instrument names into lowercase ASCII with dashes between words, such as space-debris. Overrides the other filename options besides the max length
+    #[clap(long, default_value = "false", conflicts_with_all = ["filename_chars", "transliterate", "filename_case"])]
+    slug: bool,
+
+    /// Put the instrument name after the instrument number in stem filenames, such as song_0003_bassdrum_chan_0002. Instruments without a name only get the number
+    #[clap(long, default_value = "false")]
+    instrument_names: bool,
+
+    /// Only process one part of the input files, given as <index>/<count> (such as 2/8). Lets several machines split a large archive between them without coordination. Manifests are per song so the outputs can simply be copied together afterwards
+    #[clap(long)]
+    shard: Option<Shard>,
+
+    /// Embed the source module (with its hash and the command line used) in the full mix FLAC as an APPLICATION metadata block with id "STMG"
+    #[clap(long, default_value = "false")]
+    embed_source: bool,
+
+    /// Write a Broadcast Wave (BWF) bext chunk into wav outputs with the song title and stem as description, the source module as originator reference, the time of the render as origination and --start as time reference
+    #[clap(long, default_value = "false")]
+    bwf: bool,
+
+    /// Don't write tags (song title, artist, format, tracker, instrument, source file and for the full mix the song message and sample texts) into FLAC, Ogg Vorbis, mp3 and WavPack outputs
+    #[clap(long, default_value = "false")]
+    no_tags: bool,
+
+    /// Cover image embedded in FLAC, mp3 and Ogg Vorbis outputs and included in bundles. If this is a directory the image named after each song (<song>.png or <song>.jpg) is used
+    #[clap(long)]
+    cover: Option<PathBuf>,
+
+    /// File used to keep song info (durations, channel and instrument counts, subsongs) between runs so modules don't have to be scanned again
+    #[clap(long)]
+    info_cache: Option<PathBuf>,
+
+    /// Run with low CPU priority so long batches don't make the machine unusable
+    #[clap(long, default_value = "false")]
+    nice: bool,
+
+    /// Also lower the I/O priority when running with --nice
+    #[clap(long, default_value = "false")]
+    nice_io: bool,
+
+    /// Number of worker threads used for rendering. Defaults to the number of CPU cores
+    #[clap(long)]
+    threads: Option<usize>,
+
+    /// Upper limit in MB for the render buffers in flight. Fewer stems are rendered at the same time, and streamed stems use smaller chunks, to stay within it. The limit is shared between --jobs. Poly and NI Stems outputs always need all their channels in memory
+    #[clap(long, value_name = "MB")]
+    max_memory: Option<u64>,
+
+    /// Share of --max-memory in bytes for the song being rendered
+    #[clap(skip)]
+    song_memory: Option<u64>,
+
+    /// Estimate the output size of each song before rendering it and warn, or skip the song with abort, if it doesn't fit in the free space of the output directory
+    #[clap(long, default_value = "warn")]
+    disk_check: DiskCheck,
+}
+
+impl Args {
+    /// Patterns from --include, or the ones for all supported files. Amiga modules are usually
+    /// named with the format first (`mod.intro`) so the extensions are matched as prefix too
+    fn include_patterns(&self) -> Result<GlobSet> {
+        let patterns = match &self.include {
+            Some(patterns) => patterns.clone(),
+            None => supported_extensions()
+                .iter()
+                .flat_map(|e| [format!("*.{}", e), format!("{}.*", e)])
+                .collect(),
+        };
+
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            builder.add(GlobBuilder::new(&pattern).case_insensitive(true).build()?);
+        }
+
+        Ok(builder.build()?)
+    }
+
+    /// Patterns from --exclude
+    fn exclude_patterns(&self) -> Result<GlobSet> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in &self.exclude {
+            builder.add(GlobBuilder::new(pattern).case_insensitive(true).build()?);
+        }
+
+        Ok(builder.build()?)
+    }
+
+    /// Rate the song is rendered at. Other rates are resampled from this one
+    fn render_sample_rate(&self) -> u32 {
+        self.sample_rate.iter().copied().max().unwrap_or(48000)
+    }
+
+    /// Rates to write, each once. The resampled ones come first so the rendered buffer can be
+    /// handed over to the writer of the render rate, which is last
+    fn output_rates(&self) -> Vec<u32> {
+        let render_rate = self.render_sample_rate();
+        let mut rates = self.sample_rate.clone();
+        rates.sort_unstable();
+        rates.dedup();
+        rates.sort_by_key(|&rate| rate == render_rate);
+        rates
+    }
+
+    /// Backend for `song`, selected with --backend. SID tunes always use libsidplayfp and AHX and
+    /// HVL songs the HivelyTracker replayer
+    fn backend(&self, song: &[u8]) -> &'static dyn Backend {
+        #[cfg(feature = "sid")]
+        if sid::SidHeader::parse(song).is_some() {
+            return &sid::Sid;
+        }
+        #[cfg(feature = "hvl")]
+        if hvl::is_hvl(song) {
+            return &hvl::Hvl;
+        }
+        #[cfg(not(any(feature = "sid", feature = "hvl")))]
+        let _ = song;
+
+        match self.backend {
+            BackendKind::Openmpt => &OpenMpt,
+            #[cfg(feature = "xmp")]
+            BackendKind::Xmp => &xmp::Xmp,
+            // Rejected in main
+            #[cfg(not(feature = "xmp"))]
+            BackendKind::Xmp => &OpenMpt,
+        }
+    }
+
+    /// Number of channels of the full mix, stereo unless rendering surround
+    fn full_mix_channels(&self) -> usize {
+        self.surround.map_or(2, Surround::channel_count)
+    }
+
+    /// Speaker of each module channel with --surround
+    fn speaker_layout(&self, surround: Surround) -> Vec<Speaker> {
+        self.surround_layout
+            .clone()
+            .unwrap_or_else(|| surround.default_layout())
+    }
+
+    /// Dither for reducing the render of a stem to 16-bit. Seeded from --dither-seed so renders
+    /// stay reproducible, with the channel and instrument mixed in so every stem gets its own
+    /// noise
+    fn dither(&self, channel_count: usize, channel: i32, instrument: i32) -> dsp::Dither {
+        let seed = match self.dither_seed {
+            Some(seed) => dsp::stem_seed(seed as u64, channel, instrument),
+            None => std::collections::hash_map::RandomState::new()
+                .build_hasher()
+                .finish(),
+        };
+        dsp::Dither::new(seed, channel_count, self.noise_shaping)
+    }
+
+    /// Peak level (with full scale being 1.0) is below --skip-silent-below
+    fn is_silent(&self, peak: f32) -> bool {
+        peak <= 0.0 || 20.0 * peak.log10() < self.skip_silent_below
+    }
+
+    /// Sample format of wav outputs
+    fn wav_format(&self) -> WavFormat {
+        match self.format {
+            SampleDepth::Int16 => WavFormat::Pcm16,
+            SampleDepth::Int24 => WavFormat::Pcm24,
+            SampleDepth::Int32 => WavFormat::Pcm32,
+            SampleDepth::Float => WavFormat::Float32,
+        }
+    }
+
+    /// Encoder settings of FLAC outputs
+    fn flac_settings(&self) -> libflac_sys::EncoderSettings<'_> {
+        libflac_sys::EncoderSettings {
+            compression_level: self.flac_level,
+            verify: self.flac_verify,
+            blocksize: self.flac_blocksize.unwrap_or(0),
+            apodization: self.flac_apodization.as_deref(),
+        }
+    }
+
+    /// Reduce a float render of a stem to the output sample depth. Returns the buffer with its
+    /// bytes per sample. AAC is always encoded from 16-bit samples
+    fn to_output_depth(
+        &self,
+        buffer: Vec<u8>,
+        channel_count: usize,
+        channel: i32,
+        instrument: i32,
+    ) -> (Vec<u8>, usize) {
+        if self.format == SampleDepth::Int16 || self.write == WriteFormat::Aac {
+            let mut dither = self.dither(channel_count, channel, instrument);
+            (dither.quantize_in_place(buffer), 2)
+        } else {
+            (buffer, 4)
+        }
+    }
+
+    /// Stems are rendered in chunks that go straight to the encoder unless something needs the
+    /// whole render at once (resampling, filtering, normalizing, loudness measurements,
+    /// fingerprints, key detection, images of the render or a surround mix)
+    fn can_stream(&self, full_mix: bool) -> bool {
+        let render_rate = self.render_sample_rate();
+
+        matches!(self.write, WriteFormat::Flac | WriteFormat::Wav)
+            && self.sample_rate.iter().all(|&rate| rate == render_rate)
+            && self.lowpass.is_none()
+            && self.normalize.is_none()
+            && (self.no_tags || !self.replaygain)
+            && !self.spectrogram
+            && !self.ebu_r128
+            && !self.waveform_png
+            && !(full_mix && (self.fingerprint || self.detect_key || self.surround.is_some()))
+    }
+
+    /// Frames per chunk of a streamed render. A second unless --max-memory calls for less with
+    /// every worker thread streaming a stem
+    fn stream_chunk_frames(&self, frame_bytes: usize) -> usize {
+        let second = self.render_sample_rate() as usize;
+
+        match self.song_memory {
+            // The chunk and its copy at the output sample depth
+            Some(budget) => {
+                let per_thread = budget as usize / rayon::current_num_threads();
+                (per_thread / (frame_bytes * 2)).clamp(MIN_CHUNK_FRAMES, second)
+            }
+            None => second,
+        }
+    }
+
+    /// Bytes held while rendering a stem, for --max-memory. Streamed stems only hold a chunk and
+    /// other renders the whole song plus a copy for resampling or the output sample depth
+    fn stem_memory(&self, song_info: &SongInfo, channel_count: usize, full_mix: bool) -> u64 {
+        // Everything is rendered as float, the output depth is only applied when writing
+        let frame_bytes = std::mem::size_of::<f32>() * channel_count;
+
+        if self.can_stream(full_mix) {
+            return (self.stream_chunk_frames(frame_bytes) * frame_bytes * 2) as u64;
+        }
+
+        let seconds = estimated_seconds(song_info, self).unwrap_or(0.0);
+        (seconds * self.render_sample_rate() as f64 * frame_bytes as f64 * 2.0) as u64
+    }
+
+    /// Number of stems rendered at the same time so buffers of `stem_bytes` each stay within
+    /// --max-memory. At least one stem is always rendered
+    fn stems_in_flight(&self, stem_bytes: u64) -> usize {
+        match self.song_memory {
+            Some(budget) => (budget / stem_bytes.max(1)).max(1) as usize,
+            None => usize::MAX,
+        }
+    }
+
+    /// Rough size in bytes of one second of encoded output
+    fn estimated_bytes_per_second(&self, sample_rate: u32, channel_count: u32) -> f64 {
+        let raw = (sample_rate * channel_count * self.format.bytes()) as f64;
+
+        match self.write {
+            WriteFormat::Wav | WriteFormat::Aiff => raw,
+            // Tracker music usually compresses well but be on the safe side
+            WriteFormat::Flac => raw * 0.6,
+            WriteFormat::Vorbis => self.vorbis_bitrate as f64 * 1000.0 / 8.0,
+            // VBR can go up to the highest bitrate
+            WriteFormat::Mp3 if self.mp3_mode == Mp3Mode::Vbr => 320.0 * 1000.0 / 8.0,
+            WriteFormat::Mp3 => self.mp3_bitrate as f64 * 1000.0 / 8.0,
+            WriteFormat::Aac => self.aac_bitrate as f64 * 1000.0 / 8.0,
+            WriteFormat::Wavpack => match self.wavpack_hybrid {
+                // The lossy part plus the correction file is about the size of a lossless encode
+                Some(_) if !self.wavpack_no_correction => raw * 0.6,
+                Some(bitrate) => bitrate as f64 * 1000.0 / 8.0,
+                None => raw * 0.6,
+            },
+        }
+    }
+
+    fn sanitize_rules(&self) -> SanitizeRules {
+        SanitizeRules {
+            allowed: self.filename_chars.clone(),
+            transliterate: self.transliterate,
+            max_length: self.filename_max_length,
+            case: self.filename_case,
+            slug: self.slug,
+        }
+    }
+
+    /// Subfolder for outputs at the given rate. Only used when rendering several rates
+    fn rate_subdir(&self, sample_rate: u32) -> Option<String> {
+        if self.sample_rate.len() > 1 {
+            Some(sample_rate.to_string())
+        } else {
+            None
+        }
+    }
+
+    /// True if the existing file at `path` has to be kept because of --skip-existing or --overwrite.
+    /// Empty files are always replaced
+    fn keep_output(&self, path: &Path) -> bool {
+        let Ok(metadata) = std::fs::metadata(path) else {
+            return false;
+        };
+
+        if metadata.len() == 0 {
+            return false;
+        }
+
+        match self.overwrite {
+            _ if self.skip_existing => true,
+            Overwrite::Always => false,
+            Overwrite::Never => true,
+            // Songs without a time (such as downloads) are always considered newer
+            Overwrite::IfNewer => match (self.source_modified, metadata.modified()) {
+                (Some(source), Ok(output)) => output >= source,
+                _ => false,
+            },
+        }
+    }
+
+    /// Path of output `name` at the given rate, relative to the output directory
+    fn relative_output(&self, name: &str, sample_rate: u32) -> PathBuf {
+        match self.rate_subdir(sample_rate) {
+            Some(dir) => Path::new(&dir).join(name),
+            None => PathBuf::from(name),
+        }
+    }
+}
+
+/// Smallest chunk a streamed render is cut down to by --max-memory
+const MIN_CHUNK_FRAMES: usize = 1024;
+
+fn song_info_from_cached(info: &CachedInfo) -> SongInfo {
+    let mut channel_has_notes = [true; MAX_CHANNELS];
+    for &c in &info.empty_channels {
+        if let Some(has_notes) = channel_has_notes.get_mut(c as usize) {
+            *has_notes = false;
+        }
+    }
+
+    SongInfo {
+        channel_count: info.channel_count,
+        instrument_count: info.instrument_count,
+        duration_seconds: info.duration_seconds,
+        estimated_seconds: info.estimated_seconds,
+        channel_has_notes,
+    }
+}
+
+fn song_info_to_cached(info: &SongInfo, max_seconds: f32) -> CachedInfo {
+    CachedInfo {
+        channel_count: info.channel_count,
+        instrument_count: info.instrument_count,
+        duration_seconds: info.duration_seconds,
+        estimated_seconds: info.estimated_seconds,
+        max_seconds,
+        empty_channels: info.empty_channels(),
+    }
+}
+
+/// Quick check of the header so files that aren't songs are skipped without loading them
+fn probe_song(file_data: &[u8]) -> bool {
+    #[cfg(feature = "sid")]
+    if sid::SidHeader::parse(file_data).is_some() {
+        return true;
+    }
+
+    #[cfg(feature = "hvl")]
+    if hvl::is_hvl(file_data) {
+        return true;
+    }
+
+    stemgen_core::probe_song(file_data)
+}
+
+/// File extensions of everything that can be rendered, including archives and compressed songs
+fn supported_extensions() -> Vec<String> {
+    let mut extensions = stemgen_core::supported_extensions();
+
+    extensions.extend(input::EXTENSIONS.iter().map(|e| e.to_string()));
+
+    if cfg!(feature = "sid") {
+        extensions.push("sid".to_owned());
+    }
+
+    if cfg!(feature = "hvl") {
+        extensions.extend(["ahx".to_owned(), "hvl".to_owned()]);
+    }
+
+    extensions
+}
+
+fn get_song_info(
+    file_data: &[u8],
+    samples_output: Option<(&Path, SampleFormat)>,
+    max_seconds: f32,
+) -> SongInfo {
+    #[cfg(feature = "sid")]
+    if let Some(header) = sid::SidHeader::parse(file_data) {
+        return header.song_info();
+    }
+
+    #[cfg(feature = "hvl")]
+    if hvl::is_hvl(file_data) {
+        return hvl::song_info(file_data);
+    }
+
+    stemgen_core::song_info(file_data, samples_output, max_seconds)
+}
+
+/// Write the instrument and sample settings next to the samples from --song-samples
+fn write_song_instruments(file_data: &[u8], output: &str, stemname: &str) {
+    #[cfg(feature = "sid")]
+    if sid::SidHeader::parse(file_data).is_some() {
+        return;
+    }
+
+    #[cfg(feature = "hvl")]
+    if hvl::is_hvl(file_data) {
+        return;
+    }
+
+    let path = Path::new(output).join(format!("{}_instruments.json", stemname));
+    if let Err(e) = SongInstruments::read(file_data).write(&path) {
+        fail!("Unable to write {}: {}", path.display(), e);
+    }
+}
+
+/// Write the tempo map of the subsong for --tempo-map
+fn write_tempo_map(file_data: &[u8], subsong: i32, output: &str, stemname: &str) {
+    #[cfg(feature = "sid")]
+    if sid::SidHeader::parse(file_data).is_some() {
+        return;
+    }
+
+    #[cfg(feature = "hvl")]
+    if hvl::is_hvl(file_data) {
+        return;
+    }
+
+    let tempo_map = tempo::tempo_map(file_data, subsong);
+    let output = Path::new(output);
+
+    let path = output.join(format!("{}_tempo.json", stemname));
+    if let Err(e) = tempo::write_json(&path, &tempo_map) {
+        fail!("Unable to write {}: {}", path.display(), e);
+    }
+
+    let path = output.join(format!("{}_tempo.mid", stemname));
+    if let Err(e) = tempo::write_midi(&path, stemname, &tempo_map) {
+        fail!("Unable to write {}: {}", path.display(), e);
+    }
+}
+
+/// Write the patterns and order list for --pattern-data
+fn write_song_patterns(file_data: &[u8], output: &str, stemname: &str) {
+    #[cfg(feature = "sid")]
+    if sid::SidHeader::parse(file_data).is_some() {
+        return;
+    }
+
+    #[cfg(feature = "hvl")]
+    if hvl::is_hvl(file_data) {
+        return;
+    }
+
+    let path = Path::new(output).join(format!("{}_patterns.json", stemname));
+    if let Err(e) = SongPatterns::read(file_data).write(&path) {
+        fail!("Unable to write {}: {}", path.display(), e);
+    }
+}
+
+/// Same as `get_song_info` (without writing samples) but the result is cached by file hash.
+/// Entries estimated with another --max-duration are estimated again
+fn cached_song_info(
+    cache: &mut InfoCache,
+    key: &str,
+    file_data: &[u8],
+    max_seconds: f32,
+) -> SongInfo {
+    if let Some(info) = cache
+        .get(key)
+        .filter(|info| info.max_seconds == max_seconds)
+    {
+        return song_info_from_cached(info);
+    }
+
+    let song_info = get_song_info(file_data, None, max_seconds);
+    cache.insert(key, song_info_to_cached(&song_info, max_seconds));
+    song_info
+}
+
+/// Same as `get_subsongs` but the result is cached by file hash
+fn cached_subsongs(cache: &mut InfoCache, key: &str, file_data: &[u8]) -> Vec<Subsong> {
+    if let Some(subsongs) = cache.get_subsongs(key) {
+        return subsongs
+            .iter()
+            .enumerate()
+            .map(|(index, s)| Subsong {
+                index: index as i32,
+                name: s.name.clone(),
+                duration_seconds: s.duration_seconds,
+            })
+            .collect();
+    }
+
+    let subsongs = get_subsongs(file_data);
+    cache.insert_subsongs(
+        key,
+        subsongs
+            .iter()
+            .map(|s| CachedSubsong {
+                name: s.name.clone(),
+                duration_seconds: s.duration_seconds,
+            })
+            .collect(),
+    );
+    subsongs
+}
+
+fn get_song_metadata(file_data: &[u8], source: &str) -> SongMetadata {
+    #[cfg(feature = "sid")]
+    if let Some(header) = sid::SidHeader::parse(file_data) {
+        return header.metadata(source);
+    }
+
+    #[cfg(feature = "hvl")]
+    if hvl::is_hvl(file_data) {
+        return hvl::metadata(file_data, source);
+    }
+
+    let metadata = stemgen_core::song_metadata(file_data);
+
+    SongMetadata {
+        title: metadata.title,
+        format: metadata.format,
+        format_long: metadata.format_long,
+        tracker: metadata.tracker,
+        artist: metadata.artist,
+        message: metadata.message,
+        sample_text: metadata.sample_text,
+        instrument_names: metadata.instrument_names,
+        sample_names: metadata.sample_names,
+        source: source.to_owned(),
+        cover: None,
+    }
+}
+
+fn get_subsongs(file_data: &[u8]) -> Vec<Subsong> {
+    #[cfg(feature = "sid")]
+    if let Some(header) = sid::SidHeader::parse(file_data) {
+        return header.subsongs();
+    }
+
+    #[cfg(feature = "hvl")]
+    if hvl::is_hvl(file_data) {
+        return hvl::subsongs(file_data);
+    }
+
+    stemgen_core::subsongs(file_data)
+}
+
+/// Cue points at the time each order position of the subsong is first played
+fn get_order_cue_points(file_data: &[u8], subsong: i32) -> Vec<CuePoint> {
+    stemgen_core::order_times(file_data, subsong)
+        .iter()
+        .map(|time| CuePoint {
+            title: format!("Order {} (Pattern {})", time.order, time.pattern),
+            seconds: time.seconds,
+        })
+        .collect()
+}
+
+/// One pass over a song (a subsong or a part of it) with the name used for its outputs
+struct RenderTarget {
+    subsong: i32,
+    duration_seconds: f32,
+    name: String,
+    /// Only render these order positions
+    orders: Option<OrderRange>,
+}
+
+impl RenderTarget {
+    /// Stems of every target are named after the target so they don't overwrite each other
+    fn naming<'a>(&'a self, sanitize_rules: &'a SanitizeRules) -> StemNaming<'a> {
+        StemNaming {
+            filestem: &self.name,
+            sanitize_rules,
+        }
+    }
+}
+
+/// A target for every subsong, rendered on its own
+fn subsong_targets(stemname: &str, subsongs: &[Subsong]) -> Vec<RenderTarget> {
+    subsongs
+        .iter()
+        .map(|s| RenderTarget {
+            subsong: s.index,
+            duration_seconds: s.duration_seconds,
+            name: format!("{}_subsong_{:02}", stemname, s.index),
+            orders: None,
+        })
+        .collect()
+}
+
+/// A target for every pattern in the order list, rendered from where it's first played
+fn pattern_targets(
+    stemname: &str,
+    song_info: &SongInfo,
+    song: &[u8],
+    subsong: i32,
+) -> Vec<RenderTarget> {
+    let mut patterns = Vec::new();
+
+    stemgen_core::order_times(song, subsong)
+        .into_iter()
+        .filter(|time| {
+            let first = !patterns.contains(&time.pattern);
+            patterns.push(time.pattern);
+            first
+        })
+        .map(|time| RenderTarget {
+            subsong,
+            duration_seconds: song_info.duration_seconds,
+            name: format!("{}_pattern_{:03}", stemname, time.pattern),
+            orders: Some(OrderRange {
+                first: time.order,
+                last: Some(time.order),
+            }),
+        })
+        .collect()
+}
+
+/// Start and end time of an order range. The range starts when its first order is played and
+/// ends when an order outside of it is played after that
+fn order_range_times(
+    file_data: &[u8],
+    subsong: i32,
+    range: OrderRange,
+) -> Option<(f32, Option<f32>)> {
+    let times = stemgen_core::order_times(file_data, subsong);
+    let start = times.iter().find(|t| t.order == range.first)?.seconds;
+    let last = range.last.unwrap_or(u32::MAX);
+
+    let end = times
+        .iter()
+        .find(|t| t.seconds > start && (t.order < range.first || t.order > last))
+        .map(|t| t.seconds);
+
+    Some((start, end))
+}
+
+/// Cue points at the start of each subsong when they are played back to back
+fn subsong_cue_points(subsongs: &[Subsong]) -> Vec<CuePoint> {
+    let mut seconds = 0.0;
+
+    subsongs
+        .iter()
+        .map(|subsong| {
+            let title = if subsong.name.is_empty() {
+                format!("Subsong {}", subsong.index)
+            } else {
+                subsong.name.clone()
+            };
+            let point = CuePoint { title, seconds };
+            seconds += subsong.duration_seconds;
+            point
+        })
+        .collect()
+}
+
+/// Write a cue sheet next to each written full mix. Points are moved to match --start and --end
+fn write_cue_sheets(entries: &[OutputEntry], points: &[CuePoint], title: &str, args: &Args) {
+    let start = args.start.unwrap_or(0.0);
+    // The track playing at the start time becomes the first track
+    let first = points.iter().rposition(|p| p.seconds <= start).unwrap_or(0);
+    let points: Vec<CuePoint> = points[first..]
+        .iter()
+        .filter(|p| args.end.is_none_or(|end| p.seconds < end))
+        .map(|p| CuePoint {
+            title: p.title.clone(),
+            seconds: (p.seconds - start).max(0.0),
+        })
+        .collect();
+
+    let file_type = match args.write {
+        WriteFormat::Mp3 => "MP3",
+        WriteFormat::Aiff => "AIFF",
+        _ => "WAVE",
+    };
+
+    for entry in entries.iter().filter(|e| e.status == OutputStatus::Written) {
+        let audio_path = Path::new(&args.output).join(&entry.filename);
+        let cue_path = audio_path.with_extension("cue");
+        let audio_filename = audio_path.file_name().unwrap_or_default().to_string_lossy();
+
+        if let Err(e) = File::create(&cue_path).and_then(|file| {
+            cue::write_cue(
+                BufWriter::new(file),
+                &audio_filename,
+                file_type,
+                title,
+                &points,
+            )
+        }) {
+            fail!("Unable to write cue sheet {:?} error: {:?}", cue_path, e);
+        }
+    }
+}
+
+/// Find a subsong either by index or by name. Names are matched case insensitive, first exactly
+/// and then as a substring if only one subsong contains it
+fn find_subsong<'a>(subsongs: &'a [Subsong], selection: &str) -> Option<&'a Subsong> {
+    if let Ok(index) = selection.parse::<usize>() {
+        return subsongs.get(index);
+    }
+
+    let selection = selection.to_lowercase();
+
+    if let Some(subsong) = subsongs.iter().find(|s| s.name.to_lowercase() == selection) {
+        return Some(subsong);
+    }
+
+    let mut matches = subsongs
+        .iter()
+        .filter(|s| s.name.to_lowercase().contains(&selection));
+
+    match (matches.next(), matches.next()) {
+        (Some(subsong), None) => Some(subsong),
+        _ => None,
+    }
+}
+
+fn format_duration(seconds: f32) -> String {
+    let seconds = seconds.max(0.0) as u32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+fn print_subsongs(filename: &str, subsongs: &[Subsong]) {
+    println!("{}", filename);
+
+    for subsong in subsongs {
+        let name = if subsong.name.is_empty() {
+            "(unnamed)"
+        } else {
+            &subsong.name
+        };
+
+        println!(
+            "  {:3}: {} ({})",
+            subsong.index,
+            name,
+            format_duration(subsong.duration_seconds)
+        );
+    }
+}
+
+/// Print what is known about each song without rendering, for `stemgen info`
+fn print_info(
+    input: &str,
+    recursive: bool,
+    json: bool,
+    args: &Args,
+    cache: &mut InfoCache,
+) -> Result<()> {
+    for InputSong {
+        filename,
+        data: song_buffer,
+        ..
+    } in get_files(
+        input,
+        recursive,
+        &args.include_patterns()?,
+        &args.exclude_patterns()?,
+    )
+    .iter()
+    .flat_map(|filename| {
+        input::read(filename).unwrap_or_else(|e| {
+            fail!("Unable to read {} error: {:?}", filename, e);
+            Vec::new()
+        })
+    }) {
+        if !probe_song(&song_buffer) {
+            fail!("{} isn't a supported song so is being skipped", filename);
+            continue;
+        }
+
+        let cache_key = InfoCache::key(&song_buffer);
+
+        let song_info = cached_song_info(cache, &cache_key, &song_buffer, args.max_duration);
+        let subsongs = cached_subsongs(cache, &cache_key, &song_buffer);
+        let metadata = get_song_metadata(&song_buffer, &filename);
+
+        let report = SongReport {
+            source: filename.clone(),
+            title: metadata.title,
+            format: metadata.format,
+            format_long: metadata.format_long,
+            tracker: metadata.tracker,
+            duration_seconds: song_info.checked_duration(args.max_duration).unwrap_or(0.0),
+            channel_count: song_info.channel_count,
+            empty_channels: song_info.empty_channels(),
+            instrument_count: song_info.instrument_count,
+            instrument_names: metadata.instrument_names,
+            sample_names: metadata.sample_names,
+            subsongs: subsongs
+                .into_iter()
+                .map(|s| SubsongReport {
+                    index: s.index,
+                    name: s.name,
+                    duration_seconds: s.duration_seconds,
+                })
+                .collect(),
+        };
+
+        if json {
+            println!("{}", report.to_json()?);
+        } else {
+            println!("{}", report.to_text());
+        }
+    }
+
+    Ok(())
+}
+
+/// Human readable summary of a song used for the info text in bundles
+fn song_info_text(filename: &str, song_info: &SongInfo, subsongs: &[Subsong]) -> String {
+    let mut text = String::new();
+
+    text += &format!("Source: {}\n", filename);
+    text += &format!(
+        "Duration: {}\n",
+        format_duration(song_info.duration_seconds)
+    );
+    text += &format!("Channels: {}\n", song_info.channel_count);
+    text += &format!("Instruments: {}\n", song_info.instrument_count);
+
+    if subsongs.len() > 1 {
+        text += "Subsongs:\n";
+        for subsong in subsongs {
+            text += &format!(
+                "  {}: {} ({})\n",
+                subsong.index,
+                subsong.name,
+                format_duration(subsong.duration_seconds)
+            );
+        }
+    }
+
+    text
+}
+
+impl RenderStream for SongStream {
+    fn read(&mut self, output: &mut [u8]) -> usize {
+        SongStream::read(self, output)
+    }
+
+    fn frame_count(&self) -> Option<u64> {
+        SongStream::frame_count(self)
+    }
+}
+
+/// Length of the rendered audio, including repeats and the fade out
+fn estimated_seconds(song_info: &SongInfo, args: &Args) -> Option<f64> {
+    let duration = song_info.checked_duration(args.max_duration)?;
+    let passes = args.at_end.repeat_count().max(0) as f32 + 1.0;
+    Some((duration * passes + args.at_end.fade_seconds()) as f64)
+}
+
+/// Upper bound of the number of bytes written for a song. Silent stems are never written so the
+/// real size is usually smaller, especially with --channels
+fn estimate_output_bytes(song_info: &SongInfo, args: &Args) -> u64 {
+    let Some(seconds) = estimated_seconds(song_info, args) else {
+        return 0;
+    };
+    let stem_channels = if args.stereo { 2 } else { 1 };
+    let used_channels = song_info.channel_count - song_info.empty_channels().len() as u32;
+
+    let mut bytes = 0.0;
+
+    for rate in args.output_rates() {
+        let stem_bytes = seconds * args.estimated_bytes_per_second(rate, stem_channels);
+
+        if args.full {
+            bytes += seconds * args.estimated_bytes_per_second(rate, args.full_mix_channels() as _);
+        }
+
+        if args.poly {
+            // Poly files are always wav
+            bytes += seconds * (rate * song_info.channel_count * args.format.bytes()) as f64;
+        }
+
+        if args.ni_stems && rate == args.render_sample_rate() {
+            // Full mix and four stems, all stereo AAC
+            bytes += seconds * 5.0 * (args.aac_bitrate * 1000 / 8) as f64;
+        }
+
+        if args.channels {
+            bytes += stem_bytes * (used_channels * song_info.instrument_count) as f64;
+        } else if args.instruments {
+            bytes += stem_bytes * song_info.instrument_count as f64;
+        }
+    }
+
+    bytes as u64
+}
+
+/// Compare the estimated size of the outputs of a song with the free space of the output volume
+/// right before it's rendered, so the songs don't have to be loaded twice. Returns false if the
+/// song should be skipped
+fn check_disk_space(song_info: &SongInfo, args: &Args) -> bool {
+    if args.disk_check == DiskCheck::Off || args.dry_run {
+        return true;
+    }
+
+    let estimated = estimate_output_bytes(song_info, args);
+
+    // The output directory may not exist yet so check the closest existing parent
+    let output = Path::new(&args.output);
+    let existing = output
+        .ancestors()
+        .find(|p| p.exists())
+        .unwrap_or_else(|| Path::new("."));
+
+    let available = match fs2::available_space(existing) {
+        Ok(available) => available,
+        Err(e) => {
+            log::error!("Unable to get free space for {:?} error: {:?}", existing, e);
+            return true;
+        }
+    };
+
+    if estimated <= available {
+        return true;
+    }
+
+    log::error!(
+        "Estimated output size is {:.0} MB but only {:.0} MB is free on the output volume",
+        estimated as f64 / MB,
+        available as f64 / MB
+    );
+
+    args.disk_check != DiskCheck::Abort
+}
+
+/// Files to render. Files in directories have to match `include` and not `exclude` (which also
+/// skips whole directories), a single file is always used
+fn get_files(path: &str, recurse: bool, include: &GlobSet, exclude: &GlobSet) -> Vec<String> {
+    if input::is_url(path) || path == input::STDIN {
+        return vec![path.to_owned()];
+    }
+
+    if !Path::new(path).exists() {
+        log::info!(
+            "Path/File \"{}\" doesn't exist. No file(s) will be processed.",
+            path
+        );
+        return Vec::new();
+    }
+
+    // Check if "path" is a single file
+    let md = std::fs::metadata(path).unwrap();
+
+    if md.is_file() {
+        return vec![path.to_owned()];
+    }
+
+    let max_depth = if !recurse { 1 } else { usize::MAX };
+
+    let files: Vec<String> = WalkDir::new(path)
+        .max_depth(max_depth)
+        .into_iter()
+        .filter_entry(|e| {
+            let relative = e.path().strip_prefix(path).unwrap_or(e.path());
+            e.depth() == 0 || !(exclude.is_match(e.file_name()) || exclude.is_match(relative))
+        })
+        .filter_map(|e| {
+            let file = e.unwrap();
+            let metadata = file.metadata().unwrap();
+
+            if let Some(filename) = file.path().to_str() {
+                if metadata.is_file() && include.is_match(file.file_name()) {
+                    return Some(filename.to_owned());
+                }
+            }
+            None
+        })
+        .collect();
+    files
+}
+
+/// Id of the FLAC APPLICATION block holding the source module
+const EMBEDDED_SOURCE_ID: [u8; 4] = *b"STMG";
+
+/// Payload of the embedded source block: a little endian u32 with the size of a JSON header
+/// (source name, SHA-256 of the module, stemgen version and the command line used) followed by
+/// the JSON and then the unmodified module data
+fn embedded_source_block(song: &[u8], source_name: &str) -> Option<Vec<u8>> {
+    // Metadata block sizes are stored in 24 bits
+    const MAX_BLOCK_SIZE: usize = (1 << 24) - 1;
+
+    let header = serde_json::json!({
+        "source": source_name,
+        "sha256": checksum::to_hex(&Sha256::digest(song)),
+        "stemgen_version": env!("CARGO_PKG_VERSION"),
+        "command_line": std::env::args().collect::<Vec<_>>(),
+    })
+    .to_string();
+
+    // The block also starts with the 4 byte application id
+    let size = 4 + 4 + header.len() + song.len();
+    if size > MAX_BLOCK_SIZE {
+        log::error!(
+            "{} is too large ({} bytes) to embed in a FLAC file. Skipping",
+            source_name,
+            song.len()
+        );
+        return None;
+    }
+
+    let mut data = Vec::with_capacity(size);
+    data.extend_from_slice(&(header.len() as u32).to_le_bytes());
+    data.extend_from_slice(header.as_bytes());
+    data.extend_from_slice(song);
+    Some(data)
+}
+
+/// True if the path is an existing named pipe. These can't seek so writers have to stream to them
+#[cfg(unix)]
+fn is_fifo(path: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    std::fs::metadata(path)
+        .map(|m| m.file_type().is_fifo())
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_fifo(_path: &Path) -> bool {
+    false
+}
+
+/// Metadata stored in an output file besides the audio. Formats that can't hold some of it
+/// ignore those parts
+#[derive(Default)]
+struct FileMetadata<'a> {
+    tags: Option<&'a Tags>,
+    cover: Option<&'a Cover>,
+    /// Payload of the embedded source APPLICATION block (FLAC only)
+    embedded_source: Option<&'a [u8]>,
+    /// Broadcast Wave chunk (wav only)
+    bext: Option<&'a Bext>,
+}
+
+/// Metadata blocks of a FLAC file. `comments` are the vorbis comments of the tags
+fn flac_metadata<'a>(
+    file_metadata: &FileMetadata<'a>,
+    comments: &'a [(String, String)],
+) -> libflac_sys::StreamMetadata<'a> {
+    libflac_sys::StreamMetadata {
+        application: file_metadata
+            .embedded_source
+            .map(|data| libflac_sys::ApplicationBlock {
+                id: EMBEDDED_SOURCE_ID,
+                data,
+            }),
+        picture: file_metadata.cover.map(|cover| libflac_sys::Picture {
+            mime_type: cover.mime_type,
+            width: cover.width,
+            height: cover.height,
+            data: &cover.data,
+        }),
+        comments,
+    }
+}
+
+fn write_flac_file(
+    filename: &Path,
+    buffer: Vec<u8>,
+    sample_rate: u32,
+    channel_count: usize,
+    bytes_per_sample: usize,
+    file_metadata: &FileMetadata,
+    settings: &libflac_sys::EncoderSettings,
+) -> bool {
+    let comments = file_metadata
+        .tags
+        .map(|tags| tags.vorbis_comments())
+        .unwrap_or_default();
+    let stream_metadata = flac_metadata(file_metadata, &comments);
+
+    // libFLAC seeks back to update the header when writing to a file, which doesn't work for pipes
+    if is_fifo(filename) {
+        let out_file = match File::create(filename) {
+            Ok(f) => f,
+            Err(e) => {
+                fail!("Unable to write to {:?} error: {:?}", filename, e);
+                return false;
+            }
+        };
+
+        if let Err(e) = libflac_sys::encode_flac_to_writer(
+            &mut BufWriter::new(out_file),
+            &buffer,
+            channel_count as _,
+            bytes_per_sample as _,
+            sample_rate as _,
+            &stream_metadata,
+            settings,
+        ) {
+            fail!("Unable to encode {:?}: {}", filename, e);
+            return false;
+        }
+        return true;
+    }
+
+    if let Err(e) = libflac_sys::encode_flac(
+        filename,
+        &buffer,
+        channel_count as _,
         bytes_per_sample as _,
         sample_rate as _,
+        &stream_metadata,
+        settings,
+    ) {
+        fail!("Unable to encode {:?}: {}", filename, e);
+        return false;
+    }
+
+    true
+}
+
+fn write_wav_file(
+    filename: &Path,
+    buffer: Vec<u8>,
+    sample_rate: u32,
+    channel_count: usize,
+    bytes_per_sample: usize,
+    format: WavFormat,
+    bext: Option<&Bext>,
+) -> bool {
+    let out_file = match File::create(filename) {
+        Ok(f) => f,
+        Err(e) => {
+            fail!("Unable to write to {:?} error: {:?}", filename, e);
+            return false;
+        }
+    };
+
+    // The length is known so the header is written with the sizes filled in, which also works
+    // for pipes. Float renders are packed to the integer format by the encoder, 16-bit samples
+    // are already quantized
+    let writer = BufWriter::new(out_file);
+    let res = if bytes_per_sample == 4 {
+        let format = match format {
+            WavFormat::Pcm24 | WavFormat::Pcm32 => format,
+            _ => WavFormat::Float32,
+        };
+        AudioBuffer::from_bytes(buffer, channel_count, sample_rate).encode_wav(writer, format, bext)
+    } else {
+        wav_writer::write_wav(
+            writer,
+            &buffer,
+            channel_count,
+            sample_rate,
+            WavFormat::Pcm16,
+            bext,
+        )
+        .map_err(Into::into)
+    };
+
+    if let Err(e) = res {
+        fail!("Unable to write to {:?} error: {:?}", filename, e);
+        return false;
+    }
+
+    true
+}
+
+/// Lossy WavPack settings
+struct WavpackHybrid<'a> {
+    bitrate_kbps: u32,
+    /// Where the correction file goes, if one is written
+    correction: Option<&'a Path>,
+}
+
+fn write_wavpack(
+    filename: &Path,
+    buffer: Vec<u8>,
+    hybrid: Option<WavpackHybrid>,
+    sample_rate: u32,
+    channel_count: usize,
+    bytes_per_sample: usize,
+    tags: Option<&Tags>,
+) -> bool {
+    let create = |filename: &Path| match File::create(filename) {
+        Ok(f) => Some(BufWriter::new(f)),
+        Err(e) => {
+            fail!("Unable to write to {:?} error: {:?}", filename, e);
+            None
+        }
+    };
+
+    let Some(mut out_file) = create(filename) else {
+        return false;
+    };
+
+    let mut correction_file = match hybrid.as_ref().and_then(|hybrid| hybrid.correction) {
+        Some(correction) => {
+            let Some(file) = create(correction) else {
+                return false;
+            };
+            Some(file)
+        }
+        None => None,
+    };
+
+    let hybrid = hybrid.map(|hybrid| wavpack_sys::Hybrid {
+        bitrate_kbps: hybrid.bitrate_kbps as f32,
+        correction: correction_file.as_mut().map(|f| f as &mut dyn Write),
+    });
+
+    let res = wavpack_sys::encode_wavpack(
+        &mut out_file,
+        &buffer,
+        channel_count as _,
+        bytes_per_sample as _,
+        sample_rate,
+        hybrid,
+        &tags.map(|tags| tags.vorbis_comments()).unwrap_or_default(),
+    )
+    .and_then(|()| out_file.flush().map_err(|e| e.to_string()))
+    .and_then(|()| match correction_file.as_mut() {
+        Some(f) => f.flush().map_err(|e| e.to_string()),
+        None => Ok(()),
+    });
+
+    if let Err(e) = res {
+        fail!("Unable to write {:?} error: {}", filename, e);
+        return false;
+    }
+
+    true
+}
+
+fn write_aiff_file(
+    filename: &Path,
+    buffer: Vec<u8>,
+    sample_rate: u32,
+    channel_count: usize,
+    bytes_per_sample: usize,
+) -> bool {
+    let out_file = match File::create(filename) {
+        Ok(f) => f,
+        Err(e) => {
+            fail!("Unable to write to {:?} error: {:?}", filename, e);
+            return false;
+        }
+    };
+
+    if let Err(e) = aiff::write_aiff(
+        BufWriter::new(out_file),
+        &buffer,
+        channel_count,
+        sample_rate,
+        bytes_per_sample,
+    ) {
+        fail!("Unable to write to {:?} error: {:?}", filename, e);
+        return false;
+    }
+
+    true
+}
+
+fn write_ogg_vorbis(
+    filename: &Path,
+    buffer: Vec<u8>,
+    args: &Args,
+    sample_rate: u32,
+    channel_count: usize,
+    file_metadata: &FileMetadata,
+) -> bool {
+    let mut out_file = match File::create(filename) {
+        Ok(f) => f,
+        Err(e) => {
+            fail!("Unable to write to {:?} error: {:?}", filename, e);
+            return false;
+        }
+    };
+
+    let br = core::num::NonZeroU32::new((args.vorbis_bitrate * 1000) as _).unwrap();
+    let target_quality = f32::clamp(args.vorbis_quality, -0.2, 1.0);
+
+    let bitrate_mode = match args.vorbis_mode {
+        OggMode::Vbr => VorbisBitrateManagementStrategy::Vbr { target_bitrate: br },
+        OggMode::Abr => VorbisBitrateManagementStrategy::Abr {
+            average_bitrate: br,
+        },
+        OggMode::ConstrainedAbr => VorbisBitrateManagementStrategy::ConstrainedAbr {
+            maximum_bitrate: br,
+        },
+        OggMode::QualityVbr => VorbisBitrateManagementStrategy::QualityVbr { target_quality },
+    };
+
+    let mut builder = VorbisEncoderBuilder::new(
+        core::num::NonZeroU32::new(sample_rate as _).unwrap(),
+        core::num::NonZeroU8::new(channel_count as _).unwrap(),
+        &mut out_file,
+    )
+    .unwrap();
+
+    builder.bitrate_management_strategy(bitrate_mode);
+
+    // Comments that can't be stored (a value with a NUL byte) are skipped like for FLAC rather
+    // than failing the whole file
+    if let Some(tags) = file_metadata.tags {
+        for (name, value) in tags.vorbis_comments() {
+            let _ = builder.comment_tag(name, value);
+        }
+    }
+
+    if let Some(cover) = file_metadata.cover {
+        let _ = builder.comment_tag("METADATA_BLOCK_PICTURE", cover.metadata_block_picture());
+    }
+
+    let mut encoder = match builder.build() {
+        Ok(encoder) => encoder,
+        Err(e) => {
+            fail!(
+                "Unable to create vorbis encoder for {:?}: {:?}",
+                filename,
+                e
+            );
+            return false;
+        }
+    };
+
+    if channel_count == 1 {
+        let data: &[f32] = bytemuck::cast_slice(&buffer);
+
+        let sample_step = 48000;
+        let len = data.len();
+        let mut offset = 0;
+
+        loop {
+            let step_value = std::cmp::min(sample_step, len - offset);
+
+            let t = [&data[offset..offset + step_value]];
+
+            match encoder.encode_audio_block(t) {
+                Ok(_) => (),
+                Err(e) => {
+                    fail!("Unable to encode vorbis file: {:?}", e);
+                    return false;
+                }
+            }
+
+            if step_value != sample_step {
+                break;
+            }
+
+            offset += step_value;
+        }
+    } else {
+        let data: &[f32] = bytemuck::cast_slice(&buffer);
+        let channel0: Vec<f32> = data.iter().step_by(2).copied().collect();
+        let channel1: Vec<f32> = data.iter().skip(1).step_by(2).copied().collect();
+
+        let sample_step = 48000;
+        let len = channel0.len();
+        let mut offset = 0;
+
+        loop {
+            let step_value = std::cmp::min(sample_step, len - offset);
+
+            let t = [
+                &channel0[offset..offset + step_value],
+                &channel1[offset..offset + step_value],
+            ];
+
+            match encoder.encode_audio_block(t) {
+                Ok(_) => (),
+                Err(e) => {
+                    fail!("Unable to encode vorbis file: {:?}", e);
+                    return false;
+                }
+            }
+
+            if step_value != sample_step {
+                break;
+            }
+
+            offset += step_value;
+        }
+    }
+
+    match encoder.finish() {
+        Ok(_) => true,
+        Err(e) => {
+            fail!("Unable to finish vorbis file: {:?}", e);
+            false
+        }
+    }
+}
+
+fn write_mp3(
+    filename: &Path,
+    buffer: Vec<u8>,
+    args: &Args,
+    sample_rate: u32,
+    channel_count: usize,
+    bytes_per_sample: usize,
+    file_metadata: &FileMetadata,
+) -> bool {
+    let mut out_file = match File::create(filename) {
+        Ok(f) => f,
+        Err(e) => {
+            fail!("Unable to write to {:?} error: {:?}", filename, e);
+            return false;
+        }
+    };
+
+    // ID3v2 goes in front of the audio
+    let mut tag = file_metadata.tags.map(|tags| tags.id3());
+
+    if let Some(cover) = file_metadata.cover {
+        cover.add_to_id3(tag.get_or_insert_with(id3::Tag::new));
+    }
+
+    if let Some(tag) = tag {
+        if let Err(e) = tag.write_to(&mut out_file, id3::Version::Id3v24) {
+            fail!("Unable to write tags to {:?} error: {:?}", filename, e);
+            return false;
+        }
+    }
+
+    // Checked by parse_mp3_bitrate
+    let bitrate = mp3_bitrate(args.mp3_bitrate).expect("Invalid bitrate for mp3");
+
+    let mut mp3_encoder = Builder::new().expect("Create LAME builder");
+    mp3_encoder
+        .set_num_channels(channel_count as _)
+        .expect("set channels");
+    mp3_encoder
+        .set_sample_rate(sample_rate as _)
+        .expect("set sample rate");
+    mp3_encoder.set_brate(bitrate).expect("set brate");
+    mp3_encoder
+        .set_quality(args.mp3_quality.into())
+        .expect("set quality");
+    mp3_encoder.set_to_write_vbr_tag(true).expect("set vbr tag");
+
+    match args.mp3_mode {
+        Mp3Mode::Cbr => {
+            mp3_encoder
+                .set_vbr_mode(mp3lame_encoder::VbrMode::Off)
+                .expect("set vbr mode");
+        }
+        Mp3Mode::Abr => {
+            mp3_encoder
+                .set_vbr_mode(mp3lame_encoder::VbrMode::Abr)
+                .expect("set vbr mode");
+        }
+        Mp3Mode::Vbr => {
+            mp3_encoder
+                .set_vbr_mode(mp3lame_encoder::VbrMode::Mtrh)
+                .expect("set vbr mode");
+            mp3_encoder
+                .set_vbr_quality(args.mp3_vbr_level.into())
+                .expect("set vbr quality");
+        }
+    }
+    let mut mp3_encoder = mp3_encoder.build().expect("To initialize LAME encoder");
+
+    let mut mp3_out_buffer = Vec::new();
+    let encoded_size;
+
+    if channel_count == 2 {
+        if bytes_per_sample == 2 {
+            let data: &[i16] = bytemuck::cast_slice(&buffer);
+            let input = InterleavedPcm(data);
+
+            mp3_out_buffer.reserve(mp3lame_encoder::max_required_buffer_size(data.len() / 2));
+            encoded_size = mp3_encoder
+                .encode(input, mp3_out_buffer.spare_capacity_mut())
+                .expect("To encode");
+        } else {
+            let data: &[f32] = bytemuck::cast_slice(&buffer);
+            let input = InterleavedPcm(data);
+
+            mp3_out_buffer.reserve(mp3lame_encoder::max_required_buffer_size(data.len() / 2));
+            encoded_size = mp3_encoder
+                .encode(input, mp3_out_buffer.spare_capacity_mut())
+                .expect("To encode");
+        }
+    } else {
+        if bytes_per_sample == 2 {
+            let data: &[i16] = bytemuck::cast_slice(&buffer);
+            let input = MonoPcm(data);
+
+            mp3_out_buffer.reserve(mp3lame_encoder::max_required_buffer_size(data.len()));
+            encoded_size = mp3_encoder
+                .encode(input, mp3_out_buffer.spare_capacity_mut())
+                .expect("To encode");
+        } else {
+            let data: &[f32] = bytemuck::cast_slice(&buffer);
+            let input = MonoPcm(data);
+
+            mp3_out_buffer.reserve(mp3lame_encoder::max_required_buffer_size(data.len()));
+            encoded_size = mp3_encoder
+                .encode(input, mp3_out_buffer.spare_capacity_mut())
+                .expect("To encode");
+        }
+    }
+
+    unsafe {
+        mp3_out_buffer.set_len(mp3_out_buffer.len().wrapping_add(encoded_size));
+    }
+
+    let encoded_size = mp3_encoder
+        .flush::<FlushNoGap>(mp3_out_buffer.spare_capacity_mut())
+        .expect("to flush");
+    unsafe {
+        mp3_out_buffer.set_len(mp3_out_buffer.len().wrapping_add(encoded_size));
+    }
+
+    if let Err(e) = out_file.write_all(&mp3_out_buffer) {
+        fail!("Unable to write to {:?} error: {:?}", filename, e);
+        return false;
+    }
+
+    true
+}
+
+fn write_aac(
+    filename: &Path,
+    buffer: Vec<u8>,
+    args: &Args,
+    sample_rate: u32,
+    channel_count: usize,
+) -> bool {
+    // Already dithered to 16-bit by Args::to_output_depth
+    let samples: &[i16] = bytemuck::cast_slice(&buffer);
+
+    let out_file = match File::create(filename) {
+        Ok(f) => f,
+        Err(e) => {
+            fail!("Unable to write to {:?} error: {:?}", filename, e);
+            return false;
+        }
+    };
+
+    let res = aac::write_m4a(
+        BufWriter::new(out_file),
+        samples,
+        channel_count,
+        sample_rate,
+        args.aac_bitrate,
+    );
+
+    if let Err(e) = res {
+        fail!("Unable to write {:?} error: {:?}", filename, e);
+        return false;
+    }
+
+    true
+}
+
+/// Where a file is written before it's renamed to `path` when finished
+fn part_path(path: &Path) -> PathBuf {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".part");
+    PathBuf::from(partial)
+}
+
+/// Write a file as `<path>.part` and rename it to `path` once `write` succeeds, so a run that is
+/// killed never leaves a truncated file under a name that --skip-existing would keep. Pipes are
+/// written directly
+fn write_staged(path: &Path, write: impl FnOnce(&Path) -> bool) -> bool {
+    if is_fifo(path) {
+        return write(path);
+    }
+
+    let partial = part_path(path);
+    let _partial_file = interrupt::PartialFile::new(&partial);
+
+    if !write(&partial) {
+        let _ = std::fs::remove_file(&partial);
+        return false;
+    }
+
+    if let Err(e) = std::fs::rename(&partial, path) {
+        fail!(
+            "Unable to rename {:?} to {:?} error: {:?}",
+            partial,
+            path,
+            e
+        );
+        let _ = std::fs::remove_file(&partial);
+        return false;
+    }
+
+    true
+}
+
+/// Write a render to `filename` with the extension of the output format. Returns false if it
+/// failed (the error has been reported)
+fn write_output(
+    filename: &Path,
+    buffer: Vec<u8>,
+    args: &Args,
+    sample_rate: u32,
+    channel_count: usize,
+    bytes_per_sample: usize,
+    file_metadata: &FileMetadata,
+) -> bool {
+    let filename = filename.with_extension(args.write.extension());
+
+    match args.write {
+        WriteFormat::Flac => write_staged(&filename, |path| {
+            write_flac_file(
+                path,
+                buffer,
+                sample_rate,
+                channel_count,
+                bytes_per_sample,
+                file_metadata,
+                &args.flac_settings(),
+            )
+        }),
+        WriteFormat::Wav => write_staged(&filename, |path| {
+            write_wav_file(
+                path,
+                buffer,
+                sample_rate,
+                channel_count,
+                bytes_per_sample,
+                args.wav_format(),
+                file_metadata.bext,
+            )
+        }),
+        WriteFormat::Vorbis => write_staged(&filename, |path| {
+            write_ogg_vorbis(
+                path,
+                buffer,
+                args,
+                sample_rate,
+                channel_count,
+                file_metadata,
+            )
+        }),
+        WriteFormat::Mp3 => write_staged(&filename, |path| {
+            write_mp3(
+                path,
+                buffer,
+                args,
+                sample_rate,
+                channel_count,
+                bytes_per_sample,
+                file_metadata,
+            )
+        }),
+        WriteFormat::Aac => write_staged(&filename, |path| {
+            write_aac(path, buffer, args, sample_rate, channel_count)
+        }),
+        WriteFormat::Wavpack => {
+            let write = |path: &Path, correction: Option<&Path>| {
+                let hybrid = args.wavpack_hybrid.map(|bitrate_kbps| WavpackHybrid {
+                    bitrate_kbps,
+                    correction,
+                });
+
+                write_wavpack(
+                    path,
+                    buffer,
+                    hybrid,
+                    sample_rate,
+                    channel_count,
+                    bytes_per_sample,
+                    file_metadata.tags,
+                )
+            };
+
+            // The correction file is renamed first so a finished .wv always has its .wvc
+            if args.wavpack_hybrid.is_some() && !args.wavpack_no_correction {
+                let correction = filename.with_extension("wvc");
+                write_staged(&filename, |path| {
+                    write_staged(&correction, |correction| write(path, Some(correction)))
+                })
+            } else {
+                write_staged(&filename, |path| write(path, None))
+            }
+        }
+        WriteFormat::Aiff => write_staged(&filename, |path| {
+            write_aiff_file(path, buffer, sample_rate, channel_count, bytes_per_sample)
+        }),
+    }
+}
+
+/// Encoder that is fed while a stem renders
+enum StreamEncoder {
+    Flac(libflac_sys::FlacEncoder<'static>),
+    Wav(WavWriter),
+}
+
+impl StreamEncoder {
+    /// Only FLAC and wav are streamed. Other formats are written from the whole render.
+    /// `frame_count` is the expected length, used to lay out the FLAC seek table
+    fn create(
+        filename: &Path,
+        args: &Args,
+        sample_rate: u32,
+        channel_count: usize,
+        frame_count: Option<u64>,
+        file_metadata: &FileMetadata,
+    ) -> Result<StreamEncoder> {
+        let bytes_per_sample = if args.format == SampleDepth::Int16 {
+            2
+        } else {
+            4
+        };
+
+        match args.write {
+            WriteFormat::Flac => {
+                let comments = file_metadata
+                    .tags
+                    .map(|tags| tags.vorbis_comments())
+                    .unwrap_or_default();
+
+                let encoder = libflac_sys::FlacEncoder::to_file(
+                    filename,
+                    channel_count as _,
+                    bytes_per_sample,
+                    sample_rate,
+                    frame_count.unwrap_or(0),
+                    &flac_metadata(file_metadata, &comments),
+                    &args.flac_settings(),
+                )?;
+
+                Ok(StreamEncoder::Flac(encoder))
+            }
+            WriteFormat::Wav => Ok(StreamEncoder::Wav(WavWriter::create(
+                filename,
+                channel_count,
+                sample_rate,
+                args.wav_format(),
+                file_metadata.bext,
+            )?)),
+            format => anyhow::bail!("{:?} output can't be streamed", format),
+        }
+    }
+
+    /// Encode samples at the output sample depth (with 24-bit wav samples already packed)
+    fn write(&mut self, buffer: &[u8]) -> Result<()> {
+        match self {
+            StreamEncoder::Flac(encoder) => encoder.process(buffer)?,
+            StreamEncoder::Wav(writer) => writer.write(buffer)?,
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            StreamEncoder::Flac(encoder) => encoder.finish()?,
+            StreamEncoder::Wav(writer) => {
+                writer.finish()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Rendered audio for one stem, before it gets written out
+struct RenderedStem {
+    buffer: Vec<u8>,
+    channel_count: usize,
+    bytes_per_sample: usize,
+    sample_rate: u32,
+    /// Peak level (before normalizing) is below --skip-silent-below
+    silent: bool,
+}
+
+/// Options for rendering a stem at the highest requested sample rate. `stereo` is false for mono
+/// stems and the channels of a surround mix
+fn stem_render_options(args: &Args, stereo: bool) -> RenderOptions {
+    let mut options = RenderOptions {
+        sample_rate: args.render_sample_rate(),
+        stereo,
+        stereo_separation: args.stereo_separation,
+        interpolation_filter_length: args.interpolation.map_or(0, Interpolation::filter_length),
+        gain_db: args.gain,
+        at_end: args.at_end,
+        start_seconds: args.start.unwrap_or(0.0),
+        end_seconds: args.end,
+        // The render stops at the end of the song, the fade or --end
+        max_seconds: None,
+        dither_seed: args.dither_seed,
+        amiga_filter: args.amiga_filter.map(Into::into),
+        ..RenderOptions::default()
+    };
+
+    for (i, mix) in args.channel_mix.channels() {
+        if let Some(pan) = mix.pan {
+            options.channel_pan.insert(i, pan);
+        }
+        if let Some(gain) = mix.gain {
+            options.channel_gain_db.insert(i, gain);
+        }
+    }
+
+    options
+}
+
+/// Parameters for rendering a stem with the backends that don't go through `Song`
+fn stem_render_params(
+    song_info: &SongInfo,
+    args: &Args,
+    stem: StemSelection,
+    stereo_output: bool,
+) -> RenderParams {
+    stem_render_options(args, stereo_output).render_params(song_info, stem)
+}
+
+/// Length in frames of the full mix rendered with `render_params`, which every stem gets with
+/// --equal-length
+fn equal_length_frames(song_info: &SongInfo, render_params: &RenderParams) -> u64 {
+    stemgen_core::render_frame_count(song_info.duration_seconds, render_params).unwrap_or(0)
+}
+
+/// Render a stem at the highest requested sample rate and apply the low-pass and fade out.
+/// `progress` is advanced while the song renders
+fn render_stem(
+    song_info: &SongInfo,
+    song: &[u8],
+    args: &Args,
+    stem: StemSelection,
+    stereo: bool,
+    progress: Option<&ProgressBar>,
+) -> RenderedStem {
+    let StemSelection {
+        channel,
+        instrument,
+        ..
+    } = stem;
+
+    // two channels for full track, or one per speaker in surround where each module channel is
+    // rendered in mono and mixed into its speaker
+    let full_mix = channel == -1 && instrument == -1;
+    let surround = args.surround.filter(|_| full_mix);
+    let stereo = (stereo || full_mix) && surround.is_none();
+    let channel_count = match surround {
+        Some(surround) => surround.channel_count(),
+        None if stereo => 2,
+        None => 1,
+    };
+
+    let options = stem_render_options(args, stereo);
+    let render_params = options.render_params(song_info, stem);
+    let bytes_per_sample = render_params.bytes_per_sample;
+    let render_rate = render_params.sample_rate;
+    let sample_rate = render_rate as usize;
+    let song_len = song_info.duration_seconds as usize;
+    let fade_seconds = args.at_end.fade_seconds();
+    // Where the song ends relative to the start of the render
+    let end_seconds = (song_info.duration_seconds - render_params.start_seconds).max(0.0);
+
+    let rendered_channels = if stereo { 2 } else { 1 };
+    let frame_bytes = bytes_per_sample as usize * rendered_channels;
+
+    // libopenmpt renders go through `Song::render_stem`, which allocates exactly the frame count
+    // the render core calculates for this stem and cuts the render there. The other backends get
+    // room for the longest render the song could produce, where each extra loop may be as long
+    // as the song itself
+    let render = |stem: StemSelection| {
+        let backend = args.backend(song);
+        let on_progress = |event: RenderProgress| {
+            if let Some(p) = progress {
+                match event {
+                    // Surround renders several channels into the same bar
+                    RenderProgress::Started { frame_count } => {
+                        p.inc_length(frame_count.unwrap_or(0))
+                    }
+                    RenderProgress::Rendered { frames } => p.inc(frames),
+                }
+            }
+        };
+
+        if let Some(rendered) = backend.render_stem(song, song_info, stem, &options, &on_progress) {
+            return match rendered {
+                Ok(buffer) => buffer.into_bytes(),
+                Err(e) => {
+                    song_fail!(args, "Unable to render: {}", e);
+                    Vec::new()
+                }
+            };
+        }
+
+        let passes = args.at_end.repeat_count().max(0) as usize + 1;
+        let render_seconds = song_len * passes * 2 + fade_seconds.ceil() as usize;
+        let mut output_buffer = vec![0u8; render_seconds * sample_rate * frame_bytes];
+        let render_params = options.render_params(song_info, stem);
+        let render_len = backend.render(&mut output_buffer, song, &render_params);
+        output_buffer.truncate(render_len as _);
+        output_buffer
+    };
+
+    let mut output_buffer = match surround {
+        Some(surround) => {
+            let layout = args.speaker_layout(surround);
+            let empty_channels = song_info.empty_channels();
+
+            let speakers: Vec<(Vec<u8>, usize)> = (0..song_info.channel_count)
+                .into_par_iter()
+                .filter(|c| !empty_channels.contains(c))
+                .filter_map(|c| {
+                    let speaker = layout[c as usize % layout.len()].index(surround)?;
+                    let stem = StemSelection {
+                        channel: c as i32,
+                        ..stem
+                    };
+                    Some((render(stem), speaker))
+                })
+                .collect();
+
+            dsp::mix_to_channels(&speakers, channel_count)
+        }
+        None => render(stem),
+    };
+
+    // Silence at the end is zero for both integer and float samples
+    if args.equal_length {
+        let frames = equal_length_frames(song_info, &render_params) as usize;
+        output_buffer.resize(frames * bytes_per_sample as usize * channel_count, 0);
+    }
+
+    if let Some(cutoff) = args.lowpass {
+        if cutoff > 0.0 && cutoff < render_rate as f32 / 2.0 {
+            dsp::lowpass(
+                &mut output_buffer,
+                bytes_per_sample as _,
+                channel_count,
+                render_rate,
+                cutoff,
+            );
+        } else {
+            song_warn!(
+                args,
+                "Low-pass cutoff {} Hz is outside (0, {}) Hz. Not filtering",
+                cutoff,
+                render_rate / 2
+            );
+        }
+    }
+
+    if fade_seconds > 0.0 {
+        let fade_start = (end_seconds * render_rate as f32) as usize;
+        dsp::fade_out(
+            &mut output_buffer,
+            bytes_per_sample as _,
+            channel_count,
+            fade_start,
+        );
+    }
+
+    let peak = dsp::peak(&output_buffer, bytes_per_sample as _);
+    let silent = args.is_silent(peak);
+
+    // Silent renders are skipped later on so leave them as is
+    if args.normalize == Some(Normalize::Peak) && !silent {
+        let target = 10f32.powf(args.normalize_target / 20.0);
+        dsp::apply_gain(&mut output_buffer, bytes_per_sample as _, target / peak);
+    }
+
+    RenderedStem {
+        buffer: output_buffer,
+        channel_count,
+        bytes_per_sample: bytes_per_sample as _,
+        sample_rate: render_rate,
+        silent,
+    }
+}
+
+/// Bar that follows a single render of the full mix. Stems of the channels and instruments are
+/// many short renders so they only advance the bar of the song
+fn render_progress(args: &Args, name: &str, stem: StemSelection) -> Option<ProgressBar> {
+    if !args.progress || stem.channel != -1 || stem.instrument != -1 {
+        return None;
+    }
+
+    let p = add_progress_bar(ProgressBar::new(0));
+    p.set_style(
+        ProgressStyle::with_template("{prefix:.bold.dim} {wide_bar} {percent}% {eta}").unwrap(),
+    );
+    p.set_prefix(name.to_owned());
+    Some(p)
+}
+
+/// Render a stem in chunks (see `Args::stream_chunk_frames`) that are fed straight to the encoder
+/// so memory use doesn't grow with the length of the song. Returns None if the stem has to be rendered in one go (see
+/// `Args::can_stream`). The file is written under a temporary name and only renamed when done, so
+/// silent stems and failed renders leave nothing behind
+fn stream_stem(
+    name: &str,
+    song_info: &SongInfo,
+    song: &[u8],
+    args: &Args,
+    stem: StemSelection,
+    stereo: bool,
+    file_metadata: &FileMetadata,
+) -> Result<Option<OutputEntry>> {
+    let StemSelection {
+        channel,
+        instrument,
+        ..
+    } = stem;
+
+    let full_mix = channel == -1 && instrument == -1;
+    if !args.can_stream(full_mix) {
+        return Ok(None);
+    }
+
+    let rate = args.render_sample_rate();
+    let relative = args
+        .relative_output(name, rate)
+        .with_extension(args.write.extension());
+    let filename = Path::new(&args.output).join(&relative);
+
+    // The encoders seek back to fill in the header so pipes get the whole render in one go
+    if is_fifo(&filename) {
+        return Ok(None);
+    }
+
+    let stereo = stereo || full_mix;
+    let channel_count = if stereo { 2 } else { 1 };
+    let render_params = stem_render_params(song_info, args, stem, stereo);
+    let bytes_per_sample = render_params.bytes_per_sample as usize;
+    let frame_bytes = bytes_per_sample * channel_count;
+
+    let Some(mut stream) = args.backend(song).open_stream(song, &render_params) else {
+        return Ok(None);
+    };
+
+    let equal_length = args
+        .equal_length
+        .then(|| equal_length_frames(song_info, &render_params) as usize);
+    let frame_count = match equal_length {
+        Some(frames) => Some(frames as u64),
+        None => stream.frame_count(),
+    };
+
+    let progress = render_progress(args, name, stem);
+    if let Some(p) = &progress {
+        p.set_length(frame_count.unwrap_or(0));
+    }
+
+    if let Some(dir) = filename.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let partial = part_path(&filename);
+    let _partial_file = interrupt::PartialFile::new(&partial);
+
+    let fade_seconds = args.at_end.fade_seconds();
+    let end_seconds = (song_info.duration_seconds - render_params.start_seconds).max(0.0);
+    let fade_start = (end_seconds * rate as f32) as usize;
+
+    let mut encode = || -> Result<(dsp::Levels, Option<dsp::Clipping>)> {
+        let mut encoder = StreamEncoder::create(
+            &partial,
+            args,
+            rate,
+            channel_count,
+            frame_count,
+            file_metadata,
+        )?;
+        let mut dither = args.dither(channel_count, channel, instrument);
+        let mut chunk = vec![0u8; args.stream_chunk_frames(frame_bytes) * frame_bytes];
+        let mut frames = 0;
+        let mut levels = dsp::Levels::default();
+        let mut clipping: Option<dsp::Clipping> = None;
+
+        loop {
+            let mut len = stream.read(&mut chunk);
+            let ended = len < chunk.len();
+
+            // Renders that are too long are cut and ones that end early are padded with silence
+            if let Some(total) = equal_length {
+                if ended {
+                    chunk[len..].fill(0);
+                    len = chunk.len();
+                }
+                len = len.min(total.saturating_sub(frames) * frame_bytes);
+            }
+
+            let buffer = &mut chunk[..len];
+
+            if fade_seconds > 0.0 {
+                dsp::fade_out_chunk(
+                    buffer,
+                    bytes_per_sample,
+                    channel_count,
+                    frames,
+                    fade_start,
+                    render_params.max_frames as usize,
+                );
+            }
+
+            levels.add(dsp::Levels::measure(
+                buffer,
+                bytes_per_sample,
+                channel_count,
+            ));
+
+            if let Some(c) = dsp::clipping(buffer, bytes_per_sample, channel_count) {
+                clipping = Some(match clipping {
+                    Some(first) => dsp::Clipping {
+                        samples: first.samples + c.samples,
+                        first_frame: first.first_frame,
+                    },
+                    None => dsp::Clipping {
+                        samples: c.samples,
+                        first_frame: frames + c.first_frame,
+                    },
+                });
+            }
+
+            match (args.format, args.write) {
+                (SampleDepth::Int16, _) => encoder.write(&dither.quantize_bytes(buffer))?,
+                (SampleDepth::Int24, WriteFormat::Wav) => {
+                    encoder.write(&stemgen_core::pack_pcm24(bytemuck::cast_slice(buffer)))?
+                }
+                (SampleDepth::Int32, WriteFormat::Wav) => {
+                    encoder.write(&stemgen_core::pack_pcm32(bytemuck::cast_slice(buffer)))?
+                }
+                _ => encoder.write(buffer)?,
+            }
+
+            frames += len / frame_bytes;
+
+            if let Some(p) = &progress {
+                p.inc((len / frame_bytes) as u64);
+            }
+
+            let done = match equal_length {
+                Some(total) => frames >= total,
+                None => ended,
+            };
+
+            if done {
+                break;
+            }
+        }
+
+        encoder.finish()?;
+        Ok((levels, clipping))
+    };
+
+    let result = encode();
+
+    if let Some(p) = &progress {
+        p.finish_and_clear();
+    }
+
+    let (levels, clipping) = match result {
+        Ok(result) => result,
+        Err(e) => {
+            let _ = std::fs::remove_file(&partial);
+            return Err(e);
+        }
+    };
+
+    let silent = args.is_silent(levels.peak);
+
+    if silent {
+        std::fs::remove_file(&partial)?;
+    } else {
+        std::fs::rename(&partial, &filename)?;
+    }
+
+    let mut entry = OutputEntry {
+        filename: relative.to_string_lossy().into_owned(),
+        channel,
+        instrument,
+        status: if silent {
+            OutputStatus::Silent
+        } else {
+            OutputStatus::Written
+        },
+        clipped_samples: clipping.map(|c| c.samples),
+        first_clip_seconds: clipping.map(|c| c.first_frame as f32 / rate as f32),
+        peak_dbfs: levels.peak_dbfs(),
+        rms_dbfs: levels.rms_dbfs(),
+        duration_seconds: Some(levels.seconds(rate)),
+        ..Default::default()
+    };
+
+    if args.checksums && !silent {
+        add_checksums(&mut entry, &args.output);
+    }
+
+    Ok(Some(entry))
+}
+
+/// Write the Ardour session for the stems of a song at the highest rendered rate
+fn write_ardour_session(stemname: &str, metadata: &SongMetadata, manifest: &Manifest, args: &Args) {
+    let rate = args.render_sample_rate();
+    let output = Path::new(&args.output);
+    let tracks = session_tracks(
+        &manifest.outputs,
+        output,
+        &args.relative_output("", rate),
+        metadata,
+    );
+
+    if tracks.is_empty() {
+        return;
+    }
+
+    let session_dir = output.join(format!("{}_ardour", stemname));
+    let session_path = session_dir.join(format!("{}.ardour", stemname));
+
+    let result = std::fs::create_dir_all(&session_dir)
+        .map_err(anyhow::Error::from)
+        .and_then(|_| ardour::write_session(&session_path, stemname, rate, &tracks));
+
+    if let Err(e) = result {
+        fail!(
+            "Unable to write Ardour session {:?} error: {:?}",
+            session_path,
+            e
+        );
+    }
+}
+
+/// Write the Live set for the stems of a song at the highest rendered rate, with the tempo of
+/// the rendered subsong
+fn write_ableton_set(
+    stemname: &str,
+    song: &[u8],
+    subsong: i32,
+    metadata: &SongMetadata,
+    manifest: &Manifest,
+    args: &Args,
+) {
+    let rate = args.render_sample_rate();
+    let output = Path::new(&args.output);
+    let tracks = session_tracks(
+        &manifest.outputs,
+        output,
+        &args.relative_output("", rate),
+        metadata,
+    );
+
+    if tracks.is_empty() {
+        return;
+    }
+
+    let set_path = output.join(format!("{}.als", stemname));
+    let tempo_map = tempo::tempo_map(song, subsong);
+
+    if let Err(e) = ableton::write_set(&set_path, rate, &tracks, &tempo_map) {
+        fail!("Unable to write Live set {:?} error: {:?}", set_path, e);
+    }
+}
+
+/// Entry for a file that --dry-run would write
+fn planned_output(relative: &Path, source: OutputSource, estimated_bytes: f64) -> OutputEntry {
+    OutputEntry {
+        estimated_bytes: Some(estimated_bytes as u64),
+        ..source.entry(relative, OutputStatus::Planned)
+    }
+}
+
+/// Entry for a file that couldn't be rendered or written, so --repair can pick it up
+fn failed_output(relative: &Path, source: OutputSource) -> OutputEntry {
+    source.entry(relative, OutputStatus::Failed)
+}
+
+/// Entries for outputs of an earlier run if all of `relatives` are kept (see `Args::keep_output`)
+/// so nothing has to be rendered
+fn existing_outputs(
+    args: &Args,
+    relatives: &[PathBuf],
+    source: OutputSource,
+) -> Option<Vec<OutputEntry>> {
+    relatives
+        .iter()
+        .map(|relative| {
+            let keep = args.keep_output(&Path::new(&args.output).join(relative));
+            keep.then(|| source.entry(relative, OutputStatus::Existing))
+        })
+        .collect()
+}
+
+/// Print the outputs that clip and return how many there are
+fn report_clipping(outputs: &[OutputEntry]) -> usize {
+    let clipped: Vec<&OutputEntry> = outputs
+        .iter()
+        .filter(|o| o.status == OutputStatus::Written && o.clipped_samples.is_some())
+        .collect();
+
+    for output in &clipped {
+        message!(
+            "Warning: {} clips ({} samples, first at {})",
+            output.filename,
+            output.clipped_samples.unwrap_or(0),
+            format_duration(output.first_clip_seconds.unwrap_or(0.0))
+        );
+    }
+
+    clipped.len()
+}
+
+/// Hash a written output and store the result in its manifest entry
+fn add_checksums(entry: &mut OutputEntry, output_dir: &str) {
+    let path = Path::new(output_dir).join(&entry.filename);
+
+    // Whatever was written to a pipe is gone so there is nothing to hash
+    if is_fifo(&path) {
+        return;
+    }
+
+    match checksum::sha256_file(&path) {
+        Ok(hash) => entry.sha256 = Some(hash),
+        Err(e) => fail!("Unable to hash {:?} error: {:?}", path, e),
+    }
+
+    if path.extension().is_some_and(|ext| ext == "flac") {
+        match checksum::flac_audio_md5(&path) {
+            Ok(md5) => entry.audio_md5 = md5,
+            Err(e) => fail!("Unable to read FLAC MD5 of {:?} error: {:?}", path, e),
+        }
+    }
+}
+
+/// Renders an output was made from, as recorded in its manifest entry
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct OutputSource {
+    channel: i32,
+    instrument: i32,
+    combined: Option<Combined>,
+}
+
+impl OutputSource {
+    fn stem(channel: i32, instrument: i32) -> OutputSource {
+        OutputSource {
+            channel,
+            instrument,
+            combined: None,
+        }
+    }
+
+    fn combined(combined: Combined) -> OutputSource {
+        OutputSource {
+            channel: -1,
+            instrument: -1,
+            combined: Some(combined),
+        }
+    }
+
+    /// Source of an entry of an earlier run. Manifests written before `combined` was recorded
+    /// only tell poly and NI Stems files apart from the full mix by name
+    fn of(entry: &OutputEntry) -> OutputSource {
+        let path = Path::new(&entry.filename);
+
+        let combined = entry.combined.or_else(|| {
+            if entry.filename.ends_with(".stem.mp4") {
+                Some(Combined::NiStems)
+            } else if path
+                .file_stem()
+                .is_some_and(|stem| stem.to_string_lossy().ends_with("_poly"))
+            {
+                Some(Combined::Poly)
+            } else {
+                None
+            }
+        });
+
+        OutputSource {
+            channel: entry.channel,
+            instrument: entry.instrument,
+            combined,
+        }
+    }
+
+    /// Entry with only the filename, the source and the status filled in
+    fn entry(self, relative: &Path, status: OutputStatus) -> OutputEntry {
+        OutputEntry {
+            filename: relative.to_string_lossy().into_owned(),
+            channel: self.channel,
+            instrument: self.instrument,
+            combined: self.combined,
+            status,
+            ..Default::default()
+        }
+    }
+}
+
+/// True if a written output from an earlier run is still there and intact
+fn output_ok(entry: &OutputEntry, output_dir: &str) -> bool {
+    let path = Path::new(output_dir).join(&entry.filename);
+
+    match std::fs::metadata(&path) {
+        Ok(m) if m.len() > 0 => {}
+        _ => return false,
+    }
+
+    match &entry.sha256 {
+        Some(expected) => checksum::sha256_file(&path).is_ok_and(|hash| &hash == expected),
+        None => true,
+    }
+}
+
+/// Check the outputs of an earlier run and write the ones again that failed, are missing, empty
+/// or don't match their recorded checksum. The stems they belong to are rendered again but only
+/// the broken files are rewritten. Returns the updated list of outputs
+fn repair_outputs(
+    naming: StemNaming,
+    song_info: &SongInfo,
+    song_metadata: &SongMetadata,
+    song: &[u8],
+    args: &Args,
+    subsong: i32,
+    previous: Manifest,
+) -> Vec<OutputEntry> {
+    let filestem = naming.filestem;
+    let broken: Vec<&OutputEntry> = previous
+        .outputs
+        .iter()
+        .filter(|o| match o.status {
+            OutputStatus::Failed => true,
+            OutputStatus::Written => !output_ok(o, &args.output),
+            _ => false,
+        })
+        .collect();
+
+    if broken.is_empty() {
+        message!("All outputs of {} are intact", filestem);
+        return previous.outputs;
+    }
+
+    message!(
+        "Writing {} failed, broken or missing outputs of {}",
+        broken.len(),
+        filestem
     );
+
+    // Broken files are removed and the stems rendered as with --skip-existing, so the intact
+    // outputs of the same stems (such as other sample rates) are kept as they are
+    for entry in &broken {
+        let _ = std::fs::remove_file(Path::new(&args.output).join(&entry.filename));
+    }
+
+    let filenames: HashSet<String> = broken.iter().map(|o| o.filename.clone()).collect();
+    let mut stems: Vec<OutputSource> = broken.iter().map(|o| OutputSource::of(o)).collect();
+
+    stems.sort();
+    stems.dedup();
+
+    let args = &Args {
+        skip_existing: true,
+        ..args.clone()
+    };
+
+    let stem_channels = if args.stereo { 2 } else { 1 };
+    let in_flight = args.stems_in_flight(args.stem_memory(song_info, stem_channels, false));
+
+    let repaired: Vec<OutputEntry> =
+        par_map_batched(&stems, in_flight, |source| match source.combined {
+            Some(Combined::Poly) => gen_poly(filestem, song_info, song, args, subsong),
+            Some(Combined::NiStems) => gen_ni_stems(filestem, song_info, song, args, subsong),
+            None => gen_song(
+                naming,
+                song_info,
+                song_metadata,
+                song,
+                args,
+                StemSelection {
+                    channel: source.channel,
+                    instrument: source.instrument,
+                    subsong,
+                },
+                args.stereo,
+            ),
+        })
+        .into_iter()
+        .flatten()
+        .filter(|o| filenames.contains(&o.filename))
+        .collect();
+
+    // Outputs that weren't rendered again (such as after Ctrl-C) keep their old entry
+    let mut outputs: Vec<OutputEntry> = previous
+        .outputs
+        .into_iter()
+        .filter(|o| !repaired.iter().any(|r| r.filename == o.filename))
+        .collect();
+
+    outputs.extend(repaired);
+    outputs
+}
+
+/// Parallel map with at most `in_flight` items processed at the same time (see --max-memory).
+/// The items are split into batches and each batch is done before the next one starts
+fn par_map_batched<T: Sync, R: Send>(
+    items: &[T],
+    in_flight: usize,
+    f: impl Fn(&T) -> R + Sync + Send,
+) -> Vec<R> {
+    items
+        .chunks(in_flight)
+        .flat_map(|batch| batch.par_iter().map(&f).collect::<Vec<R>>())
+        .collect()
+}
+
+/// What the filenames of the stems of a song are made from
+#[derive(Copy, Clone)]
+struct StemNaming<'a> {
+    /// Sanitized name of the song
+    filestem: &'a str,
+    /// Rules the instrument names are sanitized with (see --instrument-names)
+    sanitize_rules: &'a SanitizeRules,
+}
+
+/// Filename (without extension) of a stem. `channel` and `instrument` are -1 when not soloed
+fn stem_name(
+    naming: StemNaming,
+    song_metadata: &SongMetadata,
+    args: &Args,
+    channel: i32,
+    instrument: i32,
+) -> String {
+    let StemNaming {
+        filestem,
+        sanitize_rules,
+    } = naming;
+
+    let instrument_name = usize::try_from(instrument)
+        .ok()
+        .and_then(|i| song_metadata.instrument_names.get(i));
+
+    let instrument_part = match instrument_name {
+        Some(name) if args.instrument_names && !name.trim().is_empty() => format!(
+            "{:04}_{}",
+            instrument + 1,
+            sanitize::sanitize(name.trim(), sanitize_rules)
+        ),
+        _ => format!("{:04}", instrument + 1),
+    };
+
+    if channel == -1 && instrument == -1 {
+        filestem.to_owned()
+    } else if channel == -1 {
+        format!("{}_{}_chan_full", filestem, instrument_part)
+    } else if instrument == -1 {
+        format!("{}_chan_{:04}", filestem, channel)
+    } else {
+        format!("{}_{}_chan_{:04}", filestem, instrument_part, channel)
+    }
+}
+
+fn gen_song(
+    naming: StemNaming,
+    song_info: &SongInfo,
+    song_metadata: &SongMetadata,
+    song: &[u8],
+    args: &Args,
+    stem: StemSelection,
+    stereo: bool,
+) -> Vec<OutputEntry> {
+    // Stems that haven't started when Ctrl-C is pressed are left for --resume
+    if interrupt::interrupted() {
+        return Vec::new();
+    }
+
+    let StemSelection {
+        channel,
+        instrument,
+        ..
+    } = stem;
+
+    let filestem = naming.filestem;
+    let name = stem_name(naming, song_metadata, args, channel, instrument);
+
+    if args.skip_existing || args.overwrite != Overwrite::Always {
+        let relatives: Vec<PathBuf> = args
+            .output_rates()
+            .into_iter()
+            .map(|rate| {
+                args.relative_output(&name, rate)
+                    .with_extension(args.write.extension())
+            })
+            .collect();
+
+        if let Some(entries) =
+            existing_outputs(args, &relatives, OutputSource::stem(channel, instrument))
+        {
+            return entries;
+        }
+    }
+
+    if args.dry_run {
+        let seconds = estimated_seconds(song_info, args).unwrap_or(0.0);
+        let channel_count = if channel == -1 && instrument == -1 {
+            args.full_mix_channels()
+        } else if stereo {
+            2
+        } else {
+            1
+        };
+
+        return args
+            .output_rates()
+            .into_iter()
+            .map(|rate| {
+                let relative = args
+                    .relative_output(&name, rate)
+                    .with_extension(args.write.extension());
+                let bytes = seconds * args.estimated_bytes_per_second(rate, channel_count as _);
+                planned_output(&relative, OutputSource::stem(channel, instrument), bytes)
+            })
+            .collect();
+    }
+
+    let embedded_source = if args.embed_source && channel == -1 && instrument == -1 {
+        embedded_source_block(song, filestem)
+    } else {
+        None
+    };
+
+    let mut tags = (!args.no_tags).then(|| Tags::for_stem(song_metadata, channel, instrument));
+
+    let bext = args.bwf.then(|| {
+        Bext::new(
+            &Tags::for_stem(song_metadata, channel, instrument).title,
+            &song_metadata.source,
+            args.start.unwrap_or(0.0) as f64,
+        )
+    });
+
+    let file_metadata = FileMetadata {
+        tags: tags.as_ref(),
+        cover: song_metadata.cover.as_ref(),
+        embedded_source: embedded_source.as_deref(),
+        bext: bext.as_ref(),
+    };
+
+    match stream_stem(&name, song_info, song, args, stem, stereo, &file_metadata) {
+        Ok(Some(entry)) => return vec![entry],
+        Ok(None) => {}
+        Err(e) => {
+            song_fail!(args, "Unable to write {} error: {:?}", name, e);
+            let relative = args
+                .relative_output(&name, args.render_sample_rate())
+                .with_extension(args.write.extension());
+            return vec![failed_output(
+                &relative,
+                OutputSource::stem(channel, instrument),
+            )];
+        }
+    }
+
+    let progress = render_progress(args, &name, stem);
+
+    let RenderedStem {
+        buffer: output_buffer,
+        channel_count,
+        bytes_per_sample,
+        sample_rate: render_rate,
+        silent,
+    } = render_stem(song_info, song, args, stem, stereo, progress.as_ref());
+
+    if let Some(p) = progress {
+        p.finish_and_clear();
+    }
+
+    let fingerprint = if args.fingerprint && channel == -1 && instrument == -1 {
+        fingerprint::calculate(&output_buffer, bytes_per_sample, channel_count, render_rate)
+    } else {
+        None
+    };
+
+    let key = if args.detect_key && channel == -1 && instrument == -1 && !silent {
+        key::estimate(&output_buffer, bytes_per_sample, channel_count, render_rate)
+    } else {
+        None
+    };
+
+    if let Some(tags) = tags.as_mut() {
+        tags.key = key.clone();
+    }
+
+    let clipping = dsp::clipping(&output_buffer, bytes_per_sample, channel_count);
+    let levels = dsp::Levels::measure(&output_buffer, bytes_per_sample, channel_count);
+    let r128 = (args.ebu_r128 && !silent).then(|| {
+        loudness::R128::measure(&output_buffer, bytes_per_sample, channel_count, render_rate)
+    });
+
+    let spectrogram = (args.spectrogram && !silent).then(|| {
+        Spectrogram::analyze(&output_buffer, bytes_per_sample, channel_count, render_rate)
+    });
+    let waveform = (args.waveform_png && !silent)
+        .then(|| Waveform::analyze(&output_buffer, bytes_per_sample, channel_count));
+
+    // Tags are written with the file so the gain has to be known before any of them
+    if let Some(tags) = tags.as_mut().filter(|_| args.replaygain && !silent) {
+        tags.replay_gain = loudness::ReplayGain::measure(
+            &output_buffer,
+            bytes_per_sample,
+            channel_count,
+            render_rate,
+        );
+    }
+
+    let file_metadata = FileMetadata {
+        tags: tags.as_ref(),
+        cover: song_metadata.cover.as_ref(),
+        embedded_source: embedded_source.as_deref(),
+        bext: bext.as_ref(),
+    };
+
+    let entry = |relative: &Path, status| OutputEntry {
+        filename: relative.to_string_lossy().into_owned(),
+        channel,
+        instrument,
+        status,
+        fingerprint: fingerprint.clone(),
+        key: key.clone(),
+        clipped_samples: clipping.map(|c| c.samples),
+        first_clip_seconds: clipping.map(|c| c.first_frame as f32 / render_rate as f32),
+        peak_dbfs: levels.peak_dbfs(),
+        rms_dbfs: levels.rms_dbfs(),
+        duration_seconds: Some(levels.seconds(render_rate)),
+        loudness_lufs: r128.and_then(|r| r.integrated).map(|l| l as f32),
+        loudness_range_lu: r128.and_then(|r| r.range).map(|r| r as f32),
+        true_peak_dbtp: r128.and_then(|r| dsp::to_dbfs(r.true_peak as f64)),
+        ..Default::default()
+    };
+
+    let write = |filename: &Path, buffer: Vec<u8>, rate: u32| {
+        let (buffer, output_bytes_per_sample) =
+            args.to_output_depth(buffer, channel_count, channel, instrument);
+
+        if !write_output(
+            filename,
+            buffer,
+            args,
+            rate,
+            channel_count,
+            output_bytes_per_sample,
+            &file_metadata,
+        ) {
+            return false;
+        }
+
+        if let Some(spectrogram) = &spectrogram {
+            let png_path = filename.with_extension("png");
+            if let Err(e) = spectrogram.write_png(&png_path) {
+                song_fail!(
+                    args,
+                    "Unable to write spectrogram {:?} error: {:?}",
+                    png_path,
+                    e
+                );
+            }
+        }
+
+        if let Some(waveform) = &waveform {
+            let mut png_path = filename.to_owned().into_os_string();
+            png_path.push("_waveform.png");
+            let png_path = PathBuf::from(png_path);
+            if let Err(e) = waveform.write_png(&png_path) {
+                song_fail!(
+                    args,
+                    "Unable to write waveform {:?} error: {:?}",
+                    png_path,
+                    e
+                );
+            }
+        }
+
+        true
+    };
+
+    let rendered = RenderedStem {
+        buffer: output_buffer,
+        channel_count,
+        bytes_per_sample,
+        sample_rate: render_rate,
+        silent,
+    };
+
+    write_rates(
+        args,
+        &name,
+        args.write.extension(),
+        rendered,
+        &entry,
+        &write,
+    )
+}
+
+/// Write a render once for every output sample rate, resampled where needed. `write` gets the
+/// filename without extension, the render at the rate and the rate, and returns false if writing
+/// failed. `entry` makes the manifest entry of an output from its relative filename
+fn write_rates(
+    args: &Args,
+    name: &str,
+    extension: &str,
+    rendered: RenderedStem,
+    entry: &dyn Fn(&Path, OutputStatus) -> OutputEntry,
+    write: &dyn Fn(&Path, Vec<u8>, u32) -> bool,
+) -> Vec<OutputEntry> {
+    let RenderedStem {
+        buffer: mut output_buffer,
+        channel_count,
+        bytes_per_sample,
+        sample_rate: render_rate,
+        silent,
+    } = rendered;
+
+    let mut entries = Vec::new();
+
+    for rate in args.output_rates() {
+        let relative = args.relative_output(name, rate);
+        let filename = Path::new(&args.output).join(&relative);
+        let relative = relative.with_extension(extension);
+
+        // Silent stems are only listed in the manifest, nothing is written for them
+        if silent {
+            entries.push(entry(&relative, OutputStatus::Silent));
+            continue;
+        }
+
+        if args.keep_output(&filename.with_extension(extension)) {
+            entries.push(entry(&relative, OutputStatus::Existing));
+            continue;
+        }
+
+        if let Some(dir) = filename.parent() {
+            if let Err(e) = std::fs::create_dir_all(dir) {
+                song_fail!(args, "Unable to create {:?} error: {:?}", dir, e);
+            }
+        }
+
+        let buffer = if rate == render_rate {
+            std::mem::take(&mut output_buffer)
+        } else {
+            match dsp::resample(
+                &output_buffer,
+                bytes_per_sample,
+                channel_count,
+                render_rate,
+                rate,
+            ) {
+                Ok(buffer) => buffer,
+                Err(e) => {
+                    song_fail!(
+                        args,
+                        "Unable to resample {:?} to {} error: {:?}",
+                        filename,
+                        rate,
+                        e
+                    );
+                    entries.push(entry(&relative, OutputStatus::Failed));
+                    continue;
+                }
+            }
+        };
+
+        if !write(&filename, buffer, rate) {
+            entries.push(entry(&relative, OutputStatus::Failed));
+            continue;
+        }
+
+        let mut written = entry(&relative, OutputStatus::Written);
+        if args.checksums {
+            add_checksums(&mut written, &args.output);
+        }
+        entries.push(written);
+    }
+
+    entries
+}
+
+/// Render every tracker channel on its own and write them as a single multichannel wav where
+/// channel N of the file is tracker channel N. Empty channels are kept (as silence) so the
+/// channel numbers line up with the tracker.
+fn gen_poly(
+    filestem: &str,
+    song_info: &SongInfo,
+    song: &[u8],
+    args: &Args,
+    subsong: i32,
+) -> Vec<OutputEntry> {
+    if interrupt::interrupted() {
+        return Vec::new();
+    }
+
+    let name = format!("{}_poly", filestem);
+    let source = OutputSource::combined(Combined::Poly);
+
+    if args.skip_existing || args.overwrite != Overwrite::Always {
+        let relatives: Vec<PathBuf> = args
+            .output_rates()
+            .into_iter()
+            .map(|rate| args.relative_output(&name, rate).with_extension("wav"))
+            .collect();
+
+        if let Some(entries) = existing_outputs(args, &relatives, source) {
+            return entries;
+        }
+    }
+
+    if args.dry_run {
+        // Poly files are always wav
+        let seconds = estimated_seconds(song_info, args).unwrap_or(0.0);
+
+        return args
+            .output_rates()
+            .into_iter()
+            .map(|rate| {
+                let relative = args.relative_output(&name, rate).with_extension("wav");
+                let bytes = seconds * (rate * song_info.channel_count * args.format.bytes()) as f64;
+                planned_output(&relative, source, bytes)
+            })
+            .collect();
+    }
+
+    let empty_channels = song_info.empty_channels();
+
+    let stems: Vec<RenderedStem> = (0..song_info.channel_count)
+        .into_par_iter()
+        .filter(|c| !empty_channels.contains(c))
+        .map(|channel| {
+            render_stem(
+                song_info,
+                song,
+                args,
+                StemSelection {
+                    channel: channel as _,
+                    instrument: -1,
+                    subsong,
+                },
+                false,
+                None,
+            )
+        })
+        .collect();
+
+    let Some(first) = stems.first() else {
+        return Vec::new();
+    };
+
+    let bytes_per_sample = first.bytes_per_sample;
+    let render_rate = first.sample_rate;
+
+    let mut stems = stems.into_iter();
+    let channels: Vec<Vec<u8>> = (0..song_info.channel_count)
+        .map(|c| {
+            if empty_channels.contains(&c) {
+                Vec::new()
+            } else {
+                stems.next().map(|stem| stem.buffer).unwrap_or_default()
+            }
+        })
+        .collect();
+
+    let channel_count = channels.len();
+    let output_buffer = dsp::interleave(&channels, bytes_per_sample);
+    drop(channels);
+
+    let clipping = dsp::clipping(&output_buffer, bytes_per_sample, channel_count);
+    let levels = dsp::Levels::measure(&output_buffer, bytes_per_sample, channel_count);
+
+    let entry = |relative: &Path, status| OutputEntry {
+        clipped_samples: clipping.map(|c| c.samples),
+        first_clip_seconds: clipping.map(|c| c.first_frame as f32 / render_rate as f32),
+        peak_dbfs: levels.peak_dbfs(),
+        rms_dbfs: levels.rms_dbfs(),
+        duration_seconds: Some(levels.seconds(render_rate)),
+        ..source.entry(relative, status)
+    };
+
+    // Poly files are always wav as that is what DAWs expect for split tracks
+    let write = |filename: &Path, buffer: Vec<u8>, rate: u32| {
+        let (buffer, output_bytes_per_sample) = args.to_output_depth(buffer, channel_count, -1, -1);
+
+        write_staged(&filename.with_extension("wav"), |path| {
+            write_wav_file(
+                path,
+                buffer,
+                rate,
+                channel_count,
+                output_bytes_per_sample,
+                args.wav_format(),
+                None,
+            )
+        })
+    };
+
+    let rendered = RenderedStem {
+        buffer: output_buffer,
+        channel_count,
+        bytes_per_sample,
+        sample_rate: render_rate,
+        silent: false,
+    };
+
+    write_rates(args, &name, "wav", rendered, &entry, &write)
 }
 
-fn write_wav_file(
-    filename: &Path,
-    buffer: Vec<u8>,
-    sample_rate: u32,
-    channel_count: usize,
-    bytes_per_sample: usize,
-) {
-    let filename = PathBuf::from(filename).with_extension("wav");
+/// Render the channels of each stem group in stereo, mix them together and write the groups and
+/// the full mix as a Native Instruments Stems file. Only written at the render sample rate
+fn gen_ni_stems(
+    filestem: &str,
+    song_info: &SongInfo,
+    song: &[u8],
+    args: &Args,
+    subsong: i32,
+) -> Vec<OutputEntry> {
+    if interrupt::interrupted() {
+        return Vec::new();
+    }
 
-    let (format, bits) = if bytes_per_sample == 4 {
-        (wav::header::WAV_FORMAT_IEEE_FLOAT, 32)
-    } else {
-        (wav::header::WAV_FORMAT_PCM, 16)
+    let relative = PathBuf::from(format!("{}.stem.mp4", filestem));
+    let source = OutputSource::combined(Combined::NiStems);
+
+    if args.skip_existing || args.overwrite != Overwrite::Always {
+        if let Some(entries) = existing_outputs(args, std::slice::from_ref(&relative), source) {
+            return entries;
+        }
+    }
+
+    if args.dry_run {
+        // Full mix and four stems, all stereo AAC
+        let seconds = estimated_seconds(song_info, args).unwrap_or(0.0);
+        let bytes = seconds * 5.0 * (args.aac_bitrate * 1000 / 8) as f64;
+        return vec![planned_output(&relative, source, bytes)];
+    }
+
+    let empty_channels = song_info.empty_channels();
+
+    let groups: Vec<StemGroup> = match &args.ni_stem_groups {
+        Some(groups) => groups.0.clone(),
+        None => {
+            let channels: Vec<u32> = (0..song_info.channel_count)
+                .filter(|c| !empty_channels.contains(c))
+                .collect();
+            ni_stems::default_groups(&channels, 4)
+        }
     };
 
-    let mut out_file = match File::create(&filename) {
-        Ok(f) => f,
-        Err(e) => {
-            log::error!("Unable to write to {:?} error: {:?}", filename, e);
-            return;
+    if groups.len() != 4 {
+        song_warn!(
+            args,
+            "{} has {} stem groups, Traktor expects exactly 4",
+            filestem,
+            groups.len()
+        );
+    }
+
+    let mix = render_stem(
+        song_info,
+        song,
+        args,
+        StemSelection {
+            channel: -1,
+            instrument: -1,
+            subsong,
+        },
+        true,
+        None,
+    );
+
+    let stems: Vec<(&str, Vec<i16>)> = groups
+        .par_iter()
+        .map(|group| {
+            let renders: Vec<RenderedStem> = group
+                .channels
+                .iter()
+                .filter(|&c| *c < song_info.channel_count && !empty_channels.contains(c))
+                .map(|&channel| {
+                    render_stem(
+                        song_info,
+                        song,
+                        args,
+                        StemSelection {
+                            channel: channel as _,
+                            instrument: -1,
+                            subsong,
+                        },
+                        true,
+                        None,
+                    )
+                })
+                .collect();
+
+            let buffers: Vec<&[u8]> = renders.iter().map(|r| r.buffer.as_slice()).collect();
+            // Groups are told apart by their first channel for the dither
+            let first_channel = group.channels.first().map_or(-1, |&c| c as i32);
+            let samples = dsp::mix_to_i16(
+                &buffers,
+                mix.bytes_per_sample,
+                &mut args.dither(2, first_channel, -1),
+            );
+            (group.name.as_str(), samples)
+        })
+        .collect();
+
+    let mix_samples = dsp::mix_to_i16(
+        &[&mix.buffer],
+        mix.bytes_per_sample,
+        &mut args.dither(2, -1, -1),
+    );
+    let levels = dsp::Levels::measure(&mix.buffer, mix.bytes_per_sample, mix.channel_count);
+
+    let filename = Path::new(&args.output).join(&relative);
+
+    if let Some(dir) = filename.parent() {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            song_fail!(args, "Unable to create {:?} error: {:?}", dir, e);
+        }
+    }
+
+    let written = write_staged(&filename, |path| {
+        let res = File::create(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| {
+                ni_stems::write_stem_mp4(
+                    BufWriter::new(file),
+                    &mix_samples,
+                    &stems,
+                    mix.sample_rate,
+                    args.aac_bitrate,
+                )
+            });
+
+        if let Err(e) = &res {
+            song_fail!(args, "Unable to write {:?} error: {:?}", filename, e);
         }
+        res.is_ok()
+    });
+
+    if !written {
+        return vec![failed_output(&relative, source)];
+    }
+
+    let mut entry = OutputEntry {
+        peak_dbfs: levels.peak_dbfs(),
+        rms_dbfs: levels.rms_dbfs(),
+        duration_seconds: Some(levels.seconds(mix.sample_rate)),
+        ..source.entry(&relative, OutputStatus::Written)
     };
+    if args.checksums {
+        add_checksums(&mut entry, &args.output);
+    }
 
-    // Write out wav file
-    let wav_header = wav::Header::new(format, channel_count as _, sample_rate, bits);
-    wav::write(wav_header, &buffer.into(), &mut out_file).unwrap();
+    vec![entry]
 }
 
-fn write_ogg_vorbis(filename: &Path, buffer: Vec<u8>, args: &Args, channel_count: usize) {
-    let filename = PathBuf::from(filename).with_extension("ogg");
-    let mut out_file = match File::create(&filename) {
-        Ok(f) => f,
-        Err(e) => {
-            log::error!("Unable to write to {:?} error: {:?}", filename, e);
-            return;
+/// Outputs counted over all songs of a run
+#[derive(Default)]
+struct RunTotals {
+    clipped_outputs: usize,
+    planned_files: usize,
+    planned_bytes: u64,
+    /// Lines of the --level-report
+    level_rows: Vec<String>,
+}
+
+impl RunTotals {
+    fn add(&mut self, other: RunTotals) {
+        self.clipped_outputs += other.clipped_outputs;
+        self.planned_files += other.planned_files;
+        self.planned_bytes += other.planned_bytes;
+        self.level_rows.extend(other.level_rows);
+    }
+}
+
+/// Render everything requested for one song. Gets its own copy of the arguments as some of them
+/// are adjusted for each song
+fn process_song(
+    song: InputSong,
+    mut args: Args,
+    info_cache: &Mutex<InfoCache>,
+    sanitize_rules: &SanitizeRules,
+) -> RunTotals {
+    let InputSong {
+        filename,
+        data: song_buffer,
+        modified,
+    } = song;
+
+    let mut totals = RunTotals::default();
+    args.source_modified = modified;
+
+    if !probe_song(&song_buffer) {
+        fail!("{} isn't a supported song so is being skipped", filename);
+        return totals;
+    }
+
+    let file_path = Path::new(&filename);
+
+    let stemname = file_path.file_stem().unwrap_or_default().to_string_lossy();
+    let stemname = &sanitize::sanitize(&stemname, sanitize_rules);
+    let cache_key = InfoCache::key(&song_buffer);
+
+    if args.list {
+        let subsongs = cached_subsongs(&mut info_cache.lock().unwrap(), &cache_key, &song_buffer);
+        print_subsongs(&filename, &subsongs);
+        return totals;
+    }
+
+    message!("Processing file {}", filename);
+    events::emit(&Event::FileStarted { file: &filename });
+    let started = Instant::now();
+    let mut output_count = 0;
+
+    let mut song_info = if let Some(sample_format) = args.song_samples {
+        let sample_path = Path::new(&args.output).join(stemname);
+
+        // libopenmpt writes the samples (with their loops in a smpl chunk) but doesn't create
+        // the directory, so without this nothing is written to a new output directory
+        if let Some(dir) = sample_path.parent() {
+            if let Err(e) = std::fs::create_dir_all(dir) {
+                fail!("Unable to create {:?} error: {:?}", dir, e);
+            }
         }
+
+        write_song_instruments(&song_buffer, &args.output, stemname);
+        get_song_info(
+            &song_buffer,
+            Some((&sample_path, sample_format.into())),
+            args.max_duration,
+        )
+    } else {
+        cached_song_info(
+            &mut info_cache.lock().unwrap(),
+            &cache_key,
+            &song_buffer,
+            args.max_duration,
+        )
     };
 
-    let br = core::num::NonZeroU32::new((args.vorbis_bitrate * 1000) as _).unwrap();
-    let target_quality = f32::clamp(args.vorbis_quality, -0.2, 1.0);
+    if song_info.channel_count == 0 || song_info.instrument_count == 0 {
+        fail!(
+            "Song {} doesn'n contain any channels or instruments so is being skipped!",
+            &filename
+        );
+        return totals;
+    }
 
-    let bitrate_mode = match args.vorbis_mode {
-        OggMode::Vbr => VorbisBitrateManagementStrategy::Vbr { target_bitrate: br },
-        OggMode::Abr => VorbisBitrateManagementStrategy::Abr {
-            average_bitrate: br,
-        },
-        OggMode::ConstrainedAbr => VorbisBitrateManagementStrategy::ConstrainedAbr {
-            maximum_bitrate: br,
-        },
-        OggMode::QualityVbr => VorbisBitrateManagementStrategy::QualityVbr { target_quality },
+    if args.pattern_data {
+        write_song_patterns(&song_buffer, &args.output, stemname);
+    }
+
+    let mut subsong = -1;
+
+    if let Some(selection) = &args.subsong {
+        let subsongs = cached_subsongs(&mut info_cache.lock().unwrap(), &cache_key, &song_buffer);
+        match find_subsong(&subsongs, selection) {
+            Some(s) => {
+                subsong = s.index;
+                song_info.duration_seconds = s.duration_seconds;
+            }
+            None => {
+                fail!(
+                    "Song {} doesn't have a subsong matching \"{}\". Skipping",
+                    &filename,
+                    selection
+                );
+                return totals;
+            }
+        }
+    }
+
+    if !check_disk_space(&song_info, &args) {
+        fail!(
+            "Song {} doesn't fit in the free space of the output volume so is being skipped. Use --disk-check warn to render anyway",
+            &filename
+        );
+        return totals;
+    }
+
+    if args.tempo_map {
+        write_tempo_map(&song_buffer, subsong, &args.output, stemname);
+    }
+
+    if args.song_log && !args.dry_run {
+        let song_log = SongLog::new();
+        song_log.note(&format!(
+            "stemgen {} rendering {}",
+            env!("CARGO_PKG_VERSION"),
+            filename
+        ));
+        song_log.note(&render_parameters(&args));
+        song_log.note(&format!(
+            "{} channels, {} instruments, {} seconds",
+            song_info.channel_count, song_info.instrument_count, song_info.duration_seconds
+        ));
+        args.render_log = Some(Arc::new(song_log));
+    }
+
+    let mut song_metadata = if args.no_tags {
+        SongMetadata::default()
+    } else {
+        let source = file_path.file_name().unwrap_or_default().to_string_lossy();
+        get_song_metadata(&song_buffer, &source)
+    };
+
+    let song_stem = file_path.file_stem().unwrap_or_default().to_string_lossy();
+    if let Some(path) = args
+        .cover
+        .as_deref()
+        .and_then(|c| cover::find(c, &song_stem))
+    {
+        match Cover::load(&path) {
+            Ok(cover) => song_metadata.cover = Some(cover),
+            Err(e) => log::error!("Unable to load cover {:?} error: {:?}", path, e),
+        }
+    }
+
+    let targets: Vec<RenderTarget> = if args.all_subsongs {
+        let subsongs = cached_subsongs(&mut info_cache.lock().unwrap(), &cache_key, &song_buffer);
+        subsong_targets(stemname, &subsongs)
+    } else if args.patterns {
+        pattern_targets(stemname, &song_info, &song_buffer, subsong)
+    } else {
+        vec![RenderTarget {
+            subsong,
+            duration_seconds: song_info.duration_seconds,
+            name: stemname.clone(),
+            orders: args.order_range,
+        }]
     };
 
-    let mut encoder = VorbisEncoderBuilder::new(
-        core::num::NonZeroU32::new(args.sample_rate as _).unwrap(),
-        core::num::NonZeroU8::new(channel_count as _).unwrap(),
-        &mut out_file,
-    )
-    .unwrap()
-    .bitrate_management_strategy(bitrate_mode)
-    .build()
-    .unwrap();
+    for target in &targets {
+        let stemname = &target.name;
+        let naming = target.naming(sanitize_rules);
+        let mut subsong = target.subsong;
+        song_info.duration_seconds = target.duration_seconds;
+
+        let mut cue_points = Vec::new();
+
+        match args.cue {
+            Some(CueMode::Subsongs) => {
+                let subsongs =
+                    cached_subsongs(&mut info_cache.lock().unwrap(), &cache_key, &song_buffer);
+                subsong = SUBSONG_ALL;
+                song_info.duration_seconds = subsongs.iter().map(|s| s.duration_seconds).sum();
+                cue_points = subsong_cue_points(&subsongs);
+            }
+            Some(CueMode::Orders) => cue_points = get_order_cue_points(&song_buffer, subsong),
+            None => (),
+        }
+
+        match song_info.checked_duration(args.max_duration) {
+            Some(duration) => {
+                // checked_duration has logged the fallback already
+                if let Some(song_log) = args
+                    .render_log
+                    .as_ref()
+                    .filter(|_| duration != song_info.duration_seconds)
+                {
+                    song_log.note(&format!(
+                        "Warning: reported duration {} seconds looks invalid, using {} seconds",
+                        song_info.duration_seconds, duration
+                    ));
+                }
+                song_info.duration_seconds = duration;
+            }
+            None => {
+                song_fail!(args, "Song {} doesn'n have a duration. Skipping", &filename);
+                continue;
+            }
+        }
+
+        if let Some(range) = target.orders {
+            match order_range_times(&song_buffer, subsong, range) {
+                Some((start, end)) => {
+                    args.start = Some(start);
+                    args.end = end;
+                }
+                None => {
+                    song_fail!(
+                        args,
+                        "Song {} never plays order {}. Skipping",
+                        &filename,
+                        range.first
+                    );
+                    continue;
+                }
+            }
+        }
+
+        if let Some(song_log) = &args.render_log {
+            song_log.note(&format!(
+                "Rendering {} (subsong {}, {} seconds)",
+                stemname, subsong, song_info.duration_seconds
+            ));
+        }
+        let target_started = Instant::now();
+
+        let manifest_path = Path::new(&args.output).join(format!("{}_manifest.json", stemname));
+        let mut manifest = Manifest {
+            source: filename.clone(),
+            channel_count: song_info.channel_count,
+            instrument_count: song_info.instrument_count,
+            duration_seconds: song_info.duration_seconds,
+            empty_channels: song_info.empty_channels(),
+            outputs: Vec::new(),
+        };
+
+        // Without a manifest from an earlier run there is nothing to compare against so everything is rendered
+        let previous = if args.repair {
+            Manifest::read(&manifest_path).ok()
+        } else {
+            None
+        };
+
+        if let Some(previous) = previous {
+            manifest.outputs = repair_outputs(
+                naming,
+                &song_info,
+                &song_metadata,
+                &song_buffer,
+                &args,
+                subsong,
+                previous,
+            );
+        } else {
+            if args.full {
+                let entries = gen_song(
+                    naming,
+                    &song_info,
+                    &song_metadata,
+                    &song_buffer,
+                    &args,
+                    StemSelection {
+                        channel: -1,
+                        instrument: -1,
+                        subsong,
+                    },
+                    true,
+                );
+
+                if args.cue.is_some() {
+                    let title = if song_metadata.title.is_empty() {
+                        stemname
+                    } else {
+                        &song_metadata.title
+                    };
+                    write_cue_sheets(&entries, &cue_points, title, &args);
+                }
+
+                manifest.outputs.extend(entries);
+            }
+
+            if args.poly {
+                let entries = gen_poly(stemname, &song_info, &song_buffer, &args, subsong);
+                manifest.outputs.extend(entries);
+            }
+
+            if args.ni_stems {
+                let entries = gen_ni_stems(stemname, &song_info, &song_buffer, &args, subsong);
+                manifest.outputs.extend(entries);
+            }
+
+            let mut pb = None;
+
+            let spinner_style =
+                ProgressStyle::with_template("{prefix:.bold.dim} {wide_bar} {pos}/{len}").unwrap();
+
+            if args.channels {
+                // Channels without any note data would only render silence so skip them entirely
+                let channels: Vec<u32> = (0..song_info.channel_count)
+                    .filter(|c| !manifest.empty_channels.contains(c))
+                    .collect();
+
+                if !manifest.empty_channels.is_empty() {
+                    log::info!(
+                        "Skipping channels {:?} in {} as they contain no note data",
+                        manifest.empty_channels,
+                        &filename
+                    );
+                }
+
+                // Backends that can't solo instruments render each channel on its own
+                let solo_instruments = args.backend(&song_buffer).can_solo_instruments();
+                let channel_count = channels.len() as u32;
+                let instrument_count = if solo_instruments {
+                    song_info.instrument_count
+                } else {
+                    1
+                };
+                let total_count = channel_count * instrument_count;
+
+                if args.progress {
+                    let p = add_progress_bar(ProgressBar::new(total_count as u64));
+                    p.set_style(spinner_style);
+                    p.set_prefix(stemname.to_owned());
+                    pb = Some(p);
+                }
+
+                let stem_channels = if args.stereo { 2 } else { 1 };
+                let in_flight =
+                    args.stems_in_flight(args.stem_memory(&song_info, stem_channels, false));
+                let indices: Vec<u32> = (0..total_count).collect();
+
+                let entries: Vec<OutputEntry> = par_map_batched(&indices, in_flight, |&index| {
+                    let instrument = index / channel_count;
+                    let channel = channels[(index % channel_count) as usize];
+                    let entries = gen_song(
+                        naming,
+                        &song_info,
+                        &song_metadata,
+                        &song_buffer,
+                        &args,
+                        StemSelection {
+                            channel: channel as _,
+                            instrument: if solo_instruments {
+                                instrument as _
+                            } else {
+                                -1
+                            },
+                            subsong,
+                        },
+                        args.stereo,
+                    );
+
+                    if let Some(p) = &pb {
+                        p.inc(1);
+                    }
+
+                    entries
+                })
+                .into_iter()
+                .flatten()
+                .collect();
+
+                manifest.outputs.extend(entries);
+            } else if args.instruments && !args.backend(&song_buffer).can_solo_instruments() {
+                fail!(
+                    "{} can't be split into instruments with this backend",
+                    &filename
+                );
+            } else if args.instruments {
+                if args.progress {
+                    let p = add_progress_bar(ProgressBar::new(song_info.instrument_count as u64));
+                    p.set_style(spinner_style);
+                    p.set_prefix(stemname.to_owned());
+                    pb = Some(p);
+                }
+                let stem_channels = if args.stereo { 2 } else { 1 };
+                let in_flight =
+                    args.stems_in_flight(args.stem_memory(&song_info, stem_channels, false));
+                let instruments: Vec<u32> = (0..song_info.instrument_count).collect();
+
+                let entries: Vec<OutputEntry> =
+                    par_map_batched(&instruments, in_flight, |&instrument| {
+                        let entries = gen_song(
+                            naming,
+                            &song_info,
+                            &song_metadata,
+                            &song_buffer,
+                            &args,
+                            StemSelection {
+                                channel: -1,
+                                instrument: instrument as _,
+                                subsong,
+                            },
+                            args.stereo,
+                        );
+
+                        if let Some(p) = &pb {
+                            p.inc(1);
+                        }
+
+                        entries
+                    })
+                    .into_iter()
+                    .flatten()
+                    .collect();
+
+                manifest.outputs.extend(entries);
+            }
+
+            if let Some(p) = pb {
+                p.finish_and_clear();
+            }
+        }
+
+        output_count += manifest.outputs.len();
+
+        if events::json() && !args.dry_run {
+            emit_output_events(&filename, &manifest.outputs, &args.output);
+        }
+
+        if args.dry_run {
+            // Printed in one go so the lists of songs rendered at the same time don't mix
+            let mut list = String::new();
+            for output in &manifest.outputs {
+                let bytes = output.estimated_bytes.unwrap_or(0);
+                totals.planned_bytes += bytes;
+                totals.planned_files += 1;
+                list += &format!("  {} ({:.1} MB)\n", output.filename, bytes as f64 / MB);
+            }
+            message!("{}", list.trim_end());
+            continue;
+        }
+
+        totals.clipped_outputs += report_clipping(&manifest.outputs);
+
+        if args.level_report.is_some() {
+            totals.level_rows.extend(
+                manifest
+                    .outputs
+                    .iter()
+                    .map(|output| level_report::row(&filename, output)),
+            );
+        }
+
+        if let Some(song_log) = &args.render_log {
+            log_outputs(song_log, &manifest.outputs, &args.output);
+            song_log.note(&format!(
+                "Rendered {} in {:.3} seconds",
+                stemname,
+                target_started.elapsed().as_secs_f64()
+            ));
+        }
+
+        if args.manifest {
+            if let Err(e) = manifest.write(&manifest_path) {
+                fail!(
+                    "Unable to write manifest {:?} error: {:?}",
+                    manifest_path,
+                    e
+                );
+            }
+        }
+
+        if args.bundle {
+            let bundle_path = Path::new(&args.output).join(format!("{}_stems.zip", stemname));
+            let subsongs =
+                cached_subsongs(&mut info_cache.lock().unwrap(), &cache_key, &song_buffer);
+            let info_text = song_info_text(&filename, &song_info, &subsongs);
+
+            if let Err(e) = bundle::write_bundle(
+                &bundle_path,
+                Path::new(&args.output),
+                stemname,
+                &manifest,
+                &info_text,
+                song_metadata.cover.as_ref().map(|c| c.path.as_path()),
+            ) {
+                fail!("Unable to write bundle {:?} error: {:?}", bundle_path, e);
+            }
+        }
+
+        if args.ardour {
+            write_ardour_session(stemname, &song_metadata, &manifest, &args);
+        }
+
+        if args.ableton {
+            write_ableton_set(
+                stemname,
+                &song_buffer,
+                subsong,
+                &song_metadata,
+                &manifest,
+                &args,
+            );
+        }
+    }
+
+    events::emit(&Event::FileFinished {
+        file: &filename,
+        outputs: output_count,
+        seconds: started.elapsed().as_secs_f64(),
+    });
+
+    if let Some(song_log) = &args.render_log {
+        song_log.note(&format!(
+            "Finished {} outputs in {:.3} seconds",
+            output_count,
+            song_log.seconds()
+        ));
+
+        let log_path = Path::new(&args.output).join(format!("{}.log", stemname));
+        if let Err(e) = song_log.write(&log_path) {
+            fail!("Unable to write song log {:?} error: {:?}", log_path, e);
+        }
+    }
+
+    totals
+}
+
+/// Settings that change how a song sounds, for --song-log
+fn render_parameters(args: &Args) -> String {
+    format!(
+        "Sample rate {:?}, {:?} {:?}, backend {:?}, interpolation {:?}, stereo separation {:?}, \
+         gain {}, normalize {:?}, at end {:?}, repeat count {:?}, amiga filter {:?}, \
+         low-pass {:?}, start {:?}, end {:?}, max duration {}",
+        args.sample_rate,
+        args.write,
+        args.format,
+        args.backend,
+        args.interpolation,
+        args.stereo_separation,
+        args.gain,
+        args.normalize,
+        args.at_end,
+        args.repeat_count,
+        args.amiga_filter,
+        args.lowpass,
+        args.start,
+        args.end,
+        args.max_duration
+    )
+}
+
+/// Add what happened to each output of a render to its song log
+fn log_outputs(song_log: &SongLog, outputs: &[OutputEntry], output_dir: &str) {
+    for output in outputs {
+        let status = match output.status {
+            OutputStatus::Written => {
+                match std::fs::metadata(Path::new(output_dir).join(&output.filename)) {
+                    Ok(metadata) => format!("written, {} bytes", metadata.len()),
+                    Err(_) => "written".to_owned(),
+                }
+            }
+            OutputStatus::Silent => "silent, not written".to_owned(),
+            OutputStatus::Existing => "kept from an earlier run".to_owned(),
+            OutputStatus::Planned => "planned".to_owned(),
+            OutputStatus::Failed => "failed".to_owned(),
+        };
+
+        song_log.note(&format!("{}: {}", output.filename, status));
+
+        if let Some(samples) = output.clipped_samples {
+            song_log.note(&format!(
+                "Warning: {} clips ({} samples, first at {})",
+                output.filename,
+                samples,
+                format_duration(output.first_clip_seconds.unwrap_or(0.0))
+            ));
+        }
+    }
+}
+
+/// Report the outputs of a song with --log-format json
+fn emit_output_events(filename: &str, outputs: &[OutputEntry], output_dir: &str) {
+    for entry in outputs {
+        events::emit(&Event::StemRendered {
+            file: filename,
+            output: &entry.filename,
+            channel: entry.channel,
+            instrument: entry.instrument,
+            status: entry.status,
+        });
+
+        if entry.status == OutputStatus::Written {
+            if let Ok(metadata) = std::fs::metadata(Path::new(output_dir).join(&entry.filename)) {
+                events::emit(&Event::BytesWritten {
+                    output: &entry.filename,
+                    bytes: metadata.len(),
+                });
+            }
+        }
+    }
+}
+
+/// Copy the single file rendered for --stdout to stdout
+fn write_to_stdout(dir: &Path) -> Result<()> {
+    let files: Vec<PathBuf> = WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.into_path())
+        .collect();
+
+    match files.as_slice() {
+        [] => anyhow::bail!("Nothing was rendered for stdout"),
+        [file] => {
+            let mut stdout = std::io::stdout().lock();
+            std::io::copy(&mut File::open(file)?, &mut stdout)?;
+            stdout.flush()?;
+            Ok(())
+        }
+        _ => anyhow::bail!("--stdout can only write a single song"),
+    }
+}
+
+const MB: f64 = 1024.0 * 1024.0;
+
+/// Set by --stdout so messages don't end up in the audio stream
+static STDOUT_AUDIO: AtomicBool = AtomicBool::new(false);
+
+/// Everything reported with `fail!`
+static FAILURES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Exit code when the run finished but some songs or outputs failed. Errors that stop the whole
+/// run exit with 1
+const EXIT_FAILED_FILES: i32 = 3;
+
+/// Exit code when stopped with Ctrl-C, as for shells (128 + SIGINT)
+const EXIT_INTERRUPTED: i32 = 130;
+
+/// List everything that failed during the run and return how many there are
+fn print_failures() -> usize {
+    let failures = FAILURES.lock().unwrap();
+
+    if events::json() {
+        events::emit(&Event::RunFinished {
+            failures: failures.len(),
+        });
+    } else if !failures.is_empty() {
+        eprintln!("{} songs or outputs failed:", failures.len());
+        for failure in failures.iter() {
+            eprintln!("  {}", failure);
+        }
+    }
+
+    failures.len()
+}
+
+/// Bars of all songs rendered at the same time, set with --progress
+static PROGRESS_BARS: OnceLock<MultiProgress> = OnceLock::new();
 
-    if channel_count == 1 {
-        let data: &[f32] = bytemuck::cast_slice(&buffer);
+/// Show `bar` below the other bars (see --progress)
+fn add_progress_bar(bar: ProgressBar) -> ProgressBar {
+    match PROGRESS_BARS.get() {
+        Some(bars) => bars.add(bar),
+        None => bar,
+    }
+}
 
-        let sample_step = 48000;
-        let len = data.len();
-        let mut offset = 0;
+fn main() -> Result<()> {
+    let mut args = Args::parse();
+    // Progress bars and colors only make sense when a human is watching
+    if !std::io::stdout().is_terminal() {
+        args.plain = true;
+    }
 
-        loop {
-            let step_value = std::cmp::min(sample_step, len - offset);
+    if args.log_format == LogFormat::Json {
+        events::enable_json();
+        args.plain = true;
+    }
 
-            let t = [&data[offset..offset + step_value]];
+    if args.plain {
+        args.progress = false;
+    }
 
-            match encoder.encode_audio_block(&t) {
-                Ok(_) => (),
-                Err(e) => {
-                    log::error!("Unable to encode vorbis file: {:?}", e);
-                    return;
-                }
-            }
+    if args.patterns && !(args.channels || args.instruments) {
+        args.full = true;
+    }
 
-            if step_value != sample_step {
-                break;
-            }
+    if args.backend == BackendKind::Xmp && !cfg!(feature = "xmp") {
+        anyhow::bail!("stemgen was built without the xmp feature needed for --backend xmp");
+    }
 
-            offset += step_value;
-        }
-    } else {
-        let data: &[f32] = bytemuck::cast_slice(&buffer);
-        let channel0: Vec<f32> = data.iter().skip(0).step_by(2).copied().collect();
-        let channel1: Vec<f32> = data.iter().skip(1).step_by(2).copied().collect();
+    if args.amiga_resampler && args.amiga_filter.is_none() {
+        args.amiga_filter = Some(AmigaFilter::Auto);
+    }
 
-        let sample_step = 48000;
-        let len = channel0.len();
-        let mut offset = 0;
+    if let Some(count) = args.repeat_count {
+        args.at_end = AtEnd::Loop(count);
+    }
 
-        loop {
-            let step_value = std::cmp::min(sample_step, len - offset);
+    if let (Some(start), Some(end)) = (args.start, args.end) {
+        if end <= start {
+            anyhow::bail!("--end has to be after --start");
+        }
+    }
 
-            let t = [
-                &channel0[offset..offset + step_value],
-                &channel1[offset..offset + step_value],
-            ];
+    if let Some(path) = &args.pan_map {
+        args.channel_mix = PanMap::load(path)?;
+    }
 
-            match encoder.encode_audio_block(&t) {
-                Ok(_) => (),
-                Err(e) => {
-                    log::error!("Unable to encode vorbis file: {:?}", e);
-                    return;
-                }
-            }
+    // FLAC is always written with 24 bits from the float render, the other formats have no
+    // integer PCM writer for these depths
+    if matches!(args.format, SampleDepth::Int24 | SampleDepth::Int32)
+        && !matches!(args.write, WriteFormat::Wav | WriteFormat::Flac)
+    {
+        anyhow::bail!("--format int24 and int32 can only be written as wav or flac");
+    }
 
-            if step_value != sample_step {
-                break;
-            }
+    if args.ardour && !matches!(args.write, WriteFormat::Wav | WriteFormat::Flac) {
+        anyhow::bail!("--ardour sessions can only use stems written as wav or flac");
+    }
 
-            offset += step_value;
-        }
+    if args.ableton && !matches!(args.write, WriteFormat::Wav | WriteFormat::Flac) {
+        anyhow::bail!("--ableton sets can only use stems written as wav or flac");
     }
 
-    match encoder.finish() {
-        Ok(_) => (),
-        Err(e) => {
-            log::error!("Unable to finish vorbis file: {:?}", e);
-            return;
+    if let Some(surround) = args.surround {
+        if !matches!(args.write, WriteFormat::Wav | WriteFormat::Flac) {
+            anyhow::bail!("--surround can only be written as wav or flac");
+        }
+        let layout = args.speaker_layout(surround);
+        if layout.is_empty() {
+            anyhow::bail!("--surround-layout needs at least one speaker");
+        }
+        if let Some(speaker) = layout.iter().find(|s| s.index(surround).is_none()) {
+            let speaker = speaker.to_possible_value().unwrap();
+            let surround = surround.to_possible_value().unwrap();
+            anyhow::bail!(
+                "{} isn't a speaker in {} surround",
+                speaker.get_name(),
+                surround.get_name()
+            );
         }
     }
-}
 
-fn write_mp3(
-    filename: &Path,
-    buffer: Vec<u8>,
-    args: &Args,
-    channel_count: usize,
-    bytes_per_sample: usize,
-) {
-    let filename = PathBuf::from(filename).with_extension("mp3");
+    // The full mix is written to a private directory and copied to stdout once it's done, as most
+    // writers need to seek
+    let stdout_dir = args
+        .stdout
+        .then(|| std::env::temp_dir().join(format!("stemgen-stdout-{}", std::process::id())));
 
-    let mut out_file = match File::create(&filename) {
-        Ok(f) => f,
-        Err(e) => {
-            log::error!("Unable to write to {:?} error: {:?}", filename, e);
-            return;
-        }
-    };
-
-    let bitrate = match args.mp3_bitrate {
-        8 => mp3lame_encoder::Bitrate::Kbps8,
-        16 => mp3lame_encoder::Bitrate::Kbps16,
-        24 => mp3lame_encoder::Bitrate::Kbps24,
-        32 => mp3lame_encoder::Bitrate::Kbps32,
-        40 => mp3lame_encoder::Bitrate::Kbps40,
-        48 => mp3lame_encoder::Bitrate::Kbps48,
-        64 => mp3lame_encoder::Bitrate::Kbps64,
-        80 => mp3lame_encoder::Bitrate::Kbps80,
-        96 => mp3lame_encoder::Bitrate::Kbps96,
-        112 => mp3lame_encoder::Bitrate::Kbps112,
-        128 => mp3lame_encoder::Bitrate::Kbps128,
-        160 => mp3lame_encoder::Bitrate::Kbps160,
-        192 => mp3lame_encoder::Bitrate::Kbps192,
-        224 => mp3lame_encoder::Bitrate::Kbps224,
-        256 => mp3lame_encoder::Bitrate::Kbps256,
-        320 => mp3lame_encoder::Bitrate::Kbps320,
-        _ => panic!("Invalid bitrate for mp3 {}", args.mp3_bitrate),
-    };
-
-    let quality = match args.mp3_quality {
-        Mp3Quality::Best => mp3lame_encoder::Quality::Best,
-        Mp3Quality::SecondBest => mp3lame_encoder::Quality::SecondBest,
-        Mp3Quality::NearBest => mp3lame_encoder::Quality::NearBest,
-        Mp3Quality::VeryNice => mp3lame_encoder::Quality::VeryNice,
-        Mp3Quality::Nice => mp3lame_encoder::Quality::Nice,
-        Mp3Quality::Good => mp3lame_encoder::Quality::Good,
-        Mp3Quality::Decent => mp3lame_encoder::Quality::Decent,
-        Mp3Quality::Ok => mp3lame_encoder::Quality::Ok,
-        Mp3Quality::SecondWorst => mp3lame_encoder::Quality::SecondWorst,
-        Mp3Quality::Worst => mp3lame_encoder::Quality::Worst,
-    };
-
-    let vbr_quality = match args.mp3_vbr_quality {
-        Mp3Quality::Best => mp3lame_encoder::Quality::Best,
-        Mp3Quality::SecondBest => mp3lame_encoder::Quality::SecondBest,
-        Mp3Quality::NearBest => mp3lame_encoder::Quality::NearBest,
-        Mp3Quality::VeryNice => mp3lame_encoder::Quality::VeryNice,
-        Mp3Quality::Nice => mp3lame_encoder::Quality::Nice,
-        Mp3Quality::Good => mp3lame_encoder::Quality::Good,
-        Mp3Quality::Decent => mp3lame_encoder::Quality::Decent,
-        Mp3Quality::Ok => mp3lame_encoder::Quality::Ok,
-        Mp3Quality::SecondWorst => mp3lame_encoder::Quality::SecondWorst,
-        Mp3Quality::Worst => mp3lame_encoder::Quality::Worst,
-    };
-
-    let vbr_mode = match args.mp3_vbr {
-        Mp3VbrMode::Off => mp3lame_encoder::VbrMode::Off,
-        Mp3VbrMode::Mt => mp3lame_encoder::VbrMode::Mt,
-        Mp3VbrMode::Rh => mp3lame_encoder::VbrMode::Rh,
-        Mp3VbrMode::Abr => mp3lame_encoder::VbrMode::Abr,
-        Mp3VbrMode::Mtrh => mp3lame_encoder::VbrMode::Mtrh,
-    };
+    if let Some(dir) = &stdout_dir {
+        if args.sample_rate.len() > 1 {
+            anyhow::bail!("--stdout can only write a single sample rate");
+        }
+        STDOUT_AUDIO.store(true, Ordering::Relaxed);
+        args.full = true;
+        args.output = dir.to_string_lossy().into_owned();
+    }
 
-    let mut mp3_encoder = Builder::new().expect("Create LAME builder");
-    mp3_encoder
-        .set_num_channels(channel_count as _)
-        .expect("set channels");
-    mp3_encoder
-        .set_sample_rate(args.sample_rate as _)
-        .expect("set sample rate");
-    mp3_encoder.set_brate(bitrate).expect("set brate");
-    mp3_encoder.set_quality(quality).expect("set quality");
-    mp3_encoder.set_to_write_vbr_tag(true).expect("set quality");
-    mp3_encoder.set_vbr_mode(vbr_mode).expect("set vbr mode");
-    mp3_encoder
-        .set_vbr_quality(vbr_quality)
-        .expect("set vbr quality");
-    let mut mp3_encoder = mp3_encoder.build().expect("To initialize LAME encoder");
+    // The repaired manifest has to be written for the next repair to work
+    if args.repair {
+        args.manifest = true;
+    }
 
-    let mut mp3_out_buffer = Vec::new();
-    let encoded_size;
+    // Stems of the songs that were being rendered when the batch was interrupted are kept
+    if args.resume {
+        args.skip_existing = true;
+    }
 
-    if channel_count == 2 {
-        if bytes_per_sample == 2 {
-            let data: &[i16] = bytemuck::cast_slice(&buffer);
-            let input = InterleavedPcm(data);
+    let colors = !(args.plain || args.no_color || std::env::var_os("NO_COLOR").is_some());
+    console::set_colors_enabled(colors);
+    console::set_colors_enabled_stderr(colors);
 
-            mp3_out_buffer.reserve(mp3lame_encoder::max_required_buffer_size(data.len() / 2));
-            encoded_size = mp3_encoder
-                .encode(input, mp3_out_buffer.spare_capacity_mut())
-                .expect("To encode");
-        } else {
-            let data: &[f32] = bytemuck::cast_slice(&buffer);
-            let input = InterleavedPcm(data);
+    if events::json() {
+        log::set_boxed_logger(Box::new(events::JsonLogger))?;
+        log::set_max_level(log::LevelFilter::Error);
+    } else {
+        SimpleLogger::new()
+            .with_level(log::LevelFilter::Error)
+            .with_colors(colors)
+            .init()?;
+    }
 
-            mp3_out_buffer.reserve(mp3lame_encoder::max_required_buffer_size(data.len() / 2));
-            encoded_size = mp3_encoder
-                .encode(input, mp3_out_buffer.spare_capacity_mut())
-                .expect("To encode");
+    if let Some(Command::Info {
+        input,
+        recursive,
+        json,
+    }) = &args.command
+    {
+        let mut info_cache = InfoCache::load(args.info_cache.as_deref());
+        print_info(input, *recursive, *json, &args, &mut info_cache)?;
+        info_cache.save()?;
+
+        if print_failures() > 0 {
+            std::process::exit(EXIT_FAILED_FILES);
         }
-    } else {
-        if bytes_per_sample == 2 {
-            let data: &[i16] = bytemuck::cast_slice(&buffer);
-            let input = MonoPcm(data);
 
-            mp3_out_buffer.reserve(mp3lame_encoder::max_required_buffer_size(data.len()));
-            encoded_size = mp3_encoder
-                .encode(input, mp3_out_buffer.spare_capacity_mut())
-                .expect("To encode");
-        } else {
-            let data: &[f32] = bytemuck::cast_slice(&buffer);
-            let input = MonoPcm(data);
+        return Ok(());
+    }
 
-            mp3_out_buffer.reserve(mp3lame_encoder::max_required_buffer_size(data.len()));
-            encoded_size = mp3_encoder
-                .encode(input, mp3_out_buffer.spare_capacity_mut())
-                .expect("To encode");
+    // Has to happen before the rayon thread pool is created so the worker threads inherit it
+    if args.nice {
+        if let Err(e) = priority::lower_priority(args.nice_io) {
+            log::error!("Unable to lower process priority error: {:?}", e);
         }
     }
 
-    unsafe {
-        mp3_out_buffer.set_len(mp3_out_buffer.len().wrapping_add(encoded_size));
+    if let Some(threads) = args.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()?;
     }
 
-    let encoded_size = mp3_encoder
-        .flush::<FlushNoGap>(mp3_out_buffer.spare_capacity_mut())
-        .expect("to flush");
-    unsafe {
-        mp3_out_buffer.set_len(mp3_out_buffer.len().wrapping_add(encoded_size));
+    let mut files = get_files(
+        &args.input,
+        args.recursive,
+        &args.include_patterns()?,
+        &args.exclude_patterns()?,
+    );
+
+    if let Some(shard) = args.shard {
+        files.retain(|f| {
+            // A single input file is relative to itself so use its name instead
+            let relative = Path::new(f)
+                .strip_prefix(&args.input)
+                .ok()
+                .filter(|r| !r.as_os_str().is_empty())
+                .unwrap_or(Path::new(f));
+            shard.contains(&relative.to_string_lossy())
+        });
+        message!(
+            "Shard {}/{}: processing {} files",
+            shard.index,
+            shard.count,
+            files.len()
+        );
     }
+    let sanitize_rules = args.sanitize_rules();
 
-    out_file.write_all(&mp3_out_buffer).unwrap();
-}
+    // Force float if writing vorbis
+    if args.write == WriteFormat::Vorbis {
+        args.format = SampleDepth::Float;
+    }
 
-fn gen_song(
-    filestem: &str,
-    song_info: &SongInfo,
-    song: &[u8],
-    args: &Args,
-    channel: i32,
-    instrument: i32,
-    stereo: bool,
-) {
-    // Number of bytes needed given a sample depth
-    let bytes_per_sample = if args.format == SampleDepth::Float {
-        4
-    } else {
-        2
-    };
-    // Number of bytes needed given a sample depth
-    let mut channel_count = if args.stereo { 2 } else { 1 };
+    let info_cache = InfoCache::load(args.info_cache.as_deref());
 
-    let (stereo_separation, stereo_separation_enabled) =
-        if let Some(stereo_sep) = args.stereo_separation {
-            (stereo_sep, true)
-        } else {
-            (100, false)
-        };
+    // One bar for all songs with the bars of the songs being rendered below it
+    let overall = args.progress.then(|| {
+        let bars = PROGRESS_BARS.get_or_init(MultiProgress::new);
+        let p = bars.add(ProgressBar::new(files.len() as u64));
+        p.set_style(
+            ProgressStyle::with_template("{prefix:.bold} {wide_bar} {pos}/{len} songs").unwrap(),
+        );
+        p.set_prefix("Total");
+        p
+    });
 
-    let mut stereo = stereo;
+    // Archives are expanded one at a time so only a single archive is kept in memory
+    let songs = files.iter().flat_map(|filename| {
+        let songs = input::read(filename).unwrap_or_else(|e| {
+            fail!("Unable to read {} error: {:?}", filename, e);
+            Vec::new()
+        });
 
-    // two channels for full track
-    if channel == -1 && instrument == -1 {
-        channel_count = 2;
-        stereo = true;
-    }
+        // Archives hold several songs and unreadable files none
+        if let Some(p) = &overall {
+            p.set_length(p.length().unwrap_or(0) + songs.len() as u64 - 1);
+        }
 
-    let render_params = RenderParams {
-        sample_rate: args.sample_rate as _,
-        bytes_per_sample,
-        channel_to_play: channel,
-        instrument_to_play: instrument,
-        stereo_separation,
-        stereo_separation_enabled,
-        stereo_output: stereo,
+        songs
+    });
+
+    let jobs = if args.list {
+        1
+    } else {
+        args.jobs.unwrap_or_else(rayon::current_num_threads).max(1)
     };
 
-    let sample_rate = args.sample_rate as usize;
-    // We add 5 sec extra to the duration to make sure the buffer is large enough
-    let song_len = song_info.duration_seconds as usize;
+    // Songs rendered at the same time split the memory budget between them
+    args.song_memory = args.max_memory.map(|mb| mb * 1024 * 1024 / jobs as u64);
 
-    let filename = if channel == -1 && instrument == -1 {
-        Path::new(&args.output).join(format!("{}", filestem))
-    } else if channel == -1 {
-        Path::new(&args.output).join(format!("{}_{:04}_chan_full", filestem, instrument + 1))
+    let checkpoint_path = Path::new(&args.output).join(interrupt::CHECKPOINT_NAME);
+    let finished = if args.resume {
+        interrupt::read_checkpoint(&checkpoint_path)
     } else {
-        Path::new(&args.output).join(format!(
-            "{}_{:04}_chan_{:04}",
-            filestem,
-            instrument + 1,
-            channel
-        ))
+        HashSet::new()
     };
 
-    // two channels for full track
-    if channel == -1 && instrument == -1 {
-        channel_count = 2;
+    interrupt::install()?;
+
+    let songs = Mutex::new(songs);
+    let info_cache = Mutex::new(info_cache);
+    let totals = Mutex::new(RunTotals::default());
+    let finished = Mutex::new(finished);
+
+    // Each job takes the next song when it's done with the current one. The stems of all songs
+    // share the rayon worker threads so the number of threads in use stays the same
+    rayon::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|_| loop {
+                if interrupt::interrupted() {
+                    break;
+                }
+                let Some(song) = songs.lock().unwrap().next() else {
+                    break;
+                };
+                if finished.lock().unwrap().contains(&song.filename) {
+                    if let Some(p) = &overall {
+                        p.inc(1);
+                    }
+                    continue;
+                }
+
+                let filename = song.filename.clone();
+                let song_totals = process_song(song, args.clone(), &info_cache, &sanitize_rules);
+                totals.lock().unwrap().add(song_totals);
+
+                if let Some(p) = &overall {
+                    p.inc(1);
+                }
+
+                // Songs that were cut short by Ctrl-C are rendered again by --resume
+                if !interrupt::interrupted() {
+                    finished.lock().unwrap().insert(filename);
+                }
+            });
+        }
+    });
+
+    let mut info_cache = info_cache.into_inner().unwrap();
+    let totals = totals.into_inner().unwrap();
+
+    if let Some(p) = &overall {
+        p.finish_and_clear();
     }
 
-    let output_size_bytes = song_len * sample_rate * bytes_per_sample as usize * channel_count * 2;
-    let mut output_buffer = vec![0u8; output_size_bytes];
+    if interrupt::interrupted() {
+        if let Err(e) = info_cache.save() {
+            log::error!("Unable to write info cache error: {:?}", e);
+        }
 
-    let render_len = song_render(&mut output_buffer, song, &render_params);
+        interrupt::write_checkpoint(&checkpoint_path, &finished.into_inner().unwrap())?;
+        print_failures();
+        eprintln!("Interrupted. Run again with --resume to continue");
+        std::process::exit(EXIT_INTERRUPTED);
+    }
 
-    output_buffer.truncate(render_len as _);
+    if args.resume {
+        let _ = std::fs::remove_file(&checkpoint_path);
+    }
 
-    // TODO: Optimize
-    if output_buffer.iter().any(|x| *x != 0) {
-        match args.write {
-            WriteFormat::Flac => {
-                write_flac_file(
-                    &filename,
-                    output_buffer,
-                    args.sample_rate,
-                    channel_count,
-                    bytes_per_sample as _,
-                );
-            }
-            WriteFormat::Wav => {
-                write_wav_file(
-                    &filename,
-                    output_buffer,
-                    args.sample_rate,
-                    channel_count,
-                    bytes_per_sample as _,
-                );
-            }
-            WriteFormat::Vorbis => {
-                write_ogg_vorbis(&filename, output_buffer, &args, channel_count);
-            }
-            WriteFormat::Mp3 => {
-                write_mp3(
-                    &filename,
-                    output_buffer,
-                    &args,
-                    channel_count,
-                    bytes_per_sample as _,
-                );
-            }
+    if let Err(e) = info_cache.save() {
+        log::error!("Unable to write info cache error: {:?}", e);
+    }
+
+    if args.dry_run {
+        message!(
+            "{} files would be written, about {:.1} MB",
+            totals.planned_files,
+            totals.planned_bytes as f64 / MB
+        );
+    }
+
+    if let Some(path) = args.level_report.as_ref().filter(|_| !args.dry_run) {
+        if let Err(e) = level_report::write(path, totals.level_rows) {
+            fail!("Unable to write level report {:?} error: {:?}", path, e);
         }
     }
+
+    if totals.clipped_outputs > 0 {
+        message!(
+            "{} outputs clip. Lower --gain or use --normalize peak to avoid it",
+            totals.clipped_outputs
+        );
+    }
+
+    if let Some(dir) = stdout_dir {
+        let res = write_to_stdout(&dir);
+        let _ = std::fs::remove_dir_all(&dir);
+        res?;
+    }
+
+    if print_failures() > 0 {
+        std::process::exit(EXIT_FAILED_FILES);
+    }
+
+    Ok(())
 }
 
-fn main() -> Result<()> {
-    let mut args = Args::parse();
-    SimpleLogger::new()
-        .with_level(log::LevelFilter::Error)
-        .init()?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let files = get_files(&args.input, args.recursive);
+    #[test]
+    fn patterns_match_amiga_names_and_ignore_case() {
+        let args =
+            Args::try_parse_from(["stemgen", "-i", "songs", "-o", "out", "--exclude", "*.XM"])
+                .unwrap();
+        let include = args.include_patterns().unwrap();
+        let exclude = args.exclude_patterns().unwrap();
 
-    // Force float if writing vorbis
-    if args.write == WriteFormat::Vorbis {
-        args.format = SampleDepth::Float;
+        assert!(include.is_match("song.mod"));
+        assert!(include.is_match("MOD.Intro"));
+        assert!(!include.is_match("readme.txt"));
+        assert!(exclude.is_match("song.xm"));
     }
 
-    for filename in files {
-        let file_path = Path::new(&filename);
-        let mut file = File::open(&filename)?;
-        let mut song_buffer = Vec::new();
-        file.read_to_end(&mut song_buffer)?;
+    #[test]
+    fn deprecated_mp3_flags_map_onto_the_new_ones() {
+        let args = Args::try_parse_from([
+            "stemgen",
+            "-i",
+            "song.mod",
+            "-o",
+            "out",
+            "--mp3-vbr",
+            "mt",
+            "--mp3-vbr-quality",
+            "2",
+        ])
+        .unwrap();
 
-        let stemname = file_path.file_stem().unwrap().to_str().unwrap();
+        assert_eq!(args.mp3_mode, Mp3Mode::Vbr);
+        assert_eq!(args.mp3_vbr_level, Mp3Quality::NearBest);
+        assert_eq!(args.mp3_quality, Mp3Quality::Good);
+    }
 
-        println!("Processing file {}", filename);
+    #[test]
+    fn output_rates_are_unique_with_the_render_rate_last() {
+        let args = Args::try_parse_from([
+            "stemgen",
+            "-i",
+            "song.mod",
+            "-o",
+            "out",
+            "-s",
+            "44100,96000,48000,44100",
+        ])
+        .unwrap();
 
-        let song_info = if let Some(sample_format) = args.song_samples {
-            let sample_path = Path::new(&args.output).join(format!("{}", stemname));
-            get_song_info(&song_buffer, Some(&sample_path), sample_format as _)
-        } else {
-            get_song_info(&song_buffer, None, 0)
+        assert_eq!(args.render_sample_rate(), 96000);
+        assert_eq!(args.output_rates(), [44100, 48000, 96000]);
+    }
+
+    #[test]
+    fn cached_info_is_only_used_with_the_same_max_duration() {
+        let mut cache = InfoCache::load(None);
+        let key = InfoCache::key(b"not a song");
+        let mut channel_has_notes = [true; MAX_CHANNELS];
+        channel_has_notes[1] = false;
+        let info = SongInfo {
+            channel_count: 6,
+            instrument_count: 3,
+            duration_seconds: 60.0,
+            estimated_seconds: 0.0,
+            channel_has_notes,
         };
+        cache.insert(&key, song_info_to_cached(&info, 600.0));
 
-        if song_info.channel_count == 0 || song_info.instrument_count == 0 {
-            log::error!(
-                "Song {} doesn'n contain any channels or instruments so is being skipped!",
-                &filename
-            );
-            continue;
-        }
+        let cached = cached_song_info(&mut cache, &key, b"not a song", 600.0);
+        assert_eq!(cached.channel_count, 6);
+        assert_eq!(cached.duration_seconds, 60.0);
+        assert_eq!(cached.empty_channels(), [1]);
 
-        if song_info.duration_seconds == 0.0 {
-            log::error!("Song {} doesn'n have a duration. Skipping", &filename);
-            continue;
-        }
+        // Another --max-duration loads the song again, which fails for this one
+        let loaded = cached_song_info(&mut cache, &key, b"not a song", 60.0);
+        assert_eq!(loaded.channel_count, 0);
+        assert_eq!(cache.get(&key).unwrap().max_seconds, 60.0);
+    }
 
-        if args.full {
-            gen_song(&stemname, &song_info, &song_buffer, &args, -1, -1, true);
-        }
+    #[test]
+    fn every_subsong_has_its_own_stem_names() {
+        let args = Args::try_parse_from(["stemgen", "-i", "song.mod", "-o", "out"]).unwrap();
+        let sanitize_rules = args.sanitize_rules();
+        let subsongs: Vec<Subsong> = (0..2)
+            .map(|index| Subsong {
+                index,
+                name: String::new(),
+                duration_seconds: 10.0,
+            })
+            .collect();
 
-        let mut pb = None;
+        let names: Vec<String> = subsong_targets("song", &subsongs)
+            .iter()
+            .map(|target| {
+                let naming = target.naming(&sanitize_rules);
+                stem_name(naming, &SongMetadata::default(), &args, 2, -1)
+            })
+            .collect();
 
-        let spinner_style =
-            ProgressStyle::with_template("{prefix:.bold.dim} {wide_bar} {pos}/{len}").unwrap();
+        assert_eq!(
+            names,
+            ["song_subsong_00_chan_0002", "song_subsong_01_chan_0002"]
+        );
+    }
 
-        if args.channels {
-            let channel_count = song_info.channel_count;
-            let instrument_count = song_info.instrument_count;
-            let total_count = channel_count * instrument_count;
+    #[test]
+    fn times() {
+        assert_eq!(parse_time("90"), Ok(90.0));
+        assert_eq!(parse_time("1.5"), Ok(1.5));
+        assert_eq!(parse_time("2:03.5"), Ok(123.5));
+        assert_eq!(parse_time("1:00:00"), Ok(3600.0));
 
-            if args.progress {
-                let p = ProgressBar::new(total_count as u64);
-                p.set_style(spinner_style);
-                pb = Some(p);
-            }
+        for invalid in ["", "-1", "1.5:00", "1:2:3:4", "a:00", "inf"] {
+            assert!(parse_time(invalid).is_err(), "{}", invalid);
+        }
+    }
 
-            (0..total_count).into_par_iter().for_each(|index| {
-                let instrument = index / channel_count;
-                let channel = index % channel_count;
-                gen_song(
-                    &stemname,
-                    &song_info,
-                    &song_buffer,
-                    &args,
-                    channel as _,
-                    instrument as _,
-                    args.stereo,
-                );
+    #[test]
+    fn order_ranges() {
+        let range = |s: &str| s.parse::<OrderRange>().map(|r| (r.first, r.last));
 
-                if let Some(p) = &pb {
-                    p.inc(1);
-                }
-            });
-        } else if args.instruments {
-            if args.progress {
-                let p = ProgressBar::new(song_info.instrument_count as u64);
-                p.set_style(spinner_style);
-                pb = Some(p);
-            }
-            (0..song_info.instrument_count)
-                .into_par_iter()
-                .for_each(|instrument| {
-                    gen_song(
-                        &stemname,
-                        &song_info,
-                        &song_buffer,
-                        &args,
-                        -1,
-                        instrument as _,
-                        args.stereo,
-                    );
+        assert_eq!(range("3"), Ok((3, Some(3))));
+        assert_eq!(range("2..5"), Ok((2, Some(4))));
+        assert_eq!(range("2..=5"), Ok((2, Some(5))));
+        assert_eq!(range("4.."), Ok((4, None)));
+        assert_eq!(range(" 1 .. 2 "), Ok((1, Some(1))));
 
-                    if let Some(p) = &pb {
-                        p.inc(1);
-                    }
-                });
+        for invalid in ["", "..3", "5..2", "3..3", "2..=1", "a..b", "1..=", "0..0"] {
+            assert!(range(invalid).is_err(), "{}", invalid);
         }
     }
-
-    Ok(())
 }
@@ -0,0 +1,120 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+/// What happened to a single planned output
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputStatus {
+    /// Rendered and written to disk
+    #[default]
+    Written,
+    /// Rendered but contained no audio so nothing was written
+    Silent,
+    /// Written by an earlier run and kept because of --skip-existing or --overwrite
+    Existing,
+    /// Would be written, for --dry-run
+    Planned,
+    /// Rendering or writing it failed. --repair renders it again
+    Failed,
+}
+
+/// Outputs that hold several renders in one file
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Combined {
+    /// Every tracker channel as a channel of one multichannel wav (--poly)
+    Poly,
+    /// Native Instruments Stems file (--ni-stems)
+    NiStems,
+}
+
+/// One rendered (or skipped) stem of a song
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct OutputEntry {
+    /// Filename relative to the output directory
+    pub filename: String,
+    /// Channel that was soloed, -1 if all channels were played
+    pub channel: i32,
+    /// Instrument that was soloed, -1 if all instruments were played
+    pub instrument: i32,
+    /// Set for outputs that combine several renders. Their channel and instrument are -1 as for
+    /// the full mix
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub combined: Option<Combined>,
+    pub status: OutputStatus,
+    /// Chromaprint fingerprint of the render (full mix only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    /// Estimated musical key of the render, such as "Am" (full mix only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// SHA-256 of the written file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// MD5 of the audio data as stored by the FLAC encoder
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_md5: Option<String>,
+    /// Number of samples at or beyond full scale
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clipped_samples: Option<u64>,
+    /// Position of the first clipped sample in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_clip_seconds: Option<f32>,
+    /// Highest sample level in dBFS. Not set for outputs that weren't rendered or are digital
+    /// silence
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peak_dbfs: Option<f32>,
+    /// RMS level over all channels in dBFS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rms_dbfs: Option<f32>,
+    /// Length of the render in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_seconds: Option<f32>,
+    /// EBU R128 integrated loudness in LUFS, with --ebu-r128
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loudness_lufs: Option<f32>,
+    /// EBU R128 loudness range in LU
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loudness_range_lu: Option<f32>,
+    /// Peak level between the samples as well in dBTP
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub true_peak_dbtp: Option<f32>,
+    /// Expected size of the file, for --dry-run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimated_bytes: Option<u64>,
+}
+
+/// Summary of everything generated for one input song, written as
+/// `<song>_manifest.json` next to the outputs
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Manifest {
+    pub source: String,
+    pub channel_count: u32,
+    pub instrument_count: u32,
+    pub duration_seconds: f32,
+    /// Channels without any note data. These are never rendered
+    pub empty_channels: Vec<u32>,
+    pub outputs: Vec<OutputEntry>,
+}
+
+impl Manifest {
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn read(path: &Path) -> Result<Manifest> {
+        let file = File::open(path)?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self)?;
+        Ok(())
+    }
+}
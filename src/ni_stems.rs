@@ -0,0 +1,315 @@
+use crate::aac;
+use anyhow::{bail, Result};
+use std::{io::Write, str::FromStr};
+
+/// Colors used for the stems in Traktor, in stem order
+const COLORS: &[&str] = &["#FD4A4A", "#FFA56D", "#32BE44", "#36B4D8"];
+
+/// Tracker channels that are mixed together into one NI stem
+#[derive(Debug, Clone)]
+pub struct StemGroup {
+    pub name: String,
+    pub channels: Vec<u32>,
+}
+
+/// Stem groups given on the command line as `[name=]channels;...` where channels is a comma
+/// separated list of channels or ranges, such as `Drums=0-3;Bass=4;5,7;6`
+#[derive(Debug, Clone)]
+pub struct StemGroups(pub Vec<StemGroup>);
+
+fn parse_channels(s: &str) -> Result<Vec<u32>> {
+    let mut channels = Vec::new();
+
+    for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let first: u32 = first.trim().parse()?;
+                let last: u32 = last.trim().parse()?;
+                if first > last {
+                    bail!("invalid channel range {}", part);
+                }
+                channels.extend(first..=last);
+            }
+            None => channels.push(part.parse()?),
+        }
+    }
+
+    if channels.is_empty() {
+        bail!("stem group without channels");
+    }
+
+    Ok(channels)
+}
+
+impl FromStr for StemGroups {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let groups = s
+            .split(';')
+            .map(str::trim)
+            .filter(|g| !g.is_empty())
+            .map(|group| {
+                let (name, channels) = match group.split_once('=') {
+                    Some((name, channels)) => (Some(name.trim().to_owned()), channels),
+                    None => (None, group),
+                };
+
+                let channels = parse_channels(channels)
+                    .map_err(|e| format!("invalid stem group \"{}\": {}", group, e))?;
+
+                Ok(StemGroup {
+                    name: name.unwrap_or_else(|| group_name(&channels)),
+                    channels,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        if groups.is_empty() {
+            return Err("expected at least one stem group".to_owned());
+        }
+
+        Ok(StemGroups(groups))
+    }
+}
+
+fn group_name(channels: &[u32]) -> String {
+    match channels {
+        [channel] => format!("Channel {}", channel),
+        [first, .., last] => format!("Channels {}-{}", first, last),
+        [] => String::new(),
+    }
+}
+
+/// Split the channels as evenly as possible into `count` groups of neighbouring channels
+pub fn default_groups(channels: &[u32], count: usize) -> Vec<StemGroup> {
+    let count = count.min(channels.len());
+
+    (0..count)
+        .map(|i| {
+            let start = i * channels.len() / count;
+            let end = (i + 1) * channels.len() / count;
+            let channels = channels[start..end].to_vec();
+            StemGroup {
+                name: group_name(&channels),
+                channels,
+            }
+        })
+        .collect()
+}
+
+/// Contents of the `stem` atom that describes the stems to Traktor. The mastering DSP is
+/// disabled as the renders are already mixed
+fn stem_metadata(names: &[&str]) -> String {
+    let stems: Vec<_> = names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| serde_json::json!({ "name": name, "color": COLORS[i % COLORS.len()] }))
+        .collect();
+
+    serde_json::json!({
+        "mastering_dsp": {
+            "compressor": {
+                "enabled": false,
+                "input_gain": 0.5,
+                "output_gain": 0.5,
+                "threshold": 0,
+                "dry_wet": 50,
+                "attack": 0.003,
+                "release": 0.3,
+                "ratio": 3,
+                "hp_cutoff": 300
+            },
+            "limiter": {
+                "enabled": false,
+                "threshold": 0,
+                "ceiling": -0.35,
+                "release": 0.05
+            }
+        },
+        "version": 1,
+        "stems": stems,
+    })
+    .to_string()
+}
+
+/// Disable all tracks but the first (so regular players only play the full mix) and set the
+/// `moov/udta/stem` atom
+fn set_stem_atom(data: &mut Vec<u8>, metadata: &str) -> Result<()> {
+    let (moov_offset, moov_size) = aac::find_moov(data)?;
+
+    let traks = aac::boxes(data, moov_offset + 8, moov_offset + moov_size)
+        .into_iter()
+        .filter(|(_, _, kind)| kind == b"trak");
+
+    for (offset, size, _) in traks.skip(1) {
+        if let Some((tkhd, _, _)) = aac::boxes(data, offset + 8, offset + size)
+            .into_iter()
+            .find(|(_, _, kind)| kind == b"tkhd")
+        {
+            // Lowest bit of the flags is track_enabled
+            data[tkhd + 11] &= !1;
+        }
+    }
+
+    aac::set_udta_box(data, b"stem", metadata.as_bytes())
+}
+
+/// Write a Native Instruments Stems file. The full mix is the first track followed by one
+/// track per stem, all stereo AAC
+pub fn write_stem_mp4<W: Write>(
+    mut writer: W,
+    mix: &[i16],
+    stems: &[(&str, Vec<i16>)],
+    sample_rate: u32,
+    bitrate_kbps: u32,
+) -> Result<()> {
+    let mut tracks = vec![aac::encode(mix, 2, sample_rate, bitrate_kbps)?];
+    for (_, samples) in stems {
+        tracks.push(aac::encode(samples, 2, sample_rate, bitrate_kbps)?);
+    }
+
+    let mut data = Vec::new();
+    aac::write_mp4(&mut data, &tracks, sample_rate)?;
+
+    let names: Vec<&str> = stems.iter().map(|(name, _)| *name).collect();
+    set_stem_atom(&mut data, &stem_metadata(&names))?;
+
+    writer.write_all(&data)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn groups(s: &str) -> Vec<(String, Vec<u32>)> {
+        let StemGroups(groups) = s.parse().unwrap();
+        groups.into_iter().map(|g| (g.name, g.channels)).collect()
+    }
+
+    fn mp4_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut output = (8 + payload.len() as u32).to_be_bytes().to_vec();
+        output.extend(kind);
+        output.extend(payload);
+        output
+    }
+
+    /// Flags of the tkhd box of every trak in the moov box
+    fn track_flags(data: &[u8]) -> Vec<u8> {
+        let (moov, moov_size) = aac::find_moov(data).unwrap();
+
+        aac::boxes(data, moov + 8, moov + moov_size)
+            .into_iter()
+            .filter(|(_, _, kind)| kind == b"trak")
+            .map(|(trak, size, _)| {
+                let (tkhd, _, _) = aac::boxes(data, trak + 8, trak + size)[0];
+                data[tkhd + 11]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn named_and_unnamed_groups() {
+        assert_eq!(
+            groups("Drums=0-3;Bass=4; 5, 7 ;6;"),
+            [
+                ("Drums".to_owned(), vec![0, 1, 2, 3]),
+                ("Bass".to_owned(), vec![4]),
+                ("Channels 5-7".to_owned(), vec![5, 7]),
+                ("Channel 6".to_owned(), vec![6]),
+            ]
+        );
+        assert_eq!(
+            groups(" Lead = 2 - 4 ,8"),
+            [("Lead".to_owned(), vec![2, 3, 4, 8])]
+        );
+    }
+
+    #[test]
+    fn invalid_groups() {
+        for invalid in [
+            "",
+            " ; ",
+            "3-1",
+            "Drums=",
+            "Drums=;Bass=1",
+            "a",
+            "1-",
+            "-2",
+            "1;x=2-b",
+        ] {
+            assert!(invalid.parse::<StemGroups>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn default_groups_split_channels_evenly() {
+        let split = |channels: &[u32], count| -> Vec<(String, Vec<u32>)> {
+            default_groups(channels, count)
+                .into_iter()
+                .map(|g| (g.name, g.channels))
+                .collect()
+        };
+
+        assert_eq!(
+            split(&[0, 1, 2, 3, 4, 5, 6, 7], 4),
+            [
+                ("Channels 0-1".to_owned(), vec![0, 1]),
+                ("Channels 2-3".to_owned(), vec![2, 3]),
+                ("Channels 4-5".to_owned(), vec![4, 5]),
+                ("Channels 6-7".to_owned(), vec![6, 7]),
+            ]
+        );
+        assert_eq!(
+            split(&[0, 2, 3, 5, 9], 4),
+            [
+                ("Channel 0".to_owned(), vec![0]),
+                ("Channel 2".to_owned(), vec![2]),
+                ("Channel 3".to_owned(), vec![3]),
+                ("Channels 5-9".to_owned(), vec![5, 9]),
+            ]
+        );
+        assert_eq!(split(&[1, 2], 4).len(), 2);
+        assert!(split(&[], 4).is_empty());
+    }
+
+    #[test]
+    fn only_the_first_track_stays_enabled() {
+        let tkhd = mp4_box(b"tkhd", &[0, 0, 0, 0x0f, 0, 0, 0, 0]);
+        let trak = mp4_box(b"trak", &tkhd);
+        let mut moov = mp4_box(b"mvhd", &[0; 4]);
+        for _ in 0..3 {
+            moov.extend(&trak);
+        }
+        let mut data = mp4_box(b"mdat", &[0; 16]);
+        data.extend(mp4_box(b"moov", &moov));
+
+        set_stem_atom(&mut data, "{\"version\":1}").unwrap();
+        assert_eq!(track_flags(&data), [0x0f, 0x0e, 0x0e]);
+
+        let (moov, moov_size) = aac::find_moov(&data).unwrap();
+        let (udta, udta_size, _) = *aac::boxes(&data, moov + 8, moov + moov_size)
+            .last()
+            .unwrap();
+        let (stem, stem_size, kind) = aac::boxes(&data, udta + 8, udta + udta_size)[0];
+        assert_eq!(&kind, b"stem");
+        assert_eq!(&data[stem + 8..stem + stem_size], b"{\"version\":1}");
+
+        // Running it again keeps the tracks as they are and replaces the atom
+        set_stem_atom(&mut data, "{}").unwrap();
+        assert_eq!(track_flags(&data), [0x0f, 0x0e, 0x0e]);
+    }
+
+    #[test]
+    fn stem_metadata_names_and_colors() {
+        let metadata: serde_json::Value =
+            serde_json::from_str(&stem_metadata(&["a", "b", "c", "d", "e"])).unwrap();
+        let stems = metadata["stems"].as_array().unwrap();
+
+        assert_eq!(stems.len(), 5);
+        assert_eq!(stems[1]["name"], "b");
+        assert_eq!(stems[4]["color"], COLORS[0]);
+        assert_eq!(metadata["mastering_dsp"]["limiter"]["enabled"], false);
+    }
+}
@@ -0,0 +1,120 @@
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Pan and gain of a single module channel
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ChannelMix {
+    /// -1.0 is hard left, 0.0 center and 1.0 hard right
+    pub pan: Option<f32>,
+    /// Channel volume in dB. The mixer can only lower a channel so this has to be 0 or less
+    pub gain: Option<f32>,
+}
+
+/// Pan positions and gains per module channel loaded from `--pan-map`, such as
+///
+/// ```toml
+/// [channel.0]
+/// pan = -0.5
+/// gain = -3.0
+/// ```
+///
+/// Channels are numbered from 0 as in the output filenames
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct PanMap {
+    #[serde(default)]
+    channel: BTreeMap<String, ChannelMix>,
+}
+
+impl PanMap {
+    pub fn load(path: &Path) -> Result<PanMap> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Unable to read pan map {:?}", path))?;
+        let map: PanMap =
+            toml::from_str(&text).with_context(|| format!("Invalid pan map {:?}", path))?;
+
+        for (channel, mix) in &map.channel {
+            if channel.parse::<u32>().is_err() {
+                bail!("{:?}: \"{}\" isn't a channel number", path, channel);
+            }
+            if let Some(pan) = mix.pan {
+                if !(-1.0..=1.0).contains(&pan) {
+                    bail!(
+                        "{:?}: pan {} of channel {} is outside [-1, 1]",
+                        path,
+                        pan,
+                        channel
+                    );
+                }
+            }
+            if let Some(gain) = mix.gain {
+                if gain > 0.0 {
+                    bail!(
+                        "{:?}: gain {} dB of channel {} is above 0 dB",
+                        path,
+                        gain,
+                        channel
+                    );
+                }
+            }
+        }
+
+        Ok(map)
+    }
+
+    /// Channel numbers with their pan and gain
+    pub fn channels(&self) -> impl Iterator<Item = (usize, ChannelMix)> + '_ {
+        self.channel
+            .iter()
+            .filter_map(|(key, mix)| Some((key.parse().ok()?, *mix)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(name: &str, text: &str) -> Result<PanMap> {
+        let path =
+            std::env::temp_dir().join(format!("stemgen-{}-{}.toml", std::process::id(), name));
+        std::fs::write(&path, text).unwrap();
+        let map = PanMap::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        map
+    }
+
+    #[test]
+    fn channels_with_pan_and_gain() {
+        let map = load(
+            "valid",
+            "[channel.0]\npan = -0.5\ngain = -3.0\n\n[channel.12]\npan = 1.0\n",
+        )
+        .unwrap();
+        let channels: Vec<(usize, Option<f32>, Option<f32>)> = map
+            .channels()
+            .map(|(channel, mix)| (channel, mix.pan, mix.gain))
+            .collect();
+
+        assert_eq!(
+            channels,
+            [(0, Some(-0.5), Some(-3.0)), (12, Some(1.0), None)]
+        );
+        assert_eq!(load("empty", "").unwrap().channels().count(), 0);
+    }
+
+    #[test]
+    fn invalid_maps() {
+        for (name, text) in [
+            ("name", "[channel.left]\npan = 0.0\n"),
+            ("pan", "[channel.0]\npan = 1.5\n"),
+            ("gain", "[channel.0]\ngain = 3.0\n"),
+            ("field", "[channel.0]\nvolume = 1.0\n"),
+            ("section", "[instrument.0]\npan = 0.0\n"),
+        ] {
+            assert!(load(name, text).is_err(), "{}", text);
+        }
+    }
+}
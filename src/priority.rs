@@ -0,0 +1,67 @@
+use anyhow::Result;
+
+/// Lower the priority of the process so long batches don't make the machine unusable. Needs to be
+/// called before any worker threads are created as threads inherit the priority of their parent.
+/// If `io` is set the I/O priority is lowered as well (where the platform supports it)
+#[cfg(unix)]
+pub fn lower_priority(io: bool) -> Result<()> {
+    // Lowest priority (19) is the same as running the tool with `nice -n 19`
+    let res = unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, 19) };
+    if res != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    if io {
+        lower_io_priority()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn lower_io_priority() -> Result<()> {
+    // Values from linux/ioprio.h which libc doesn't expose
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+    const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_ioprio_set,
+            IOPRIO_WHO_PROCESS,
+            0,
+            IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT,
+        )
+    };
+    if res != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    Ok(())
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn lower_io_priority() -> Result<()> {
+    log::warn!("Lowering I/O priority isn't supported on this platform");
+    Ok(())
+}
+
+#[cfg(windows)]
+pub fn lower_priority(io: bool) -> Result<()> {
+    use windows_sys::Win32::System::Threading::{
+        GetCurrentProcess, SetPriorityClass, IDLE_PRIORITY_CLASS, PROCESS_MODE_BACKGROUND_BEGIN,
+    };
+
+    // Background mode lowers both the CPU and I/O priority
+    let class = if io {
+        PROCESS_MODE_BACKGROUND_BEGIN
+    } else {
+        IDLE_PRIORITY_CLASS
+    };
+
+    if unsafe { SetPriorityClass(GetCurrentProcess(), class) } == 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    Ok(())
+}
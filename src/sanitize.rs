@@ -0,0 +1,198 @@
+use clap::ValueEnum;
+
+/// Case folding applied to generated filenames
+#[derive(ValueEnum, Debug, Copy, Clone, PartialEq)]
+pub enum FilenameCase {
+    Keep,
+    Lower,
+    Upper,
+}
+
+/// Characters that are invalid in filenames on Windows, FAT/exFAT and Samba shares
+const RESERVED: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+/// Device names that Windows won't create files with, even with an extension added
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Invisible characters that aren't control characters but make names look the same as others
+/// (zero width spaces and joiners, direction overrides and byte order marks). They are dropped
+fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        '\u{200b}'..='\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2060}'..='\u{2064}' | '\u{feff}'
+    )
+}
+
+/// How names coming from songs are turned into filenames
+#[derive(Debug, Clone)]
+pub struct SanitizeRules {
+    /// Non alphanumeric characters that are kept. If None everything but reserved and control
+    /// characters is kept
+    pub allowed: Option<String>,
+    /// Replace non-ASCII characters with ASCII approximations ("Motörhead" -> "Motorhead")
+    pub transliterate: bool,
+    /// Max length in bytes of the sanitized name, 0 means no limit
+    pub max_length: usize,
+    pub case: FilenameCase,
+    /// Lowercase ASCII letters and digits with a single dash between words ("Space Debris!" ->
+    /// "space-debris"). The other rules besides the max length are ignored
+    pub slug: bool,
+}
+
+impl SanitizeRules {
+    fn is_allowed(&self, c: char) -> bool {
+        if c.is_control() || RESERVED.contains(&c) {
+            return false;
+        }
+
+        match &self.allowed {
+            Some(allowed) => c.is_alphanumeric() || allowed.contains(c),
+            None => true,
+        }
+    }
+}
+
+fn slugify(name: &str) -> String {
+    let mut output = String::new();
+
+    for c in deunicode::deunicode(&name.replace(is_invisible, "")).chars() {
+        if c.is_ascii_alphanumeric() {
+            output.push(c.to_ascii_lowercase());
+        } else if !output.is_empty() && !output.ends_with('-') {
+            output.push('-');
+        }
+    }
+
+    output
+}
+
+/// Turn a name into something that is safe to use as (part of) a filename on all common
+/// filesystems. Characters that aren't allowed are replaced with `_`
+pub fn sanitize(name: &str, rules: &SanitizeRules) -> String {
+    let mut output = if rules.slug {
+        slugify(name)
+    } else {
+        let name = if rules.transliterate {
+            deunicode::deunicode(name)
+        } else {
+            name.to_owned()
+        };
+
+        let name = match rules.case {
+            FilenameCase::Keep => name,
+            FilenameCase::Lower => name.to_lowercase(),
+            FilenameCase::Upper => name.to_uppercase(),
+        };
+
+        name.trim()
+            .chars()
+            .filter(|&c| !is_invisible(c))
+            .map(|c| if rules.is_allowed(c) { c } else { '_' })
+            .collect()
+    };
+
+    // Names starting with a dot are hidden on Unix
+    if output.starts_with('.') {
+        output.replace_range(..1, "_");
+    }
+
+    let device = output.split('.').next().unwrap_or_default();
+    if RESERVED_NAMES
+        .iter()
+        .any(|reserved| device.eq_ignore_ascii_case(reserved))
+    {
+        output.insert(device.len(), '_');
+    }
+
+    // The cut is done last so nothing added above can make the name longer than the limit
+    if rules.max_length > 0 && output.len() > rules.max_length {
+        let mut end = rules.max_length;
+        while !output.is_char_boundary(end) {
+            end -= 1;
+        }
+        output.truncate(end);
+    }
+
+    // Windows and Samba don't allow names ending with a space or dot. Slugs can end with a dash
+    // after being cut to length
+    let trimmed = if rules.slug {
+        output.trim_end_matches('-').len()
+    } else {
+        output.trim_end_matches([' ', '.']).len()
+    };
+    output.truncate(trimmed);
+
+    if output.is_empty() {
+        output.push('_');
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules() -> SanitizeRules {
+        SanitizeRules {
+            allowed: None,
+            transliterate: false,
+            max_length: 0,
+            case: FilenameCase::Keep,
+            slug: false,
+        }
+    }
+
+    #[test]
+    fn reserved_characters_are_replaced() {
+        assert_eq!(sanitize("a/b:c?", &rules()), "a_b_c_");
+        assert_eq!(sanitize(" .hidden. ", &rules()), "_hidden");
+        assert_eq!(sanitize("zero\u{200b}width", &rules()), "zerowidth");
+        assert_eq!(sanitize("", &rules()), "_");
+    }
+
+    #[test]
+    fn device_names_get_an_underscore() {
+        assert_eq!(sanitize("con", &rules()), "con_");
+        assert_eq!(sanitize("LPT1.mod", &rules()), "LPT1_.mod");
+        assert_eq!(sanitize("console", &rules()), "console");
+    }
+
+    #[test]
+    fn max_length_is_applied_last() {
+        let rules = SanitizeRules {
+            max_length: 4,
+            ..rules()
+        };
+
+        assert_eq!(sanitize("COM1", &rules), "COM1");
+        assert_eq!(sanitize("abc. def", &rules), "abc");
+        // Cut on a char boundary
+        assert_eq!(sanitize("abcö", &rules), "abc");
+    }
+
+    #[test]
+    fn slugs() {
+        let rules = SanitizeRules {
+            slug: true,
+            ..rules()
+        };
+
+        assert_eq!(slugify("Space Debris!"), "space-debris-");
+        assert_eq!(sanitize("Space Debris!", &rules), "space-debris");
+        assert_eq!(sanitize("Motörhead", &rules), "motorhead");
+        assert_eq!(
+            sanitize(
+                "Space Debris",
+                &SanitizeRules {
+                    max_length: 6,
+                    ..rules
+                }
+            ),
+            "space"
+        );
+    }
+}
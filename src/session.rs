@@ -0,0 +1,169 @@
+//! Stems of a song laid out as tracks for the DAW session exporters
+
+use crate::manifest::{OutputEntry, OutputStatus};
+use crate::tags::SongMetadata;
+use anyhow::Result;
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+/// A written stem placed at the start of its own track
+#[derive(Debug, Clone)]
+pub struct SessionTrack {
+    pub name: String,
+    /// Absolute path, so the session can be moved independently of the stems
+    pub path: PathBuf,
+    pub channel_count: u32,
+    pub frames: u64,
+}
+
+/// Name of the track for a stem. `channel` and `instrument` are -1 when not soloed
+pub fn track_name(metadata: &SongMetadata, channel: i32, instrument: i32) -> String {
+    let instrument_name =
+        (instrument >= 0).then(
+            || match metadata.instrument_names.get(instrument as usize) {
+                Some(name) if !name.trim().is_empty() => name.trim().to_owned(),
+                _ => format!("Instrument {}", instrument + 1),
+            },
+        );
+
+    match (channel, instrument_name) {
+        (-1, None) => "Mix".to_owned(),
+        (-1, Some(name)) => name,
+        (channel, None) => format!("Channel {}", channel),
+        (channel, Some(name)) => format!("{} (Channel {})", name, channel),
+    }
+}
+
+/// Channel count and length in frames of a wav or FLAC file
+pub fn audio_file_layout(path: &Path) -> Result<(u32, u64)> {
+    let mut file = File::open(path)?;
+    let mut magic = [0u8; 4];
+    file.read_exact(&mut magic)?;
+
+    match &magic {
+        // STREAMINFO comes first: rate (20 bits), channels - 1 (3 bits), bits - 1 (5 bits) and
+        // the number of frames (36 bits) follow the block and frame sizes
+        b"fLaC" => {
+            let mut header = [0u8; 22];
+            file.read_exact(&mut header)?;
+            if header[0] & 0x7f != 0 {
+                anyhow::bail!("{:?} doesn't start with a FLAC STREAMINFO block", path);
+            }
+            let channel_count = ((header[16] >> 1) & 7) as u32 + 1;
+            let frames = ((header[17] & 0x0f) as u64) << 32
+                | u32::from_be_bytes([header[18], header[19], header[20], header[21]]) as u64;
+            Ok((channel_count, frames))
+        }
+        b"RIFF" | b"RF64" => {
+            file.seek(SeekFrom::Start(12))?;
+
+            let mut channel_count = 0;
+            let mut block_align = 0;
+            let mut ds64_size = None;
+
+            loop {
+                let mut chunk = [0u8; 8];
+                file.read_exact(&mut chunk)?;
+                let size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as u64;
+
+                match &chunk[0..4] {
+                    b"fmt " => {
+                        let mut fmt = [0u8; 16];
+                        file.read_exact(&mut fmt)?;
+                        channel_count = u16::from_le_bytes([fmt[2], fmt[3]]) as u32;
+                        block_align = u16::from_le_bytes([fmt[12], fmt[13]]) as u64;
+                        file.seek(SeekFrom::Current(size as i64 - 16 + (size & 1) as i64))?;
+                    }
+                    // RF64 stores the real size of the data chunk here
+                    b"ds64" => {
+                        let mut ds64 = [0u8; 16];
+                        file.read_exact(&mut ds64)?;
+                        ds64_size = Some(u64::from_le_bytes(ds64[8..16].try_into()?));
+                        file.seek(SeekFrom::Current(size as i64 - 16 + (size & 1) as i64))?;
+                    }
+                    b"data" => {
+                        if block_align == 0 {
+                            anyhow::bail!("{:?} has no fmt chunk before the audio", path);
+                        }
+                        let size = ds64_size.unwrap_or(size);
+                        return Ok((channel_count, size / block_align));
+                    }
+                    _ => {
+                        file.seek(SeekFrom::Current((size + (size & 1)) as i64))?;
+                    }
+                }
+            }
+        }
+        _ => anyhow::bail!("{:?} isn't a wav or FLAC file", path),
+    }
+}
+
+/// Tracks for the stems of a song that were written (or kept from an earlier run) in
+/// `relative_dir` of the output directory. Stems that can't be read are left out
+pub fn session_tracks(
+    outputs: &[OutputEntry],
+    output_dir: &Path,
+    relative_dir: &Path,
+    metadata: &SongMetadata,
+) -> Vec<SessionTrack> {
+    let output_dir = output_dir
+        .canonicalize()
+        .unwrap_or_else(|_| output_dir.to_owned());
+    let mut names = HashSet::new();
+
+    outputs
+        .iter()
+        .filter(|o| matches!(o.status, OutputStatus::Written | OutputStatus::Existing))
+        .filter(|o| Path::new(&o.filename).parent() == Some(relative_dir))
+        .filter_map(|output| {
+            let path = output_dir.join(&output.filename);
+            let (channel_count, frames) = match audio_file_layout(&path) {
+                Ok(layout) => layout,
+                Err(e) => {
+                    log::warn!("Leaving {:?} out of the session: {}", path, e);
+                    return None;
+                }
+            };
+
+            // DAWs refer to tracks by name so they have to be unique
+            let base = track_name(metadata, output.channel, output.instrument);
+            let mut name = base.clone();
+            let mut index = 2;
+            while !names.insert(name.clone()) {
+                name = format!("{} {}", base, index);
+                index += 1;
+            }
+
+            Some(SessionTrack {
+                name,
+                path,
+                channel_count,
+                frames,
+            })
+        })
+        .collect()
+}
+
+/// Text for XML attributes and elements
+pub fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters aren't allowed in XML 1.0
+            c if (c as u32) < 0x20 && !matches!(c, '\t' | '\n' | '\r') => escaped.push(' '),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
@@ -0,0 +1,185 @@
+use crate::backend::Backend;
+use crate::tags::SongMetadata;
+use crate::{RenderParams, SongInfo, Subsong, MAX_CHANNELS};
+
+/// SID tunes play forever so each tune is rendered for this long (before --repeat-count)
+pub const TUNE_SECONDS: f32 = 180.0;
+
+/// 3 voices and the sample (digi) channel per SID chip
+const CHANNELS_PER_SID: u32 = 4;
+
+// Size of the version 1 header
+const HEADER_SIZE: usize = 0x76;
+
+/// Header of a PSID or RSID file
+#[derive(Debug, Clone)]
+pub struct SidHeader {
+    /// RSID tunes need a real C64 environment
+    pub rsid: bool,
+    pub songs: u32,
+    /// First tune, counted from 1
+    pub start_song: u32,
+    pub name: String,
+    pub author: String,
+    pub released: String,
+    /// 1 to 3 SID chips
+    pub sid_count: u32,
+}
+
+/// Strings in the header are Latin-1 padded with zeros
+fn latin1(data: &[u8]) -> String {
+    data.iter()
+        .take_while(|&&c| c != 0)
+        .map(|&c| c as char)
+        .collect::<String>()
+        .trim()
+        .to_owned()
+}
+
+impl SidHeader {
+    pub fn parse(data: &[u8]) -> Option<SidHeader> {
+        if data.len() < HEADER_SIZE || !(data.starts_with(b"PSID") || data.starts_with(b"RSID")) {
+            return None;
+        }
+
+        let word = |offset: usize| u16::from_be_bytes([data[offset], data[offset + 1]]) as u32;
+        let version = word(0x04);
+
+        // Version 3 and 4 headers have the addresses of a second and third SID
+        let mut sid_count = 1;
+        if version >= 3 && data.len() >= 0x7c {
+            sid_count += (data[0x7a] != 0) as u32;
+            sid_count += (version >= 4 && data[0x7b] != 0) as u32;
+        }
+
+        let songs = word(0x0e).max(1);
+
+        Some(SidHeader {
+            rsid: data.starts_with(b"RSID"),
+            songs,
+            start_song: word(0x10).clamp(1, songs),
+            name: latin1(&data[0x16..0x36]),
+            author: latin1(&data[0x36..0x56]),
+            released: latin1(&data[0x56..0x76]),
+            sid_count,
+        })
+    }
+
+    /// Voices of all SID chips, each followed by the sample channel of the chip
+    pub fn song_info(&self) -> SongInfo {
+        SongInfo {
+            channel_count: self.sid_count * CHANNELS_PER_SID,
+            instrument_count: 1,
+            duration_seconds: TUNE_SECONDS,
+            estimated_seconds: TUNE_SECONDS,
+            channel_has_notes: [true; MAX_CHANNELS],
+        }
+    }
+
+    pub fn subsongs(&self) -> Vec<Subsong> {
+        (0..self.songs)
+            .map(|index| Subsong {
+                index: index as i32,
+                name: if index + 1 == self.start_song {
+                    format!("Tune {} (start tune)", index + 1)
+                } else {
+                    format!("Tune {}", index + 1)
+                },
+                duration_seconds: TUNE_SECONDS,
+            })
+            .collect()
+    }
+
+    pub fn metadata(&self, source: &str) -> SongMetadata {
+        let mut message = String::new();
+        if !self.author.is_empty() {
+            message += &format!("Author: {}\n", self.author);
+        }
+        if !self.released.is_empty() {
+            message += &format!("Released: {}\n", self.released);
+        }
+
+        SongMetadata {
+            title: self.name.clone(),
+            format: "sid".to_owned(),
+            format_long: if self.rsid { "RSID" } else { "PSID" }.to_owned(),
+            tracker: self.author.clone(),
+            artist: self.author.clone(),
+            message: message.trim_end().to_owned(),
+            source: source.to_owned(),
+            ..Default::default()
+        }
+    }
+}
+
+// Has to match the struct in the C code
+#[repr(C)]
+struct SidRenderParams {
+    sample_rate: u32,
+    stereo_output: bool,
+    channel_to_play: i32, // if -1 play all voices, otherwise sid * 4 + voice where voice 3 is samples
+    tune: u32,            // counted from 1, 0 plays the start tune
+    skip_frames: u32,     // frames played and thrown away before the output starts
+}
+
+extern "C" {
+    fn sid_render_c(
+        output: *mut i16,
+        max_frames: u32,
+        input_data: *const u8,
+        input_len: u32,
+        params: *const SidRenderParams,
+    ) -> u32;
+}
+
+/// libsidplayfp (reSIDfp emulation) for C64 SID tunes. The voices can be soloed but there are
+/// no instruments or per voice gain and pan
+pub struct Sid;
+
+impl Backend for Sid {
+    fn render(&self, output: &mut [u8], input: &[u8], render_params: &RenderParams) -> u32 {
+        let channel_count = if render_params.stereo_output { 2 } else { 1 };
+        let rate = render_params.sample_rate as f32;
+
+        // Tunes never end so the song length is used unless the render is limited already
+        let mut max_frames = output.len() / (4 * channel_count);
+        if render_params.max_frames != 0 {
+            max_frames = max_frames.min(render_params.max_frames as usize);
+        } else if render_params.repeat_count >= 0 {
+            let passes = render_params.repeat_count as f32 + 1.0;
+            let frames = (TUNE_SECONDS - render_params.start_seconds).max(0.0) * passes * rate;
+            max_frames = max_frames.min(frames as usize);
+        }
+
+        let params = SidRenderParams {
+            sample_rate: render_params.sample_rate,
+            stereo_output: render_params.stereo_output,
+            channel_to_play: render_params.channel_to_play,
+            tune: (render_params.subsong + 1).max(0) as u32,
+            skip_frames: (render_params.start_seconds * rate) as u32,
+        };
+
+        let mut samples = vec![0i16; max_frames * channel_count];
+        let frames = unsafe {
+            sid_render_c(
+                samples.as_mut_ptr(),
+                max_frames as u32,
+                input.as_ptr(),
+                input.len() as u32,
+                &params,
+            )
+        } as usize;
+
+        let gain = 10f32.powf(render_params.master_gain_millibel as f32 / 2000.0);
+        for (i, sample) in samples[..frames * channel_count].iter().enumerate() {
+            let value = *sample as f32 / 32768.0 * gain;
+            output[i * 4..i * 4 + 4].copy_from_slice(&value.to_ne_bytes());
+        }
+
+        (frames * channel_count * 4) as u32
+    }
+
+    fn can_solo_instruments(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,40 @@
+use std::{path::Path, sync::Mutex, time::Instant};
+
+/// What happened while a song was rendered: the parameters, how long each part took and any
+/// warnings or errors. Written next to the outputs with --song-log
+#[derive(Debug)]
+pub struct SongLog {
+    started: Instant,
+    lines: Mutex<Vec<String>>,
+}
+
+impl SongLog {
+    pub fn new() -> SongLog {
+        SongLog {
+            started: Instant::now(),
+            lines: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Add a line, prefixed with the time since the song was started
+    pub fn note(&self, text: &str) {
+        let line = format!("[{:9.3}s] {}", self.seconds(), text);
+        self.lines.lock().unwrap().push(line);
+    }
+
+    /// Seconds since the song was started
+    pub fn seconds(&self) -> f64 {
+        self.started.elapsed().as_secs_f64()
+    }
+
+    pub fn write(&self, path: &Path) -> std::io::Result<()> {
+        let text: String = self
+            .lines
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|line| format!("{}\n", line))
+            .collect();
+        std::fs::write(path, text)
+    }
+}
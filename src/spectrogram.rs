@@ -0,0 +1,137 @@
+//! Spectrogram images of renders, written as PNGs next to the stems with --spectrogram
+
+use anyhow::Result;
+use realfft::RealFftPlanner;
+use std::{fs::File, io::BufWriter, path::Path};
+
+const WIDTH: usize = 1024;
+const HEIGHT: usize = 512;
+
+/// Long enough to tell apart the low notes of a bass line at 48 kHz
+const FFT_SIZE: usize = 4096;
+
+/// The frequency axis is logarithmic from here up to half the sample rate
+const MIN_FREQUENCY: f32 = 20.0;
+
+/// Levels (in dBFS) from black to white
+const FLOOR_DB: f32 = -100.0;
+
+/// Colours from silence to full scale, spaced evenly over the level range
+const PALETTE: [[f32; 3]; 6] = [
+    [0.0, 0.0, 0.0],
+    [32.0, 0.0, 96.0],
+    [128.0, 0.0, 128.0],
+    [224.0, 32.0, 0.0],
+    [255.0, 160.0, 0.0],
+    [255.0, 255.0, 200.0],
+];
+
+/// Time runs from left to right over the whole render and frequency from the bottom up
+pub struct Spectrogram {
+    /// RGB, a row at a time from the top
+    pixels: Vec<u8>,
+}
+
+fn color(db: f32) -> [u8; 3] {
+    let position = ((db - FLOOR_DB) / -FLOOR_DB).clamp(0.0, 1.0) * (PALETTE.len() - 1) as f32;
+    let index = (position as usize).min(PALETTE.len() - 2);
+    let t = position - index as f32;
+    let (from, to) = (PALETTE[index], PALETTE[index + 1]);
+
+    [0, 1, 2].map(|c| (from[c] + (to[c] - from[c]) * t).round() as u8)
+}
+
+impl Spectrogram {
+    /// Analyze an interleaved render, with the channels mixed together
+    pub fn analyze(
+        buffer: &[u8],
+        bytes_per_sample: usize,
+        channel_count: usize,
+        sample_rate: u32,
+    ) -> Spectrogram {
+        let mono: Vec<f32> = if bytes_per_sample == 4 {
+            let data: &[f32] = bytemuck::cast_slice(buffer);
+            data.chunks_exact(channel_count)
+                .map(|frame| frame.iter().sum::<f32>() / channel_count as f32)
+                .collect()
+        } else {
+            let data: &[i16] = bytemuck::cast_slice(buffer);
+            data.chunks_exact(channel_count)
+                .map(|frame| {
+                    frame.iter().map(|&s| s as f32).sum::<f32>() / (channel_count as f32 * 32768.0)
+                })
+                .collect()
+        };
+
+        let fft = RealFftPlanner::<f32>::new().plan_fft_forward(FFT_SIZE);
+        let mut input = fft.make_input_vec();
+        let mut spectrum = fft.make_output_vec();
+
+        // Hann window, scaled so a full scale sine shows up at 0 dB
+        let window: Vec<f32> = (0..FFT_SIZE)
+            .map(|i| 0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / FFT_SIZE as f32).cos())
+            .collect();
+        let scale = 2.0 / window.iter().sum::<f32>();
+
+        // Bins covered by each row, from the top (highest frequency) down
+        let nyquist = sample_rate as f32 / 2.0;
+        let bin_width = sample_rate as f32 / FFT_SIZE as f32;
+        let frequency = |edge: f32| MIN_FREQUENCY * (nyquist / MIN_FREQUENCY).powf(edge);
+        let rows: Vec<(usize, usize)> = (0..HEIGHT)
+            .map(|y| {
+                let top = frequency(1.0 - y as f32 / HEIGHT as f32);
+                let bottom = frequency(1.0 - (y + 1) as f32 / HEIGHT as f32);
+                let first = ((bottom / bin_width).round() as usize).min(spectrum.len() - 1);
+                let last = ((top / bin_width).round() as usize).clamp(first + 1, spectrum.len());
+                (first, last)
+            })
+            .collect();
+
+        let mut pixels = vec![0u8; WIDTH * HEIGHT * 3];
+
+        for x in 0..WIDTH {
+            let center = x * mono.len() / WIDTH + mono.len() / (WIDTH * 2);
+
+            for (i, (sample, w)) in input.iter_mut().zip(&window).enumerate() {
+                *sample = (center + i)
+                    .checked_sub(FFT_SIZE / 2)
+                    .and_then(|frame| mono.get(frame))
+                    .map_or(0.0, |s| s * w);
+            }
+
+            if fft.process(&mut input, &mut spectrum).is_err() {
+                continue;
+            }
+
+            for (y, &(first, last)) in rows.iter().enumerate() {
+                let magnitude = spectrum[first..last]
+                    .iter()
+                    .map(|c| c.norm())
+                    .fold(0.0f32, f32::max);
+                let db = 20.0 * (magnitude * scale).max(1e-10).log10();
+                let offset = (y * WIDTH + x) * 3;
+                pixels[offset..offset + 3].copy_from_slice(&color(db));
+            }
+        }
+
+        Spectrogram { pixels }
+    }
+
+    pub fn write_png(&self, path: &Path) -> Result<()> {
+        write_rgb_png(path, WIDTH as u32, HEIGHT as u32, &self.pixels)
+    }
+}
+
+/// Write 8-bit RGB pixels (a row at a time from the top) as a PNG
+pub fn write_rgb_png(path: &Path, width: u32, height: u32, pixels: &[u8]) -> Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)?;
+    writer.finish()?;
+
+    Ok(())
+}
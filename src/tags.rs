@@ -0,0 +1,210 @@
+use crate::cover::Cover;
+use crate::loudness::ReplayGain;
+use id3::TagLike;
+
+/// Metadata of a song, read once and shared by all of its stems
+#[derive(Debug, Clone, Default)]
+pub struct SongMetadata {
+    pub title: String,
+    /// Short format name such as "xm"
+    pub format: String,
+    /// Long format name such as "FastTracker II"
+    pub format_long: String,
+    pub tracker: String,
+    /// Composer as stored in the song, empty for most formats
+    pub artist: String,
+    /// Song message
+    pub message: String,
+    /// Instrument and sample names, one per line. Scene modules often use these for credits
+    pub sample_text: String,
+    /// Names of the instruments (or samples if the song has no instruments) in render order
+    pub instrument_names: Vec<String>,
+    pub sample_names: Vec<String>,
+    /// Filename of the module
+    pub source: String,
+    pub cover: Option<Cover>,
+}
+
+/// Tags written into a single output file
+#[derive(Debug, Clone)]
+pub struct Tags {
+    pub title: String,
+    /// Song title, so all stems of a song group together
+    pub album: String,
+    pub format: String,
+    pub tracker: String,
+    pub artist: String,
+    pub instrument: Option<String>,
+    pub channel: Option<i32>,
+    pub source: String,
+    /// Song message (full mix only)
+    pub comment: String,
+    /// Instrument and sample texts (full mix only)
+    pub description: String,
+    /// Measured after rendering with --replaygain
+    pub replay_gain: Option<ReplayGain>,
+    /// Estimated with --detect-key (full mix only), such as "Am"
+    pub key: Option<String>,
+}
+
+impl Tags {
+    /// Tags for one stem of a song. `channel` and `instrument` are -1 when not soloed
+    pub fn for_stem(metadata: &SongMetadata, channel: i32, instrument: i32) -> Tags {
+        let song_title = if metadata.title.trim().is_empty() {
+            metadata.source.clone()
+        } else {
+            metadata.title.trim().to_owned()
+        };
+
+        let instrument_name =
+            (instrument >= 0).then(
+                || match metadata.instrument_names.get(instrument as usize) {
+                    Some(name) if !name.trim().is_empty() => name.trim().to_owned(),
+                    _ => format!("Instrument {}", instrument + 1),
+                },
+            );
+
+        let title = match (channel, &instrument_name) {
+            (-1, None) => song_title.clone(),
+            (-1, Some(name)) => format!("{} ({})", song_title, name),
+            (channel, None) => format!("{} (Channel {})", song_title, channel),
+            (channel, Some(name)) => format!("{} ({}, Channel {})", song_title, name, channel),
+        };
+
+        let format = if metadata.format_long.is_empty() {
+            metadata.format.clone()
+        } else {
+            metadata.format_long.clone()
+        };
+
+        // The texts are the same for every stem so they are only stored with the full mix
+        let full_mix = channel == -1 && instrument == -1;
+
+        Tags {
+            title,
+            album: song_title,
+            format,
+            tracker: metadata.tracker.clone(),
+            artist: metadata.artist.trim().to_owned(),
+            instrument: instrument_name,
+            channel: (channel >= 0).then_some(channel),
+            source: metadata.source.clone(),
+            comment: if full_mix {
+                metadata.message.clone()
+            } else {
+                String::new()
+            },
+            description: if full_mix {
+                metadata.sample_text.clone()
+            } else {
+                String::new()
+            },
+            replay_gain: None,
+            key: None,
+        }
+    }
+
+    fn encoder() -> String {
+        format!("stemgen {}", env!("CARGO_PKG_VERSION"))
+    }
+
+    /// Tags as Vorbis comments (used by FLAC, Ogg Vorbis and as APEv2 items in WavPack)
+    pub fn vorbis_comments(&self) -> Vec<(String, String)> {
+        let mut comments = vec![
+            ("TITLE", self.title.clone()),
+            ("ALBUM", self.album.clone()),
+            ("ARTIST", self.artist.clone()),
+            ("SOURCE_FORMAT", self.format.clone()),
+            ("TRACKER", self.tracker.clone()),
+            ("SOURCE_FILE", self.source.clone()),
+            ("ENCODER", Self::encoder()),
+            ("COMMENT", self.comment.clone()),
+            ("DESCRIPTION", self.description.clone()),
+        ];
+
+        if let Some(instrument) = &self.instrument {
+            comments.push(("INSTRUMENT", instrument.clone()));
+        }
+
+        if let Some(channel) = self.channel {
+            comments.push(("CHANNEL", channel.to_string()));
+        }
+
+        if let Some(replay_gain) = self.replay_gain {
+            comments.push((
+                "REPLAYGAIN_TRACK_GAIN",
+                format!("{:.2} dB", replay_gain.gain),
+            ));
+            comments.push(("REPLAYGAIN_TRACK_PEAK", format!("{:.6}", replay_gain.peak)));
+        }
+
+        if let Some(key) = &self.key {
+            comments.push(("INITIALKEY", key.clone()));
+        }
+
+        comments
+            .into_iter()
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(key, value)| (key.to_owned(), value))
+            .collect()
+    }
+
+    /// Tags as an ID3v2 tag for mp3 files. The song title is used as both album and content group
+    /// (TIT1) so the stems of a song group together. Fields without a standard frame are stored as
+    /// TXXX
+    pub fn id3(&self) -> id3::Tag {
+        let mut tag = id3::Tag::new();
+
+        tag.set_title(&self.title);
+        tag.set_album(&self.album);
+        tag.set_text("TIT1", &self.album);
+
+        if !self.artist.is_empty() {
+            tag.set_artist(&self.artist);
+        }
+        tag.set_text("TSSE", Self::encoder());
+
+        if !self.source.is_empty() {
+            tag.set_text("TOFN", &self.source);
+        }
+
+        if let Some(key) = &self.key {
+            tag.set_text("TKEY", key);
+        }
+
+        if !self.comment.is_empty() {
+            tag.add_frame(id3::frame::Comment {
+                lang: "eng".to_owned(),
+                description: String::new(),
+                text: self.comment.clone(),
+            });
+        }
+
+        if !self.description.is_empty() {
+            tag.add_frame(id3::frame::Comment {
+                lang: "eng".to_owned(),
+                description: "Sample text".to_owned(),
+                text: self.description.clone(),
+            });
+        }
+
+        for (key, value) in self.vorbis_comments() {
+            if matches!(
+                key.as_str(),
+                "SOURCE_FORMAT"
+                    | "TRACKER"
+                    | "INSTRUMENT"
+                    | "CHANNEL"
+                    | "REPLAYGAIN_TRACK_GAIN"
+                    | "REPLAYGAIN_TRACK_PEAK"
+            ) {
+                tag.add_frame(id3::frame::ExtendedText {
+                    description: key,
+                    value,
+                });
+            }
+        }
+
+        tag
+    }
+}
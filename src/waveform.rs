@@ -0,0 +1,98 @@
+//! Waveform overview images of renders, written as `<stem>_waveform.png` with --waveform-png
+
+use crate::spectrogram::write_rgb_png;
+use anyhow::Result;
+use std::path::Path;
+
+const WIDTH: usize = 1024;
+/// Height of each channel, which are drawn below each other
+const CHANNEL_HEIGHT: usize = 128;
+
+const BACKGROUND: [u8; 3] = [24, 24, 32];
+const CENTER_LINE: [u8; 3] = [64, 64, 80];
+const PEAK: [u8; 3] = [64, 128, 224];
+const RMS: [u8; 3] = [160, 208, 255];
+/// Columns with samples at or beyond full scale
+const CLIPPED: [u8; 3] = [255, 64, 64];
+
+/// Peaks (the outline) and RMS level (the brighter middle) of each channel over the whole render
+pub struct Waveform {
+    height: usize,
+    /// RGB, a row at a time from the top
+    pixels: Vec<u8>,
+}
+
+impl Waveform {
+    /// Draw an interleaved render
+    pub fn analyze(buffer: &[u8], bytes_per_sample: usize, channel_count: usize) -> Waveform {
+        let converted: Vec<f32>;
+        let (samples, full_scale): (&[f32], f32) = if bytes_per_sample == 4 {
+            (bytemuck::cast_slice(buffer), 1.0)
+        } else {
+            let data: &[i16] = bytemuck::cast_slice(buffer);
+            converted = data.iter().map(|s| *s as f32 / 32768.0).collect();
+            (&converted, i16::MAX as f32 / 32768.0)
+        };
+
+        let frame_count = samples.len() / channel_count.max(1);
+        let height = CHANNEL_HEIGHT * channel_count;
+        let mut pixels: Vec<u8> = BACKGROUND.repeat(WIDTH * height);
+
+        let mut plot = |x: usize, y: usize, color: [u8; 3]| {
+            let offset = (y * WIDTH + x) * 3;
+            pixels[offset..offset + 3].copy_from_slice(&color);
+        };
+
+        // Row of a sample value within the lane of a channel, full scale at the edges
+        let row = |channel: usize, value: f32| {
+            let half = (CHANNEL_HEIGHT / 2) as f32;
+            let y = half - value.clamp(-1.0, 1.0) * (half - 1.0);
+            channel * CHANNEL_HEIGHT + (y as usize).min(CHANNEL_HEIGHT - 1)
+        };
+
+        for x in 0..WIDTH {
+            let first = x * frame_count / WIDTH;
+            let last = ((x + 1) * frame_count / WIDTH)
+                .max(first + 1)
+                .min(frame_count);
+
+            for channel in 0..channel_count {
+                plot(x, row(channel, 0.0), CENTER_LINE);
+
+                if first >= last {
+                    continue;
+                }
+
+                let mut min = 0.0f32;
+                let mut max = 0.0f32;
+                let mut energy = 0.0f32;
+
+                for frame in first..last {
+                    let sample = samples[frame * channel_count + channel];
+                    min = min.min(sample);
+                    max = max.max(sample);
+                    energy += sample * sample;
+                }
+
+                let rms = (energy / (last - first) as f32).sqrt();
+                let clipped = max >= full_scale || min <= -1.0;
+
+                for y in row(channel, max)..=row(channel, min) {
+                    plot(x, y, if clipped { CLIPPED } else { PEAK });
+                }
+
+                if !clipped {
+                    for y in row(channel, rms.min(max))..=row(channel, (-rms).max(min)) {
+                        plot(x, y, RMS);
+                    }
+                }
+            }
+        }
+
+        Waveform { height, pixels }
+    }
+
+    pub fn write_png(&self, path: &Path) -> Result<()> {
+        write_rgb_png(path, WIDTH as u32, self.height as u32, &self.pixels)
+    }
+}
@@ -0,0 +1,195 @@
+use crate::backend::Backend;
+use crate::RenderParams;
+use std::mem::MaybeUninit;
+use std::os::raw::{c_char, c_int, c_long, c_void};
+
+type XmpContext = *mut c_char;
+
+// Has to match xmp.h
+const XMP_FORMAT_MONO: c_int = 1 << 2;
+const XMP_PLAYER_MIX: c_int = 1;
+const XMP_PLAYER_INTERP: c_int = 2;
+const XMP_PLAYER_FLAGS: c_int = 4;
+const XMP_INTERP_NEAREST: c_int = 0;
+const XMP_INTERP_LINEAR: c_int = 1;
+const XMP_INTERP_SPLINE: c_int = 2;
+const XMP_FLAGS_A500: c_int = 1 << 3;
+
+// Frames rendered by libxmp per call
+const CHUNK_FRAMES: usize = 1024;
+
+// Has to match the struct in xmp.h
+#[repr(C)]
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+struct XmpSequence {
+    entry_point: c_int,
+    duration: c_int,
+}
+
+// Has to match the struct in xmp.h
+#[repr(C)]
+#[allow(dead_code)]
+struct XmpModuleInfo {
+    md5: [u8; 16],
+    vol_base: c_int,
+    module: *mut c_void,
+    comment: *mut c_char,
+    num_sequences: c_int,
+    seq_data: *mut XmpSequence,
+}
+
+#[link(name = "xmp")]
+extern "C" {
+    fn xmp_create_context() -> XmpContext;
+    fn xmp_free_context(ctx: XmpContext);
+    fn xmp_load_module_from_memory(ctx: XmpContext, mem: *const c_void, size: c_long) -> c_int;
+    fn xmp_release_module(ctx: XmpContext);
+    fn xmp_start_player(ctx: XmpContext, rate: c_int, format: c_int) -> c_int;
+    fn xmp_end_player(ctx: XmpContext);
+    fn xmp_play_buffer(ctx: XmpContext, buffer: *mut c_void, size: c_int, loops: c_int) -> c_int;
+    fn xmp_get_module_info(ctx: XmpContext, info: *mut XmpModuleInfo);
+    fn xmp_set_position(ctx: XmpContext, pos: c_int) -> c_int;
+    fn xmp_seek_time(ctx: XmpContext, time: c_int) -> c_int;
+    fn xmp_set_player(ctx: XmpContext, param: c_int, value: c_int) -> c_int;
+    fn xmp_channel_mute(ctx: XmpContext, channel: c_int, status: c_int) -> c_int;
+    fn xmp_channel_vol(ctx: XmpContext, channel: c_int, vol: c_int) -> c_int;
+}
+
+/// libxmp, for songs where its replay is closer to the original tracker than libopenmpt. It
+/// renders 16-bit audio that is converted to float and has no pan or instrument control, so pan
+/// maps only apply their gain and instruments can't be soloed
+pub struct Xmp;
+
+impl Backend for Xmp {
+    fn render(&self, output: &mut [u8], input: &[u8], render_params: &RenderParams) -> u32 {
+        unsafe {
+            let ctx = xmp_create_context();
+            if ctx.is_null() {
+                fail!("Unable to create libxmp context");
+                return 0;
+            }
+
+            let len = if xmp_load_module_from_memory(
+                ctx,
+                input.as_ptr() as *const c_void,
+                input.len() as c_long,
+            ) == 0
+            {
+                let len = play(ctx, output, render_params);
+                xmp_release_module(ctx);
+                len
+            } else {
+                fail!("libxmp is unable to load the song");
+                0
+            };
+
+            xmp_free_context(ctx);
+            len
+        }
+    }
+
+    fn can_solo_instruments(&self) -> bool {
+        false
+    }
+}
+
+/// Play the loaded song into `output` and return the number of bytes written
+unsafe fn play(ctx: XmpContext, output: &mut [u8], params: &RenderParams) -> u32 {
+    let channel_count = if params.stereo_output { 2 } else { 1 };
+    let format = if params.stereo_output {
+        0
+    } else {
+        XMP_FORMAT_MONO
+    };
+
+    // Paula mixer for Amiga formats
+    if params.amiga_filter != 0 {
+        xmp_set_player(ctx, XMP_PLAYER_FLAGS, XMP_FLAGS_A500);
+    }
+
+    if xmp_start_player(ctx, params.sample_rate as c_int, format) != 0 {
+        fail!(
+            "libxmp is unable to play at {} Hz (supported rates are 8000 - 48000 Hz)",
+            params.sample_rate
+        );
+        return 0;
+    }
+
+    if params.subsong >= 0 {
+        let mut info = MaybeUninit::<XmpModuleInfo>::zeroed();
+        xmp_get_module_info(ctx, info.as_mut_ptr());
+        let info = info.assume_init();
+
+        if params.subsong < info.num_sequences && !info.seq_data.is_null() {
+            let sequence = *info.seq_data.add(params.subsong as usize);
+            xmp_set_position(ctx, sequence.entry_point);
+        }
+    }
+
+    // libxmp's separation goes to 100% which is 200% in libopenmpt
+    if params.stereo_separation_enabled {
+        let mix = (params.stereo_separation / 2).min(100);
+        xmp_set_player(ctx, XMP_PLAYER_MIX, mix as c_int);
+    }
+
+    match params.interpolation_filter_length {
+        1 => xmp_set_player(ctx, XMP_PLAYER_INTERP, XMP_INTERP_NEAREST),
+        2 => xmp_set_player(ctx, XMP_PLAYER_INTERP, XMP_INTERP_LINEAR),
+        4 | 8 => xmp_set_player(ctx, XMP_PLAYER_INTERP, XMP_INTERP_SPLINE),
+        _ => 0,
+    };
+
+    for channel in 0..crate::MAX_CHANNELS {
+        if params.channel_to_play >= 0 {
+            let mute = channel as i32 != params.channel_to_play;
+            xmp_channel_mute(ctx, channel as c_int, mute as c_int);
+        }
+
+        if params.channel_volume_mapped[channel] {
+            let volume = (params.channel_volume[channel] * 100.0).round();
+            xmp_channel_vol(ctx, channel as c_int, volume as c_int);
+        }
+    }
+
+    if params.start_seconds > 0.0 {
+        xmp_seek_time(ctx, (params.start_seconds * 1000.0) as c_int);
+    }
+
+    // libxmp counts the times the song is played where 0 loops forever
+    let loops = if params.repeat_count < 0 {
+        0
+    } else {
+        params.repeat_count + 1
+    };
+
+    let frame_bytes = 4 * channel_count;
+    let mut max_frames = output.len() / frame_bytes;
+    if params.max_frames != 0 {
+        max_frames = max_frames.min(params.max_frames as usize);
+    }
+
+    let gain = 10f32.powf(params.master_gain_millibel as f32 / 2000.0);
+    let mut chunk = vec![0i16; CHUNK_FRAMES * channel_count];
+    let mut frames = 0;
+
+    while frames < max_frames {
+        let size = (chunk.len() * 2) as c_int;
+        if xmp_play_buffer(ctx, chunk.as_mut_ptr() as *mut c_void, size, loops) != 0 {
+            break;
+        }
+
+        let count = (max_frames - frames).min(CHUNK_FRAMES);
+        for (i, sample) in chunk[..count * channel_count].iter().enumerate() {
+            let offset = (frames * channel_count + i) * 4;
+            let value = *sample as f32 / 32768.0 * gain;
+            output[offset..offset + 4].copy_from_slice(&value.to_ne_bytes());
+        }
+
+        frames += count;
+    }
+
+    xmp_end_player(ctx);
+
+    (frames * frame_bytes) as u32
+}
@@ -0,0 +1,259 @@
+use crate::wav_writer::{self, Bext, WavFormat};
+use anyhow::Result;
+use std::io::Write;
+
+/// File format and sample depth of an encoded stem
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Format {
+    Wav16,
+    Wav24,
+    WavFloat,
+    Flac16,
+    Flac24,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Wav16 | Format::Wav24 | Format::WavFloat => "wav",
+            Format::Flac16 | Format::Flac24 => "flac",
+        }
+    }
+}
+
+/// Convert f32 samples to 24-bit little endian PCM, 3 bytes per sample
+pub fn pack_pcm24(samples: &[f32]) -> Vec<u8> {
+    samples
+        .iter()
+        .flat_map(|s| {
+            let value = (s * 8388608.0).clamp(-8388608.0, 8388607.0) as i32;
+            let bytes = value.to_le_bytes();
+            [bytes[0], bytes[1], bytes[2]]
+        })
+        .collect()
+}
+
+/// Convert f32 samples to 32-bit little endian PCM
+pub fn pack_pcm32(samples: &[f32]) -> Vec<u8> {
+    samples
+        .iter()
+        .flat_map(|s| {
+            let value = (*s as f64 * 2147483648.0).clamp(-2147483648.0, 2147483647.0) as i32;
+            value.to_le_bytes()
+        })
+        .collect()
+}
+
+/// Rendered audio as interleaved float samples. The samples are kept as the bytes the render
+/// core wrote so they can be taken out without a copy
+#[derive(Debug, Clone)]
+pub struct AudioBuffer {
+    data: Vec<u8>,
+    pub channel_count: usize,
+    pub sample_rate: u32,
+}
+
+impl AudioBuffer {
+    pub fn new(samples: &[f32], channel_count: usize, sample_rate: u32) -> AudioBuffer {
+        AudioBuffer::from_bytes(
+            bytemuck::cast_slice(samples).to_vec(),
+            channel_count,
+            sample_rate,
+        )
+    }
+
+    /// Buffer of native endian f32 samples
+    pub fn from_bytes(data: Vec<u8>, channel_count: usize, sample_rate: u32) -> AudioBuffer {
+        AudioBuffer {
+            data,
+            channel_count,
+            sample_rate,
+        }
+    }
+
+    pub fn samples(&self) -> &[f32] {
+        bytemuck::cast_slice(&self.data)
+    }
+
+    pub fn samples_mut(&mut self) -> &mut [f32] {
+        bytemuck::cast_slice_mut(&mut self.data)
+    }
+
+    /// The samples as native endian f32 bytes
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.samples().len() / self.channel_count
+    }
+
+    /// Highest absolute sample value
+    pub fn peak(&self) -> f32 {
+        self.samples().iter().fold(0.0, |peak, s| peak.max(s.abs()))
+    }
+
+    /// Encode the samples into `writer`. Integer formats are clamped without dithering. Nothing
+    /// needs to seek so the writer can be a pipe
+    pub fn encode<W: Write>(&self, format: Format, writer: &mut W) -> Result<()> {
+        match format {
+            Format::Wav16 => self.encode_wav(writer, WavFormat::Pcm16, None),
+            Format::Wav24 => self.encode_wav(writer, WavFormat::Pcm24, None),
+            Format::WavFloat => self.encode_wav(writer, WavFormat::Float32, None),
+            Format::Flac16 => self.write_flac(writer, &self.pcm16(), 2),
+            Format::Flac24 => {
+                let clamped: Vec<f32> = self.samples().iter().map(|s| s.clamp(-1.0, 1.0)).collect();
+                self.write_flac(writer, bytemuck::cast_slice(&clamped), 4)
+            }
+        }
+    }
+
+    /// Encode the samples as wav in `format`, with a `bext` chunk if given
+    pub fn encode_wav<W: Write>(
+        &self,
+        writer: W,
+        format: WavFormat,
+        bext: Option<&Bext>,
+    ) -> Result<()> {
+        let packed;
+        let data = match format {
+            WavFormat::Pcm16 => {
+                packed = self.pcm16();
+                &packed
+            }
+            WavFormat::Pcm24 => {
+                packed = pack_pcm24(self.samples());
+                &packed
+            }
+            WavFormat::Pcm32 => {
+                packed = pack_pcm32(self.samples());
+                &packed
+            }
+            WavFormat::Float32 => &self.data,
+        };
+
+        wav_writer::write_wav(
+            writer,
+            data,
+            self.channel_count,
+            self.sample_rate,
+            format,
+            bext,
+        )?;
+
+        Ok(())
+    }
+
+    fn pcm16(&self) -> Vec<u8> {
+        self.samples()
+            .iter()
+            .flat_map(|s| ((s.clamp(-1.0, 1.0) * 32767.0).round() as i16).to_le_bytes())
+            .collect()
+    }
+
+    fn write_flac<W: Write>(
+        &self,
+        writer: &mut W,
+        data: &[u8],
+        bytes_per_sample: u32,
+    ) -> Result<()> {
+        let metadata = libflac_sys::StreamMetadata {
+            application: None,
+            picture: None,
+            comments: &[],
+        };
+
+        libflac_sys::encode_flac_to_writer(
+            writer,
+            data,
+            self.channel_count as u32,
+            bytes_per_sample,
+            self.sample_rate,
+            &metadata,
+            &libflac_sys::EncoderSettings::default(),
+        )?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pcm24_is_clamped_to_full_scale() {
+        let packed = pack_pcm24(&[0.0, 0.5, -1.0, 1.0, 2.0, -2.0]);
+
+        assert_eq!(
+            packed,
+            [
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x80, 0xff, 0xff, 0x7f, 0xff, 0xff,
+                0x7f, 0x00, 0x00, 0x80
+            ]
+        );
+    }
+
+    #[test]
+    fn pcm32_is_clamped_to_full_scale() {
+        let packed = pack_pcm32(&[0.5, 1.0, -1.0, -3.0]);
+        let values: Vec<i32> = packed
+            .chunks(4)
+            .map(|b| i32::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+
+        assert_eq!(values, [1 << 30, i32::MAX, i32::MIN, i32::MIN]);
+    }
+
+    #[test]
+    fn buffer_frames_and_peak() {
+        let mut buffer = AudioBuffer::new(&[0.25, -0.5, 0.0, 0.75], 2, 44100);
+
+        assert_eq!(buffer.frame_count(), 2);
+        assert_eq!(buffer.peak(), 0.75);
+
+        buffer.samples_mut()[1] = -1.0;
+        assert_eq!(buffer.peak(), 1.0);
+        assert_eq!(buffer.into_bytes().len(), 16);
+    }
+
+    #[test]
+    fn encode_wav_formats() {
+        let buffer = AudioBuffer::new(&[0.5, -0.5, 1.5], 1, 8000);
+
+        let mut wav16 = Vec::new();
+        buffer.encode(Format::Wav16, &mut wav16).unwrap();
+        assert_eq!(&wav16[..4], b"RIFF");
+        assert_eq!(wav16.len() % 2, 0);
+        let data: Vec<i16> = wav16[wav16.len() - 6..]
+            .chunks(2)
+            .map(|b| i16::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        assert_eq!(data, [16384, -16384, 32767]);
+
+        let mut float = Vec::new();
+        buffer.encode(Format::WavFloat, &mut float).unwrap();
+        assert!(float.ends_with(bytemuck::cast_slice(buffer.samples())));
+
+        let mut wav24 = Vec::new();
+        buffer.encode(Format::Wav24, &mut wav24).unwrap();
+        // 9 bytes of samples padded to an even size
+        assert_eq!(
+            wav24[wav24.len() - 10..wav24.len() - 1],
+            pack_pcm24(buffer.samples())
+        );
+    }
+
+    #[test]
+    fn encode_flac_to_a_stream() {
+        let samples: Vec<f32> = (0..4000).map(|i| (i as f32 * 0.01).sin() * 0.5).collect();
+        let buffer = AudioBuffer::new(&samples, 2, 48000);
+
+        for format in [Format::Flac16, Format::Flac24] {
+            let mut flac = Vec::new();
+            buffer.encode(format, &mut flac).unwrap();
+            assert_eq!(&flac[..4], b"fLaC");
+            assert_eq!(format.extension(), "flac");
+        }
+    }
+}
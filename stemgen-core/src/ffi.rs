@@ -0,0 +1,337 @@
+//! Interface to the C++ code in external/libopenmpt/interface.cpp. Everything here has to match
+//! the C side
+
+use std::ffi::c_void;
+
+// Has to match MAX_CHANNELS in the C code
+pub const MAX_CHANNELS: usize = 128;
+
+// Has to match SUBSONG_ALL in the C code
+pub const SUBSONG_ALL: i32 = -2;
+
+// Max number of subsongs fetched from the C code
+pub const MAX_SUBSONGS: usize = 256;
+
+// Max number of instrument names fetched from the C code
+pub const MAX_INSTRUMENTS: usize = 256;
+
+// Max number of order positions fetched from the C code
+pub const MAX_ORDERS: usize = 1024;
+
+// Max number of tempo changes fetched from the C code. Tempo slides change it every tick
+pub const MAX_TEMPO_CHANGES: usize = 8192;
+
+// Max number of patterns fetched from the C code, same as libopenmpt supports
+pub const MAX_PATTERNS: usize = 4000;
+
+// Has to match PathChar in the C code. Paths are passed NUL terminated in the native encoding of
+// the OS: bytes on Unix and UTF-16 on Windows
+#[cfg(windows)]
+pub type PathChar = u16;
+#[cfg(not(windows))]
+pub type PathChar = u8;
+
+// Has to match the struct in the C code
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SongInfo {
+    pub channel_count: u32,
+    pub instrument_count: u32,
+    pub duration_seconds: f32,
+    /// Row counting estimate of the duration. Only worked out (otherwise 0) when
+    /// `duration_seconds` looks broken
+    pub estimated_seconds: f32,
+    pub channel_has_notes: [bool; MAX_CHANNELS],
+}
+
+impl SongInfo {
+    /// Channels that never trigger a note or instrument in the song
+    pub fn empty_channels(&self) -> Vec<u32> {
+        (0..self.channel_count)
+            .filter(|&c| {
+                !self
+                    .channel_has_notes
+                    .get(c as usize)
+                    .copied()
+                    .unwrap_or(true)
+            })
+            .collect()
+    }
+
+    /// Duration used for buffer sizing. libopenmpt's reported duration is used if it looks sane,
+    /// otherwise the row counting estimate from the C code is used. Both are capped at `max_seconds`
+    pub fn checked_duration(&self, max_seconds: f32) -> Option<f32> {
+        let reported = self.duration_seconds;
+        let estimated = self.estimated_seconds.min(max_seconds);
+
+        if reported.is_finite() && reported > 0.0 && reported <= max_seconds {
+            return Some(reported);
+        }
+
+        log::warn!(
+            "Reported duration {} seconds looks invalid, using estimated {} seconds",
+            reported,
+            estimated
+        );
+
+        if estimated > 0.0 {
+            Some(estimated)
+        } else {
+            None
+        }
+    }
+}
+
+// Has to match the struct in the C code
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct RenderParams {
+    pub sample_rate: u32,
+    pub bytes_per_sample: u32,
+    pub channel_to_play: i32, // if -1 use all channels, otherwise pick one channel
+    pub instrument_to_play: i32, // if -1 use all instruments, otherwise pick one
+    pub stereo_separation: u32,
+    pub stereo_separation_enabled: bool,
+    pub stereo_output: bool,
+    pub repeat_count: i32,  // passed to libopenmpt, -1 loops forever
+    pub max_frames: u32,    // if 0 render until the song ends or the buffer is full
+    pub start_seconds: f32, // position to seek to before rendering
+    pub subsong: i32,       // if -1 use the default subsong, otherwise select this one
+    pub dither_seed: u32,
+    pub dither_seed_enabled: bool, // if false the dither noise differs between runs
+    pub interpolation_filter_length: i32, // if 0 use libopenmpt's default
+    pub master_gain_millibel: i32,
+    pub channel_pan: [f32; MAX_CHANNELS], // -1.0 to 1.0, only used where channel_pan_mapped is set
+    pub channel_volume: [f32; MAX_CHANNELS], // 0.0 to 1.0, only used where channel_volume_mapped is set
+    pub channel_pan_mapped: [bool; MAX_CHANNELS],
+    pub channel_volume_mapped: [bool; MAX_CHANNELS],
+    pub amiga_filter: i32, // if 0 use libopenmpt's default, otherwise a filter index of the C code
+}
+
+// Has to match the struct in the C code
+#[repr(C)]
+pub struct SubsongInfoC {
+    pub name: [u8; 256],
+    pub duration_seconds: f32,
+}
+
+// Has to match the struct in the C code
+#[repr(C)]
+pub struct SongMetadataC {
+    pub title: [u8; 256],
+    pub format: [u8; 16],
+    pub format_long: [u8; 256],
+    pub tracker: [u8; 256],
+    pub artist: [u8; 256],
+    pub message: [u8; 16384],
+    pub sample_text: [u8; 16384],
+    pub sample_name_count: u32,
+}
+
+// Has to match the struct in the C code
+#[repr(C)]
+pub struct ItemNameC {
+    pub name: [u8; 256],
+}
+
+// Has to match the struct in the C code
+#[repr(C)]
+pub struct OrderTimeC {
+    pub order: u32,
+    pub pattern: u32,
+    pub seconds: f32,
+}
+
+// Has to match the struct in the C code
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct TempoChangeC {
+    pub seconds: f64,
+    pub bpm: f64,
+    pub speed: u32,
+    pub tempo: f32,
+    pub rows_per_beat: u32,
+}
+
+// Has to match the struct in the C code
+#[repr(C)]
+pub struct PatternSummaryC {
+    pub channel_count: u32,
+    pub order_count: u32,
+    pub initial_speed: u32,
+    pub initial_tempo: f32,
+    pub rows_per_beat: u32,
+    pub rows_per_measure: u32,
+}
+
+// Has to match the struct in the C code
+#[repr(C)]
+pub struct PatternInfoC {
+    pub name: [u8; 256],
+    pub rows: u32, // 0 for pattern numbers that aren't used
+}
+
+// Has to match the struct in the C code
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct PatternCellC {
+    pub note: u8, // 0 for none, 1 to 120 (61 is middle C), 253 to 255 for note fade, cut and off
+    pub instrument: u8,
+    pub volume: u8,         // parameter of the volume command
+    pub param: u8,          // parameter of the effect
+    pub note_name: [u8; 8], // as shown in the tracker, such as "C-5" or "==="
+    pub volume_command: u8, // letter of the volume column command, 0 for none
+    pub effect: u8,         // letter of the effect, 0 for none
+}
+
+// Has to match MAX_ENVELOPE_POINTS in the C code
+pub const MAX_ENVELOPE_POINTS: usize = 240;
+
+// Has to match the struct in the C code
+#[repr(C)]
+pub struct EnvelopeInfoC {
+    pub ticks: [u16; MAX_ENVELOPE_POINTS],
+    pub values: [u8; MAX_ENVELOPE_POINTS], // 0 to 64, 32 is the centre of panning and pitch envelopes
+    pub point_count: u32,
+    pub enabled: bool,
+    pub looped: bool,
+    pub sustain: bool,
+    pub carry: bool,
+    pub filter: bool, // the pitch envelope controls the filter cutoff instead
+    pub loop_start: u8,
+    pub loop_end: u8,
+    pub sustain_start: u8,
+    pub sustain_end: u8,
+}
+
+// Has to match the struct in the C code
+#[repr(C)]
+pub struct InstrumentInfoC {
+    pub name: [u8; 256],
+    pub fadeout: u32,
+    pub global_volume: u32, // 0 to 64
+    pub panning: i32,       // 0 to 256, -1 if the instrument doesn't set a panning
+    pub new_note_action: u8,
+    pub duplicate_check: u8,
+    pub duplicate_action: u8,
+    pub pitch_pan_separation: i8,
+    pub pitch_pan_center: u8, // MIDI note
+    pub random_volume: u8,
+    pub random_pan: u8,
+    pub sample_map: [u16; 128], // sample played by each MIDI note, 0 for none
+    pub note_map: [u8; 128],    // MIDI note the sample is played at
+    pub volume_envelope: EnvelopeInfoC,
+    pub panning_envelope: EnvelopeInfoC,
+    pub pitch_envelope: EnvelopeInfoC,
+}
+
+// Has to match the struct in the C code
+#[repr(C)]
+pub struct SampleInfoC {
+    pub name: [u8; 256],
+    pub length: u32,
+    pub loop_start: u32,
+    pub loop_end: u32,
+    pub sustain_start: u32,
+    pub sustain_end: u32,
+    pub c5_speed: u32,
+    pub volume: u16,        // 0 to 256
+    pub global_volume: u16, // 0 to 64
+    pub panning: i32,       // 0 to 256, -1 if the sample doesn't set a panning
+    pub relative_tone: i8,
+    pub finetune: i8,
+    pub looped: bool,
+    pub ping_pong_loop: bool,
+    pub sustain_loop: bool,
+    pub ping_pong_sustain_loop: bool,
+    pub vibrato_type: u8,
+    pub vibrato_sweep: u8,
+    pub vibrato_depth: u8,
+    pub vibrato_rate: u8,
+}
+
+extern "C" {
+    pub fn get_song_info_c(
+        data: *const u8,
+        len: u32,
+        sample_output_path: *const PathChar,
+        sample_format: u32,
+        max_seconds: f32,
+    ) -> SongInfo;
+    pub fn get_subsongs_c(
+        data: *const u8,
+        len: u32,
+        output: *mut SubsongInfoC,
+        max_count: u32,
+    ) -> u32;
+    pub fn get_song_metadata_c(
+        data: *const u8,
+        len: u32,
+        output: *mut SongMetadataC,
+        instrument_names: *mut ItemNameC,
+        sample_names: *mut ItemNameC,
+        max_count: u32,
+    ) -> u32;
+    pub fn get_instruments_c(
+        data: *const u8,
+        len: u32,
+        instruments: *mut InstrumentInfoC,
+        max_instruments: u32,
+        samples: *mut SampleInfoC,
+        max_samples: u32,
+        sample_count: *mut u32,
+    ) -> u32;
+    pub fn get_patterns_c(
+        data: *const u8,
+        len: u32,
+        summary: *mut PatternSummaryC,
+        patterns: *mut PatternInfoC,
+        max_patterns: u32,
+        orders: *mut i32,
+        max_orders: u32,
+    ) -> u32;
+    pub fn get_pattern_cells_c(
+        data: *const u8,
+        len: u32,
+        cells: *mut PatternCellC,
+        max_cells: u32,
+    ) -> u32;
+    pub fn get_order_times_c(
+        data: *const u8,
+        len: u32,
+        subsong: i32,
+        output: *mut OrderTimeC,
+        max_count: u32,
+    ) -> u32;
+    pub fn get_tempo_map_c(
+        data: *const u8,
+        len: u32,
+        subsong: i32,
+        output: *mut TempoChangeC,
+        max_count: u32,
+    ) -> u32;
+    pub fn song_render_c(
+        output: *mut u8,
+        output_len: u32,
+        input_data: *const u8,
+        input_len: u32,
+        params: *const RenderParams,
+    ) -> u32;
+    pub fn song_open_c(
+        input_data: *const u8,
+        input_len: u32,
+        params: *const RenderParams,
+    ) -> *mut c_void;
+    pub fn song_read_c(stream: *mut c_void, output: *mut u8, max_frames: u32) -> u32;
+    pub fn song_frame_count_c(stream: *mut c_void) -> u64;
+    pub fn render_frame_count_c(duration_seconds: f64, params: *const RenderParams) -> u64;
+    pub fn song_close_c(stream: *mut c_void);
+    pub fn probe_song_c(data: *const u8, len: u32) -> bool;
+    pub fn get_supported_extensions_c(output: *mut u8, size: u32) -> u32;
+}
+
+/// String from a zero terminated buffer filled in by the C code
+pub fn c_string(buffer: &[u8]) -> String {
+    let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    String::from_utf8_lossy(&buffer[..len]).trim().to_owned()
+}
@@ -0,0 +1,277 @@
+//! Instrument and sample settings written as `<song>_instruments.json` next to the samples from
+//! --song-samples, so instruments can be rebuilt in other samplers
+
+use crate::ffi::{
+    c_string, get_instruments_c, EnvelopeInfoC, InstrumentInfoC, SampleInfoC, MAX_INSTRUMENTS,
+};
+use anyhow::Result;
+use serde::Serialize;
+use std::{fs::File, io::BufWriter, path::Path};
+
+/// What happens to a playing note when a new note is played in the same channel
+#[derive(Serialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum NewNoteAction {
+    NoteCut,
+    Continue,
+    NoteOff,
+    NoteFade,
+}
+
+/// Which earlier notes count as duplicates of a new note
+#[derive(Serialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateCheck {
+    None,
+    Note,
+    Sample,
+    Instrument,
+    Plugin,
+}
+
+/// What happens to the duplicates found by the duplicate check
+#[derive(Serialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateAction {
+    Cut,
+    Off,
+    Fade,
+}
+
+/// Point of an envelope. Values are 0 to 64, panning and pitch envelopes have their centre at 32
+#[derive(Serialize, Debug)]
+pub struct EnvelopePoint {
+    pub tick: u16,
+    pub value: u8,
+}
+
+/// Loops and sustain loops are given as indices into the points
+#[derive(Serialize, Debug)]
+pub struct Envelope {
+    pub enabled: bool,
+    pub points: Vec<EnvelopePoint>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub loop_points: Option<(u8, u8)>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sustain_points: Option<(u8, u8)>,
+    /// Keep the envelope position when a new note is played with the instrument
+    pub carry: bool,
+    /// The pitch envelope controls the filter cutoff instead of the pitch
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub filter: bool,
+}
+
+/// Sample and note played by a MIDI note
+#[derive(Serialize, Debug)]
+pub struct KeyMapping {
+    pub key: u8,
+    /// 1 based sample number
+    pub sample: u16,
+    pub note: u8,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Instrument {
+    /// 1 based instrument number as used in the patterns
+    pub index: u32,
+    pub name: String,
+    pub global_volume: u32,
+    /// 0 (left) to 256 (right). Not set if the instrument uses the panning of the channel or sample
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub panning: Option<u32>,
+    /// Volume fade speed after note off
+    pub fadeout: u32,
+    pub new_note_action: NewNoteAction,
+    pub duplicate_check: DuplicateCheck,
+    pub duplicate_action: DuplicateAction,
+    /// Panning spread across the keys (-32 to 32) around `pitch_pan_center`
+    pub pitch_pan_separation: i8,
+    pub pitch_pan_center: u8,
+    /// Random variation of the volume in percent
+    pub random_volume: u8,
+    /// Random variation of the panning (0 to 64)
+    pub random_pan: u8,
+    /// Keys that play a sample. Keys and notes are MIDI note numbers (60 is middle C)
+    pub keys: Vec<KeyMapping>,
+    pub volume_envelope: Envelope,
+    pub panning_envelope: Envelope,
+    pub pitch_envelope: Envelope,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Sample {
+    /// 1 based sample number
+    pub index: u32,
+    pub name: String,
+    /// Length in frames
+    pub length: u32,
+    /// Playback rate of middle C
+    pub c5_speed: u32,
+    /// Default volume 0 to 64
+    pub volume: u16,
+    pub global_volume: u16,
+    /// 0 (left) to 256 (right). Not set if the sample uses the panning of the channel
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub panning: Option<u32>,
+    /// Transpose in semitones (MOD and XM)
+    pub relative_tone: i8,
+    /// Finetune in 1/128th of a semitone (MOD and XM)
+    pub finetune: i8,
+    /// Start and end frame of the loop, end is exclusive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub loop_points: Option<(u32, u32)>,
+    pub ping_pong_loop: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sustain_loop_points: Option<(u32, u32)>,
+    pub ping_pong_sustain_loop: bool,
+    pub vibrato_type: u8,
+    pub vibrato_sweep: u8,
+    pub vibrato_depth: u8,
+    pub vibrato_rate: u8,
+}
+
+/// All instruments and samples of a song. Songs that only use samples (such as MODs) have no
+/// instruments
+#[derive(Serialize, Debug)]
+pub struct SongInstruments {
+    pub instruments: Vec<Instrument>,
+    pub samples: Vec<Sample>,
+}
+
+impl Envelope {
+    fn from_c(envelope: &EnvelopeInfoC) -> Envelope {
+        let count = (envelope.point_count as usize).min(envelope.ticks.len());
+
+        Envelope {
+            enabled: envelope.enabled,
+            points: envelope.ticks[..count]
+                .iter()
+                .zip(&envelope.values)
+                .map(|(&tick, &value)| EnvelopePoint { tick, value })
+                .collect(),
+            loop_points: envelope
+                .looped
+                .then_some((envelope.loop_start, envelope.loop_end)),
+            sustain_points: envelope
+                .sustain
+                .then_some((envelope.sustain_start, envelope.sustain_end)),
+            carry: envelope.carry,
+            filter: envelope.filter,
+        }
+    }
+}
+
+impl Instrument {
+    fn from_c(index: u32, instrument: &InstrumentInfoC) -> Instrument {
+        Instrument {
+            index,
+            name: c_string(&instrument.name),
+            global_volume: instrument.global_volume,
+            panning: u32::try_from(instrument.panning).ok(),
+            fadeout: instrument.fadeout,
+            new_note_action: match instrument.new_note_action {
+                1 => NewNoteAction::Continue,
+                2 => NewNoteAction::NoteOff,
+                3 => NewNoteAction::NoteFade,
+                _ => NewNoteAction::NoteCut,
+            },
+            duplicate_check: match instrument.duplicate_check {
+                1 => DuplicateCheck::Note,
+                2 => DuplicateCheck::Sample,
+                3 => DuplicateCheck::Instrument,
+                4 => DuplicateCheck::Plugin,
+                _ => DuplicateCheck::None,
+            },
+            duplicate_action: match instrument.duplicate_action {
+                1 => DuplicateAction::Off,
+                2 => DuplicateAction::Fade,
+                _ => DuplicateAction::Cut,
+            },
+            pitch_pan_separation: instrument.pitch_pan_separation,
+            pitch_pan_center: instrument.pitch_pan_center,
+            random_volume: instrument.random_volume,
+            random_pan: instrument.random_pan,
+            keys: (0..128u8)
+                .zip(instrument.sample_map.iter().zip(&instrument.note_map))
+                .filter(|(_, (&sample, _))| sample != 0)
+                .map(|(key, (&sample, &note))| KeyMapping { key, sample, note })
+                .collect(),
+            volume_envelope: Envelope::from_c(&instrument.volume_envelope),
+            panning_envelope: Envelope::from_c(&instrument.panning_envelope),
+            pitch_envelope: Envelope::from_c(&instrument.pitch_envelope),
+        }
+    }
+}
+
+impl Sample {
+    fn from_c(index: u32, sample: &SampleInfoC) -> Sample {
+        Sample {
+            index,
+            name: c_string(&sample.name),
+            length: sample.length,
+            c5_speed: sample.c5_speed,
+            volume: sample.volume / 4,
+            global_volume: sample.global_volume,
+            panning: u32::try_from(sample.panning).ok(),
+            relative_tone: sample.relative_tone,
+            finetune: sample.finetune,
+            loop_points: sample
+                .looped
+                .then_some((sample.loop_start, sample.loop_end)),
+            ping_pong_loop: sample.ping_pong_loop,
+            sustain_loop_points: sample
+                .sustain_loop
+                .then_some((sample.sustain_start, sample.sustain_end)),
+            ping_pong_sustain_loop: sample.ping_pong_sustain_loop,
+            vibrato_type: sample.vibrato_type,
+            vibrato_sweep: sample.vibrato_sweep,
+            vibrato_depth: sample.vibrato_depth,
+            vibrato_rate: sample.vibrato_rate,
+        }
+    }
+}
+
+impl SongInstruments {
+    /// Read the instruments and samples of a song libopenmpt can load
+    pub fn read(file_data: &[u8]) -> SongInstruments {
+        // All zeroes is valid for the C structs (empty names, envelopes and maps)
+        let mut instruments: Vec<InstrumentInfoC> = (0..MAX_INSTRUMENTS)
+            .map(|_| unsafe { std::mem::zeroed() })
+            .collect();
+        let mut samples: Vec<SampleInfoC> = (0..MAX_INSTRUMENTS)
+            .map(|_| unsafe { std::mem::zeroed() })
+            .collect();
+        let mut sample_count = 0;
+
+        let count = unsafe {
+            get_instruments_c(
+                file_data.as_ptr(),
+                file_data.len() as u32,
+                instruments.as_mut_ptr(),
+                instruments.len() as u32,
+                samples.as_mut_ptr(),
+                samples.len() as u32,
+                &mut sample_count,
+            )
+        };
+
+        SongInstruments {
+            instruments: instruments[..count as usize]
+                .iter()
+                .zip(1..)
+                .map(|(instrument, index)| Instrument::from_c(index, instrument))
+                .collect(),
+            samples: samples[..sample_count as usize]
+                .iter()
+                .zip(1..)
+                .map(|(sample, index)| Sample::from_c(index, sample))
+                .collect(),
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self)?;
+        Ok(())
+    }
+}
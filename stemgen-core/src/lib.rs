@@ -0,0 +1,456 @@
+//! Probing, rendering and encoding of the songs stemgen turns into stems. The stemgen CLI is
+//! built on top of this, other tools can use it to render stems without running the CLI
+//!
+//! ```no_run
+//! use stemgen_core::{Format, RenderOptions, Song, StemSelection};
+//!
+//! let song = Song::load("song.xm")?;
+//! let stem = song.render_stem(StemSelection::channel(0), &RenderOptions::default())?;
+//! stem.encode(Format::Flac24, &mut std::fs::File::create("song_ch00.flac")?)?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+mod audio;
+pub(crate) mod ffi;
+pub mod instruments;
+pub mod patterns;
+mod song;
+pub mod tempo;
+pub mod wav_writer;
+
+pub use audio::{pack_pcm24, pack_pcm32, AudioBuffer, Format};
+pub use ffi::{c_string, RenderParams, SongInfo, MAX_CHANNELS, SUBSONG_ALL};
+pub use song::{AmigaFilter, AtEnd, RenderOptions, RenderProgress, Song, StemSelection};
+
+use ffi::*;
+use std::{ffi::c_void, path::Path};
+
+/// Quick check of the header so files that aren't songs are skipped without loading them
+pub fn probe_song(file_data: &[u8]) -> bool {
+    unsafe { probe_song_c(file_data.as_ptr(), file_data.len() as u32) }
+}
+
+/// File extensions of the song formats libopenmpt can play
+pub fn supported_extensions() -> Vec<String> {
+    let mut buffer = vec![0u8; 4096];
+    let len = unsafe { get_supported_extensions_c(buffer.as_mut_ptr(), buffer.len() as u32) };
+
+    String::from_utf8_lossy(&buffer[..len as usize])
+        .split(';')
+        .map(|e| e.to_owned())
+        .collect()
+}
+
+/// NUL terminated path in the native encoding of the OS for the C code. None if it contains a
+/// NUL, which no OS allows in paths so nothing could be written there anyway
+fn native_path(path: &Path) -> Option<Vec<PathChar>> {
+    #[cfg(windows)]
+    let mut chars: Vec<PathChar> =
+        std::os::windows::ffi::OsStrExt::encode_wide(path.as_os_str()).collect();
+    #[cfg(not(windows))]
+    let mut chars: Vec<PathChar> =
+        std::os::unix::ffi::OsStrExt::as_bytes(path.as_os_str()).to_vec();
+
+    if chars.contains(&0) {
+        return None;
+    }
+
+    chars.push(0);
+    Some(chars)
+}
+
+/// Default for the longest duration of a song. Anything longer is considered broken
+pub const DEFAULT_MAX_SECONDS: f32 = 3600.0;
+
+/// Format of the samples written by `song_info`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SampleFormat {
+    /// FLAC with the loops in a smpl chunk inside a "riff" APPLICATION block
+    Flac,
+    /// Wav with the loops in a smpl chunk
+    Wav,
+    /// Wav samples plus an sfz instrument per instrument
+    Sfz,
+    /// A single SoundFont with a preset per instrument
+    Sf2,
+    /// Amiga IFF 8SVX
+    Iff8svx,
+}
+
+impl SampleFormat {
+    /// Has to match the sample formats of the C code
+    fn c_value(self) -> u32 {
+        match self {
+            SampleFormat::Flac => 0,
+            SampleFormat::Wav => 1,
+            SampleFormat::Sfz => 2,
+            SampleFormat::Sf2 => 3,
+            SampleFormat::Iff8svx => 4,
+        }
+    }
+}
+
+/// Channels, instruments and length of a song, with the estimated duration capped at
+/// `max_seconds`. With `samples_output` the samples of the song are also written to the path in
+/// the given format. Nothing is written if the path contains a NUL
+pub fn song_info(
+    file_data: &[u8],
+    samples_output: Option<(&Path, SampleFormat)>,
+    max_seconds: f32,
+) -> SongInfo {
+    let samples_output =
+        samples_output.and_then(|(path, format)| Some((native_path(path)?, format.c_value())));
+
+    if let Some((path, sample_format)) = samples_output {
+        unsafe {
+            get_song_info_c(
+                file_data.as_ptr(),
+                file_data.len() as u32,
+                path.as_ptr(),
+                sample_format,
+                max_seconds,
+            )
+        }
+    } else {
+        unsafe {
+            get_song_info_c(
+                file_data.as_ptr(),
+                file_data.len() as u32,
+                std::ptr::null(),
+                0,
+                max_seconds,
+            )
+        }
+    }
+}
+
+/// Names and texts stored in a song
+#[derive(Debug, Clone, Default)]
+pub struct Metadata {
+    pub title: String,
+    /// Short name of the format, such as "xm"
+    pub format: String,
+    pub format_long: String,
+    pub tracker: String,
+    pub artist: String,
+    pub message: String,
+    /// Sample names joined with newlines, where many songs hide their message
+    pub sample_text: String,
+    pub instrument_names: Vec<String>,
+    pub sample_names: Vec<String>,
+}
+
+/// Names and texts of a song libopenmpt can load. Everything is empty if it can't
+pub fn song_metadata(file_data: &[u8]) -> Metadata {
+    let mut output = Box::new(SongMetadataC {
+        title: [0; 256],
+        format: [0; 16],
+        format_long: [0; 256],
+        tracker: [0; 256],
+        artist: [0; 256],
+        message: [0; 16384],
+        sample_text: [0; 16384],
+        sample_name_count: 0,
+    });
+
+    let mut names: Vec<ItemNameC> = (0..MAX_INSTRUMENTS)
+        .map(|_| ItemNameC { name: [0; 256] })
+        .collect();
+    let mut sample_names: Vec<ItemNameC> = (0..MAX_INSTRUMENTS)
+        .map(|_| ItemNameC { name: [0; 256] })
+        .collect();
+
+    let count = unsafe {
+        get_song_metadata_c(
+            file_data.as_ptr(),
+            file_data.len() as u32,
+            &mut *output,
+            names.as_mut_ptr(),
+            sample_names.as_mut_ptr(),
+            MAX_INSTRUMENTS as u32,
+        )
+    };
+
+    Metadata {
+        title: c_string(&output.title),
+        format: c_string(&output.format),
+        format_long: c_string(&output.format_long),
+        tracker: c_string(&output.tracker),
+        artist: c_string(&output.artist),
+        message: c_string(&output.message),
+        sample_text: c_string(&output.sample_text),
+        instrument_names: names[..count as usize]
+            .iter()
+            .map(|n| c_string(&n.name))
+            .collect(),
+        sample_names: sample_names[..output.sample_name_count as usize]
+            .iter()
+            .map(|n| c_string(&n.name))
+            .collect(),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Subsong {
+    pub index: i32,
+    pub name: String,
+    pub duration_seconds: f32,
+}
+
+/// Subsongs of a song libopenmpt can load, empty if it can't
+pub fn subsongs(file_data: &[u8]) -> Vec<Subsong> {
+    let mut output: Vec<SubsongInfoC> = (0..MAX_SUBSONGS)
+        .map(|_| SubsongInfoC {
+            name: [0; 256],
+            duration_seconds: 0.0,
+        })
+        .collect();
+
+    let count = unsafe {
+        get_subsongs_c(
+            file_data.as_ptr(),
+            file_data.len() as u32,
+            output.as_mut_ptr(),
+            output.len() as u32,
+        )
+    };
+
+    output
+        .iter()
+        .take(count as usize)
+        .enumerate()
+        .map(|(index, info)| Subsong {
+            index: index as i32,
+            name: c_string(&info.name),
+            duration_seconds: info.duration_seconds,
+        })
+        .collect()
+}
+
+/// Time an order position is first played
+#[derive(Debug, Copy, Clone)]
+pub struct OrderTime {
+    pub order: u32,
+    pub pattern: u32,
+    pub seconds: f32,
+}
+
+/// Time each order position of the subsong (-1 for the default one) is first played, sorted by
+/// time
+pub fn order_times(file_data: &[u8], subsong: i32) -> Vec<OrderTime> {
+    let mut output: Vec<OrderTimeC> = (0..MAX_ORDERS)
+        .map(|_| OrderTimeC {
+            order: 0,
+            pattern: 0,
+            seconds: 0.0,
+        })
+        .collect();
+
+    let count = unsafe {
+        get_order_times_c(
+            file_data.as_ptr(),
+            file_data.len() as u32,
+            subsong,
+            output.as_mut_ptr(),
+            output.len() as u32,
+        )
+    };
+
+    let mut times: Vec<OrderTime> = output[..count as usize]
+        .iter()
+        .map(|time| OrderTime {
+            order: time.order,
+            pattern: time.pattern,
+            seconds: time.seconds,
+        })
+        .collect();
+    times.sort_by(|a, b| a.seconds.total_cmp(&b.seconds));
+    times
+}
+
+/// Render `input` into `output` in one go and return the number of bytes written
+pub fn render(output: &mut [u8], input: &[u8], render_params: &RenderParams) -> u32 {
+    unsafe {
+        song_render_c(
+            output.as_mut_ptr(),
+            output.len() as u32,
+            input.as_ptr(),
+            input.len() as u32,
+            render_params,
+        )
+    }
+}
+
+/// Number of frames a render of a song lasting `duration_seconds` produces with `render_params`.
+/// This is the count `SongStream::frame_count` cuts streams to. None if the song plays forever
+pub fn render_frame_count(duration_seconds: f32, render_params: &RenderParams) -> Option<u64> {
+    let frames = unsafe { render_frame_count_c(duration_seconds as f64, render_params) };
+    (frames != 0).then_some(frames)
+}
+
+/// libopenmpt render that is read in chunks
+pub struct SongStream {
+    stream: *mut c_void,
+    frame_bytes: usize,
+}
+
+impl SongStream {
+    /// None if libopenmpt can't load the song
+    pub fn open(input: &[u8], render_params: &RenderParams) -> Option<SongStream> {
+        let stream = unsafe { song_open_c(input.as_ptr(), input.len() as u32, render_params) };
+        let channel_count = if render_params.stereo_output { 2 } else { 1 };
+
+        if stream.is_null() {
+            return None;
+        }
+
+        Some(SongStream {
+            stream,
+            frame_bytes: render_params.bytes_per_sample as usize * channel_count,
+        })
+    }
+
+    /// Render into `output` and return the number of bytes written. Fewer bytes than the size
+    /// of `output` means the song has ended
+    pub fn read(&mut self, output: &mut [u8]) -> usize {
+        let max_frames = (output.len() / self.frame_bytes) as u32;
+        let frames = unsafe { song_read_c(self.stream, output.as_mut_ptr(), max_frames) };
+        frames as usize * self.frame_bytes
+    }
+
+    /// Number of frames the whole render produces, worked out for the selected subsong, channel
+    /// and instrument when the stream is opened. Reads stop there. None if the song plays forever
+    pub fn frame_count(&self) -> Option<u64> {
+        let frames = unsafe { song_frame_count_c(self.stream) };
+        (frames != 0).then_some(frames)
+    }
+}
+
+impl Drop for SongStream {
+    fn drop(&mut self) {
+        unsafe { song_close_c(self.stream) };
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// 4 channel ProTracker module with one pattern of 64 rows, where channel 0 plays a square
+    /// and channel 1 a saw from the first row on. Channels 2 and 3 are empty
+    pub(crate) fn test_mod() -> Vec<u8> {
+        let mut data = Vec::new();
+
+        let mut title = b"stemgen test".to_vec();
+        title.resize(20, 0);
+        data.extend(title);
+
+        for sample in 0..31 {
+            let mut name = match sample {
+                0 => b"square".to_vec(),
+                1 => b"saw".to_vec(),
+                _ => Vec::new(),
+            };
+            name.resize(22, 0);
+            data.extend(name);
+
+            let words: u16 = if sample < 2 { 16 } else { 0 };
+            data.extend(words.to_be_bytes());
+            // Finetune and volume
+            data.extend([0, if sample < 2 { 64 } else { 0 }]);
+            // Loop over the whole sample
+            data.extend(0u16.to_be_bytes());
+            data.extend(words.max(1).to_be_bytes());
+        }
+
+        // One order playing pattern 0
+        data.extend([1, 127]);
+        data.extend([0; 128]);
+        data.extend(b"M.K.");
+
+        let mut pattern = [0u8; 64 * 4 * 4];
+        for (channel, sample, period) in [(0, 1u8, 428u16), (1, 2, 214)] {
+            let cell = &mut pattern[channel * 4..channel * 4 + 4];
+            cell[0] = (sample & 0xf0) | (period >> 8) as u8;
+            cell[1] = period as u8;
+            cell[2] = (sample & 0x0f) << 4;
+        }
+        data.extend(pattern);
+
+        data.extend((0..32).map(|i| if i < 16 { 64u8 } else { (-64i8) as u8 }));
+        data.extend((0..32).map(|i| (i * 8 - 128) as i8 as u8));
+        data
+    }
+
+    #[test]
+    fn probe() {
+        assert!(probe_song(&test_mod()));
+        assert!(!probe_song(b"not a song at all"));
+        assert!(supported_extensions().iter().any(|e| e == "mod"));
+    }
+
+    #[test]
+    fn info_of_a_song() {
+        let info = song_info(&test_mod(), None, DEFAULT_MAX_SECONDS);
+
+        assert_eq!(info.channel_count, 4);
+        assert_eq!(info.empty_channels(), [2, 3]);
+        // 64 rows at speed 6 and 125 BPM
+        assert!((info.duration_seconds - 7.68).abs() < 0.01);
+        assert_eq!(
+            info.checked_duration(DEFAULT_MAX_SECONDS),
+            Some(info.duration_seconds)
+        );
+    }
+
+    #[test]
+    fn info_of_something_else() {
+        let info = song_info(b"not a song at all", None, DEFAULT_MAX_SECONDS);
+        assert_eq!(info.channel_count, 0);
+    }
+
+    #[test]
+    fn metadata() {
+        let metadata = song_metadata(&test_mod());
+
+        assert_eq!(metadata.title, "stemgen test");
+        assert_eq!(metadata.format, "mod");
+        assert_eq!(metadata.sample_names[..2], ["square", "saw"]);
+    }
+
+    #[test]
+    fn subsongs_and_orders() {
+        let subsongs = subsongs(&test_mod());
+        assert_eq!(subsongs.len(), 1);
+        assert_eq!(subsongs[0].index, 0);
+
+        let times = order_times(&test_mod(), -1);
+        assert_eq!(times.len(), 1);
+        assert_eq!((times[0].order, times[0].pattern), (0, 0));
+        assert_eq!(times[0].seconds, 0.0);
+    }
+
+    #[test]
+    fn samples_are_written_in_the_requested_format() {
+        let dir = std::env::temp_dir().join(format!("stemgen-core-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("song");
+
+        song_info(
+            &test_mod(),
+            Some((&path, SampleFormat::Wav)),
+            DEFAULT_MAX_SECONDS,
+        );
+        let sample = std::fs::read(dir.join("song_sample_0001.wav")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(&sample[..4], b"RIFF");
+        assert!(sample.windows(4).any(|chunk| chunk == b"smpl"));
+    }
+
+    #[test]
+    fn c_strings_stop_at_nul() {
+        assert_eq!(c_string(b" name \0garbage"), "name");
+        assert_eq!(c_string(b"full"), "full");
+    }
+}
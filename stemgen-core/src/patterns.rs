@@ -0,0 +1,167 @@
+//! Pattern and order data written as `<song>_patterns.json` with --pattern-data, for tools that
+//! work with the notes and effects rather than the audio
+
+use crate::ffi::{
+    c_string, get_pattern_cells_c, get_patterns_c, PatternCellC, PatternInfoC, PatternSummaryC,
+    MAX_ORDERS, MAX_PATTERNS,
+};
+use anyhow::Result;
+use serde::Serialize;
+use std::{fs::File, io::BufWriter, path::Path};
+
+/// One channel of a row. Only the columns that have something in them are set
+#[derive(Serialize, Debug)]
+pub struct Cell {
+    /// 1 to 120 (61 is middle C), 253 to 255 for note fade, cut and off
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<u8>,
+    /// The note as shown in the tracker, such as "C-5" or "==="
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instrument: Option<u8>,
+    /// Letter of the volume column command in the notation of the format, such as "v"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume_command: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<u8>,
+    /// Letter of the effect in the notation of the format, such as "F" for set speed in MODs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effect: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub param: Option<u8>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Pattern {
+    /// Pattern number as used in the order list
+    pub index: u32,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    /// Each row has a cell per channel
+    pub rows: Vec<Vec<Cell>>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SongPatterns {
+    pub channel_count: u32,
+    pub initial_speed: u32,
+    pub initial_tempo: f32,
+    pub rows_per_beat: u32,
+    pub rows_per_measure: u32,
+    /// Patterns in playing order. +++ and --- markers are null
+    pub orders: Vec<Option<u32>>,
+    /// Pattern numbers that aren't used by the song are left out
+    pub patterns: Vec<Pattern>,
+}
+
+impl Cell {
+    fn from_c(cell: &PatternCellC) -> Cell {
+        let letter = |c: u8| (c != 0).then(|| (c as char).to_string());
+
+        Cell {
+            note: (cell.note != 0).then_some(cell.note),
+            note_name: (cell.note_name[0] != 0).then(|| c_string(&cell.note_name)),
+            instrument: (cell.instrument != 0).then_some(cell.instrument),
+            volume_command: letter(cell.volume_command),
+            volume: (cell.volume_command != 0).then_some(cell.volume),
+            effect: letter(cell.effect),
+            param: (cell.effect != 0).then_some(cell.param),
+        }
+    }
+}
+
+impl SongPatterns {
+    /// Read the patterns of a song libopenmpt can load
+    pub fn read(file_data: &[u8]) -> SongPatterns {
+        let mut summary = PatternSummaryC {
+            channel_count: 0,
+            order_count: 0,
+            initial_speed: 0,
+            initial_tempo: 0.0,
+            rows_per_beat: 0,
+            rows_per_measure: 0,
+        };
+        let mut patterns: Vec<PatternInfoC> = (0..MAX_PATTERNS)
+            .map(|_| PatternInfoC {
+                name: [0; 256],
+                rows: 0,
+            })
+            .collect();
+        let mut orders = vec![0i32; MAX_ORDERS];
+
+        let count = unsafe {
+            get_patterns_c(
+                file_data.as_ptr(),
+                file_data.len() as u32,
+                &mut summary,
+                patterns.as_mut_ptr(),
+                patterns.len() as u32,
+                orders.as_mut_ptr(),
+                orders.len() as u32,
+            )
+        };
+        patterns.truncate(count as usize);
+
+        // The cells come a row at a time for all used patterns after each other
+        let channel_count = summary.channel_count as usize;
+        let cell_count = patterns.iter().map(|p| p.rows as usize).sum::<usize>() * channel_count;
+        let mut cells = vec![
+            PatternCellC {
+                note: 0,
+                instrument: 0,
+                volume: 0,
+                param: 0,
+                note_name: [0; 8],
+                volume_command: 0,
+                effect: 0,
+            };
+            cell_count
+        ];
+
+        let cell_count = unsafe {
+            get_pattern_cells_c(
+                file_data.as_ptr(),
+                file_data.len() as u32,
+                cells.as_mut_ptr(),
+                cells.len() as u32,
+            )
+        };
+        cells.truncate(cell_count as usize);
+
+        let mut rows = cells.chunks(channel_count.max(1));
+
+        SongPatterns {
+            channel_count: summary.channel_count,
+            initial_speed: summary.initial_speed,
+            initial_tempo: summary.initial_tempo,
+            rows_per_beat: summary.rows_per_beat,
+            rows_per_measure: summary.rows_per_measure,
+            orders: orders[..summary.order_count as usize]
+                .iter()
+                .map(|&pattern| u32::try_from(pattern).ok())
+                .collect(),
+            patterns: patterns
+                .iter()
+                .zip(0..)
+                .filter(|(pattern, _)| pattern.rows != 0)
+                .map(|(pattern, index)| Pattern {
+                    index,
+                    name: c_string(&pattern.name),
+                    rows: rows
+                        .by_ref()
+                        .take(pattern.rows as usize)
+                        .map(|row| row.iter().map(Cell::from_c).collect())
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Written without indentation as songs can have many thousands of cells
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let file = File::create(path)?;
+        serde_json::to_writer(BufWriter::new(file), self)?;
+        Ok(())
+    }
+}
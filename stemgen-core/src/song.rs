@@ -0,0 +1,597 @@
+use crate::ffi::{MAX_CHANNELS, SUBSONG_ALL};
+use crate::{AudioBuffer, RenderParams, SongInfo, SongStream};
+use anyhow::{bail, Result};
+use std::{collections::BTreeMap, path::Path};
+
+/// Frames rendered per read of the song stream
+const CHUNK_FRAMES: usize = 48000;
+
+/// Which part of the song a single render covers
+#[derive(Debug, Copy, Clone)]
+pub struct StemSelection {
+    /// -1 plays all channels, otherwise only this channel
+    pub channel: i32,
+    /// -1 plays all instruments, otherwise only this instrument
+    pub instrument: i32,
+    /// -1 uses the default subsong, SUBSONG_ALL plays all subsongs, otherwise this subsong
+    pub subsong: i32,
+}
+
+impl StemSelection {
+    /// The whole song
+    pub fn full() -> StemSelection {
+        StemSelection {
+            channel: -1,
+            instrument: -1,
+            subsong: -1,
+        }
+    }
+
+    /// Only `channel` is played
+    pub fn channel(channel: u32) -> StemSelection {
+        StemSelection {
+            channel: channel as i32,
+            ..StemSelection::full()
+        }
+    }
+
+    /// Only `instrument` is played
+    pub fn instrument(instrument: u32) -> StemSelection {
+        StemSelection {
+            instrument: instrument as i32,
+            ..StemSelection::full()
+        }
+    }
+
+    /// Render `subsong` instead of the default one
+    pub fn in_subsong(self, subsong: u32) -> StemSelection {
+        StemSelection {
+            subsong: subsong as i32,
+            ..self
+        }
+    }
+
+    /// Render all subsongs after each other
+    pub fn in_all_subsongs(self) -> StemSelection {
+        StemSelection {
+            subsong: SUBSONG_ALL,
+            ..self
+        }
+    }
+}
+
+/// What to do when the song reaches its end (or jumps back to the start)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AtEnd {
+    /// Stop rendering at the end of the song
+    Stop,
+    /// Keep playing and fade out over the given number of seconds
+    Fadeout(f32),
+    /// Keep playing for the given number of extra loops
+    Loop(u32),
+}
+
+impl AtEnd {
+    /// Value passed to libopenmpt's `set_repeat_count`
+    pub fn repeat_count(self) -> i32 {
+        match self {
+            AtEnd::Stop => 0,
+            AtEnd::Fadeout(_) => -1,
+            AtEnd::Loop(count) => count as i32,
+        }
+    }
+
+    pub fn fade_seconds(self) -> f32 {
+        match self {
+            AtEnd::Fadeout(seconds) => seconds,
+            _ => 0.0,
+        }
+    }
+}
+
+impl std::str::FromStr for AtEnd {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mode, value) = s.split_once('-').unwrap_or((s, ""));
+
+        match (mode, value) {
+            ("stop", "") => Ok(AtEnd::Stop),
+            ("fadeout", "") => Ok(AtEnd::Fadeout(10.0)),
+            ("fadeout", secs) => match secs.parse::<f32>() {
+                Ok(secs) if secs > 0.0 => Ok(AtEnd::Fadeout(secs)),
+                _ => Err(format!("Invalid fadeout length \"{}\"", secs)),
+            },
+            ("loop", "") => Ok(AtEnd::Loop(1)),
+            ("loop", count) => count
+                .parse::<u32>()
+                .map(AtEnd::Loop)
+                .map_err(|_| format!("Invalid loop count \"{}\"", count)),
+            _ => Err(format!(
+                "Unknown at-end mode \"{}\". Use stop, fadeout-<seconds> or loop-<count>",
+                s
+            )),
+        }
+    }
+}
+
+/// Filter model of the Paula emulation for Amiga formats such as mod
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AmigaFilter {
+    /// A500 or A1200 depending on the format of the song
+    Auto,
+    A500,
+    A1200,
+    /// Paula output without the filters of the Amiga models
+    Unfiltered,
+}
+
+impl AmigaFilter {
+    /// Has to match the AmigaFilter enum in the C code where 0 keeps libopenmpt's default
+    fn render_param(self) -> i32 {
+        match self {
+            AmigaFilter::Auto => 1,
+            AmigaFilter::A500 => 2,
+            AmigaFilter::A1200 => 3,
+            AmigaFilter::Unfiltered => 4,
+        }
+    }
+}
+
+/// How a stem is rendered. Defaults to stereo at 48 kHz without repeats
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    pub sample_rate: u32,
+    /// Mono if false
+    pub stereo: bool,
+    /// Stereo separation in percent, None uses the default of the song
+    pub stereo_separation: Option<u32>,
+    /// Length of the interpolation filter in taps, 0 uses libopenmpt's default
+    pub interpolation_filter_length: i32,
+    pub gain_db: f32,
+    pub at_end: AtEnd,
+    /// Position to start rendering at
+    pub start_seconds: f32,
+    /// Position to stop rendering at, None renders to the end of the song
+    pub end_seconds: Option<f32>,
+    /// Longest render, for songs that never end. None only stops at the end of the song or the
+    /// fade out
+    pub max_seconds: Option<f32>,
+    /// Pan of module channels from -1.0 (left) to 1.0 (right), replacing the pan of the song
+    pub channel_pan: BTreeMap<usize, f32>,
+    /// Volume of module channels in dB. The mixer can only lower a channel
+    pub channel_gain_db: BTreeMap<usize, f32>,
+    /// Seed of libopenmpt's dither, None gives different noise on every run
+    pub dither_seed: Option<u32>,
+    /// None uses libopenmpt's default
+    pub amiga_filter: Option<AmigaFilter>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions {
+            sample_rate: 48000,
+            stereo: true,
+            stereo_separation: None,
+            interpolation_filter_length: 0,
+            gain_db: 0.0,
+            at_end: AtEnd::Stop,
+            start_seconds: 0.0,
+            end_seconds: None,
+            max_seconds: Some(crate::DEFAULT_MAX_SECONDS),
+            channel_pan: BTreeMap::new(),
+            channel_gain_db: BTreeMap::new(),
+            dither_seed: None,
+            amiga_filter: None,
+        }
+    }
+}
+
+impl RenderOptions {
+    /// Parameters for the C code to render `stem` of the song described by `song_info`. Samples
+    /// are rendered as floats
+    pub fn render_params(&self, song_info: &SongInfo, stem: StemSelection) -> RenderParams {
+        let rate = self.sample_rate as f32;
+        let fade_seconds = self.at_end.fade_seconds();
+        // Where the song ends relative to the start of the render
+        let end_of_song = (song_info.duration_seconds - self.start_seconds).max(0.0);
+
+        // When fading out the song plays forever so we stop after the fade has finished
+        let mut max_frames = if fade_seconds > 0.0 {
+            ((end_of_song + fade_seconds) * rate) as u32
+        } else {
+            0
+        };
+
+        let end_seconds = match (self.end_seconds, self.max_seconds) {
+            (Some(end), Some(max)) => Some(end.min(self.start_seconds + max)),
+            (end, max) => end.or(max.map(|max| self.start_seconds + max)),
+        };
+
+        if let Some(end) = end_seconds {
+            let frames = (((end - self.start_seconds) * rate) as u32).max(1);
+            max_frames = if max_frames == 0 {
+                frames
+            } else {
+                max_frames.min(frames)
+            };
+        }
+
+        let mut channel_pan = [0.0; MAX_CHANNELS];
+        let mut channel_volume = [1.0; MAX_CHANNELS];
+        let mut channel_pan_mapped = [false; MAX_CHANNELS];
+        let mut channel_volume_mapped = [false; MAX_CHANNELS];
+
+        for (&channel, &pan) in self.channel_pan.range(..MAX_CHANNELS) {
+            channel_pan[channel] = pan;
+            channel_pan_mapped[channel] = true;
+        }
+
+        for (&channel, &gain) in self.channel_gain_db.range(..MAX_CHANNELS) {
+            channel_volume[channel] = 10f32.powf(gain / 20.0);
+            channel_volume_mapped[channel] = true;
+        }
+
+        RenderParams {
+            sample_rate: self.sample_rate,
+            bytes_per_sample: 4,
+            channel_to_play: stem.channel,
+            instrument_to_play: stem.instrument,
+            stereo_separation: self.stereo_separation.unwrap_or(100),
+            stereo_separation_enabled: self.stereo_separation.is_some(),
+            stereo_output: self.stereo,
+            repeat_count: self.at_end.repeat_count(),
+            max_frames,
+            start_seconds: self.start_seconds,
+            subsong: stem.subsong,
+            dither_seed: self.dither_seed.unwrap_or(0),
+            dither_seed_enabled: self.dither_seed.is_some(),
+            interpolation_filter_length: self.interpolation_filter_length,
+            master_gain_millibel: (self.gain_db * 100.0).round() as i32,
+            channel_pan,
+            channel_volume,
+            channel_pan_mapped,
+            channel_volume_mapped,
+            amiga_filter: self.amiga_filter.map_or(0, AmigaFilter::render_param),
+        }
+    }
+}
+
+/// Progress of `Song::render_stem_with_progress`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RenderProgress {
+    /// The render has started. None if the song doesn't say how long it is
+    Started { frame_count: Option<u64> },
+    /// Another chunk of frames has been rendered
+    Rendered { frames: u64 },
+}
+
+/// Song that has been loaded and checked by libopenmpt
+pub struct Song {
+    data: Vec<u8>,
+    info: SongInfo,
+}
+
+impl Song {
+    /// Read a song file. Archives and compressed songs aren't unpacked
+    pub fn load(path: impl AsRef<Path>) -> Result<Song> {
+        Song::from_bytes(std::fs::read(path)?)
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<Song> {
+        if !crate::probe_song(&data) {
+            bail!("Not a song supported by libopenmpt");
+        }
+
+        let info = crate::song_info(&data, None, crate::DEFAULT_MAX_SECONDS);
+
+        if info.channel_count == 0 {
+            bail!("The song doesn't have any channels");
+        }
+
+        Ok(Song { data, info })
+    }
+
+    /// Song with info that has already been read with `song_info`, so it isn't loaded again
+    pub fn from_parts(data: Vec<u8>, info: SongInfo) -> Song {
+        Song { data, info }
+    }
+
+    /// Channels, instruments and length
+    pub fn info(&self) -> &SongInfo {
+        &self.info
+    }
+
+    /// Render the part of the song picked by `stem`
+    pub fn render_stem(&self, stem: StemSelection, options: &RenderOptions) -> Result<AudioBuffer> {
+        self.render_stem_with_progress(stem, options, &|_| {})
+    }
+
+    /// Same as `render_stem`, with `on_progress` called as the render goes along. The render
+    /// core works out the frame count of the selected channel or instrument when the render
+    /// starts, which is what gets allocated and where the render is cut
+    pub fn render_stem_with_progress(
+        &self,
+        stem: StemSelection,
+        options: &RenderOptions,
+        on_progress: &dyn Fn(RenderProgress),
+    ) -> Result<AudioBuffer> {
+        let render_params = options.render_params(&self.info, stem);
+        let Some(mut stream) = SongStream::open(&self.data, &render_params) else {
+            bail!("libopenmpt is unable to load the song");
+        };
+
+        let channel_count = if options.stereo { 2 } else { 1 };
+        let frame_bytes = channel_count * 4;
+        let chunk_bytes = CHUNK_FRAMES * frame_bytes;
+        let frame_count = stream.frame_count();
+        let total_bytes = frame_count.map(|frames| frames as usize * frame_bytes);
+        let mut data = Vec::with_capacity(total_bytes.unwrap_or(0));
+
+        on_progress(RenderProgress::Started { frame_count });
+
+        loop {
+            let start = data.len();
+            let request = match total_bytes {
+                Some(total) => chunk_bytes.min(total - start),
+                None => chunk_bytes,
+            };
+
+            if request == 0 {
+                break;
+            }
+
+            data.resize(start + request, 0);
+            let len = stream.read(&mut data[start..]);
+            data.truncate(start + len);
+            on_progress(RenderProgress::Rendered {
+                frames: (len / frame_bytes) as u64,
+            });
+
+            if len < request {
+                break;
+            }
+        }
+
+        Ok(AudioBuffer::from_bytes(
+            data,
+            channel_count,
+            options.sample_rate,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_mod;
+
+    fn song_info(duration_seconds: f32) -> SongInfo {
+        SongInfo {
+            channel_count: 4,
+            instrument_count: 2,
+            duration_seconds,
+            estimated_seconds: 0.0,
+            channel_has_notes: [true; MAX_CHANNELS],
+        }
+    }
+
+    fn options() -> RenderOptions {
+        RenderOptions {
+            sample_rate: 8000,
+            ..RenderOptions::default()
+        }
+    }
+
+    #[test]
+    fn at_end_modes() {
+        assert_eq!("stop".parse(), Ok(AtEnd::Stop));
+        assert_eq!("fadeout".parse(), Ok(AtEnd::Fadeout(10.0)));
+        assert_eq!("fadeout-2.5".parse(), Ok(AtEnd::Fadeout(2.5)));
+        assert_eq!("loop".parse(), Ok(AtEnd::Loop(1)));
+        assert_eq!("loop-3".parse(), Ok(AtEnd::Loop(3)));
+
+        for invalid in ["fadeout-0", "fadeout-x", "loop--1", "stop-1", "repeat", ""] {
+            assert!(invalid.parse::<AtEnd>().is_err(), "{}", invalid);
+        }
+
+        assert_eq!(AtEnd::Stop.repeat_count(), 0);
+        assert_eq!(AtEnd::Fadeout(4.0).repeat_count(), -1);
+        assert_eq!(AtEnd::Loop(2).repeat_count(), 2);
+        assert_eq!(AtEnd::Fadeout(4.0).fade_seconds(), 4.0);
+        assert_eq!(AtEnd::Loop(2).fade_seconds(), 0.0);
+    }
+
+    #[test]
+    fn stem_selections() {
+        let stem = StemSelection::instrument(3).in_subsong(2);
+        assert_eq!((stem.channel, stem.instrument, stem.subsong), (-1, 3, 2));
+
+        let stem = StemSelection::channel(1).in_all_subsongs();
+        assert_eq!(
+            (stem.channel, stem.instrument, stem.subsong),
+            (1, -1, SUBSONG_ALL)
+        );
+    }
+
+    #[test]
+    fn render_length() {
+        let stem = StemSelection::full();
+        let info = song_info(10.0);
+
+        // Capped at the longest render by default
+        let params = options().render_params(&info, stem);
+        assert_eq!(params.max_frames, 3600 * 8000);
+        assert_eq!(params.repeat_count, 0);
+
+        let unlimited = RenderOptions {
+            max_seconds: None,
+            ..options()
+        };
+        assert_eq!(unlimited.render_params(&info, stem).max_frames, 0);
+
+        // The fade starts at the end of the song, counted from the start of the render
+        let fadeout = RenderOptions {
+            at_end: AtEnd::Fadeout(2.0),
+            start_seconds: 4.0,
+            ..unlimited.clone()
+        };
+        let params = fadeout.render_params(&info, stem);
+        assert_eq!(params.max_frames, 8 * 8000);
+        assert_eq!(params.repeat_count, -1);
+
+        let cut = RenderOptions {
+            end_seconds: Some(5.0),
+            ..fadeout
+        };
+        assert_eq!(cut.render_params(&info, stem).max_frames, 8000);
+
+        let capped = RenderOptions {
+            end_seconds: Some(100.0),
+            max_seconds: Some(3.0),
+            ..options()
+        };
+        assert_eq!(capped.render_params(&info, stem).max_frames, 3 * 8000);
+    }
+
+    #[test]
+    fn frame_counts() {
+        let stem = StemSelection::full();
+        let info = song_info(10.0);
+        let frames = |options: RenderOptions| {
+            let params = options.render_params(&info, stem);
+            crate::render_frame_count(info.duration_seconds, &params)
+        };
+        let unlimited = RenderOptions {
+            max_seconds: None,
+            ..options()
+        };
+
+        assert_eq!(frames(unlimited.clone()), Some(10 * 8000));
+
+        // The rest of the song after the start and then the whole song for every extra loop
+        let looped = RenderOptions {
+            start_seconds: 4.0,
+            at_end: AtEnd::Loop(1),
+            ..unlimited.clone()
+        };
+        assert_eq!(frames(looped), Some(16 * 8000));
+
+        let fadeout = RenderOptions {
+            at_end: AtEnd::Fadeout(2.0),
+            ..unlimited
+        };
+        assert_eq!(frames(fadeout.clone()), Some(12 * 8000));
+
+        let mut forever = fadeout.render_params(&info, stem);
+        forever.max_frames = 0;
+        assert_eq!(crate::render_frame_count(10.0, &forever), None);
+    }
+
+    #[test]
+    fn streams_are_cut_to_the_frame_count() {
+        let song = Song::from_bytes(test_mod()).unwrap();
+        let params = options().render_params(song.info(), StemSelection::full());
+        let stream = SongStream::open(&test_mod(), &params).unwrap();
+        let frames = crate::render_frame_count(song.info().duration_seconds, &params).unwrap();
+
+        // The stream rounds up the length libopenmpt calculates, which SongInfo only keeps as f32
+        assert!(stream.frame_count().unwrap().abs_diff(frames) <= 1);
+    }
+
+    #[test]
+    fn render_settings() {
+        let options = RenderOptions {
+            stereo: false,
+            stereo_separation: Some(50),
+            gain_db: -3.0,
+            channel_pan: BTreeMap::from([(1, -0.5), (MAX_CHANNELS, 1.0)]),
+            channel_gain_db: BTreeMap::from([(2, -20.0)]),
+            dither_seed: Some(7),
+            amiga_filter: Some(AmigaFilter::A1200),
+            ..options()
+        };
+        let params = options.render_params(&song_info(10.0), StemSelection::channel(2));
+
+        assert_eq!((params.channel_to_play, params.instrument_to_play), (2, -1));
+        assert!(!params.stereo_output);
+        assert_eq!(params.bytes_per_sample, 4);
+        assert!(params.stereo_separation_enabled);
+        assert_eq!(params.stereo_separation, 50);
+        assert_eq!(params.master_gain_millibel, -300);
+        assert!(params.channel_pan_mapped[1]);
+        assert_eq!(params.channel_pan[1], -0.5);
+        assert!(!params.channel_pan_mapped[0]);
+        assert!(params.channel_volume_mapped[2]);
+        assert!((params.channel_volume[2] - 0.1).abs() < 1e-6);
+        assert!(params.dither_seed_enabled);
+        assert_eq!(params.dither_seed, 7);
+        assert_eq!(params.amiga_filter, 3);
+    }
+
+    #[test]
+    fn only_songs_are_loaded() {
+        assert!(Song::from_bytes(b"not a song at all".to_vec()).is_err());
+
+        let song = Song::from_bytes(test_mod()).unwrap();
+        assert_eq!(song.info().channel_count, 4);
+    }
+
+    #[test]
+    fn render_stems() {
+        let song = Song::from_bytes(test_mod()).unwrap();
+        let full_frames = (song.info().duration_seconds * 8000.0).round() as usize;
+
+        let full = song.render_stem(StemSelection::full(), &options()).unwrap();
+        assert_eq!(full.channel_count, 2);
+        assert_eq!(full.sample_rate, 8000);
+        assert!(full.frame_count().abs_diff(full_frames) <= 1);
+        assert!(full.peak() > 0.1);
+
+        let mono = RenderOptions {
+            stereo: false,
+            ..options()
+        };
+        let square = song.render_stem(StemSelection::channel(0), &mono).unwrap();
+        assert_eq!(square.channel_count, 1);
+        assert!(square.peak() > 0.1);
+
+        let empty = song.render_stem(StemSelection::channel(2), &mono).unwrap();
+        assert_eq!(empty.peak(), 0.0);
+
+        // Instruments are counted from 0
+        let saw = song
+            .render_stem(StemSelection::instrument(1), &mono)
+            .unwrap();
+        assert!(saw.peak() > 0.1);
+    }
+
+    #[test]
+    fn render_progress_adds_up_to_the_frame_count() {
+        let song = Song::from_bytes(test_mod()).unwrap();
+        let events = std::sync::Mutex::new(Vec::new());
+
+        let stem = song
+            .render_stem_with_progress(StemSelection::full(), &options(), &|event| {
+                events.lock().unwrap().push(event)
+            })
+            .unwrap();
+
+        let events = events.into_inner().unwrap();
+        let Some(RenderProgress::Started { frame_count }) = events.first() else {
+            panic!("No start event in {:?}", events);
+        };
+        let rendered: u64 = events[1..]
+            .iter()
+            .map(|event| match event {
+                RenderProgress::Rendered { frames } => *frames,
+                _ => panic!("Second start event in {:?}", events),
+            })
+            .sum();
+
+        // The song can end a frame before the calculated count, but never renders past it
+        assert!(frame_count.unwrap().abs_diff(rendered) <= 1);
+        assert!(frame_count.unwrap() >= rendered);
+        assert_eq!(rendered, stem.frame_count() as u64);
+    }
+}
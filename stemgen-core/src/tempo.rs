@@ -0,0 +1,144 @@
+//! Tempo of a song over time, for exports that put the stems on a timeline in beats. Written as
+//! `<song>_tempo.json` and `<song>_tempo.mid` with --tempo-map
+
+use crate::ffi::{get_tempo_map_c, TempoChangeC, MAX_TEMPO_CHANGES};
+use anyhow::Result;
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+/// Ticks per quarter note of the MIDI tempo track
+const MIDI_PPQ: u16 = 480;
+
+/// Tempo of the song from a point on. Changes of the speed, tempo or rows per beat all change it
+#[derive(Serialize, Debug, Copy, Clone)]
+pub struct TempoChange {
+    pub seconds: f64,
+    /// Beats played before the change
+    pub beat: f64,
+    pub bpm: f64,
+    /// Ticks per row
+    pub speed: u32,
+    /// Tempo as set in the song, which is only the BPM for 4 rows per beat and speed 6
+    pub tempo: f32,
+    pub rows_per_beat: u32,
+}
+
+/// Tempo changes of the subsong (-1 for the default one) in the order they are played. The first
+/// one is the tempo at the start. Empty if the song can't be played
+pub fn tempo_map(file_data: &[u8], subsong: i32) -> Vec<TempoChange> {
+    let mut output = vec![
+        TempoChangeC {
+            seconds: 0.0,
+            bpm: 0.0,
+            speed: 0,
+            tempo: 0.0,
+            rows_per_beat: 0,
+        };
+        MAX_TEMPO_CHANGES
+    ];
+
+    let count = unsafe {
+        get_tempo_map_c(
+            file_data.as_ptr(),
+            file_data.len() as u32,
+            subsong,
+            output.as_mut_ptr(),
+            output.len() as u32,
+        )
+    };
+
+    let mut beat = 0.0;
+    let mut previous: Option<&TempoChangeC> = None;
+
+    output[..count as usize]
+        .iter()
+        .map(|change| {
+            if let Some(previous) = previous {
+                beat += (change.seconds - previous.seconds) * previous.bpm / 60.0;
+            }
+            previous = Some(change);
+
+            TempoChange {
+                seconds: change.seconds,
+                beat,
+                bpm: change.bpm,
+                speed: change.speed,
+                tempo: change.tempo,
+                rows_per_beat: change.rows_per_beat,
+            }
+        })
+        .collect()
+}
+
+/// Beat position of a time in seconds
+pub fn beat_at(tempo_map: &[TempoChange], seconds: f64) -> f64 {
+    match tempo_map.iter().rev().find(|c| c.seconds <= seconds) {
+        Some(change) => change.beat + (seconds - change.seconds) * change.bpm / 60.0,
+        // Songs that can't be played are placed at 120 BPM
+        None => seconds * 2.0,
+    }
+}
+
+pub fn write_json(path: &Path, tempo_map: &[TempoChange]) -> Result<()> {
+    let file = File::create(path)?;
+    serde_json::to_writer_pretty(BufWriter::new(file), tempo_map)?;
+    Ok(())
+}
+
+/// MIDI variable length quantity, 7 bits at a time with the high bit set on all but the last
+fn push_variable_length(out: &mut Vec<u8>, value: u32) {
+    let mut bytes = vec![(value & 0x7f) as u8];
+    let mut value = value >> 7;
+
+    while value != 0 {
+        bytes.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+
+    out.extend(bytes.iter().rev());
+}
+
+/// Write a type 0 MIDI file with a single track holding the tempo changes, to import into a DAW
+/// as the tempo of the project
+pub fn write_midi(path: &Path, name: &str, tempo_map: &[TempoChange]) -> Result<()> {
+    let mut track = Vec::new();
+
+    // Track name
+    let name = name.as_bytes();
+    track.extend([0x00, 0xff, 0x03]);
+    push_variable_length(&mut track, name.len() as u32);
+    track.extend(name);
+
+    let mut last_tick = 0;
+
+    for change in tempo_map.iter().filter(|c| c.bpm > 0.0) {
+        let tick = (change.beat * MIDI_PPQ as f64).round() as u32;
+        let quarter_micros = ((60_000_000.0 / change.bpm).round() as u32).min(0xff_ffff);
+
+        push_variable_length(&mut track, tick.saturating_sub(last_tick));
+        track.extend([0xff, 0x51, 0x03]);
+        track.extend(&quarter_micros.to_be_bytes()[1..]);
+        last_tick = last_tick.max(tick);
+    }
+
+    // End of track
+    track.extend([0x00, 0xff, 0x2f, 0x00]);
+
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(b"MThd")?;
+    file.write_all(&6u32.to_be_bytes())?;
+    // Format 0 with one track
+    file.write_all(&0u16.to_be_bytes())?;
+    file.write_all(&1u16.to_be_bytes())?;
+    file.write_all(&MIDI_PPQ.to_be_bytes())?;
+    file.write_all(b"MTrk")?;
+    file.write_all(&(track.len() as u32).to_be_bytes())?;
+    file.write_all(&track)?;
+    file.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,515 @@
+use std::fs::File;
+use std::io::{BufWriter, Cursor, Result, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::SystemTime;
+
+const FORMAT_PCM: u16 = 1;
+const FORMAT_IEEE_FLOAT: u16 = 3;
+const FORMAT_EXTENSIBLE: u16 = 0xfffe;
+
+// Offset and size of the JUNK chunk that is replaced by ds64 for RF64 files
+const DS64_OFFSET: u64 = 12;
+const DS64_SIZE: u32 = 28;
+
+// Tail of the KSDATAFORMAT_SUBTYPE_PCM and KSDATAFORMAT_SUBTYPE_IEEE_FLOAT GUIDs, the first two
+// bytes are the format tag
+const SUBFORMAT_GUID_TAIL: [u8; 14] = [
+    0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xaa, 0x00, 0x38, 0x9b, 0x71,
+];
+
+/// Sample format of the data chunk
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum WavFormat {
+    Pcm16,
+    Pcm24,
+    Pcm32,
+    Float32,
+}
+
+impl WavFormat {
+    pub fn bits(self) -> u16 {
+        match self {
+            WavFormat::Pcm16 => 16,
+            WavFormat::Pcm24 => 24,
+            WavFormat::Pcm32 | WavFormat::Float32 => 32,
+        }
+    }
+
+    fn tag(self) -> u16 {
+        match self {
+            WavFormat::Float32 => FORMAT_IEEE_FLOAT,
+            _ => FORMAT_PCM,
+        }
+    }
+}
+
+/// Broadcast Wave Format `bext` chunk (EBU Tech 3285, version 1). The coding history is filled in
+/// from the format of the file when it's written
+#[derive(Debug, Clone)]
+pub struct Bext {
+    /// Module title and stem identity. Written as ASCII and cut to 256 bytes
+    pub description: String,
+    pub originator: String,
+    /// Source module filename, cut to 32 bytes
+    pub originator_reference: String,
+    /// UTC date as yyyy-mm-dd
+    pub origination_date: String,
+    /// UTC time as hh:mm:ss
+    pub origination_time: String,
+    /// Position of the first sample in the song. Written as samples at the rate of the file
+    pub time_reference_seconds: f64,
+}
+
+impl Bext {
+    /// Originated by stemgen now
+    pub fn new(description: &str, originator_reference: &str, time_reference_seconds: f64) -> Bext {
+        let seconds = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let (year, month, day) = civil_date(seconds / 86400);
+        let time = seconds % 86400;
+
+        Bext {
+            description: description.to_owned(),
+            originator: "stemgen".to_owned(),
+            originator_reference: originator_reference.to_owned(),
+            origination_date: format!("{:04}-{:02}-{:02}", year, month, day),
+            origination_time: format!("{:02}:{:02}:{:02}", time / 3600, time / 60 % 60, time % 60),
+            time_reference_seconds,
+        }
+    }
+
+    fn write<W: Write>(
+        &self,
+        writer: &mut W,
+        format: WavFormat,
+        sample_rate: u32,
+        channel_count: usize,
+    ) -> Result<()> {
+        let mode = match channel_count {
+            1 => "mono",
+            2 => "stereo",
+            _ => "multichannel",
+        };
+        let coding_history = format!(
+            "A=PCM,F={},W={},M={},T=stemgen {}\r\n",
+            sample_rate,
+            format.bits(),
+            mode,
+            env!("CARGO_PKG_VERSION")
+        );
+        let time_reference = (self.time_reference_seconds * sample_rate as f64).round() as u64;
+
+        // Fixed part is 602 bytes, the coding history is padded to an even size
+        let mut history = coding_history.into_bytes();
+        if history.len() % 2 == 1 {
+            history.push(0);
+        }
+
+        writer.write_all(b"bext")?;
+        writer.write_all(&(602 + history.len() as u32).to_le_bytes())?;
+        write_ascii(writer, &self.description, 256)?;
+        write_ascii(writer, &self.originator, 32)?;
+        write_ascii(writer, &self.originator_reference, 32)?;
+        write_ascii(writer, &self.origination_date, 10)?;
+        write_ascii(writer, &self.origination_time, 8)?;
+        writer.write_all(&time_reference.to_le_bytes())?;
+        writer.write_all(&1u16.to_le_bytes())?;
+        // UMID and reserved space
+        writer.write_all(&[0; 64 + 190])?;
+        writer.write_all(&history)
+    }
+}
+
+/// `text` as ASCII in a zero padded field of `len` bytes
+fn write_ascii<W: Write>(writer: &mut W, text: &str, len: usize) -> Result<()> {
+    let mut field: Vec<u8> = deunicode::deunicode(text)
+        .bytes()
+        .filter(|c| !c.is_ascii_control())
+        .take(len)
+        .collect();
+    field.resize(len, 0);
+    writer.write_all(&field)
+}
+
+/// Year, month and day of a count of days since 1970-01-01
+fn civil_date(days: u64) -> (i64, u32, u32) {
+    // Howard Hinnant's days_from_civil in reverse, with eras of 400 years starting at March 1st
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Speaker positions of the channels. Mono is front center, otherwise the channels are assigned
+/// in the standard order (front left, front right, center, LFE, back left, back right, ...)
+fn channel_mask(channel_count: usize) -> u32 {
+    match channel_count {
+        1 => 0x4,
+        2..=18 => (1 << channel_count) - 1,
+        _ => 0,
+    }
+}
+
+/// Wav file that is written while the song renders. The chunk sizes aren't known until all
+/// samples are written so they are patched in by `finish`, which needs a writer that can seek.
+///
+/// Mono and stereo 16-bit files get a plain PCM header and mono and stereo float files an IEEE
+/// float header. Everything else uses WAVE_FORMAT_EXTENSIBLE as the spec requires for more than
+/// 16 bits or two channels. Float files also get the `fact` chunk required for non-PCM formats.
+///
+/// Room for a `ds64` chunk is kept with a `JUNK` chunk in front of `fmt `. Files that end up
+/// larger than 4 GB are turned into RF64 (EBU Tech 3306) by `finish`, with the 64-bit sizes in
+/// `ds64` and the 32-bit sizes set to 0xffffffff. Smaller files stay plain wav. A BWF `bext`
+/// chunk can be written in front of `fmt `
+pub struct WavWriter<W: Write + Seek = BufWriter<File>> {
+    writer: W,
+    data_bytes: u64,
+    frame_bytes: u64,
+    /// Offset of the `fact` sample count, if there is one
+    fact_offset: Option<u64>,
+    /// Offset of the `data` chunk size
+    data_size_offset: u64,
+}
+
+impl WavWriter {
+    pub fn create(
+        filename: &Path,
+        channel_count: usize,
+        sample_rate: u32,
+        format: WavFormat,
+        bext: Option<&Bext>,
+    ) -> Result<WavWriter> {
+        let writer = BufWriter::new(File::create(filename)?);
+        WavWriter::new(writer, channel_count, sample_rate, format, bext)
+    }
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(
+        mut writer: W,
+        channel_count: usize,
+        sample_rate: u32,
+        format: WavFormat,
+        bext: Option<&Bext>,
+    ) -> Result<WavWriter<W>> {
+        let bits = format.bits();
+        let block_align = channel_count as u16 * bits / 8;
+        let float = format == WavFormat::Float32;
+        let extensible = channel_count > 2 || (bits > 16 && !float);
+
+        let fmt_size: u32 = if extensible {
+            40
+        } else if float {
+            18
+        } else {
+            16
+        };
+
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        writer.write_all(b"WAVE")?;
+
+        writer.write_all(b"JUNK")?;
+        writer.write_all(&DS64_SIZE.to_le_bytes())?;
+        writer.write_all(&[0; DS64_SIZE as usize])?;
+
+        let mut offset = DS64_OFFSET + 8 + DS64_SIZE as u64;
+
+        if let Some(bext) = bext {
+            let mut chunk = Vec::new();
+            bext.write(&mut chunk, format, sample_rate, channel_count)?;
+            writer.write_all(&chunk)?;
+            offset += chunk.len() as u64;
+        }
+
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&fmt_size.to_le_bytes())?;
+        let tag = if extensible {
+            FORMAT_EXTENSIBLE
+        } else {
+            format.tag()
+        };
+        writer.write_all(&tag.to_le_bytes())?;
+        writer.write_all(&(channel_count as u16).to_le_bytes())?;
+        writer.write_all(&sample_rate.to_le_bytes())?;
+        writer.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&bits.to_le_bytes())?;
+
+        if extensible {
+            writer.write_all(&22u16.to_le_bytes())?;
+            writer.write_all(&bits.to_le_bytes())?;
+            writer.write_all(&channel_mask(channel_count).to_le_bytes())?;
+            writer.write_all(&format.tag().to_le_bytes())?;
+            writer.write_all(&SUBFORMAT_GUID_TAIL)?;
+        } else if float {
+            writer.write_all(&0u16.to_le_bytes())?;
+        }
+
+        offset += 8 + fmt_size as u64;
+        let mut fact_offset = None;
+
+        if float {
+            writer.write_all(b"fact")?;
+            writer.write_all(&4u32.to_le_bytes())?;
+            writer.write_all(&0u32.to_le_bytes())?;
+            fact_offset = Some(offset + 8);
+            offset += 12;
+        }
+
+        writer.write_all(b"data")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+
+        Ok(WavWriter {
+            writer,
+            data_bytes: 0,
+            frame_bytes: block_align as u64,
+            fact_offset,
+            data_size_offset: offset + 4,
+        })
+    }
+
+    /// Append little endian samples in the format of the file
+    pub fn write(&mut self, buffer: &[u8]) -> Result<()> {
+        self.data_bytes += buffer.len() as u64;
+        self.writer.write_all(buffer)
+    }
+
+    /// Pad the data chunk to an even size, fill in the chunk sizes and return the writer
+    pub fn finish(mut self) -> Result<W> {
+        if self.data_bytes % 2 == 1 {
+            self.writer.write_all(&[0])?;
+        }
+
+        self.write_sizes()?;
+
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    /// Fill in the chunk sizes for `data_bytes` of samples
+    fn write_sizes(&mut self) -> Result<()> {
+        let padded = self.data_bytes + self.data_bytes % 2;
+        let riff_size = self.data_size_offset + 4 - 8 + padded;
+        let frames = self.data_bytes / self.frame_bytes.max(1);
+
+        // The sizes that don't fit in 32 bits are read from ds64 instead
+        let rf64 = riff_size > u32::MAX as u64;
+        let size32 = |size: u64| if rf64 { u32::MAX } else { size as u32 };
+
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.writer
+            .write_all(if rf64 { b"RF64" } else { b"RIFF" })?;
+        self.writer.write_all(&size32(riff_size).to_le_bytes())?;
+
+        if rf64 {
+            self.writer.seek(SeekFrom::Start(DS64_OFFSET))?;
+            self.writer.write_all(b"ds64")?;
+            self.writer.write_all(&DS64_SIZE.to_le_bytes())?;
+            self.writer.write_all(&riff_size.to_le_bytes())?;
+            self.writer.write_all(&self.data_bytes.to_le_bytes())?;
+            self.writer.write_all(&frames.to_le_bytes())?;
+            // No table of other chunk sizes
+            self.writer.write_all(&0u32.to_le_bytes())?;
+        }
+
+        if let Some(offset) = self.fact_offset {
+            self.writer.seek(SeekFrom::Start(offset))?;
+            self.writer.write_all(&size32(frames).to_le_bytes())?;
+        }
+
+        self.writer.seek(SeekFrom::Start(self.data_size_offset))?;
+        self.writer
+            .write_all(&size32(self.data_bytes).to_le_bytes())
+    }
+}
+
+/// Write a whole wav file strictly in order, for writers that can't seek (such as a pipe). The
+/// header is the same as the one of `WavWriter` with the sizes of `buffer` filled in up front
+pub fn write_wav<W: Write>(
+    mut writer: W,
+    buffer: &[u8],
+    channel_count: usize,
+    sample_rate: u32,
+    format: WavFormat,
+    bext: Option<&Bext>,
+) -> Result<()> {
+    let mut header = WavWriter::new(
+        Cursor::new(Vec::new()),
+        channel_count,
+        sample_rate,
+        format,
+        bext,
+    )?;
+    header.data_bytes = buffer.len() as u64;
+    header.write_sizes()?;
+
+    writer.write_all(header.writer.get_ref())?;
+    writer.write_all(buffer)?;
+
+    if buffer.len() % 2 == 1 {
+        writer.write_all(&[0])?;
+    }
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_wav_matches_wav_writer() {
+        let buffer: Vec<u8> = (0..=254).collect();
+
+        for format in [WavFormat::Pcm16, WavFormat::Pcm24, WavFormat::Float32] {
+            let mut writer =
+                WavWriter::new(Cursor::new(Vec::new()), 3, 44100, format, None).unwrap();
+            writer.write(&buffer).unwrap();
+            let expected = writer.finish().unwrap().into_inner();
+
+            let mut streamed = Vec::new();
+            write_wav(&mut streamed, &buffer, 3, 44100, format, None).unwrap();
+
+            assert_eq!(streamed, expected);
+        }
+    }
+
+    fn header(channel_count: usize, format: WavFormat) -> Vec<u8> {
+        let writer = WavWriter::new(Cursor::new(Vec::new()), channel_count, 48000, format, None);
+        writer.unwrap().finish().unwrap().into_inner()
+    }
+
+    fn u16_at(data: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap())
+    }
+
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn stereo_16_bit_has_a_plain_pcm_header() {
+        let data = header(2, WavFormat::Pcm16);
+
+        assert_eq!(&data[0..4], b"RIFF");
+        assert_eq!(&data[12..16], b"JUNK");
+        assert_eq!(&data[48..52], b"fmt ");
+        assert_eq!(u32_at(&data, 52), 16);
+        assert_eq!(u16_at(&data, 56), FORMAT_PCM);
+        assert_eq!(&data[72..76], b"data");
+    }
+
+    #[test]
+    fn more_than_16_bits_or_two_channels_is_extensible() {
+        for (channel_count, format, subformat, mask) in [
+            (2, WavFormat::Pcm24, FORMAT_PCM, 0x3),
+            (1, WavFormat::Pcm32, FORMAT_PCM, 0x4),
+            (6, WavFormat::Float32, FORMAT_IEEE_FLOAT, 0x3f),
+            (4, WavFormat::Pcm16, FORMAT_PCM, 0xf),
+        ] {
+            let data = header(channel_count, format);
+
+            assert_eq!(u32_at(&data, 52), 40);
+            assert_eq!(u16_at(&data, 56), FORMAT_EXTENSIBLE);
+            assert_eq!(u16_at(&data, 58), channel_count as u16);
+            assert_eq!(u16_at(&data, 72), 22);
+            assert_eq!(u16_at(&data, 74), format.bits());
+            assert_eq!(u32_at(&data, 76), mask);
+            assert_eq!(u16_at(&data, 80), subformat);
+            assert_eq!(data[82..96], SUBFORMAT_GUID_TAIL);
+        }
+    }
+
+    #[test]
+    fn float_has_a_fact_chunk_with_the_frame_count() {
+        let mut writer =
+            WavWriter::new(Cursor::new(Vec::new()), 2, 48000, WavFormat::Float32, None).unwrap();
+        writer.write(&[0; 8 * 10]).unwrap();
+        let data = writer.finish().unwrap().into_inner();
+
+        assert_eq!(u32_at(&data, 52), 18);
+        assert_eq!(u16_at(&data, 56), FORMAT_IEEE_FLOAT);
+        assert_eq!(&data[74..78], b"fact");
+        assert_eq!(u32_at(&data, 82), 10);
+        assert_eq!(&data[86..90], b"data");
+        assert_eq!(u32_at(&data, 90), 80);
+        assert_eq!(u32_at(&data, 4), data.len() as u32 - 8);
+    }
+
+    #[test]
+    fn odd_data_is_padded() {
+        let mut writer =
+            WavWriter::new(Cursor::new(Vec::new()), 1, 8000, WavFormat::Pcm24, None).unwrap();
+        writer.write(&[1, 2, 3]).unwrap();
+        let data = writer.finish().unwrap().into_inner();
+
+        assert_eq!(data.len() % 2, 0);
+        assert_eq!(u32_at(&data, data.len() - 8), 3);
+        assert_eq!(u32_at(&data, 4), data.len() as u32 - 8);
+    }
+
+    #[test]
+    fn files_over_4_gb_become_rf64() {
+        let mut writer =
+            WavWriter::new(Cursor::new(Vec::new()), 2, 48000, WavFormat::Float32, None).unwrap();
+        // Only the sizes are written so the samples don't have to be there
+        writer.data_bytes = 5 << 30;
+        writer.write_sizes().unwrap();
+        let data = writer.writer.into_inner();
+        let riff_size = writer.data_size_offset + 4 - 8 + (5 << 30);
+
+        assert_eq!(&data[0..4], b"RF64");
+        assert_eq!(u32_at(&data, 4), u32::MAX);
+        assert_eq!(&data[12..16], b"ds64");
+        assert_eq!(u32_at(&data, 16), DS64_SIZE);
+        assert_eq!(data[20..28], riff_size.to_le_bytes());
+        assert_eq!(data[28..36], (5u64 << 30).to_le_bytes());
+        assert_eq!(data[36..44], (5u64 << 30 >> 3).to_le_bytes());
+        // fact and data sizes are read from ds64
+        assert_eq!(u32_at(&data, 82), u32::MAX);
+        assert_eq!(u32_at(&data, 90), u32::MAX);
+    }
+
+    #[test]
+    fn bext_is_written_in_front_of_fmt() {
+        let bext = Bext {
+            description: "Motörhead".to_owned(),
+            originator: "stemgen".to_owned(),
+            originator_reference: "song.mod".to_owned(),
+            origination_date: "2024-01-02".to_owned(),
+            origination_time: "03:04:05".to_owned(),
+            time_reference_seconds: 1.0,
+        };
+        let writer = WavWriter::new(
+            Cursor::new(Vec::new()),
+            2,
+            48000,
+            WavFormat::Pcm16,
+            Some(&bext),
+        );
+        let data = writer.unwrap().finish().unwrap().into_inner();
+
+        assert_eq!(&data[48..52], b"bext");
+        assert!(data[56..].starts_with(b"Motorhead\0"));
+        let fmt = 56 + u32_at(&data, 52) as usize;
+        assert_eq!(&data[fmt..fmt + 4], b"fmt ");
+    }
+
+    #[test]
+    fn civil_dates() {
+        assert_eq!(civil_date(0), (1970, 1, 1));
+        assert_eq!(civil_date(11016), (2000, 2, 29));
+        assert_eq!(civil_date(19723), (2024, 1, 1));
+    }
+}
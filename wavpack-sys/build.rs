@@ -0,0 +1,25 @@
+#[cfg(not(feature = "build-wavpack"))]
+fn main() {
+    println!("cargo:rustc-link-lib=wavpack");
+}
+
+#[cfg(feature = "build-wavpack")]
+fn main() {
+    let wavpack_path = cmake::Config::new("WavPack")
+        .define("BUILD_SHARED_LIBS", "OFF")
+        .define("BUILD_TESTING", "OFF")
+        .define("WAVPACK_BUILD_PROGRAMS", "OFF")
+        .define("WAVPACK_BUILD_DOCS", "OFF")
+        .define("WAVPACK_BUILD_COOLEDIT_PLUGIN", "OFF")
+        .define("WAVPACK_BUILD_WINAMP_PLUGIN", "OFF")
+        .define("WAVPACK_INSTALL_DOCS", "OFF")
+        .define("WAVPACK_INSTALL_CMAKE_MODULE", "OFF")
+        .define("WAVPACK_INSTALL_PKGCONFIG_MODULE", "OFF")
+        .define("WAVPACK_ENABLE_LIBCRYPTO", "OFF")
+        .build();
+
+    println!("cargo:rustc-link-search=native={}/lib", wavpack_path.display());
+    // This path is used on 64 bit Fedora:
+    println!("cargo:rustc-link-search=native={}/lib64", wavpack_path.display());
+    println!("cargo:rustc-link-lib=static=wavpack");
+}
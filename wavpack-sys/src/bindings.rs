@@ -0,0 +1,96 @@
+// Declarations from WavPack/include/wavpack.h needed for encoding. Kept by hand as only a small
+// part of the API is used
+
+pub const CONFIG_HYBRID_FLAG: u32 = 8;
+pub const CONFIG_JOINT_STEREO: u32 = 0x10;
+pub const CONFIG_CROSS_DECORR: u32 = 0x20;
+pub const CONFIG_HYBRID_SHAPE: u32 = 0x40;
+pub const CONFIG_FAST_FLAG: u32 = 0x200;
+pub const CONFIG_HIGH_FLAG: u32 = 0x800;
+pub const CONFIG_VERY_HIGH_FLAG: u32 = 0x1000;
+pub const CONFIG_BITRATE_KBPS: u32 = 0x2000;
+pub const CONFIG_SHAPE_OVERRIDE: u32 = 0x8000;
+pub const CONFIG_JOINT_OVERRIDE: u32 = 0x10000;
+pub const CONFIG_DYNAMIC_SHAPING: u32 = 0x20000;
+pub const CONFIG_CREATE_EXE: u32 = 0x40000;
+pub const CONFIG_CREATE_WVC: u32 = 0x80000;
+pub const CONFIG_OPTIMIZE_WVC: u32 = 0x100000;
+pub const CONFIG_COMPATIBLE_WRITE: u32 = 0x400000;
+pub const CONFIG_CALC_NOISE: u32 = 0x800000;
+pub const CONFIG_EXTRA_MODE: u32 = 0x2000000;
+pub const CONFIG_SKIP_WVX: u32 = 0x4000000;
+pub const CONFIG_MD5_CHECKSUM: u32 = 0x8000000;
+pub const CONFIG_MERGE_BLOCKS: u32 = 0x10000000;
+pub const CONFIG_PAIR_UNDEF_CHANS: u32 = 0x20000000;
+pub const CONFIG_OPTIMIZE_MONO: u32 = 0x80000000;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct WavpackConfig {
+    pub bitrate: f32,
+    pub shaping_weight: f32,
+    pub bits_per_sample: ::std::os::raw::c_int,
+    pub bytes_per_sample: ::std::os::raw::c_int,
+    pub qmode: ::std::os::raw::c_int,
+    pub flags: ::std::os::raw::c_int,
+    pub xmode: ::std::os::raw::c_int,
+    pub num_channels: ::std::os::raw::c_int,
+    pub float_norm_exp: ::std::os::raw::c_int,
+    pub block_samples: i32,
+    pub extra_flags: i32,
+    pub sample_rate: i32,
+    pub channel_mask: i32,
+    pub md5_checksum: [::std::os::raw::c_uchar; 16usize],
+    pub md5_read: ::std::os::raw::c_uchar,
+    pub num_tag_strings: ::std::os::raw::c_int,
+    pub tag_strings: *mut *mut ::std::os::raw::c_char,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct WavpackContext {
+    _unused: [u8; 0],
+}
+
+pub type WavpackBlockOutput = ::std::option::Option<
+    unsafe extern "C" fn(
+        id: *mut ::std::os::raw::c_void,
+        data: *mut ::std::os::raw::c_void,
+        bcount: i32,
+    ) -> ::std::os::raw::c_int,
+>;
+
+extern "C" {
+    pub fn WavpackGetErrorMessage(wpc: *mut WavpackContext) -> *mut ::std::os::raw::c_char;
+    pub fn WavpackCloseFile(wpc: *mut WavpackContext) -> *mut WavpackContext;
+    pub fn WavpackOpenFileOutput(
+        blockout: WavpackBlockOutput,
+        wv_id: *mut ::std::os::raw::c_void,
+        wvc_id: *mut ::std::os::raw::c_void,
+    ) -> *mut WavpackContext;
+    pub fn WavpackSetConfiguration64(
+        wpc: *mut WavpackContext,
+        config: *mut WavpackConfig,
+        total_samples: i64,
+        chan_ids: *const ::std::os::raw::c_uchar,
+    ) -> ::std::os::raw::c_int;
+    pub fn WavpackStoreMD5Sum(
+        wpc: *mut WavpackContext,
+        data: *mut ::std::os::raw::c_uchar,
+    ) -> ::std::os::raw::c_int;
+    pub fn WavpackPackInit(wpc: *mut WavpackContext) -> ::std::os::raw::c_int;
+    pub fn WavpackPackSamples(
+        wpc: *mut WavpackContext,
+        sample_buffer: *mut i32,
+        sample_count: u32,
+    ) -> ::std::os::raw::c_int;
+    pub fn WavpackFlushSamples(wpc: *mut WavpackContext) -> ::std::os::raw::c_int;
+    pub fn WavpackAppendTagItem(
+        wpc: *mut WavpackContext,
+        item: *const ::std::os::raw::c_char,
+        value: *const ::std::os::raw::c_char,
+        vsize: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+    pub fn WavpackWriteTag(wpc: *mut WavpackContext) -> ::std::os::raw::c_int;
+    pub fn WavpackUpdateNumSamples(wpc: *mut WavpackContext, first_block: *mut ::std::os::raw::c_void);
+}
@@ -0,0 +1,150 @@
+//! Raw FFI bindings to the `WavPack` library.
+//!
+//! Original C API documentation: <https://www.wavpack.com/wavpack_doc.html>
+//!
+
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+use std::ffi::{CStr, CString};
+use std::io::Write;
+use std::os::raw::c_void;
+
+include!("bindings.rs");
+
+/// Lossy hybrid mode settings. With a correction writer the lossy stream plus the correction
+/// (.wvc) data decode to the original samples
+pub struct Hybrid<'a> {
+    /// Target bitrate of the lossy stream in kbps for all channels together
+    pub bitrate_kbps: f32,
+    pub correction: Option<&'a mut dyn Write>,
+}
+
+/// Called by the encoder for every finished block. `id` is the writer of the main or the
+/// correction stream
+unsafe extern "C" fn block_output(id: *mut c_void, data: *mut c_void, bcount: i32) -> i32 {
+    let writer = &mut *(id as *mut &mut dyn Write);
+    let data = std::slice::from_raw_parts(data as *const u8, bcount as usize);
+
+    writer.write_all(data).is_ok() as i32
+}
+
+unsafe fn error_message(context: *mut WavpackContext) -> String {
+    let message = WavpackGetErrorMessage(context);
+    if message.is_null() {
+        return "unknown error".to_owned();
+    }
+    String::from_utf8_lossy(CStr::from_ptr(message).to_bytes()).into_owned()
+}
+
+unsafe fn write_tags(context: *mut WavpackContext, tags: &[(String, String)]) -> bool {
+    if tags.is_empty() {
+        return true;
+    }
+
+    for (item, value) in tags {
+        let Ok(item) = CString::new(item.as_str()) else {
+            continue;
+        };
+
+        if WavpackAppendTagItem(context, item.as_ptr(), value.as_ptr() as *const _, value.len() as i32) == 0 {
+            return false;
+        }
+    }
+
+    WavpackWriteTag(context) != 0
+}
+
+fn to_wavpack_samples(buffer: &[u8], bytes_per_sample: u32) -> Vec<i32> {
+    if bytes_per_sample == 4 {
+        // Floats are passed as their bit patterns
+        bytemuck::cast_slice::<u8, i32>(buffer).to_vec()
+    } else {
+        let data: &[i16] = bytemuck::cast_slice(buffer);
+        data.iter().map(|x| *x as i32).collect()
+    }
+}
+
+/// Encode interleaved samples (i16 or f32 depending on `bytes_per_sample`). Blocks are written
+/// strictly in order and the sample count is set up front so the writers never have to seek.
+/// `tags` are written as an APEv2 tag at the end of the file.
+pub fn encode_wavpack(
+    writer: &mut dyn Write,
+    buffer: &[u8],
+    channels: u32,
+    bytes_per_sample: u32,
+    sample_rate: u32,
+    hybrid: Option<Hybrid>,
+    tags: &[(String, String)],
+) -> Result<(), String> {
+    let mut samples = to_wavpack_samples(buffer, bytes_per_sample);
+    let total_samples = samples.len() as i64 / channels as i64;
+
+    let mut flags = CONFIG_HIGH_FLAG;
+    let mut bitrate = 0.0;
+
+    let mut main = writer;
+    let mut correction = None;
+
+    if let Some(hybrid) = hybrid {
+        flags |= CONFIG_HYBRID_FLAG | CONFIG_BITRATE_KBPS;
+        bitrate = hybrid.bitrate_kbps;
+
+        if hybrid.correction.is_some() {
+            flags |= CONFIG_CREATE_WVC;
+        }
+        correction = hybrid.correction;
+    }
+
+    let mut config = WavpackConfig {
+        bitrate,
+        shaping_weight: 0.0,
+        bits_per_sample: bytes_per_sample as i32 * 8,
+        bytes_per_sample: bytes_per_sample as i32,
+        qmode: 0,
+        flags: flags as i32,
+        xmode: 0,
+        num_channels: channels as i32,
+        float_norm_exp: if bytes_per_sample == 4 { 127 } else { 0 },
+        block_samples: 0,
+        extra_flags: 0,
+        sample_rate: sample_rate as i32,
+        channel_mask: match channels {
+            1 => 0x4,
+            2 => 0x3,
+            _ => 0,
+        },
+        md5_checksum: [0; 16],
+        md5_read: 0,
+        num_tag_strings: 0,
+        tag_strings: std::ptr::null_mut(),
+    };
+
+    unsafe {
+        let main_id = &mut main as *mut &mut dyn Write as *mut c_void;
+        let correction_id = match &mut correction {
+            Some(correction) => correction as *mut &mut dyn Write as *mut c_void,
+            None => std::ptr::null_mut(),
+        };
+
+        let context = WavpackOpenFileOutput(Some(block_output), main_id, correction_id);
+        if context.is_null() {
+            return Err("unable to create WavPack context".to_owned());
+        }
+
+        let result = if WavpackSetConfiguration64(context, &mut config, total_samples, std::ptr::null()) == 0
+            || WavpackPackInit(context) == 0
+            || WavpackPackSamples(context, samples.as_mut_ptr(), total_samples as u32) == 0
+            || WavpackFlushSamples(context) == 0
+            || !write_tags(context, tags)
+        {
+            Err(error_message(context))
+        } else {
+            Ok(())
+        };
+
+        WavpackCloseFile(context);
+        result
+    }
+}